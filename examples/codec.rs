@@ -1,53 +1,6 @@
 use std::io;
 
-use bytes::BytesMut;
-use tokio_util::codec::{Decoder, Encoder};
-
-use smtpkit::{Command, Parser, ToBytes};
-
-#[derive(Debug, Default)]
-struct Smtp(Parser);
-
-impl Smtp {
-    fn new(size: usize) -> Self {
-        Self(Parser::new(size))
-    }
-}
-
-#[derive(Debug, derive_more::Display, thiserror::Error)]
-enum Error {
-    Io(#[from] io::Error),
-    Smtp(#[from] smtpkit::Error),
-}
-
-impl Decoder for Smtp {
-    type Item = Result<Command, smtpkit::Error>;
-    type Error = Error;
-
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let res = self.0.parse(src);
-        Ok(res.transpose())
-    }
-}
-
-impl Encoder<Command> for Smtp {
-    type Error = io::Error; // Infallible
-
-    fn encode(&mut self, item: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        item.to_bytes_into(dst);
-
-        Ok(())
-    }
-}
-
-impl Encoder<&[u8]> for Smtp {
-    type Error = io::Error; // Infallible
-
-    fn encode(&mut self, item: &[u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.extend_from_slice(item);
-        Ok(())
-    }
-}
+use smtpkit::{Error, Parser, ServerCodec};
 
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
@@ -68,7 +21,7 @@ async fn main() -> Result<(), io::Error> {
         println!("Accepted from: {addr}");
 
         tokio::spawn(async move {
-            let mut framed = Framed::new(socket, Smtp::new(100));
+            let mut framed = Framed::new(socket, ServerCodec::new(Parser::new(100)));
 
             while let Some(result) = framed.next().await {
                 match result {
@@ -76,17 +29,21 @@ async fn main() -> Result<(), io::Error> {
                         println!("[{addr}] Got: {line}");
                         framed.send(format!("Got: {line}\r\n").as_bytes()).await?;
                     }
+                    // a line that long is not worth recovering from
+                    Ok(Err(e @ Error::TooLong)) => {
+                        eprintln!("[{addr}] Fatal error: {e}");
+                        return Err(e.into());
+                    }
                     Ok(Err(e)) => {
                         eprintln!("[{addr}] Error: {e:?}");
                         framed.send(format!("Error: {e:?}\r\n").as_bytes()).await?;
                     }
-                    Err(Error::Io(e)) => {
+                    Err(e) => {
                         eprintln!("[{addr}] Fatal error: {e:?}");
                         return framed
                             .send(format!("Fatal Error: {e:?}\r\n").as_bytes())
                             .await;
                     }
-                    Err(Error::Smtp(_)) => unreachable!(),
                 }
             }
             Ok(())
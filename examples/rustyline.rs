@@ -1,9 +1,10 @@
-use bytes::*;
-use rustyline::DefaultEditor;
+#[cfg(not(target_family = "wasm"))]
+fn main() {
+    use bytes::*;
+    use rustyline::DefaultEditor;
 
-use smtpkit::*;
+    use smtpkit::*;
 
-fn main() {
     let mut rl = DefaultEditor::new().expect("Failed to init editor");
 
     let mut parser = Parser::default();
@@ -38,3 +39,7 @@ fn main() {
         //buf.clear();
     }
 }
+
+// there is no terminal to read from
+#[cfg(target_family = "wasm")]
+fn main() {}
@@ -0,0 +1,609 @@
+#![cfg(feature = "client")]
+
+use std::io;
+use std::num::NonZeroUsize;
+
+use derive_more::Display;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use crate::*;
+
+/// The size of `BDAT` chunks, when the server advertised `CHUNKING`.
+const CHUNK_SIZE: NonZeroUsize = NonZeroUsize::new(64 * 1024).unwrap();
+
+/// # TLS Connector
+///
+/// Performs the client side of a TLS handshake over `S` for [`send`], e.g. by wrapping a
+/// `tokio_rustls::TlsConnector` with the server name to verify. Bring your own TLS library.
+pub trait TlsConnector<S> {
+    /// The encrypted stream.
+    type Stream: AsyncRead + AsyncWrite + Unpin;
+
+    /// Perform the handshake over `stream`, after the server accepted `STARTTLS`.
+    fn connect(&self, stream: S) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+}
+
+/// A [`TlsConnector`] for [`Tls::Disabled`], which cannot be constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoTls {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> TlsConnector<S> for NoTls {
+    type Stream = S;
+
+    fn connect(&self, _stream: S) -> impl Future<Output = io::Result<S>> + Send {
+        let never = *self;
+        async move { match never {} }
+    }
+}
+
+/// # TLS Configuration
+///
+/// Whether [`send`] uses `STARTTLS`, with the [`TlsConnector`] to perform the handshake.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3207>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tls<C> {
+    /// Never use TLS, e.g. `Tls::<NoTls>::Disabled`.
+    Disabled,
+    /// Use TLS if the server offers `STARTTLS`, otherwise carry on in plaintext.
+    Opportunistic(C),
+    /// Use TLS, failing with [`ClientError::TlsUnavailable`] if the server does not offer it.
+    Required(C),
+}
+
+/// # `AUTH` Credentials
+///
+/// Sent with `AUTH PLAIN` by [`send`].
+///
+/// <https://datatracker.ietf.org/doc/html/rfc4616>
+#[derive(derive_more::Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    #[debug("{:?}", Redact::bytes(username))]
+    username: Bytes,
+    #[debug(skip)]
+    password: Bytes,
+}
+
+impl Credentials {
+    /// Create `Credentials` authenticating as `username` with `password`.
+    #[must_use]
+    pub const fn new(username: Bytes, password: Bytes) -> Self {
+        Self { username, password }
+    }
+
+    /// The `AUTH PLAIN` command, authorizing as the authenticated identity.
+    fn plain(&self) -> Command {
+        let mut response = Vec::with_capacity(self.username.len() + self.password.len() + 2);
+        response.push(b'\0');
+        response.extend_from_slice(&self.username);
+        response.push(b'\0');
+        response.extend_from_slice(&self.password);
+        let auth = auth::Auth::with_initial_response(Mechanism::Plain, &response)
+            .expect("PLAIN allows an initial response");
+        Command::Auth(auth)
+    }
+}
+
+/// # Delivery Result
+///
+/// Returned by [`send`] once the server accepted the message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delivery {
+    /// The reply to the end of the message, e.g. `250 2.0.0 OK: queued as 4F2A1`.
+    pub reply: Reply,
+    /// The recipients the server refused, with its reply to each. The message was delivered to
+    /// the rest.
+    pub refused: Vec<(rcpt::Rcpt, Reply)>,
+}
+
+/// # Send Error
+///
+/// Returned by [`send`].
+#[non_exhaustive]
+#[derive(Debug, Display, derive_more::Error)]
+pub enum SendError {
+    /// Connecting, reading, writing, or the TLS handshake failed.
+    #[display("{_0}")]
+    Io(io::Error),
+    /// The server sent an invalid reply.
+    #[display("{_0}")]
+    Reply(ReplyParseError),
+    /// The greeting, `EHLO`, or `STARTTLS` failed, `AUTH` would be sent before TLS, or the message
+    /// is larger than the server's maximum; see [`ClientSession`].
+    #[display("{_0}")]
+    Session(ClientError),
+    /// Credentials were given, but the server did not offer `AUTH PLAIN`.
+    #[display("AUTH PLAIN not offered")]
+    AuthUnavailable,
+    /// The server refused `AUTH`, `MAIL`, every recipient, or the message, or closed the connection
+    /// with `421`.
+    #[display("Refused with {}", reply.code())]
+    Refused {
+        /// The server's reply.
+        reply: Reply,
+    },
+}
+
+impl From<io::Error> for SendError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ReplyParseError> for SendError {
+    fn from(error: ReplyParseError) -> Self {
+        Self::Reply(error)
+    }
+}
+
+impl From<ClientError> for SendError {
+    fn from(error: ClientError) -> Self {
+        Self::Session(error)
+    }
+}
+
+/// Send `envelope` to the server at `server`, for when sending mail is all you need.
+///
+/// Connects over TCP and runs a [`ClientSession`] to completion: `EHLO`, `STARTTLS` as configured
+/// by `tls`, `AUTH PLAIN` if `credentials` are given, then the transaction, pipelined if the
+/// server advertised `PIPELINING` and sent with `BDAT` if it advertised `CHUNKING`, and `QUIT`.
+/// The client identifies itself with the address literal of its end of the connection.
+///
+/// There are no timeouts; wrap the future in `tokio::time::timeout` as needed.
+///
+/// ```rust,no_run
+/// # use bytes::Bytes;
+/// # use smtpkit::{Command, Envelope, NoTls, Tls, send};
+/// # async fn run() -> Result<(), smtpkit::SendError> {
+/// # let Ok(Command::Mail(mail)) = Command::try_from(Bytes::from("MAIL FROM:<bob@example.com>"))
+/// # else {
+/// #     unreachable!();
+/// # };
+/// # let Ok(Command::Rcpt(rcpt)) = Command::try_from(Bytes::from("RCPT TO:<alice@example.com>"))
+/// # else {
+/// #     unreachable!();
+/// # };
+/// let envelope = Envelope {
+///     mail: *mail,
+///     rcpts: vec![*rcpt],
+///     payload: Bytes::from("Subject: Hi!\r\n\r\nHi Alice!").into(),
+/// };
+/// // to authenticate, pass `Tls::Required` with a `TlsConnector`, and `Some(&Credentials)`
+/// let delivery = send(&envelope, "mx.example.com:587", Tls::<NoTls>::Disabled, None).await?;
+/// assert!(delivery.refused.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// See [`SendError`]. Refused recipients are not an error unless all are; see
+/// [`Delivery::refused`].
+pub async fn send<C: TlsConnector<TcpStream>>(
+    envelope: &Envelope,
+    server: impl ToSocketAddrs,
+    tls: Tls<C>,
+    credentials: Option<&Credentials>,
+) -> Result<Delivery, SendError> {
+    let stream = TcpStream::connect(server).await?;
+    let hostname = Host::Ip(stream.local_addr()?.ip());
+    deliver(stream, hostname, envelope, tls, credentials).await
+}
+
+/// [`send`] over `stream`.
+async fn deliver<S, C>(
+    stream: S,
+    hostname: Host,
+    envelope: &Envelope,
+    tls: Tls<C>,
+    credentials: Option<&Credentials>,
+) -> Result<Delivery, SendError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    C: TlsConnector<S>,
+{
+    let (connector, require_tls) = match tls {
+        Tls::Disabled => (None, false),
+        Tls::Opportunistic(connector) => (Some(connector), false),
+        Tls::Required(connector) => (Some(connector), true),
+    };
+    let mut session = ClientSession::new(hostname)
+        .with_starttls(connector.is_some())
+        .with_require_tls(require_tls);
+
+    let mut connection = Connection::new(stream);
+    let greeting = connection.read().await?;
+    let event = session.receive(greeting)?;
+    match (connection.negotiate(&mut session, event).await?, connector) {
+        (ClientEvent::StartTlsNegotiated, Some(connector)) => {
+            let stream = connector.connect(connection.into_stream()?).await?;
+            let mut connection = Connection::new(stream);
+            let ehlo = session.tls_established()?;
+            connection
+                .negotiate(&mut session, ClientEvent::Send(ehlo))
+                .await?;
+            connection.run(&mut session, envelope, credentials).await
+        }
+        _ => connection.run(&mut session, envelope, credentials).await,
+    }
+}
+
+/// A connection to the server, with the replies read but not yet parsed.
+struct Connection<S> {
+    stream: S,
+    buf: BytesMut,
+    parser: ReplyParser,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buf: BytesMut::with_capacity(4096),
+            parser: ReplyParser::default(),
+        }
+    }
+
+    /// The stream, to perform the TLS handshake over.
+    fn into_stream(self) -> io::Result<S> {
+        // anything sent before the handshake could be injected by an attacker
+        // https://datatracker.ietf.org/doc/html/rfc3207#section-6
+        if !self.buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "data after the reply to STARTTLS",
+            ));
+        }
+
+        Ok(self.stream)
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    async fn read(&mut self) -> Result<Reply, SendError> {
+        loop {
+            if let Some(reply) = self.parser.parse(&mut self.buf)? {
+                return Ok(reply);
+            }
+
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+
+    /// Send the commands `session` sequences itself, starting from `event`, until it is ready or
+    /// has negotiated `STARTTLS`.
+    async fn negotiate(
+        &mut self,
+        session: &mut ClientSession,
+        mut event: ClientEvent,
+    ) -> Result<ClientEvent, SendError> {
+        while let ClientEvent::Send(command) = event {
+            self.write(&command.to_bytes()).await?;
+            let reply = self.read().await?;
+            event = session.receive(reply)?;
+        }
+
+        match event {
+            ClientEvent::Closing(reply) => Err(SendError::Refused { reply }),
+            event => Ok(event),
+        }
+    }
+
+    /// The reply to a command sent with [`ClientSession::send`].
+    async fn reply(&mut self, session: &mut ClientSession) -> Result<Reply, SendError> {
+        let reply = self.read().await?;
+        match session.receive(reply)? {
+            ClientEvent::Reply(reply) => Ok(reply),
+            ClientEvent::Closing(reply) => Err(SendError::Refused { reply }),
+            _ => Err(ClientError::Sequence.into()),
+        }
+    }
+
+    /// Authenticate and send `envelope`, then `QUIT`, unless the connection failed.
+    async fn run(
+        &mut self,
+        session: &mut ClientSession,
+        envelope: &Envelope,
+        credentials: Option<&Credentials>,
+    ) -> Result<Delivery, SendError> {
+        let result = match credentials {
+            Some(credentials) => match self.authenticate(session, credentials).await {
+                Ok(()) => self.transaction(session, envelope).await,
+                Err(e) => Err(e),
+            },
+            None => self.transaction(session, envelope).await,
+        };
+
+        if matches!(result, Ok(_) | Err(SendError::Refused { .. }))
+            && session.pending() == 0
+            && !session.is_closed()
+        {
+            let quit = Command::Quit;
+            session.send(&quit)?;
+            self.write(&quit.to_bytes()).await?;
+            self.reply(session).await?;
+        }
+
+        result
+    }
+
+    async fn authenticate(
+        &mut self,
+        session: &mut ClientSession,
+        credentials: &Credentials,
+    ) -> Result<(), SendError> {
+        let offered = session
+            .capabilities()
+            .is_some_and(|capabilities| capabilities.auth().contains(&Mechanism::Plain));
+        if !offered {
+            return Err(SendError::AuthUnavailable);
+        }
+
+        let auth = credentials.plain();
+        session.send(&auth)?;
+        self.write(&auth.to_bytes()).await?;
+        match self.reply(session).await? {
+            reply if reply.is_positive_completion() => Ok(()),
+            reply => Err(SendError::Refused { reply }),
+        }
+    }
+
+    async fn transaction(
+        &mut self,
+        session: &mut ClientSession,
+        envelope: &Envelope,
+    ) -> Result<Delivery, SendError> {
+        session.check_size(envelope.payload.len())?;
+        let capabilities = session.capabilities().cloned().unwrap_or_default();
+
+        let mut delivery = None;
+        let mut refused = Vec::new();
+        let mut batch = Batch::new();
+        let mut group = Vec::new();
+        for command in envelope.commands(&capabilities, CHUNK_SIZE) {
+            session.send(&command)?;
+            let send = batch.push(&command) || !capabilities.pipelining();
+            group.push(command);
+            if !send {
+                continue;
+            }
+
+            self.write(&batch.take()).await?;
+            for command in group.drain(..) {
+                let reply = self.reply(session).await?;
+                match command {
+                    Command::Rcpt(rcpt) if !reply.is_positive_completion() => {
+                        refused.push((*rcpt, reply));
+                    }
+                    Command::Data(payload) if reply.code() == 354 => {
+                        batch.push_data(&payload);
+                        self.write(&batch.take()).await?;
+                        match self.reply(session).await? {
+                            reply if reply.is_positive_completion() => delivery = Some(reply),
+                            reply => return Err(SendError::Refused { reply }),
+                        }
+                    }
+                    _ if !reply.is_positive_completion() => {
+                        return Err(SendError::Refused { reply });
+                    }
+                    Command::Data(_) | Command::Bdat(_) => delivery = Some(reply),
+                    _ => {}
+                }
+            }
+        }
+
+        let reply = delivery.ok_or(ClientError::Sequence)?;
+        Ok(Delivery { reply, refused })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, duplex};
+
+    /// A [`TlsConnector`] that performs no handshake.
+    struct Passthrough;
+
+    impl<S: AsyncRead + AsyncWrite + Unpin + Send> TlsConnector<S> for Passthrough {
+        type Stream = S;
+
+        fn connect(&self, stream: S) -> impl Future<Output = io::Result<S>> + Send {
+            core::future::ready(Ok(stream))
+        }
+    }
+
+    fn envelope(rcpts: &[&'static str]) -> Envelope {
+        let Ok(Command::Mail(mail)) = Command::try_from(Bytes::from("MAIL FROM:<bob@example.com>"))
+        else {
+            unreachable!();
+        };
+        let rcpts = rcpts
+            .iter()
+            .map(|rcpt| match Command::try_from(Bytes::from(*rcpt)) {
+                Ok(Command::Rcpt(rcpt)) => *rcpt,
+                _ => unreachable!(),
+            })
+            .collect();
+        Envelope {
+            mail: *mail,
+            rcpts,
+            payload: Bytes::from("Hi!").into(),
+        }
+    }
+
+    /// Play the server: send each reply once the client has sent the given number of lines in
+    /// total, returning everything the client sent.
+    async fn serve(mut stream: DuplexStream, script: &[(usize, &'static str)]) -> String {
+        let mut received = Vec::new();
+        for &(lines, reply) in script {
+            while received.windows(2).filter(|w| *w == b"\r\n").count() < lines {
+                if stream.read_buf(&mut received).await.unwrap() == 0 {
+                    break;
+                }
+            }
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        }
+        String::from_utf8(received).unwrap()
+    }
+
+    async fn converse<C: TlsConnector<DuplexStream>>(
+        envelope: &Envelope,
+        tls: Tls<C>,
+        credentials: Option<&Credentials>,
+        script: &[(usize, &'static str)],
+    ) -> (Result<Delivery, SendError>, String) {
+        let (client, server) = duplex(64 * 1024);
+        let hostname = Host::Domain(Domain::from_static("client.example.com"));
+        tokio::join!(
+            deliver(client, hostname, envelope, tls, credentials),
+            serve(server, script)
+        )
+    }
+
+    #[tokio::test]
+    async fn pipelining() {
+        let envelope = envelope(&["RCPT TO:<alice@example.com>", "RCPT TO:<carol@example.com>"]);
+        let (delivery, sent) = converse(
+            &envelope,
+            Tls::<NoTls>::Disabled,
+            None,
+            &[
+                (0, "220 mx.example.com ESMTP\r\n"),
+                (
+                    1,
+                    "250-mx.example.com\r\n250-PIPELINING\r\n250 SIZE 1024\r\n",
+                ),
+                (
+                    5,
+                    "250 2.1.0 OK\r\n250 2.1.5 OK\r\n550 5.1.1 No such user\r\n354 Go ahead\r\n",
+                ),
+                (7, "250 2.0.0 OK: queued\r\n"),
+                (8, "221 2.0.0 Bye\r\n"),
+            ],
+        )
+        .await;
+
+        let delivery = delivery.unwrap();
+        assert_eq!(delivery.reply.lines(), ["2.0.0 OK: queued"]);
+        assert_eq!(delivery.refused.len(), 1);
+        assert_eq!(delivery.refused[0].0, envelope.rcpts[1]);
+        assert_eq!(
+            sent,
+            "EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+             RCPT TO:<alice@example.com>\r\nRCPT TO:<carol@example.com>\r\nDATA\r\n\
+             Hi!\r\n.\r\nQUIT\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn starttls() {
+        let credentials = Credentials::new(Bytes::from("user"), Bytes::from("pencil"));
+        let (delivery, sent) = converse(
+            &envelope(&["RCPT TO:<alice@example.com>"]),
+            Tls::Required(Passthrough),
+            Some(&credentials),
+            &[
+                (0, "220 mx.example.com ESMTP\r\n"),
+                (1, "250-mx.example.com\r\n250 STARTTLS\r\n"),
+                (2, "220 2.0.0 Ready to start TLS\r\n"),
+                (3, "250-mx.example.com\r\n250 AUTH PLAIN\r\n"),
+                (4, "235 2.7.0 Authentication successful\r\n"),
+                (5, "250 2.1.0 OK\r\n"),
+                (6, "250 2.1.5 OK\r\n"),
+                (7, "354 Go ahead\r\n"),
+                (9, "250 2.0.0 OK: queued\r\n"),
+                (10, "221 2.0.0 Bye\r\n"),
+            ],
+        )
+        .await;
+
+        assert!(delivery.unwrap().refused.is_empty());
+        assert_eq!(
+            sent,
+            "EHLO client.example.com\r\nSTARTTLS\r\nEHLO client.example.com\r\n\
+             AUTH PLAIN AHVzZXIAcGVuY2ls\r\nMAIL FROM:<bob@example.com>\r\n\
+             RCPT TO:<alice@example.com>\r\nDATA\r\nHi!\r\n.\r\nQUIT\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn tls_unavailable() {
+        let credentials = Credentials::new(Bytes::from("user"), Bytes::from("pencil"));
+        let (delivery, sent) = converse(
+            &envelope(&["RCPT TO:<alice@example.com>"]),
+            Tls::Required(Passthrough),
+            Some(&credentials),
+            &[
+                (0, "220 mx.example.com ESMTP\r\n"),
+                (1, "250-mx.example.com\r\n250 AUTH PLAIN\r\n"),
+            ],
+        )
+        .await;
+
+        assert!(matches!(
+            delivery,
+            Err(SendError::Session(ClientError::TlsUnavailable))
+        ));
+        // no credentials were sent
+        assert_eq!(sent, "EHLO client.example.com\r\n");
+    }
+
+    #[tokio::test]
+    async fn refused() {
+        let (delivery, sent) = converse(
+            &envelope(&["RCPT TO:<alice@example.com>"]),
+            Tls::<NoTls>::Disabled,
+            None,
+            &[
+                (0, "220 mx.example.com ESMTP\r\n"),
+                (1, "250 mx.example.com\r\n"),
+                (2, "550 5.7.1 Sender rejected\r\n"),
+                (3, "221 2.0.0 Bye\r\n"),
+            ],
+        )
+        .await;
+
+        let Err(SendError::Refused { reply }) = delivery else {
+            panic!("expected a refusal");
+        };
+        assert_eq!(reply.code(), 550);
+        assert_eq!(
+            sent,
+            "EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\nQUIT\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn closing() {
+        let (delivery, sent) = converse(
+            &envelope(&["RCPT TO:<alice@example.com>"]),
+            Tls::<NoTls>::Disabled,
+            None,
+            &[
+                (0, "220 mx.example.com ESMTP\r\n"),
+                (1, "250 mx.example.com\r\n"),
+                (2, "421 4.3.2 Service shutting down\r\n"),
+                // read until the client hangs up
+                (3, ""),
+            ],
+        )
+        .await;
+
+        let Err(SendError::Refused { reply }) = delivery else {
+            panic!("expected a refusal");
+        };
+        assert_eq!(reply.code(), 421);
+        // no `QUIT` after `421`
+        assert_eq!(
+            sent,
+            "EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n"
+        );
+    }
+}
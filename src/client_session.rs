@@ -0,0 +1,745 @@
+#![cfg(feature = "parse")]
+
+use core::time::Duration;
+
+use derive_more::Display;
+
+use crate::*;
+
+/// # Client Session Event
+///
+/// Returned by [`ClientSession::receive`]: what the client should do next.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub enum ClientEvent {
+    /// Send the command, which the session sequences itself, e.g. `EHLO`.
+    Send(Command),
+    /// The server accepted `STARTTLS`: perform the TLS handshake, then send the `EHLO` returned by
+    /// [`ClientSession::tls_established`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207#section-4.2>
+    StartTlsNegotiated,
+    /// The client has been greeted, with TLS if it was required, so mail transactions may begin;
+    /// see [`ClientSession::send`].
+    Ready,
+    /// The reply to a command sent with [`ClientSession::send`].
+    Reply(Reply),
+    /// The server sent `421` and is closing the connection, whatever the session was waiting for.
+    /// The session is closed: close the connection without sending `QUIT`, and retry any
+    /// unfinished transaction later.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5321#section-3.8>
+    Closing(Reply),
+}
+
+/// # Client Session Error
+#[non_exhaustive]
+#[derive(Debug, Display, derive_more::Error, Clone, PartialEq, Eq, Hash)]
+pub enum ClientError {
+    /// The server refused the greeting, `EHLO` and `HELO`, or a required `STARTTLS`.
+    #[display("Refused with {}", reply.code())]
+    Refused {
+        /// The server's reply.
+        reply: Reply,
+    },
+    /// TLS is required, but the server did not offer `STARTTLS`.
+    #[display("STARTTLS not offered")]
+    TlsUnavailable,
+    /// `AUTH` before TLS, unless allowed by [`ClientSession::with_plaintext_auth`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc4954#section-4>
+    #[display("Refusing to send credentials before TLS")]
+    TlsRequired,
+    /// The message is larger than the maximum the server advertised with `SIZE`, so sending it
+    /// would only be refused with `552`.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870#section-6>
+    #[display("Message size {size} exceeds the server's maximum of {limit}")]
+    TooLarge {
+        /// The size of the message.
+        size: usize,
+        /// The maximum the server advertised.
+        limit: usize,
+    },
+    /// A reply the session was not waiting for, or a command it sequences itself or cannot send
+    /// yet, or any reply or command after [`ClientEvent::Closing`].
+    #[display("Out of sequence")]
+    Sequence,
+}
+
+/// # Keepalive Advice
+///
+/// Returned by [`ClientSession::keepalive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keepalive {
+    /// Nothing to do yet; ask again after this long idle.
+    Wait(Duration),
+    /// Send `NOOP` to keep the connection alive.
+    Noop,
+    /// Idle too long; send `QUIT` rather than keep the connection.
+    Quit,
+}
+
+/// # Idle Connection Policy
+///
+/// When to send `NOOP` while a [`ClientSession`] is idle between transactions, e.g. in a
+/// connection pool, and when to give up and `QUIT`; see [`ClientSession::keepalive`].
+///
+/// The default sends `NOOP` after each minute idle, well within the five minutes a server may
+/// wait for a command, and quits once four have gone unanswered by a transaction.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.3.2.7>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdlePolicy {
+    interval: Duration,
+    max_noops: u32,
+}
+
+impl IdlePolicy {
+    const DEFAULT: Self = Self::new(Duration::from_secs(60), 4);
+
+    /// Create an `IdlePolicy` sending `NOOP` after each `interval` idle, and quitting instead once
+    /// `max_noops` have been sent since the last other command.
+    #[must_use]
+    pub const fn new(interval: Duration, max_noops: u32) -> Self {
+        Self {
+            interval,
+            max_noops,
+        }
+    }
+}
+
+impl Default for IdlePolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// # `ClientSession` State
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum State {
+    /// Awaiting the `220` greeting.
+    Greeting,
+    /// Awaiting the reply to `EHLO`.
+    Ehlo,
+    /// Awaiting the reply to `HELO`, after `EHLO` was not recognized.
+    Helo,
+    /// Awaiting the reply to `STARTTLS`.
+    StartTls,
+    /// Awaiting [`ClientSession::tls_established`].
+    Handshake,
+    /// Greeted; commands may be sent.
+    Ready,
+    /// The server sent `421`.
+    Closed,
+}
+
+/// # Client Session State Machine
+///
+/// The client-side counterpart of [`Session`]: sequences the greeting, `EHLO`, and `STARTTLS`
+/// from the replies fed to [`ClientSession::receive`], then tracks the commands the client sends
+/// with [`ClientSession::send`]. Parse replies with a [`ReplyParser`].
+///
+/// If the server does not recognize `EHLO`, the session falls back to `HELO`, and no extensions
+/// are used.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{ClientEvent, ClientSession, Command, Domain, Host, Reply, ToBytes};
+/// let mut session = ClientSession::new(Host::Domain(Domain::from_static("client.example.com")))
+///     .with_require_tls(true);
+///
+/// let event = session.receive(Reply::new(220, Bytes::from("mx.example.com ESMTP"))?)?;
+/// assert_eq!(event, ClientEvent::Send(session.ehlo()));
+///
+/// let ehlo = Reply::multiline(250, ["mx.example.com", "STARTTLS"].map(Bytes::from))?;
+/// assert_eq!(session.receive(ehlo)?, ClientEvent::Send(Command::StartTls));
+///
+/// let event = session.receive(Reply::new(220, Bytes::from("Go ahead"))?)?;
+/// assert_eq!(event, ClientEvent::StartTlsNegotiated);
+/// // ... perform the TLS handshake ...
+/// let ehlo = session.tls_established()?;
+/// assert_eq!(ehlo.to_bytes(), "EHLO client.example.com\r\n");
+///
+/// let ehlo = Reply::multiline(250, ["mx.example.com", "AUTH PLAIN"].map(Bytes::from))?;
+/// assert_eq!(session.receive(ehlo)?, ClientEvent::Ready);
+/// assert!(session.is_tls());
+/// # Ok::<(), Box<dyn core::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientSession {
+    hostname: Host,
+    state: State,
+    starttls: bool,
+    require_tls: bool,
+    plaintext_auth: bool,
+    tls: bool,
+    capabilities: Option<Capabilities>,
+    /// Commands sent with `send` still awaiting a reply.
+    pending: usize,
+    idle: IdlePolicy,
+    /// `NOOP`s sent since the last other command.
+    noops: u32,
+}
+
+impl ClientSession {
+    /// Create a `ClientSession` identifying the client as `hostname`, awaiting the greeting.
+    #[must_use]
+    pub const fn new(hostname: Host) -> Self {
+        Self {
+            hostname,
+            state: State::Greeting,
+            starttls: true,
+            require_tls: false,
+            plaintext_auth: false,
+            tls: false,
+            capabilities: None,
+            pending: 0,
+            idle: IdlePolicy::DEFAULT,
+            noops: 0,
+        }
+    }
+
+    /// Set whether to send `STARTTLS` when the server offers it. Enabled by default.
+    #[must_use]
+    pub const fn with_starttls(mut self, starttls: bool) -> Self {
+        self.starttls = starttls;
+        self
+    }
+
+    /// Require TLS: fail with [`ClientError::TlsUnavailable`] if the server does not offer
+    /// `STARTTLS`, or [`ClientError::Refused`] if it refuses it, instead of carrying on in
+    /// plaintext.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207#section-4.1>
+    #[must_use]
+    pub const fn with_require_tls(mut self, require_tls: bool) -> Self {
+        self.require_tls = require_tls;
+        self
+    }
+
+    /// Start with TLS already established, for implicit TLS (SMTPS, usually port 465), so
+    /// `STARTTLS` is never sent, even if offered, and TLS is not required of the server.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc8314#section-3>
+    #[must_use]
+    pub const fn with_implicit_tls(mut self, implicit_tls: bool) -> Self {
+        self.tls = implicit_tls;
+        self
+    }
+
+    /// Allow `AUTH` before TLS, sending credentials in plaintext. Refused with
+    /// [`ClientError::TlsRequired`] by default.
+    #[must_use]
+    pub const fn with_plaintext_auth(mut self, plaintext_auth: bool) -> Self {
+        self.plaintext_auth = plaintext_auth;
+        self
+    }
+
+    /// Use `idle` to advise on keeping the connection alive. See [`IdlePolicy::default`].
+    #[must_use]
+    pub const fn with_idle_policy(mut self, idle: IdlePolicy) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// The `EHLO` identifying the client.
+    #[must_use]
+    pub fn ehlo(&self) -> Command {
+        Command::Ehlo(self.hostname.clone())
+    }
+
+    /// Whether TLS has been established.
+    #[must_use]
+    pub const fn is_tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Whether the session is ready for commands, see [`ClientEvent::Ready`].
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.state == State::Ready
+    }
+
+    /// Whether the server closed the session with `421`, see [`ClientEvent::Closing`].
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    /// The commands sent with [`ClientSession::send`] still awaiting a reply.
+    #[must_use]
+    pub const fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// What the server advertised in its reply to the latest `EHLO`, with no extensions after a
+    /// fallback to `HELO`.
+    #[must_use]
+    pub const fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+
+    /// Check a message of `size` bytes against the maximum the server advertised with `SIZE`, if
+    /// any. [`ClientSession::send`] checks the `SIZE` parameter of `MAIL` itself.
+    ///
+    /// # Errors
+    ///
+    /// [`ClientError::TooLarge`] if the message exceeds the maximum.
+    pub fn check_size(&self, size: usize) -> Result<(), ClientError> {
+        match self.capabilities.as_ref().and_then(Capabilities::size) {
+            // `0` is no fixed maximum
+            Some(limit) if limit > 0 && size > limit => Err(ClientError::TooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Feed the next reply from the server.
+    ///
+    /// A positive intermediate reply, e.g. `354` to `DATA` or `334` to `AUTH`, leaves the command
+    /// awaiting its final reply. A `421` closes the session in any state; see
+    /// [`ClientEvent::Closing`].
+    ///
+    /// # Errors
+    ///
+    /// [`ClientError::Refused`] if the server refused the greeting, `EHLO` and `HELO`, or a
+    /// required `STARTTLS`; [`ClientError::TlsUnavailable`] if TLS is required but not offered; or
+    /// [`ClientError::Sequence`] if no reply was expected.
+    pub fn receive(&mut self, reply: Reply) -> Result<ClientEvent, ClientError> {
+        if reply.code() == 421 && self.state != State::Closed {
+            self.state = State::Closed;
+            self.pending = 0;
+            return Ok(ClientEvent::Closing(reply));
+        }
+
+        match self.state {
+            State::Greeting if reply.code() == 220 => {
+                self.state = State::Ehlo;
+                Ok(ClientEvent::Send(self.ehlo()))
+            }
+            State::Ehlo if reply.is_positive_completion() => {
+                self.capabilities = Capabilities::parse(&reply);
+                self.negotiated()
+            }
+            // https://datatracker.ietf.org/doc/html/rfc5321#section-3.2
+            State::Ehlo if matches!(reply.code(), 500 | 502) => {
+                self.state = State::Helo;
+                Ok(ClientEvent::Send(Command::Helo(self.hostname.clone())))
+            }
+            State::Helo if reply.is_positive_completion() => {
+                self.capabilities = Capabilities::parse(&reply)
+                    .map(|capabilities| Capabilities::new(capabilities.domain().clone()));
+                self.negotiated()
+            }
+            State::StartTls if reply.code() == 220 => {
+                self.state = State::Handshake;
+                Ok(ClientEvent::StartTlsNegotiated)
+            }
+            State::StartTls if !self.require_tls => {
+                self.state = State::Ready;
+                Ok(ClientEvent::Ready)
+            }
+            State::Greeting | State::Ehlo | State::Helo | State::StartTls => {
+                Err(ClientError::Refused { reply })
+            }
+            State::Ready if self.pending > 0 => {
+                if !reply.is_positive_intermediate() {
+                    self.pending -= 1;
+                }
+                Ok(ClientEvent::Reply(reply))
+            }
+            State::Handshake | State::Ready | State::Closed => Err(ClientError::Sequence),
+        }
+    }
+
+    /// Send `STARTTLS` if wanted and offered, otherwise become ready, unless TLS is required.
+    fn negotiated(&mut self) -> Result<ClientEvent, ClientError> {
+        let offered = self
+            .capabilities
+            .as_ref()
+            .is_some_and(Capabilities::starttls);
+        if !self.tls && self.starttls && offered {
+            self.state = State::StartTls;
+            return Ok(ClientEvent::Send(Command::StartTls));
+        }
+
+        if self.require_tls && !self.tls {
+            return Err(ClientError::TlsUnavailable);
+        }
+
+        self.state = State::Ready;
+        Ok(ClientEvent::Ready)
+    }
+
+    /// Advise on keeping the connection alive after `idle` without a command or reply, as timed by
+    /// the caller, or `None` unless the session is ready with no reply outstanding; waiting for a
+    /// reply is subject to the caller's reply timeouts instead.
+    ///
+    /// A `NOOP` sent on [`Keepalive::Noop`] is sent with [`ClientSession::send`] as usual, after
+    /// which the caller restarts its idle timer.
+    #[must_use]
+    pub fn keepalive(&self, idle: Duration) -> Option<Keepalive> {
+        if self.state != State::Ready || self.pending > 0 {
+            return None;
+        }
+
+        Some(match self.idle.interval.checked_sub(idle) {
+            Some(wait) if !wait.is_zero() => Keepalive::Wait(wait),
+            _ if self.noops >= self.idle.max_noops => Keepalive::Quit,
+            _ => Keepalive::Noop,
+        })
+    }
+
+    /// Record that the TLS handshake following [`ClientEvent::StartTlsNegotiated`] has completed,
+    /// returning the `EHLO` to send next.
+    ///
+    /// Everything learned from the server before the handshake is discarded, as it must be asked
+    /// again.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207#section-4.2>
+    ///
+    /// # Errors
+    ///
+    /// [`ClientError::Sequence`] if no handshake was negotiated.
+    pub fn tls_established(&mut self) -> Result<Command, ClientError> {
+        if self.state != State::Handshake {
+            return Err(ClientError::Sequence);
+        }
+
+        self.tls = true;
+        self.capabilities = None;
+        self.state = State::Ehlo;
+        Ok(self.ehlo())
+    }
+
+    /// Check that `command` may be sent now, and record that it was, so its reply is expected.
+    ///
+    /// # Errors
+    ///
+    /// [`ClientError::TlsRequired`] for `AUTH` before TLS, see
+    /// [`ClientSession::with_plaintext_auth`]; [`ClientError::TooLarge`] for `MAIL` declaring a
+    /// `SIZE` over the server's maximum, see [`ClientSession::check_size`]; or
+    /// [`ClientError::Sequence`] before [`ClientEvent::Ready`], or for a command the session
+    /// sequences itself, e.g. `STARTTLS`.
+    pub fn send(&mut self, command: &Command) -> Result<(), ClientError> {
+        match command {
+            _ if self.state != State::Ready => Err(ClientError::Sequence),
+            Command::Helo(_) | Command::Ehlo(_) | Command::StartTls => Err(ClientError::Sequence),
+            Command::Auth(_) if !self.tls && !self.plaintext_auth => Err(ClientError::TlsRequired),
+            _ => {
+                if let Command::Mail(mail) = command
+                    && let Some(size) = mail.size
+                {
+                    self.check_size(size)?;
+                }
+                self.noops = match command {
+                    Command::Noop => self.noops.saturating_add(1),
+                    _ => 0,
+                };
+                self.pending += 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn session() -> ClientSession {
+        ClientSession::new(Host::Domain(Domain::from_static("client.example.com")))
+    }
+
+    fn reply(code: u16, lines: &[&'static str]) -> Reply {
+        Reply::multiline(code, lines.iter().copied().map(Bytes::from)).unwrap()
+    }
+
+    fn auth() -> Command {
+        Command::Auth(auth::Auth::new(Mechanism::Plain))
+    }
+
+    /// Greet `session`, answering `EHLO` with `ehlo`.
+    fn greet(
+        session: &mut ClientSession,
+        ehlo: &[&'static str],
+    ) -> Result<ClientEvent, ClientError> {
+        assert_eq!(
+            session.receive(reply(220, &["mx.example.com ESMTP"])),
+            Ok(ClientEvent::Send(session.ehlo()))
+        );
+        session.receive(reply(250, ehlo))
+    }
+
+    #[test]
+    fn starttls() {
+        let mut session = session().with_require_tls(true);
+        assert_eq!(
+            greet(&mut session, &["mx.example.com", "STARTTLS"]),
+            Ok(ClientEvent::Send(Command::StartTls))
+        );
+        assert_eq!(session.send(&auth()), Err(ClientError::Sequence));
+        assert_eq!(session.tls_established(), Err(ClientError::Sequence));
+
+        assert_eq!(
+            session.receive(reply(220, &["Go ahead"])),
+            Ok(ClientEvent::StartTlsNegotiated)
+        );
+        assert_eq!(
+            session.receive(reply(250, &["OK"])),
+            Err(ClientError::Sequence)
+        );
+
+        // a fresh `EHLO` is required, and its capabilities replace the old
+        assert_eq!(session.tls_established(), Ok(session.ehlo()));
+        assert!(session.capabilities().is_none());
+        assert!(!session.is_ready());
+        assert_eq!(
+            session.receive(reply(250, &["mx.example.com", "STARTTLS", "AUTH PLAIN"])),
+            Ok(ClientEvent::Ready)
+        );
+        assert!(session.is_tls());
+        assert_eq!(session.capabilities().unwrap().auth(), [Mechanism::Plain]);
+        assert_eq!(session.send(&auth()), Ok(()));
+    }
+
+    #[rstest]
+    #[case::ready(&["mx.example.com", "STARTTLS", "AUTH PLAIN"])]
+    #[case::not_offered(&["mx.example.com", "AUTH PLAIN"])]
+    fn implicit_tls(#[case] ehlo: &[&'static str]) {
+        let mut session = session().with_implicit_tls(true).with_require_tls(true);
+        assert!(session.is_tls());
+        assert_eq!(greet(&mut session, ehlo), Ok(ClientEvent::Ready));
+        assert_eq!(session.tls_established(), Err(ClientError::Sequence));
+        assert_eq!(session.send(&Command::StartTls), Err(ClientError::Sequence));
+        assert_eq!(session.send(&auth()), Ok(()));
+        session.receive(reply(235, &["2.7.0 OK"])).unwrap();
+        let mail = Command::try_from(Bytes::from("MAIL FROM:<bob@example.com>")).unwrap();
+        assert_eq!(session.send(&mail), Ok(()));
+    }
+
+    #[rstest]
+    #[case::not_offered(true, &["mx.example.com"], Err(ClientError::TlsUnavailable))]
+    #[case::opportunistic(false, &["mx.example.com"], Ok(ClientEvent::Ready))]
+    fn tls_unavailable(
+        #[case] require_tls: bool,
+        #[case] ehlo: &[&'static str],
+        #[case] expected: Result<ClientEvent, ClientError>,
+    ) {
+        let mut session = session().with_require_tls(require_tls);
+        assert_eq!(greet(&mut session, ehlo), expected);
+    }
+
+    #[test]
+    fn starttls_refused() {
+        let mut session = session().with_require_tls(true);
+        greet(&mut session, &["mx.example.com", "STARTTLS"]).unwrap();
+        let refused = reply(454, &["TLS not available"]);
+        assert_eq!(
+            session.receive(refused.clone()),
+            Err(ClientError::Refused { reply: refused })
+        );
+
+        let mut session = session();
+        greet(&mut session, &["mx.example.com", "STARTTLS"]).unwrap();
+        assert_eq!(
+            session.receive(reply(454, &["TLS not available"])),
+            Ok(ClientEvent::Ready)
+        );
+        assert!(!session.is_tls());
+    }
+
+    #[test]
+    fn plaintext_auth() {
+        let mut session = session().with_starttls(false);
+        assert_eq!(
+            greet(&mut session, &["mx.example.com", "STARTTLS"]),
+            Ok(ClientEvent::Ready)
+        );
+        assert_eq!(session.send(&auth()), Err(ClientError::TlsRequired));
+
+        let mut session = session().with_starttls(false).with_plaintext_auth(true);
+        greet(&mut session, &["mx.example.com"]).unwrap();
+        assert_eq!(session.send(&auth()), Ok(()));
+    }
+
+    #[test]
+    fn replies() {
+        let mut session = session();
+        greet(&mut session, &["mx.example.com"]).unwrap();
+        assert_eq!(session.send(&Command::StartTls), Err(ClientError::Sequence));
+        assert_eq!(session.send(&Command::Data(Bytes::from("Hi!"))), Ok(()));
+        assert_eq!(session.send(&Command::Quit), Ok(()));
+        assert_eq!(session.pending(), 2);
+
+        let intermediate = reply(354, &["Go ahead"]);
+        assert_eq!(
+            session.receive(intermediate.clone()),
+            Ok(ClientEvent::Reply(intermediate))
+        );
+        assert_eq!(session.pending(), 2);
+        session.receive(reply(250, &["OK"])).unwrap();
+        session.receive(reply(221, &["Bye"])).unwrap();
+        assert_eq!(session.pending(), 0);
+        assert_eq!(
+            session.receive(reply(250, &["OK"])),
+            Err(ClientError::Sequence)
+        );
+    }
+
+    #[rstest]
+    #[case::unrecognized(500, false, Ok(ClientEvent::Ready))]
+    #[case::not_implemented(502, false, Ok(ClientEvent::Ready))]
+    #[case::require_tls(502, true, Err(ClientError::TlsUnavailable))]
+    fn helo(
+        #[case] code: u16,
+        #[case] require_tls: bool,
+        #[case] expected: Result<ClientEvent, ClientError>,
+    ) {
+        let mut session = session().with_require_tls(require_tls);
+        session
+            .receive(reply(220, &["mx.example.com ESMTP"]))
+            .unwrap();
+        assert_eq!(
+            session.receive(reply(code, &["Command not recognized"])),
+            Ok(ClientEvent::Send(Command::Helo(session.hostname.clone())))
+        );
+        assert_eq!(session.receive(reply(250, &["mx.example.com"])), expected);
+        if expected.is_ok() {
+            let capabilities = session.capabilities().unwrap();
+            assert_eq!(capabilities.domain(), "mx.example.com");
+            assert!(capabilities.extensions().is_empty());
+        }
+    }
+
+    #[test]
+    fn helo_refused() {
+        let mut session = session();
+        session
+            .receive(reply(220, &["mx.example.com ESMTP"]))
+            .unwrap();
+        let refused = reply(550, &["Go away"]);
+        assert_eq!(
+            session.receive(refused.clone()),
+            Err(ClientError::Refused { reply: refused })
+        );
+
+        let mut session = session();
+        session
+            .receive(reply(220, &["mx.example.com ESMTP"]))
+            .unwrap();
+        session
+            .receive(reply(502, &["Command not implemented"]))
+            .unwrap();
+        let refused = reply(501, &["Invalid domain"]);
+        assert_eq!(
+            session.receive(refused.clone()),
+            Err(ClientError::Refused { reply: refused })
+        );
+    }
+
+    #[rstest]
+    #[case::under(&["mx.example.com", "SIZE 1024"], 1024, Ok(()))]
+    #[case::over(
+        &["mx.example.com", "SIZE 1024"],
+        1025,
+        Err(ClientError::TooLarge { size: 1025, limit: 1024 })
+    )]
+    #[case::unlimited(&["mx.example.com", "SIZE 0"], 1025, Ok(()))]
+    #[case::not_advertised(&["mx.example.com"], 1025, Ok(()))]
+    fn size(
+        #[case] ehlo: &[&'static str],
+        #[case] size: usize,
+        #[case] expected: Result<(), ClientError>,
+    ) {
+        let mut session = session();
+        greet(&mut session, ehlo).unwrap();
+        assert_eq!(session.check_size(size), expected);
+
+        let mail = Command::try_from(Bytes::from(format!("MAIL FROM:<> SIZE={size}"))).unwrap();
+        assert_eq!(session.send(&mail), expected);
+        assert_eq!(session.pending(), usize::from(expected.is_ok()));
+    }
+
+    #[test]
+    fn keepalive() {
+        const MINUTE: Duration = Duration::from_secs(60);
+
+        let mut session = session().with_idle_policy(IdlePolicy::new(MINUTE, 2));
+        assert_eq!(session.keepalive(MINUTE), None);
+        greet(&mut session, &["mx.example.com"]).unwrap();
+        assert_eq!(
+            session.keepalive(Duration::ZERO),
+            Some(Keepalive::Wait(MINUTE))
+        );
+        assert_eq!(
+            session.keepalive(MINUTE / 4),
+            Some(Keepalive::Wait(MINUTE * 3 / 4))
+        );
+
+        for _ in 0..2 {
+            assert_eq!(session.keepalive(MINUTE), Some(Keepalive::Noop));
+            session.send(&Command::Noop).unwrap();
+            assert_eq!(session.keepalive(MINUTE), None);
+            session.receive(reply(250, &["OK"])).unwrap();
+        }
+        assert_eq!(
+            session.keepalive(MINUTE / 2),
+            Some(Keepalive::Wait(MINUTE / 2))
+        );
+        assert_eq!(session.keepalive(MINUTE), Some(Keepalive::Quit));
+
+        // a transaction resets the count
+        session.send(&Command::Rset).unwrap();
+        session.receive(reply(250, &["OK"])).unwrap();
+        assert_eq!(session.keepalive(2 * MINUTE), Some(Keepalive::Noop));
+    }
+
+    #[test]
+    fn closing() {
+        let closing = reply(421, &["4.3.2 Service shutting down"]);
+
+        let mut session = session();
+        greet(&mut session, &["mx.example.com", "PIPELINING"]).unwrap();
+        for command in ["MAIL FROM:<bob@example.com>", "RCPT TO:<alice@example.com>"] {
+            session
+                .send(&Command::try_from(Bytes::from(command)).unwrap())
+                .unwrap();
+        }
+        session.receive(reply(250, &["OK"])).unwrap();
+        assert_eq!(
+            session.receive(closing.clone()),
+            Ok(ClientEvent::Closing(closing.clone()))
+        );
+        assert!(session.is_closed());
+        assert!(!session.is_ready());
+        assert_eq!(session.pending(), 0);
+        assert_eq!(session.keepalive(Duration::from_secs(60)), None);
+        assert_eq!(session.send(&Command::Quit), Err(ClientError::Sequence));
+        assert_eq!(
+            session.receive(reply(250, &["OK"])),
+            Err(ClientError::Sequence)
+        );
+        assert_eq!(session.receive(closing.clone()), Err(ClientError::Sequence));
+
+        // instead of the greeting
+        let mut session = session();
+        assert_eq!(
+            session.receive(closing.clone()),
+            Ok(ClientEvent::Closing(closing))
+        );
+        assert!(session.is_closed());
+        assert_eq!(session.tls_established(), Err(ClientError::Sequence));
+    }
+
+    #[test]
+    fn refused() {
+        let mut session = session();
+        let refused = reply(554, &["No SMTP service here"]);
+        assert_eq!(
+            session.receive(refused.clone()),
+            Err(ClientError::Refused { reply: refused })
+        );
+    }
+}
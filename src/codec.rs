@@ -0,0 +1,94 @@
+#![cfg(feature = "codec")]
+
+use std::io;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::*;
+
+/// # Server Codec
+///
+/// A [`tokio_util::codec`] codec for the server side of an SMTP session. Decodes [`Command`]s
+/// using a [`Parser`], preserving its `DATA`/`BDAT` state across reads, and encodes anything that
+/// implements [`ToBytes`].
+///
+/// Parse errors are yielded as items rather than terminating the stream, so the session can reply
+/// and carry on.
+#[derive(Debug, Default)]
+pub struct ServerCodec(Parser);
+
+impl ServerCodec {
+    /// Create a `ServerCodec` using the given `Parser`.
+    #[must_use]
+    pub fn new(parser: Parser) -> Self {
+        Self(parser)
+    }
+
+    /// Get a reference to the underlying `Parser`.
+    #[must_use]
+    pub fn parser(&self) -> &Parser {
+        &self.0
+    }
+
+    /// Get a mutable reference to the underlying `Parser`, e.g. to
+    /// [cancel](Parser::cancel_data) a refused `DATA`.
+    #[must_use]
+    pub fn parser_mut(&mut self) -> &mut Parser {
+        &mut self.0
+    }
+
+    /// Consume the `ServerCodec`, returning the underlying `Parser`.
+    #[must_use]
+    pub fn into_parser(self) -> Parser {
+        self.0
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = Result<Command, Error>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.0.parse(src).transpose())
+    }
+}
+
+impl<T: ToBytes> Encoder<T> for ServerCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        item.to_bytes_into(dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let mut codec = ServerCodec::default();
+        let mut buf = BytesMut::from(&b"DATA\r\nHi!\r\n"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+
+        buf.extend_from_slice(b".\r\nFOO\r\n");
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Ok(Command::Data(Bytes::from_static(b"Hi!"))))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Err(Error::CommandNotImplemented))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn encode() {
+        let mut codec = ServerCodec::default();
+        let mut buf = BytesMut::new();
+        codec.encode(&b"250 OK\r\n"[..], &mut buf).unwrap();
+        assert_eq!(&buf[..], b"250 OK\r\n");
+    }
+}
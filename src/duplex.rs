@@ -0,0 +1,204 @@
+#![cfg(feature = "parse")]
+
+use derive_more::Display;
+
+use crate::*;
+
+/// # Duplex Direction
+///
+/// What a [`DuplexParser`] parses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Commands sent by the client, with a [`Parser`].
+    #[default]
+    Commands,
+    /// Replies sent by the server, with a [`ReplyParser`].
+    Replies,
+}
+
+/// # Duplex Message
+///
+/// Returned by [`DuplexParser::parse`].
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub enum Message {
+    /// A command, see [`Parser::parse`].
+    Command(Command),
+    /// A reply, see [`ReplyParser::parse`].
+    Reply(Reply),
+}
+
+/// # Duplex Parse Error
+///
+/// Returned by [`DuplexParser::parse`], from the parser of the current [`Direction`].
+#[derive(Debug, Display, derive_more::Error, Clone, PartialEq, Hash)]
+pub enum DuplexError {
+    /// From [`Parser::parse`].
+    #[display("{_0}")]
+    Command(Error),
+    /// From [`ReplyParser::parse`].
+    #[display("{_0}")]
+    Reply(ReplyParseError),
+}
+
+impl From<Error> for DuplexError {
+    fn from(error: Error) -> Self {
+        Self::Command(error)
+    }
+}
+
+impl From<ReplyParseError> for DuplexError {
+    fn from(error: ReplyParseError) -> Self {
+        Self::Reply(error)
+    }
+}
+
+/// # Duplex Parser
+///
+/// Parses either direction of a connection, for proxies and other tooling that sees both: a
+/// [`Parser`] for commands and a [`ReplyParser`] for replies behind one `parse`, switched with
+/// [`DuplexParser::set_direction`].
+///
+/// Each parser keeps its own state, so switching in the middle of a `DATA` payload or a
+/// multiline reply resumes it when switched back; see [`DuplexParser::is_idle`].
+///
+/// ```rust
+/// # use bytes::BytesMut;
+/// # use smtpkit::{Command, Direction, DuplexParser, Message};
+/// let mut parser = DuplexParser::default();
+/// let mut buf = BytesMut::from(&b"NOOP\r\n"[..]);
+/// assert_eq!(parser.parse(&mut buf)?, Some(Message::Command(Command::Noop)));
+///
+/// parser.set_direction(Direction::Replies);
+/// buf.extend_from_slice(b"250 2.0.0 OK\r\n");
+/// let Some(Message::Reply(reply)) = parser.parse(&mut buf)? else {
+///     unreachable!()
+/// };
+/// assert_eq!(reply.code(), 250);
+/// # Ok::<(), smtpkit::DuplexError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct DuplexParser {
+    commands: Parser,
+    replies: ReplyParser,
+    direction: Direction,
+}
+
+impl DuplexParser {
+    /// Create a `DuplexParser` parsing commands, with both parsers buffering at most `max` bytes.
+    #[must_use]
+    pub fn new(max: usize) -> Self {
+        Self::from_parsers(Parser::new(max), ReplyParser::new(max))
+    }
+
+    /// Create a `DuplexParser` parsing commands, from configured parsers.
+    #[must_use]
+    pub fn from_parsers(commands: Parser, replies: ReplyParser) -> Self {
+        Self {
+            commands,
+            replies,
+            direction: Direction::Commands,
+        }
+    }
+
+    /// Start in `direction`.
+    #[must_use]
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// What is being parsed.
+    #[must_use]
+    pub const fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Switch to parsing `direction`.
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    /// Whether neither parser is in the middle of a `DATA` payload or a multiline reply, so
+    /// switching direction leaves nothing to resume.
+    #[must_use]
+    pub const fn is_idle(&self) -> bool {
+        !self.commands.in_data() && !self.replies.in_reply()
+    }
+
+    /// The command parser.
+    #[must_use]
+    pub const fn commands(&self) -> &Parser {
+        &self.commands
+    }
+
+    /// The command parser, e.g. to call [`Parser::cancel_data`].
+    pub fn commands_mut(&mut self) -> &mut Parser {
+        &mut self.commands
+    }
+
+    /// The reply parser.
+    #[must_use]
+    pub const fn replies(&self) -> &ReplyParser {
+        &self.replies
+    }
+
+    /// The reply parser.
+    pub fn replies_mut(&mut self) -> &mut ReplyParser {
+        &mut self.replies
+    }
+
+    /// Read and parse bytes from the buffer in the current direction, returning the next complete
+    /// command or reply. As [`Parser::parse`] or [`ReplyParser::parse`].
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<Message>, DuplexError> {
+        match self.direction {
+            Direction::Commands => Ok(self.commands.parse(buf)?.map(Message::Command)),
+            Direction::Replies => Ok(self.replies.parse(buf)?.map(Message::Reply)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch() {
+        let mut parser = DuplexParser::new(1024).with_direction(Direction::Replies);
+        let mut buf = BytesMut::from(&b"250-mx.example.com\r\n"[..]);
+        assert_eq!(parser.parse(&mut buf), Ok(None));
+        assert!(!parser.is_idle());
+
+        // the multiline reply resumes once switched back
+        parser.set_direction(Direction::Commands);
+        buf.extend_from_slice(b"DATA\r\nHi!\r\n.\r\n");
+        assert_eq!(
+            parser.parse(&mut buf),
+            Ok(Some(Message::Command(Command::Data(Bytes::from("Hi!")))))
+        );
+
+        parser.set_direction(Direction::Replies);
+        buf.extend_from_slice(b"250 SIZE\r\n");
+        let Ok(Some(Message::Reply(reply))) = parser.parse(&mut buf) else {
+            panic!("expected a reply");
+        };
+        assert_eq!(reply.lines(), ["mx.example.com", "SIZE"]);
+        assert!(parser.is_idle());
+    }
+
+    #[test]
+    fn errors() {
+        let mut parser = DuplexParser::default();
+        let mut buf = BytesMut::from(&b"FOO\r\n"[..]);
+        assert_eq!(
+            parser.parse(&mut buf),
+            Err(DuplexError::Command(Error::CommandNotImplemented))
+        );
+
+        parser.set_direction(Direction::Replies);
+        buf.extend_from_slice(b"FOO\r\n");
+        assert_eq!(
+            parser.parse(&mut buf),
+            Err(DuplexError::Reply(ReplyParseError::InvalidSyntax))
+        );
+    }
+}
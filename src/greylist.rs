@@ -0,0 +1,256 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::time::Duration;
+
+use crate::*;
+#[cfg(feature = "parse")]
+use mail::ReversePath;
+
+/// # Greylisting Triplet
+///
+/// The client network, sender, and recipient a greylisting [`Policy`] keys its records by.
+///
+/// [`Triplet::new`] normalizes each part, so retries from a neighbouring address of the same
+/// server, or with the addresses cased differently, map to the same record. For other prefixes,
+/// build the `Triplet` with [`subnet`].
+///
+/// ```rust
+/// # use std::net::IpAddr;
+/// # use bytes::Bytes;
+/// # use smtpkit::{Email, greylist::Triplet, mail::ReversePath};
+/// let from = ReversePath::Email(Email::try_from(Bytes::from("Bob@Example.com"))?);
+/// let to = Email::try_from(Bytes::from("alice@EXAMPLE.com"))?;
+/// let triplet = Triplet::new([192, 0, 2, 7].into(), &from, &to);
+/// assert_eq!(triplet.client, IpAddr::from([192, 0, 2, 0]));
+/// assert_eq!(triplet.from, "bob@example.com");
+/// assert_eq!(triplet.to, "alice@example.com");
+/// # Ok::<(), smtpkit::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Triplet {
+    /// The client's network.
+    pub client: IpAddr,
+    /// The normalized sender, empty for the null reverse path.
+    pub from: Bytes,
+    /// The normalized recipient.
+    pub to: Bytes,
+}
+
+impl Triplet {
+    /// The prefix length an IPv4 client is masked to by [`Triplet::new`].
+    pub const V4_PREFIX: u32 = 24;
+    /// The prefix length an IPv6 client is masked to by [`Triplet::new`].
+    pub const V6_PREFIX: u32 = 64;
+
+    /// Create a `Triplet`, masking `client` to [`Triplet::V4_PREFIX`] or [`Triplet::V6_PREFIX`],
+    /// and normalizing `from` and `to` case-insensitively, see [`Email::normalized`].
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn new(client: IpAddr, from: &ReversePath, to: &Email) -> Self {
+        let from = match from {
+            ReversePath::Null => Bytes::new(),
+            ReversePath::Email(email) => email.normalized(true).bytes().clone(),
+        };
+
+        Self {
+            client: subnet(client, Self::V4_PREFIX, Self::V6_PREFIX),
+            from,
+            to: to.normalized(true).bytes().clone(),
+        }
+    }
+}
+
+/// Mask `ip` to its first `v4_prefix` or `v6_prefix` bits, for IPv4 and IPv6 respectively.
+///
+/// IPv4-mapped IPv6 addresses are treated as IPv4.
+///
+/// ```rust
+/// # use std::net::IpAddr;
+/// # use smtpkit::greylist::subnet;
+/// let ip: IpAddr = "2001:db8::1".parse().unwrap();
+/// assert_eq!(subnet(ip, 24, 32), "2001:db8::".parse::<IpAddr>().unwrap());
+/// ```
+#[must_use]
+pub fn subnet(ip: IpAddr, v4_prefix: u32, v6_prefix: u32) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - v4_prefix.min(32)).unwrap_or(0);
+            Ipv4Addr::from_bits(ip.to_bits() & mask).into()
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - v6_prefix.min(128)).unwrap_or(0);
+            Ipv6Addr::from_bits(ip.to_bits() & mask).into()
+        }
+    }
+}
+
+/// # Greylisting Verdict
+///
+/// Whether a [`Triplet`] may pass, given when it was first seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Verdict {
+    /// Seen long enough ago; carry on.
+    Pass,
+    /// Not seen before; record it and refuse temporarily.
+    New,
+    /// Retried too soon; refuse temporarily, for at least this much longer.
+    TooSoon(Duration),
+}
+
+impl Verdict {
+    /// The recommended reply code for a refusal.
+    pub const CODE: u16 = 451;
+
+    /// The verdict at `now` for a triplet `first_seen` at that time, or not at all, which must
+    /// have waited `delay` before it may pass.
+    ///
+    /// As with [`TokenBucket`], the times are [`Duration`]s since any fixed point.
+    #[must_use]
+    pub fn at(first_seen: Option<Duration>, delay: Duration, now: Duration) -> Self {
+        match first_seen {
+            None => Self::New,
+            // a delay past the end of time never elapses
+            Some(first_seen) => match first_seen.checked_add(delay) {
+                None => Self::TooSoon(Duration::MAX),
+                Some(until) => match until.saturating_sub(now) {
+                    Duration::ZERO => Self::Pass,
+                    wait => Self::TooSoon(wait),
+                },
+            },
+        }
+    }
+
+    /// Whether this is [`Verdict::Pass`].
+    #[must_use]
+    pub const fn is_pass(self) -> bool {
+        matches!(self, Self::Pass)
+    }
+
+    /// The [`Decision`] to return from a [`Policy`]: [`Decision::Accept`] for [`Verdict::Pass`],
+    /// otherwise [`Decision::TempFail`] with the text of [`Verdict::reply`].
+    #[must_use]
+    pub fn decision(self) -> Decision {
+        match self.text() {
+            None => Decision::Accept,
+            Some(text) => Decision::TempFail(Some(text)),
+        }
+    }
+
+    /// The recommended reply, without the CRLF, or `None` for [`Verdict::Pass`].
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use smtpkit::greylist::Verdict;
+    /// assert_eq!(
+    ///     Verdict::TooSoon(Duration::from_millis(90_500)).reply().unwrap(),
+    ///     "451 4.7.1 Greylisted, try again in 91 seconds"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn reply(self) -> Option<Bytes> {
+        let text = self.text()?;
+        let mut buf = itoa::Buffer::new();
+        Some(
+            [buf.format(Self::CODE).as_bytes(), b" ", &text]
+                .concat()
+                .into(),
+        )
+    }
+
+    fn text(self) -> Option<Bytes> {
+        match self {
+            Self::Pass => None,
+            Self::New => Some(Bytes::from_static(b"4.7.1 Greylisted, try again later")),
+            Self::TooSoon(wait) => {
+                // round up, so a client waiting as long as it is told is not refused again
+                let secs = wait
+                    .as_secs()
+                    .saturating_add(u64::from(wait.subsec_nanos() > 0));
+                let mut buf = itoa::Buffer::new();
+                Some(
+                    [
+                        &b"4.7.1 Greylisted, try again in "[..],
+                        buf.format(secs).as_bytes(),
+                        b" seconds",
+                    ]
+                    .concat()
+                    .into(),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[rstest]
+    #[case::v4("192.0.2.200", 24, "192.0.2.0")]
+    #[case::v4_host("192.0.2.200", 32, "192.0.2.200")]
+    #[case::v4_all("192.0.2.200", 0, "0.0.0.0")]
+    #[case::v4_mapped("::ffff:192.0.2.200", 24, "192.0.2.0")]
+    #[case::v6("2001:db8:1:2:3::1", 64, "2001:db8:1:2::")]
+    #[case::v6_long("2001:db8::1", 200, "2001:db8::1")]
+    fn subnet(#[case] ip: &str, #[case] prefix: u32, #[case] expected: &str) {
+        let ip: IpAddr = ip.parse().unwrap();
+        let expected: IpAddr = expected.parse().unwrap();
+        assert_eq!(super::subnet(ip, prefix, prefix), expected);
+    }
+
+    #[test]
+    fn triplet() {
+        let to = Email::try_from(Bytes::from("Alice@Example.com")).unwrap();
+        let a = Triplet::new([192, 0, 2, 1].into(), &ReversePath::Null, &to);
+        let b = Triplet::new(
+            [192, 0, 2, 254].into(),
+            &ReversePath::Null,
+            &Email::try_from(Bytes::from("alice@example.COM")).unwrap(),
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.from, "");
+
+        let c = Triplet::new([192, 0, 3, 1].into(), &ReversePath::Null, &to);
+        assert_ne!(a, c);
+    }
+
+    #[rstest]
+    #[case::new(None, MINUTE, Verdict::New)]
+    #[case::too_soon(Some(Duration::ZERO), MINUTE, Verdict::TooSoon(4 * MINUTE))]
+    #[case::exact(Some(Duration::ZERO), 5 * MINUTE, Verdict::Pass)]
+    #[case::pass(Some(Duration::ZERO), 60 * MINUTE, Verdict::Pass)]
+    fn verdict(
+        #[case] first_seen: Option<Duration>,
+        #[case] now: Duration,
+        #[case] expected: Verdict,
+    ) {
+        assert_eq!(Verdict::at(first_seen, 5 * MINUTE, now), expected);
+    }
+
+    #[test]
+    fn verdict_overflow() {
+        assert_eq!(
+            Verdict::at(Some(MINUTE), Duration::MAX, 2 * MINUTE),
+            Verdict::TooSoon(Duration::MAX)
+        );
+        assert!(Verdict::TooSoon(Duration::MAX).reply().is_some());
+    }
+
+    #[test]
+    fn reply() {
+        assert_eq!(Verdict::Pass.reply(), None);
+        assert_eq!(Verdict::Pass.decision(), Decision::Accept);
+        assert_eq!(
+            Verdict::New.reply().unwrap(),
+            "451 4.7.1 Greylisted, try again later"
+        );
+        assert_eq!(
+            Verdict::TooSoon(MINUTE).decision(),
+            Decision::TempFail(Some(Bytes::from(
+                "4.7.1 Greylisted, try again in 60 seconds"
+            )))
+        );
+    }
+}
@@ -12,13 +12,40 @@
 //!   and protocol handlers.
 //!   - ✔️ Enabled by default.
 //!   - 🔋 Includes a ready-to-use [`Parser`] that can also serve as an example of how to use `parse`.
+//! - 🔌 **`codec`:** Enables [`ServerCodec`], a [`tokio_util::codec`] codec that decodes commands
+//!   and encodes replies.
+//!   - ➕ Enables `std` and `parse`.
+//! - 🖥️ **`server`:** Enables [`Server`], a minimal [`tokio`](https://docs.rs/tokio) runtime that
+//!   runs connections through a [`ServerCodec`] and [`Session`], for when a working server is all
+//!   you need.
+//!   - ➕ Enables `codec`.
+//! - 📤 **`client`:** Enables [`send`], a minimal [`tokio`](https://docs.rs/tokio) client that
+//!   delivers an [`Envelope`] with a [`ClientSession`], handling `STARTTLS` and `AUTH`, for when
+//!   sending mail is all you need.
+//!   - ➕ Enables `std` and `parse`.
+//! - 🗼 **`tower`:** Enables [`SessionService`], a [`tower_service::Service`] from commands to
+//!   replies sequenced by a [`Session`], so `tower` middleware can wrap SMTP handling.
+//! - 📈 **`metrics`:** Emits counters and histograms from [`Parser`] and [`Session`] via the
+//!   [`metrics`](https://docs.rs/metrics) facade.
+//!   - ➕ Enables `std`.
+//! - 🙈 **`redact`:** Redacts addresses, `AUTH` data, and other user input in `Debug` output and
+//!   tracing events, e.g. `"b***@example.com"` or `[12 bytes]`, so servers can log at debug level
+//!   without leaking personal data or credentials.
+//! - 🔐 **`scram`:** Enables the [`scram`](crate::scram) module, implementing the `SCRAM-SHA-1` and
+//!   `SCRAM-SHA-256` SASL mechanisms.
+//!   - ➕ Enables `parse`.
+//! - 🧪 **`test_vectors`:** Enables the `test_vectors` module, a corpus of valid and invalid command
+//!   lines with expected results, for conformance testing other implementations.
+//!   - ➕ Enables `parse`.
 //!
 //! # 🎯 Design Goals
 //!
 //! - ⚙️ **Sans I/O:** All logic is independent of any networking or I/O layer. Bring your own sync or
 //!   async runtime!
 //! - 🛠️ **Modular:** Add only what you need via Cargo features.
-//! - 💼 **Portable:** Usable in `no_std` environments (requires [`alloc`]).
+//! - 💼 **Portable:** Usable in `no_std` environments (requires [`alloc`]), and on
+//!   `wasm32-unknown-unknown`: the core and `parse` never touch a clock, a socket, or a random
+//!   number generator; the caller supplies them where needed.
 //! - 🚀 **Efficient** Leverages [`bytes`] for low-overhead, zero-copy data manipulation.
 //!
 //! # 🧪 Example
@@ -84,6 +111,20 @@ pub(crate) use bytes::{Bytes, BytesMut};
 mod types;
 pub use types::*;
 
+mod session;
+pub use session::*;
+
+mod policy;
+pub use policy::*;
+
+mod ratelimit;
+pub use ratelimit::*;
+
+pub mod greylist;
+
+mod transform;
+pub use transform::*;
+
 mod parse;
 #[cfg(feature = "parse")]
 pub use parse::*;
@@ -92,6 +133,43 @@ mod parser;
 #[cfg(feature = "parse")]
 pub use parser::*;
 
+mod reply_parser;
+#[cfg(feature = "parse")]
+pub use reply_parser::*;
+
+mod duplex;
+#[cfg(feature = "parse")]
+pub use duplex::*;
+
+mod codec;
+#[cfg(feature = "codec")]
+pub use codec::*;
+
+mod server;
+#[cfg(feature = "server")]
+pub use server::*;
+
+mod client;
+#[cfg(feature = "client")]
+pub use client::*;
+
+mod service;
+#[cfg(feature = "tower")]
+pub use service::*;
+
+mod client_session;
+#[cfg(feature = "parse")]
+pub use client_session::*;
+
+#[cfg(feature = "parse")]
+pub mod replay;
+
+#[cfg(feature = "scram")]
+pub mod scram;
+
+#[cfg(all(feature = "parse", any(test, feature = "test_vectors")))]
+pub mod test_vectors;
+
 pub mod max {
     /// Maximum length of the local part of an email address.
     pub const LOCAL_PART: usize = 64;
@@ -99,6 +177,11 @@ pub mod max {
     /// Maximum length of the domain part of an email address.
     pub const DOMAIN: usize = 255;
 
+    /// Maximum length of a label of a domain name.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1035#section-2.3.4>
+    pub const LABEL: usize = 63;
+
     /// Maximum length of an email address, **excluding** the `<>`.
     pub const EMAIL: usize = 254;
 
@@ -107,8 +190,154 @@ pub mod max {
 
     /// Maximum length of a `DATA` line, **excluding** the trailing CRLF.
     pub const DATA_LINE: usize = 998;
+
+    /// Maximum length of a reply line, **excluding** the trailing CRLF.
+    pub const REPLY_LINE: usize = 510;
+
+    /// Minimum number of recipients a server must accept in one transaction.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.3.1.8>
+    pub const RECIPIENTS: usize = 100;
+
+    /// Minimum message size, in bytes, a server must accept.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.3.1.7>
+    pub const MIN_MESSAGE: usize = 64 * 1024;
+
+    /// Additional `MAIL` command line length allowed when `SIZE` is advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870>
+    pub const SIZE_MAIL: usize = 26;
+
+    /// Additional `MAIL` command line length allowed when `DSN` is advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3461>
+    pub const DSN_MAIL: usize = 100;
+
+    /// Additional `RCPT` command line length allowed when `DSN` is advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3461>
+    pub const DSN_RCPT: usize = 500;
+
+    /// Additional `MAIL` command line length allowed when `AUTH` is advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc4954>
+    pub const AUTH_MAIL: usize = 500;
+
+    /// Additional `MAIL` command line length allowed when `SMTPUTF8` is advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6531>
+    pub const SMTPUTF8_MAIL: usize = 10;
+}
+
+/// # Protocol Limits
+///
+/// Limits enforced by a [`Parser`](crate::Parser) or [`Session`], defaulting to the constants in
+/// [`max`] where one exists.
+///
+/// Extensions may raise these once negotiated, e.g. with `SIZE` and `DSN` advertised, a `MAIL`
+/// command line may be up to `max::COMMAND_LINE + max::SIZE_MAIL + max::DSN_MAIL` long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Maximum length of a command line, **excluding** the trailing CRLF.
+    pub command_line: usize,
+
+    /// Maximum length of a `DATA` line, **excluding** the trailing CRLF.
+    pub data_line: usize,
+
+    /// Maximum number of recipients in one transaction.
+    pub recipients: usize,
+
+    /// Maximum number of recipients in one transaction with a null reverse path (`MAIL FROM:<>`).
+    /// Unlimited by default.
+    ///
+    /// Delivery status notifications go to a single recipient, so a null sender with more is
+    /// likely backscatter or spam; `1` is a common choice.
+    pub null_sender_recipients: usize,
+
+    /// Maximum number of `BDAT` chunks in one transaction. Unlimited by default.
+    pub chunks: usize,
+
+    /// Minimum size of a `BDAT` chunk, other than the last. Zero by default.
+    pub min_chunk: usize,
+
+    /// Maximum size of a message, as advertised with `SIZE`. Unlimited by default.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870>
+    pub message: usize,
+
+    /// Maximum number of mail transactions ended by a message on one connection, whether or not
+    /// the message was accepted. Unlimited by default.
+    pub transactions_per_connection: usize,
+
+    /// Maximum number of messages accepted on one connection. Unlimited by default.
+    pub messages_per_connection: usize,
+
+    /// Maximum number of recipients accepted on one connection. Unlimited by default.
+    pub recipients_per_connection: usize,
+
+    /// Maximum number of commands accepted on one connection, after which only `QUIT` is.
+    /// Unlimited by default.
+    pub commands_per_connection: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            command_line: max::COMMAND_LINE,
+            data_line: max::DATA_LINE,
+            recipients: max::RECIPIENTS,
+            null_sender_recipients: usize::MAX,
+            chunks: usize::MAX,
+            min_chunk: 0,
+            message: usize::MAX,
+            transactions_per_connection: usize::MAX,
+            messages_per_connection: usize::MAX,
+            recipients_per_connection: usize::MAX,
+            commands_per_connection: usize::MAX,
+        }
+    }
+}
+
+/// # Protocol Timeouts
+///
+/// The minimum timeouts recommended by
+/// [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.3.2), per stage. See
+/// [`Parser::timeout`](crate::Parser::timeout) for the timeout of a server's current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timeouts {
+    /// Client waiting for the `220` greeting. 5 minutes.
+    pub greeting: Duration,
+    /// Client waiting for the reply to `MAIL`. 5 minutes.
+    pub mail: Duration,
+    /// Client waiting for the reply to `RCPT`. 5 minutes.
+    pub rcpt: Duration,
+    /// Client waiting for the `354` reply to `DATA`. 2 minutes.
+    pub data_init: Duration,
+    /// Client sending each block of message data. 3 minutes.
+    pub data_block: Duration,
+    /// Client waiting for the reply after the final `.`. 10 minutes.
+    pub data_termination: Duration,
+    /// Server waiting for the next command. 5 minutes.
+    pub server: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            greeting: Duration::from_secs(5 * 60),
+            mail: Duration::from_secs(5 * 60),
+            rcpt: Duration::from_secs(5 * 60),
+            data_init: Duration::from_secs(2 * 60),
+            data_block: Duration::from_secs(3 * 60),
+            data_termination: Duration::from_secs(10 * 60),
+            server: Duration::from_secs(5 * 60),
+        }
+    }
 }
 
+mod observe;
+
 mod tracing_stub;
 #[allow(
     unused_imports,
@@ -117,6 +346,46 @@ mod tracing_stub;
 pub(crate) use tracing_stub as log;
 
 pub(crate) use core::fmt;
+use core::time::Duration;
+
+/// `Debug` formatting of user input, redacted with the `redact` feature.
+pub(crate) struct Redact<'a> {
+    bytes: &'a [u8],
+    email: bool,
+}
+
+impl<'a> Redact<'a> {
+    /// Redact `bytes` to their length.
+    pub(crate) fn bytes(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            email: false,
+        }
+    }
+
+    /// Redact the local part of an email address to its first character.
+    pub(crate) fn email(bytes: &'a [u8]) -> Self {
+        Self { bytes, email: true }
+    }
+}
+
+impl fmt::Debug for Redact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !cfg!(feature = "redact") {
+            return fmt::Debug::fmt(self.bytes.as_bstr(), f);
+        }
+
+        match self.bytes.rfind_byte(b'@').filter(|_| self.email) {
+            Some(at) => write!(
+                f,
+                "\"{}***{}\"",
+                self.bytes[..at.min(1)].as_bstr(),
+                self.bytes[at..].as_bstr()
+            ),
+            None => write!(f, "[{} bytes]", self.bytes.len()),
+        }
+    }
+}
 
 pub(crate) fn is_xchar(input: u8) -> bool {
     matches!(input, b'!'..=b'*' | b','..=b'<' | b'>'..=b'~')
@@ -210,6 +479,37 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case::bytes(
+        Redact::bytes(b"AHVzZXIAcGFzcw=="),
+        "\"AHVzZXIAcGFzcw==\"",
+        "[16 bytes]"
+    )]
+    #[case::email(
+        Redact::email(b"bob@example.com"),
+        "\"bob@example.com\"",
+        "\"b***@example.com\""
+    )]
+    #[case::quoted(
+        Redact::email(b"\"b@b\"@example.com"),
+        "\"\\\"b@b\\\"@example.com\"",
+        "\"\"***@example.com\""
+    )]
+    #[case::not_email(Redact::email(b"bob"), "\"bob\"", "[3 bytes]")]
+    #[case::empty_local(
+        Redact::email(b"@example.com"),
+        "\"@example.com\"",
+        "\"***@example.com\""
+    )]
+    fn redact(#[case] input: Redact, #[case] plain: &str, #[case] redacted: &str) {
+        let expected = if cfg!(feature = "redact") {
+            redacted
+        } else {
+            plain
+        };
+        assert_eq!(format!("{input:?}"), expected);
+    }
+
     #[cfg(feature = "parse")]
     #[rstest]
     #[case::both(b"<test>", Some(&b"test"[..]))]
@@ -244,4 +544,28 @@ mod tests {
             expected.map(Bytes::from)
         );
     }
+
+    #[test]
+    fn error_sources() {
+        use core::error::Error as _;
+
+        let refusal = Refusal::from(Violation::NoMail);
+        let source = refusal.source().unwrap();
+        assert_eq!(source.downcast_ref(), Some(&Violation::NoMail));
+        assert!(Refusal::Policy(Decision::Reject(None)).source().is_none());
+
+        let error = TransformError::from(JoinError::TooLong);
+        assert_eq!(error.source().unwrap().to_string(), "Line too long");
+        assert!(JoinError::TooLong.source().is_none());
+    }
+
+    #[cfg(feature = "parse")]
+    #[test]
+    fn parse_error_sources() {
+        use core::error::Error as _;
+
+        let error = RestoreError::from(Error::InvalidSyntax);
+        let source = error.source().unwrap();
+        assert_eq!(source.downcast_ref(), Some(&Error::InvalidSyntax));
+    }
 }
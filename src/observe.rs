@@ -0,0 +1,47 @@
+//! Emit metrics via the [`metrics`] facade, or nothing without the `metrics` feature.
+//!
+//! | Name | Type | Labels |
+//! |---|---|---|
+//! | `smtpkit_commands_total` | counter | `verb` |
+//! | `smtpkit_parse_errors_total` | counter | `kind` |
+//! | `smtpkit_message_bytes` | histogram | |
+//! | `smtpkit_parse_duration_seconds` | histogram | |
+//! | `smtpkit_violations_total` | counter | `kind` |
+
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables, dead_code))]
+
+use crate::*;
+
+/// Record a parsed command.
+#[cfg(feature = "parse")]
+pub(crate) fn command(command: &Command) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("smtpkit_commands_total", "verb" => command.verb()).increment(1);
+}
+
+/// Record a parse error.
+#[cfg(feature = "parse")]
+pub(crate) fn error(error: &Error) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("smtpkit_parse_errors_total", "kind" => error.kind().name()).increment(1);
+}
+
+/// Record a completed message of `len` bytes.
+#[cfg(feature = "parse")]
+pub(crate) fn message(len: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("smtpkit_message_bytes").record(len as f64);
+}
+
+/// Record the time taken to parse, as measured by the caller.
+#[cfg(feature = "parse")]
+pub(crate) fn duration(duration: core::time::Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("smtpkit_parse_duration_seconds").record(duration.as_secs_f64());
+}
+
+/// Record a sequence violation.
+pub(crate) fn violation(violation: Violation) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("smtpkit_violations_total", "kind" => violation.name()).increment(1);
+}
@@ -0,0 +1,176 @@
+use super::*;
+use crate::mail::ReversePath;
+
+/// # Unvalidated Command
+///
+/// A command split into its parts by [`Command::parse_deferred`], whose addresses, domains, and
+/// `MAIL` and `RCPT` parameters have not been validated yet. A relay that merely forwards the
+/// command may serialize it as is; [`Unvalidated::validate`], with the same [`Lenient`] flags,
+/// yields the [`Command`].
+#[derive(derive_more::Debug, PartialEq, Clone, Hash)]
+#[non_exhaustive]
+pub enum Unvalidated {
+    /// `HELO` and its domain.
+    Helo(#[debug("{:?}", Redact::bytes(_0))] Bytes),
+    /// `EHLO` and its domain. Address literals are cheap to validate, so they are parsed as a
+    /// [`Unvalidated::Command`] instead.
+    Ehlo(#[debug("{:?}", Redact::bytes(_0))] Bytes),
+    /// `MAIL` and its reverse path, without the angle brackets, or `None` if it is null.
+    Mail {
+        #[debug("{:?}", from.as_ref().map(|f| Redact::email(f)))]
+        from: Option<Bytes>,
+        /// The parameters, which may be separated by runs of SP and HTAB with
+        /// [`Lenient::WHITESPACE`].
+        #[debug("{:?}", Redact::bytes(parameters))]
+        parameters: Bytes,
+    },
+    /// `RCPT` and its forward path, without the angle brackets.
+    Rcpt {
+        #[debug("{:?}", Redact::email(to))]
+        to: Bytes,
+        /// The parameters, which may be separated by runs of SP and HTAB with
+        /// [`Lenient::WHITESPACE`].
+        #[debug("{:?}", Redact::bytes(parameters))]
+        parameters: Bytes,
+    },
+    /// Any other command, which was validated already.
+    Command(Command),
+}
+
+impl Unvalidated {
+    /// Validate the addresses, domains, and parameters, yielding the [`Command`].
+    ///
+    /// Pass the `lenient` flags the command was split with, so it is parsed as
+    /// [`Command::parse_lenient`] would have.
+    pub fn validate(self, lenient: Lenient) -> Result<Command> {
+        let tokens = |parameters| match lenient.contains(Lenient::WHITESPACE) {
+            true => Tokens::whitespace(parameters),
+            false => Tokens::new(parameters, b' '),
+        };
+
+        match self {
+            Self::Helo(domain) => Domain::try_from(domain)
+                .map(Host::Domain)
+                .map(Command::Helo),
+            Self::Ehlo(domain) => Domain::try_from(domain)
+                .map(Host::Domain)
+                .map(Command::Ehlo),
+            Self::Mail { from, parameters } => {
+                let from = match from {
+                    Some(path) => ReversePath::Email(Email::try_from(path)?),
+                    None => ReversePath::Null,
+                };
+                rfc5321::new_mail(from, tokens(parameters), lenient)
+            }
+            Self::Rcpt { to, parameters } => {
+                rfc5321::new_rcpt(Email::try_from(to)?, tokens(parameters), lenient)
+            }
+            Self::Command(command) => Ok(command),
+        }
+    }
+}
+
+impl Command {
+    /// Split a command line, **excluding** the trailing CRLF, into its parts as
+    /// [`Command::parse_lenient`], but defer validating its addresses, domains, and `MAIL` and
+    /// `RCPT` parameters until [`Unvalidated::validate`], for relays that merely forward traffic.
+    ///
+    /// Other commands are cheap to validate, and are parsed in full.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{Command, Error, Lenient, Unvalidated};
+    /// let input = Bytes::from_static(b"MAIL FROM:<bob@example.com> SIZE=1024");
+    /// let unvalidated = Command::parse_deferred(input, Lenient::empty())?;
+    /// assert!(matches!(unvalidated, Unvalidated::Mail { .. }));
+    ///
+    /// let Command::Mail(mail) = unvalidated.validate(Lenient::empty())? else {
+    ///     unreachable!();
+    /// };
+    /// assert_eq!(mail.size, Some(1024));
+    /// # Ok::<(), Error>(())
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn parse_deferred(input: Bytes, lenient: Lenient) -> Result<Unvalidated> {
+        let mut tokens = if lenient.contains(Lenient::WHITESPACE) {
+            Tokens::whitespace(input.clone())
+        } else {
+            Tokens::new(input.clone(), b' ')
+        };
+        let token = tokens.next().ok_or(Error::Empty)?;
+
+        match token {
+            helo if helo.eq_ignore_ascii_case(b"HELO") => rfc5321::helo_deferred(tokens, lenient),
+            ehlo if ehlo.eq_ignore_ascii_case(b"EHLO") => rfc5321::ehlo_deferred(tokens, lenient),
+            mail if mail.eq_ignore_ascii_case(b"MAIL") => rfc5321::mail_deferred(tokens, lenient),
+            rcpt if rcpt.eq_ignore_ascii_case(b"RCPT") => rfc5321::rcpt_deferred(tokens, lenient),
+            _ => Self::parse_lenient(input, lenient).map(Unvalidated::Command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::helo(b"HELO -example.com", Err(Error::InvalidDomain))]
+    #[case::ehlo(b"EHLO example.com", Ok("EHLO example.com"))]
+    #[case::ehlo_literal(b"EHLO [192.0.2.1]", Ok("EHLO [192.0.2.1]"))]
+    #[case::mail(
+        b"MAIL FROM:<bob@example.com> SIZE=1024",
+        Ok("MAIL FROM:<bob@example.com> SIZE=1024")
+    )]
+    #[case::mail_null(b"MAIL FROM:<>", Ok("MAIL FROM:<>"))]
+    #[case::mail_address(b"MAIL FROM:<bob> SIZE=1024", Err(Error::InvalidAddress))]
+    #[case::mail_parameter(b"MAIL FROM:<bob@example.com> SIZE=x", Err(Error::InvalidParameterValue { keyword: "SIZE" }))]
+    #[case::rcpt(
+        b"RCPT TO:<alice@example.com> NOTIFY=NEVER",
+        Ok("RCPT TO:<alice@example.com> NOTIFY=NEVER")
+    )]
+    #[case::rcpt_parameter(b"RCPT TO:<alice@example.com> FOO", Err(Error::InvalidParameter))]
+    fn validate(#[case] input: &'static [u8], #[case] expected: Result<&'static str>) {
+        let result = Command::parse_deferred(Bytes::from_static(input), Lenient::empty())
+            .unwrap()
+            .validate(Lenient::empty())
+            .map(|command| command.to_string());
+        assert_eq!(result, expected.map(String::from));
+    }
+
+    #[rstest]
+    #[case::mail(b"MAIL FROM:<bob@example.com>  SIZE=1024\tBODY=8BITMIME")]
+    #[case::rcpt(b"RCPT TO:<alice@example.com> \tNOTIFY=NEVER ")]
+    fn validate_lenient(#[case] input: &'static [u8]) {
+        let input = Bytes::from_static(input);
+        let eager = Command::parse_lenient(input.clone(), Lenient::WHITESPACE);
+        let deferred = Command::parse_deferred(input.clone(), Lenient::WHITESPACE)
+            .and_then(|unvalidated| unvalidated.validate(Lenient::WHITESPACE));
+        assert!(eager.is_ok());
+        assert_eq!(deferred, eager);
+
+        let strict = Command::parse_deferred(input, Lenient::empty())
+            .and_then(|unvalidated| unvalidated.validate(Lenient::empty()));
+        assert!(strict.is_err());
+    }
+
+    #[rstest]
+    #[case::helo(b"HELO -example.com")]
+    #[case::mail(b"MAIL FROM:<bob> SIZE=x")]
+    #[case::mail_null(b"MAIL FROM:<> FOO")]
+    #[case::rcpt(b"RCPT TO:<alice> FOO")]
+    fn forward(#[case] input: &'static [u8]) {
+        let unvalidated = Command::parse_deferred(Bytes::from_static(input), Lenient::empty());
+        assert_eq!(unvalidated.unwrap().to_bytes(), [input, b"\r\n"].concat());
+    }
+
+    #[rstest]
+    #[case::ehlo_literal(b"EHLO [foo]", Error::InvalidAddressLiteral)]
+    #[case::mail_syntax(b"MAIL TO:<bob@example.com>", Error::InvalidSyntax)]
+    #[case::rcpt_missing(b"RCPT", Error::MissingParameter)]
+    #[case::other(b"NOOP foo", Error::UnexpectedParameter)]
+    fn structure(#[case] input: &'static [u8], #[case] expected: Error) {
+        let result = Command::parse_deferred(Bytes::from_static(input), Lenient::empty());
+        assert_eq!(result, Err(expected));
+    }
+}
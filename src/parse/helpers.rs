@@ -1,5 +1,9 @@
 use crate::*;
 
+/// Whether `input` is a non-empty run of `atext` characters.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.3>
+#[must_use]
 pub fn is_atext(input: &[u8]) -> bool {
     if input.is_empty() {
         return false;
@@ -18,6 +22,10 @@ pub fn is_atext(input: &[u8]) -> bool {
     })
 }
 
+/// Whether `input` is a `Dot-string`: `atext` runs separated by single dots.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_dot_string(input: &[u8]) -> bool {
     let (a, b) = input.split_once_str(".").unwrap_or((input, b""));
 
@@ -32,14 +40,26 @@ pub fn is_dot_string(input: &[u8]) -> bool {
     b.split(|&x| x == b'.').all(is_atext)
 }
 
+/// Whether `input` is a `qtextSMTP` character, i.e. printable ASCII other than `"` and `\`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_qtext(input: u8) -> bool {
     matches!(input, b' '..=b'!' |  b'#'..=b'[' | b']'..=b'~')
 }
 
+/// Whether `input` may follow a `\` in a `quoted-pairSMTP`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_quoted_pair(input: u8) -> bool {
     matches!(input, b' '..=b'~')
 }
 
+/// Whether `input` is a `Quoted-string`, **including** the surrounding quotes.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_quoted_string(input: &[u8]) -> bool {
     let Some(stripped) = strip_quotes(input) else {
         return false;
@@ -61,6 +81,12 @@ pub fn is_quoted_string(input: &[u8]) -> bool {
     true
 }
 
+/// Whether `input` is a single domain label (`sub-domain`).
+///
+/// Label length is not checked.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_subdomain(input: &[u8]) -> bool {
     if input.is_empty() {
         return false;
@@ -75,6 +101,12 @@ pub fn is_subdomain(input: &[u8]) -> bool {
         .all(|&c| c.is_ascii_alphanumeric() || c == b'-')
 }
 
+/// Whether `input` is a `Domain`: labels separated by single dots.
+///
+/// Length is not checked; see [`max::DOMAIN`].
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 pub fn is_domain(input: &[u8]) -> bool {
     let (a, b) = input.split_once_str(".").unwrap_or((input, &[]));
 
@@ -89,11 +121,40 @@ pub fn is_domain(input: &[u8]) -> bool {
     b.split(|&x| x == b'.').all(is_subdomain)
 }
 
+/// Whether `input` is a `Local-part`: a `Dot-string` or a `Quoted-string`.
+///
+/// Length is not checked; see [`max::LOCAL_PART`].
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2>
+#[must_use]
 #[cfg_attr(test, mutants::skip)]
 pub fn is_local_part(input: &[u8]) -> bool {
     is_dot_string(input) || is_quoted_string(input)
 }
 
+/// Whether `input` is a solicitation class keyword, e.g. `org.example:ADV`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3865#section-2>
+#[must_use]
+pub fn is_solicitation_keyword(input: &[u8]) -> bool {
+    let Some((first, rest)) = input.split_first() else {
+        return false;
+    };
+
+    first.is_ascii_alphanumeric()
+        && rest
+            .iter()
+            .all(|&c| c.is_ascii_alphanumeric() || matches!(c, b'-' | b'.' | b':'))
+}
+
+/// Whether `input` is a base64 character, excluding the `=` padding.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc4648#section-4>
+#[must_use]
+pub fn is_base64(input: u8) -> bool {
+    input.is_ascii_alphanumeric() || matches!(input, b'+' | b'/')
+}
+
 pub fn strip_quotes(input: &[u8]) -> Option<&[u8]> {
     input.strip_prefix(b"\"")?.strip_suffix(b"\"")
 }
@@ -221,6 +282,18 @@ mod tests {
         assert_eq!(is_domain(input), expected);
     }
 
+    #[rstest]
+    #[case::simple(b"ADV", true)]
+    #[case::reverse_domain(b"org.example:ADV", true)]
+    #[case::hyphen(b"net.example:no-spam", true)]
+    #[case::empty(b"", false)]
+    #[case::leading_dot(b".example:ADV", false)]
+    #[case::space(b"org.example: ADV", false)]
+    #[case::comma(b"ADV,SPAM", false)]
+    fn test_is_solicitation_keyword(#[case] input: &[u8], #[case] expected: bool) {
+        assert_eq!(is_solicitation_keyword(input), expected);
+    }
+
     #[rstest]
     #[case::bang(b'!', true)]
     #[case::asterisk(b'*', true)]
@@ -237,4 +310,17 @@ mod tests {
     fn test_is_xchar(#[case] input: u8, #[case] expected: bool) {
         assert_eq!(is_xchar(input), expected);
     }
+
+    #[rstest]
+    #[case::upper(b'A', true)]
+    #[case::lower(b'z', true)]
+    #[case::digit(b'9', true)]
+    #[case::plus(b'+', true)]
+    #[case::slash(b'/', true)]
+    #[case::equals(b'=', false)]
+    #[case::dash(b'-', false)]
+    #[case::space(b' ', false)]
+    fn test_is_base64(#[case] input: u8, #[case] expected: bool) {
+        assert_eq!(is_base64(input), expected);
+    }
 }
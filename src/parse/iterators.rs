@@ -5,26 +5,90 @@ use bytes::{Buf, Bytes};
 
 use super::Error;
 
+/// # Token Iterator
+///
+/// Splits a command line into tokens, as used to parse a [`Command`](crate::Command), so parsers
+/// for custom commands can share the same tokenization.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::Tokens;
+/// let line = Bytes::from_static(b"XCLIENT  NAME=mx.example.com ADDR=192.0.2.1");
+/// let mut tokens = Tokens::whitespace(line);
+/// assert_eq!(tokens.next(), Some(Bytes::from_static(b"XCLIENT")));
+/// assert_eq!(tokens.peek(), Some(Bytes::from_static(b"NAME=mx.example.com")));
+/// assert_eq!(tokens.remainder(), b"NAME=mx.example.com ADDR=192.0.2.1"[..]);
+/// ```
 #[derive(Debug, Clone)]
 pub struct Tokens {
     bytes: Bytes,
     delim: u8,
+    whitespace: bool,
     //len: usize,
 }
 
 impl Tokens {
-    /// Create a new `Tokens` iterator.
+    /// Create a new `Tokens` iterator that splits on `delim`.
+    #[must_use]
     pub fn new(bytes: Bytes, delim: u8) -> Self {
         //let len = bytes.len();
-        Self { bytes, delim }
+        Self {
+            bytes,
+            delim,
+            whitespace: false,
+        }
     }
 
-    /*
-    /// Consume the `Tokens` and return the remaining `Bytes`.
-    pub fn remainder(self) -> Bytes {
-        self.bytes
+    /// Create a new `Tokens` iterator that splits on runs of SP and HTAB.
+    #[must_use]
+    pub fn whitespace(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            delim: b' ',
+            whitespace: true,
+        }
+    }
+
+    /// Get the next token without advancing.
+    #[must_use]
+    pub fn peek(&self) -> Option<Bytes> {
+        self.clone().next()
+    }
+
+    /// Get the remaining `Bytes`, without any leading separators.
+    #[must_use]
+    pub fn remainder(&self) -> Bytes {
+        let start = match self.whitespace {
+            true => self.bytes.iter().position(|&b| !is_wsp(b)),
+            false => Some(0),
+        };
+        self.bytes.slice(start.unwrap_or(self.bytes.len())..)
+    }
+
+    /// Consume the `Tokens`, returning the remainder as a single token, without any trailing
+    /// separators in whitespace mode, or `None` if it is empty.
+    ///
+    /// Useful for a final argument that may contain the separator, e.g. the text of `HELP`.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::Tokens;
+    /// let mut tokens = Tokens::whitespace(Bytes::from_static(b"VRFY Bob Smith \t"));
+    /// tokens.next();
+    /// assert_eq!(tokens.rest_as_one(), Some(Bytes::from_static(b"Bob Smith")));
+    /// ```
+    #[must_use]
+    pub fn rest_as_one(self) -> Option<Bytes> {
+        let mut rest = self.remainder();
+        if self.whitespace {
+            let end = rest.iter().rposition(|&b| !is_wsp(b)).map_or(0, |i| i + 1);
+            rest.truncate(end);
+        }
+
+        (!rest.is_empty()).then_some(rest)
     }
 
+    /*
     /// The original length of the `Bytes` when this `Tokens` was created.
     pub const fn len(&self) -> usize {
         self.len
@@ -37,18 +101,29 @@ impl Iterator for Tokens {
 
     /// Return the next token.
     fn next(&mut self) -> Option<Self::Item> {
+        if self.whitespace {
+            let start = self
+                .bytes
+                .iter()
+                .position(|&b| !is_wsp(b))
+                .unwrap_or(self.bytes.len());
+            self.bytes.advance(start);
+        }
+
         if self.bytes.is_empty() {
             return None;
         }
 
-        let pos = self
-            .bytes
-            .as_ref()
-            .find_byte(self.delim)
-            .unwrap_or(self.bytes.len());
+        let pos = if self.whitespace {
+            self.bytes.iter().position(|&b| is_wsp(b))
+        } else {
+            self.bytes.as_ref().find_byte(self.delim)
+        }
+        .unwrap_or(self.bytes.len());
         let token = self.bytes.split_to(pos);
 
-        if !self.bytes.is_empty() {
+        // separators are skipped before the next token in whitespace mode
+        if !self.whitespace && !self.bytes.is_empty() {
             self.bytes.advance(1);
         }
 
@@ -61,6 +136,12 @@ impl Iterator for Tokens {
             return (0, Some(0));
         }
 
+        if self.whitespace {
+            // the remainder may be only whitespace
+            let remaining_wsp = self.bytes.iter().filter(|&&b| is_wsp(b)).count();
+            return (0, Some(remaining_wsp + 1));
+        }
+
         #[expect(clippy::naive_bytecount, reason = "small input")]
         let remaining_delims = self.bytes.iter().filter(|&&b| b == self.delim).count();
         //let remaining_delims = self.bytes.as_ref().find_iter(&[self.delim]).count();
@@ -71,10 +152,31 @@ impl Iterator for Tokens {
 
 impl FusedIterator for Tokens {}
 
+/// Whether `b` is SP or HTAB.
+fn is_wsp(b: u8) -> bool {
+    matches!(b, b' ' | b'\t')
+}
+
 pub trait Parameters<T> {
     fn parameters(&mut self, parameters: impl Iterator<Item = T>) -> Result<(), Error>;
 }
 
+/// # CRLF Line Iterator
+///
+/// Splits bytes into lines, excluding the CRLF.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::Lines;
+/// let mut lines = Lines::new(Bytes::from_static(b"Subject: Hi\r\n\r\nHello, wor"));
+/// assert_eq!(lines.next(), Some(Bytes::from_static(b"Subject: Hi")));
+/// assert_eq!(lines.offset(), 13);
+/// assert_eq!(lines.next(), Some(Bytes::new()));
+/// assert_eq!(lines.next(), None);
+/// // resume here once more bytes arrive
+/// assert_eq!(lines.offset(), 15);
+/// assert_eq!(lines.remainder(), b"Hello, wor"[..]);
+/// ```
 #[derive(Debug, Clone)]
 pub struct Lines {
     bytes: Bytes,
@@ -84,6 +186,7 @@ pub struct Lines {
 
 impl Lines {
     /// Create a new `Lines` iterator.
+    #[must_use]
     pub fn new(bytes: Bytes) -> Self {
         Self {
             bytes,
@@ -92,10 +195,24 @@ impl Lines {
         }
     }
 
-    /// Consume the `Lines` and return the remaining `Bytes`.
+    /// Consume the `Lines` and return the original `Bytes`.
+    #[must_use]
     pub fn into_bytes(self) -> Bytes {
         self.bytes
     }
+
+    /// The byte offset of the next line, i.e. the number of bytes consumed so far.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.next_index
+    }
+
+    /// Get the bytes following the lines returned so far. Once the iterator is exhausted, this is
+    /// the incomplete line with no CRLF yet, if any.
+    #[must_use]
+    pub fn remainder(&self) -> Bytes {
+        self.bytes.slice(self.next_index..)
+    }
 }
 
 impl Iterator for Lines {
@@ -17,7 +17,7 @@ impl TryFrom<Bytes> for Parameter {
 
         match (key, value) {
             (size, Some(n)) if size.eq_ignore_ascii_case(b"SIZE") => usize::from_ascii(&n)
-                .map_err(|_| Error::InvalidSyntax)
+                .map_err(|_| Error::InvalidParameterValue { keyword: "SIZE" })
                 .map(Self::Size),
 
             (ret, Some(x)) if ret.eq_ignore_ascii_case(b"RET") => Ret::try_from(x).map(Self::Ret),
@@ -34,11 +34,19 @@ impl TryFrom<Bytes> for Parameter {
                 Body::try_from(x).map(Self::Body)
             }
 
-            /*
-            (smtputf8, None) if smtputf8.eq_ignore_ascii_case(b"SMTPUTF8") => {
-                Ok(Parameter::SmtpUtf8)
+            (conperm, Some(x)) if conperm.eq_ignore_ascii_case(b"CONPERM") => {
+                XText::try_from(x).map(ConPerm).map(Self::ConPerm)
+            }
+
+            (solicit, Some(x)) if solicit.eq_ignore_ascii_case(b"SOLICIT") => {
+                Solicit::try_from(x).map(Self::Solicit)
             }
 
+            (prdr, None) if prdr.eq_ignore_ascii_case(b"PRDR") => Ok(Self::Prdr),
+
+            (smtputf8, None) if smtputf8.eq_ignore_ascii_case(b"SMTPUTF8") => Ok(Self::SmtpUtf8),
+
+            /*
             (mtp, Some(x)) if mtp.eq_ignore_ascii_case(b"MT-PRIORITY") => {
                 Ok(Parameter::MtPriority(MtPriority::try_from(x)?))
             }
@@ -67,7 +75,7 @@ impl TryFrom<Bytes> for Ret {
         match input {
             full if full.eq_ignore_ascii_case(b"FULL") => Ok(Self::Full),
             headers if headers.eq_ignore_ascii_case(b"HDRS") => Ok(Self::Headers),
-            _ => Err(Error::InvalidSyntax),
+            _ => Err(Error::InvalidParameterValue { keyword: "RET" }),
         }
     }
 }
@@ -101,6 +109,10 @@ impl Parameters<Result<Parameter>> for Mail {
                 Parameter::EnvId(envid) => self.envid = Some(envid),
                 Parameter::Auth(auth) => self.auth = Some(auth),
                 Parameter::Body(body) => self.body = Some(body),
+                Parameter::ConPerm(conperm) => self.conperm = Some(conperm),
+                Parameter::Solicit(solicit) => self.solicit = Some(solicit),
+                Parameter::Prdr => self.prdr = true,
+                Parameter::SmtpUtf8 => self.smtputf8 = true,
             }
         }
 
@@ -108,6 +120,44 @@ impl Parameters<Result<Parameter>> for Mail {
     }
 }
 
+impl Mail {
+    /// The [raw parameters](Mail::raw_parameters), if they are set and still parse to the typed
+    /// parameters.
+    pub(crate) fn replay(&self) -> Option<&Bytes> {
+        let raw = self.raw_parameters.as_ref()?;
+        let mut parsed = Self {
+            size: None,
+            ret: None,
+            envid: None,
+            auth: None,
+            body: None,
+            conperm: None,
+            solicit: None,
+            prdr: false,
+            smtputf8: false,
+            raw_parameters: None,
+            from: self.from.clone(),
+        };
+        parsed
+            .parameters(Tokens::whitespace(raw.clone()).map(Parameter::try_from))
+            .ok()?;
+        (parsed == *self).then_some(raw)
+    }
+}
+
+impl TryFrom<Bytes> for Solicit {
+    type Error = Error;
+
+    fn try_from(input: Bytes) -> Result<Self> {
+        if input.split(|&b| b == b',').all(is_solicitation_keyword) {
+            // SAFETY: `is_solicitation_keyword` ensures every keyword is valid.
+            return unsafe { Ok(Self::new_unchecked(input)) };
+        }
+
+        Err(Error::InvalidParameterValue { keyword: "SOLICIT" })
+    }
+}
+
 impl TryFrom<Bytes> for Body {
     type Error = Error;
 
@@ -119,7 +169,7 @@ impl TryFrom<Bytes> for Body {
 
             binary if binary.eq_ignore_ascii_case(b"BINARYMIME") => Ok(Self::BinaryMime),
 
-            _ => Err(Error::InvalidSyntax),
+            _ => Err(Error::InvalidParameterValue { keyword: "BODY" }),
         }
     }
 }
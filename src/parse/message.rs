@@ -0,0 +1,522 @@
+//! # Message Content
+//!
+//! Helpers for inspecting the message content of a `DATA` or `BDAT` payload, as defined in
+//! [RFC 5322](https://datatracker.ietf.org/doc/html/rfc5322).
+
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+use super::*;
+
+/// Split a message payload into its header block and body.
+///
+/// The header block keeps the CRLF ending its last field, and the empty line separating it from
+/// the body is dropped. If there is no empty line, the entire payload is treated as the header
+/// block.
+#[must_use]
+pub fn split(payload: &Bytes) -> (Bytes, Bytes) {
+    if payload.starts_with(b"\r\n") {
+        return (Bytes::new(), payload.slice(2..));
+    }
+
+    match payload.find(b"\r\n\r\n") {
+        Some(pos) => (payload.slice(..pos + 2), payload.slice(pos + 4..)),
+        None => (payload.clone(), Bytes::new()),
+    }
+}
+
+/// Find the first byte of the header block that is not well-formed UTF-8, returning its offset
+/// into `payload`.
+///
+/// Intended for `SMTPUTF8` transactions, where header fields may contain UTF-8 but downstream
+/// stores often reject malformed sequences. Skip this check for `BODY=BINARYMIME`, where the
+/// content is not required to be text.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc6532#section-3.2>
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::message;
+/// let payload = Bytes::from_static(b"Subject: \xce\xb1\r\n\r\nHello!");
+/// assert_eq!(message::invalid_utf8(&payload), None);
+/// let payload = Bytes::from_static(b"Subject: \xce\r\n\r\nHello!");
+/// assert_eq!(message::invalid_utf8(&payload), Some(9));
+/// ```
+#[must_use]
+pub fn invalid_utf8(payload: &Bytes) -> Option<usize> {
+    let (headers, _) = split(payload);
+    // the header block always starts at the beginning of the payload
+    core::str::from_utf8(&headers)
+        .err()
+        .map(|e| e.valid_up_to())
+}
+
+/// # Header Fields
+///
+/// An iterator over the header fields of a header block, yielding `(name, value)` pairs.
+///
+/// Folded values are unfolded, which requires a copy; all other values are zero-copy. Leading
+/// whitespace is removed from values, and lines without a `:` are skipped.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::message::{self, Headers};
+/// let payload = Bytes::from("From: bob@example.com\r\nSubject: Hi\r\n\r\nHello!");
+/// let (headers, _) = message::split(&payload);
+/// assert!(Headers::new(headers).any(|(name, _)| name.eq_ignore_ascii_case(b"from")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Headers {
+    bytes: Bytes,
+}
+
+impl Headers {
+    /// Create a new `Headers` iterator over the given header block.
+    #[must_use]
+    pub fn new(bytes: Bytes) -> Self {
+        Self { bytes }
+    }
+}
+
+impl Iterator for Headers {
+    type Item = (Bytes, Bytes);
+
+    /// Return the next header field.
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.bytes.is_empty() {
+            let mut end = 0;
+            let mut folded = false;
+            let next = loop {
+                let Some(pos) = self.bytes[end..].find(b"\r\n") else {
+                    end = self.bytes.len();
+                    break end;
+                };
+
+                // CRLF followed by whitespace is a fold, not the end of the field
+                if matches!(self.bytes.get(end + pos + 2).copied(), Some(b' ' | b'\t')) {
+                    folded = true;
+                    end += pos + 2;
+                } else {
+                    end += pos;
+                    break end + 2;
+                }
+            };
+
+            let field = self.bytes.slice(..end);
+            self.bytes.advance(next);
+
+            let Some(colon) = field.find_byte(b':') else {
+                continue;
+            };
+
+            let name = field.slice(..colon);
+            let value = if folded {
+                unfold(&field[colon + 1..])
+            } else {
+                field.slice(colon + 1..)
+            };
+            let start = value
+                .iter()
+                .position(|&b| b != b' ' && b != b'\t')
+                .unwrap_or(value.len());
+
+            return Some((name, value.slice(start..)));
+        }
+
+        None
+    }
+}
+
+impl FusedIterator for Headers {}
+
+/// Remove the CRLFs from a folded header field value.
+fn unfold(value: &[u8]) -> Bytes {
+    let mut unfolded = BytesMut::with_capacity(value.len());
+    for line in value.split_str("\r\n") {
+        unfolded.extend_from_slice(line);
+    }
+    unfolded.freeze()
+}
+
+/// # `Received` Header Field
+///
+/// One hop of the trace a message accumulates on its way, parsed leniently: clauses that are
+/// missing or malformed are `None`, and additional clauses are ignored.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.4>
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::message::Received;
+/// let received = Received::parse(&Bytes::from(
+///     "from client.example.com (client.example.com [192.0.2.1]) by mx.example.net with ESMTPS \
+///      id 4Xy7 for <alice@example.net>; Tue, 1 Jul 2025 12:34:56 +0200",
+/// ));
+/// assert_eq!(received.from.unwrap(), "client.example.com");
+/// assert_eq!(received.from_info.unwrap(), "client.example.com [192.0.2.1]");
+/// assert_eq!(received.by.unwrap(), "mx.example.net");
+/// assert_eq!(received.with.unwrap(), "ESMTPS");
+/// assert_eq!(received.recipient.unwrap(), "alice@example.net");
+/// assert_eq!(received.timestamp(), Some(1_751_366_096));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Received {
+    /// The `from` clause: the name the client gave in `HELO`/`EHLO`.
+    pub from: Option<Bytes>,
+    /// The comment following the `from` clause, usually the client's reverse DNS name and address.
+    pub from_info: Option<Bytes>,
+    /// The `by` clause: the name of the receiving host.
+    pub by: Option<Bytes>,
+    /// The comment following the `by` clause.
+    pub by_info: Option<Bytes>,
+    /// The `via` clause: the link type, e.g. `TCP`.
+    pub via: Option<Bytes>,
+    /// The `with` clause: the protocol, e.g. `ESMTPS`.
+    pub with: Option<Bytes>,
+    /// The `id` clause: the receiving host's identifier for the message.
+    pub id: Option<Bytes>,
+    /// The `for` clause: the recipient, without angle brackets.
+    pub recipient: Option<Bytes>,
+    /// The date and time after the `;`, unparsed; see [`Received::timestamp`].
+    pub date: Option<Bytes>,
+}
+
+impl Received {
+    /// Parse the value of a `Received` header field, as yielded by [`Headers`].
+    #[must_use]
+    pub fn parse(value: &Bytes) -> Self {
+        let (clauses, date) = match value.rfind_byte(b';') {
+            Some(pos) => (
+                value.slice(..pos),
+                Some(value.slice_ref(value[pos + 1..].trim_ascii())),
+            ),
+            None => (value.clone(), None),
+        };
+
+        let mut received = Self {
+            date: date.filter(|date| !date.is_empty()),
+            ..Self::default()
+        };
+
+        let mut tokens = stamp_tokens(clauses).peekable();
+        while let Some((keyword, comment)) = tokens.next() {
+            // a stray comment, or a keyword without a value
+            let Some((value, false)) = tokens.next_if(|_| !comment) else {
+                continue;
+            };
+            let info = tokens
+                .next_if(|&(_, comment)| comment)
+                .map(|(info, _)| info);
+
+            let clause = match keyword.to_ascii_lowercase().as_slice() {
+                b"from" => {
+                    received.from_info = info;
+                    &mut received.from
+                }
+                b"by" => {
+                    received.by_info = info;
+                    &mut received.by
+                }
+                b"via" => &mut received.via,
+                b"with" => &mut received.with,
+                b"id" => &mut received.id,
+                b"for" => {
+                    let path = value.strip_prefix(b"<").and_then(|v| v.strip_suffix(b">"));
+                    let value = path.map_or(value.clone(), |path| value.slice_ref(path));
+                    received.recipient.get_or_insert(value);
+                    continue;
+                }
+                _ => continue,
+            };
+            clause.get_or_insert(value);
+        }
+
+        received
+    }
+
+    /// The date and time as seconds since the Unix epoch, or `None` if it is missing or
+    /// malformed.
+    ///
+    /// Obsolete two-digit years and zone names are accepted; unknown zone names are taken as UTC.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5322#section-3.3>
+    #[must_use]
+    pub fn timestamp(&self) -> Option<i64> {
+        let date = self.date.as_ref()?;
+        let mut fields = date
+            .fields_with(|c| c.is_ascii_whitespace() || c == ',')
+            .peekable();
+        // the day of the week is optional
+        fields.next_if(|field| field.iter().all(u8::is_ascii_alphabetic));
+
+        let day = u32::from_ascii(fields.next()?).ok()?;
+        let month = fields.next()?;
+        let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? as u32 + 1;
+        let year = fields.next()?;
+        if !year.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let year = match (u32::from_ascii(year).ok()?, year.len()) {
+            (year @ 0..50, 2) => year + 2000,
+            (year, 2 | 3) => year + 1900,
+            (year, _) => year,
+        };
+
+        let mut time = fields.next()?.split_str(":");
+        let hour = u32::from_ascii(time.next()?).ok()?;
+        let minute = u32::from_ascii(time.next()?).ok()?;
+        let second = time.next().map_or(Some(0), |s| u32::from_ascii(s).ok())?;
+        let offset = zone_offset(fields.next()?)?;
+
+        // a larger year is not a real date, and bounding it keeps the arithmetic from overflowing
+        if year > 9999 || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let days = days_from_civil(i64::from(year), month, day);
+        let seconds = i64::from(hour * 3_600 + minute * 60 + second);
+        Some(days * 86_400 + seconds - offset * 60)
+    }
+}
+
+/// Parse the `Received` header fields of a message `payload`, most recent hop first, e.g. to
+/// count hops for loop detection.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::message;
+/// let payload = Bytes::from(
+///     "Received: from b by c; 1 Jul 2025 12:00:05 +0000\r\n\
+///      Received: from a by b; 1 Jul 2025 12:00:00 +0000\r\n\
+///      Subject: Hi\r\n\r\nHello!",
+/// );
+/// let hops = message::received(&payload);
+/// assert_eq!(hops.len(), 2);
+/// assert_eq!(hops[0].timestamp().unwrap() - hops[1].timestamp().unwrap(), 5);
+/// ```
+#[must_use]
+pub fn received(payload: &Bytes) -> Vec<Received> {
+    let (headers, _) = split(payload);
+    Headers::new(headers)
+        .filter(|(name, _)| name.eq_ignore_ascii_case(b"Received"))
+        .map(|(_, value)| Received::parse(&value))
+        .collect()
+}
+
+const MONTHS: [&[u8]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// Split the clauses of a `Received` field into words and comments, flagging comments. Comments
+/// are returned without their parentheses, and may nest.
+fn stamp_tokens(clauses: Bytes) -> impl Iterator<Item = (Bytes, bool)> {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        while clauses.get(pos).is_some_and(u8::is_ascii_whitespace) {
+            pos += 1;
+        }
+        let start = pos;
+
+        if clauses.get(pos) == Some(&b'(') {
+            // an unterminated comment runs to the end
+            let mut end = clauses.len();
+            let mut depth = 0;
+            while let Some(&b) = clauses.get(pos) {
+                match b {
+                    b'\\' => pos += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = pos;
+                            pos += 1;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            pos = pos.min(clauses.len());
+            return Some((clauses.slice(start + 1..end), true));
+        }
+
+        while clauses
+            .get(pos)
+            .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'(')
+        {
+            pos += 1;
+        }
+        (pos > start).then(|| (clauses.slice(start..pos), false))
+    })
+}
+
+/// The offset from UTC of a zone, in minutes.
+fn zone_offset(zone: &[u8]) -> Option<i64> {
+    if let [sign @ (b'+' | b'-'), digits @ ..] = zone {
+        if digits.len() != 4 || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let hhmm = i64::from_ascii(digits).ok()?;
+        let offset = hhmm / 100 * 60 + hhmm % 100;
+        return Some(if *sign == b'-' { -offset } else { offset });
+    }
+
+    let hours = match zone.to_ascii_uppercase().as_slice() {
+        b"EDT" => -4,
+        b"EST" | b"CDT" => -5,
+        b"CST" | b"MDT" => -6,
+        b"MST" | b"PDT" => -7,
+        b"PST" => -8,
+        zone if zone.iter().all(u8::is_ascii_alphabetic) => 0,
+        _ => return None,
+    };
+    Some(hours * 60)
+}
+
+/// Days since the Unix epoch of a date in the proleptic Gregorian calendar.
+///
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::both(b"Subject: Hi\r\n\r\nHello!", b"Subject: Hi\r\n", b"Hello!")]
+    #[case::multiple(
+        b"From: bob@example.com\r\nSubject: Hi\r\n\r\nHello!\r\n\r\nBye!",
+        b"From: bob@example.com\r\nSubject: Hi\r\n",
+        b"Hello!\r\n\r\nBye!"
+    )]
+    #[case::empty_body(b"Subject: Hi\r\n\r\n", b"Subject: Hi\r\n", b"")]
+    #[case::no_body(b"Subject: Hi\r\n", b"Subject: Hi\r\n", b"")]
+    #[case::no_headers(b"\r\nHello!", b"", b"Hello!")]
+    #[case::empty(b"", b"", b"")]
+    fn split(
+        #[case] payload: &'static [u8],
+        #[case] headers: &'static [u8],
+        #[case] body: &'static [u8],
+    ) {
+        assert_eq!(
+            super::split(&Bytes::from_static(payload)),
+            (Bytes::from_static(headers), Bytes::from_static(body))
+        );
+    }
+
+    #[rstest]
+    #[case::ascii(b"Subject: Hi\r\n\r\nHello!", None)]
+    #[case::utf8(b"Subject: \xce\xb1=\xce\xb2\r\n\r\nHello!", None)]
+    #[case::truncated(b"Subject: \xce\r\n\r\nHello!", Some(9))]
+    #[case::latin1(b"From: bob\r\nSubject: caf\xe9\r\n\r\nHello!", Some(24))]
+    #[case::body_ignored(b"Subject: Hi\r\n\r\n\xff", None)]
+    #[case::no_headers(b"\r\n\xff", None)]
+    fn invalid_utf8(#[case] payload: &'static [u8], #[case] expected: Option<usize>) {
+        assert_eq!(super::invalid_utf8(&Bytes::from_static(payload)), expected);
+    }
+
+    #[rstest]
+    #[case::simple(b"Subject: Hi", &[("Subject", "Hi")])]
+    #[case::crlf(b"Subject: Hi\r\n", &[("Subject", "Hi")])]
+    #[case::multiple(
+        b"From: bob@example.com\r\nTo: alice@example.com\r\n",
+        &[("From", "bob@example.com"), ("To", "alice@example.com")]
+    )]
+    #[case::folded(
+        b"Subject: Hello,\r\n world\r\n\tand all\r\nTo: alice@example.com",
+        &[("Subject", "Hello, world\tand all"), ("To", "alice@example.com")]
+    )]
+    #[case::no_space(b"Subject:Hi", &[("Subject", "Hi")])]
+    #[case::empty_value(b"X-Empty:\r\nSubject: Hi", &[("X-Empty", ""), ("Subject", "Hi")])]
+    #[case::malformed(b"garbage\r\nSubject: Hi", &[("Subject", "Hi")])]
+    #[case::empty(b"", &[])]
+    fn headers(#[case] input: &'static [u8], #[case] expected: &[(&'static str, &'static str)]) {
+        let headers: Vec<_> = Headers::new(Bytes::from_static(input)).collect();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(name, value)| (Bytes::from(name), Bytes::from(value)))
+            .collect();
+        assert_eq!(headers, expected);
+    }
+
+    #[rstest]
+    #[case::full("Tue, 1 Jul 2025 12:34:56 +0000", Some(1_751_373_296))]
+    #[case::no_weekday("1 Jul 2025 12:34:56 +0000", Some(1_751_373_296))]
+    #[case::comment("1 Jul 2025 12:34:56 -0000 (UTC)", Some(1_751_373_296))]
+    #[case::obsolete("Fri, 31 dec 99 23:59 EST", Some(946_702_740))]
+    #[case::leap("29 Feb 2024 00:00:00 Z", Some(1_709_164_800))]
+    #[case::epoch("1 Jan 1970 00:00:00 GMT", Some(0))]
+    #[case::bad_month("1 Foo 2025 12:34:56 +0000", None)]
+    #[case::bad_zone("1 Jul 2025 12:34:56 +00", None)]
+    #[case::bad_time("1 Jul 2025 24:00:00 +0000", None)]
+    #[case::truncated("1 Jul 2025", None)]
+    #[case::huge_year("1 Jul 9223372036854775807 12:34:56 +0000", None)]
+    #[case::far_year("1 Jul 10000 12:34:56 +0000", None)]
+    #[case::max_year("31 Dec 9999 23:59:59 +0000", Some(253_402_300_799))]
+    #[case::negative_year("1 Jul -25 12:34:56 +0000", None)]
+    #[case::negative_hour("1 Jul 2025 -1:34:56 +0000", None)]
+    fn timestamp(#[case] date: &'static str, #[case] expected: Option<i64>) {
+        let received = Received::parse(&Bytes::from(["from a by b; ", date].concat()));
+        assert_eq!(received.timestamp(), expected);
+    }
+
+    #[test]
+    fn received_clauses() {
+        let received = Received::parse(&Bytes::from(
+            "from a.example (a.example [192.0.2.1] (nested)) by b.example (Postfix) via TCP \
+             with ESMTPS (TLS1.3) id 1A2b for <alice@b.example> x-extra y;\r\n 1 Jul 2025 12:34:56 +0000",
+        ));
+        assert_eq!(
+            received,
+            Received {
+                from: Some(Bytes::from("a.example")),
+                from_info: Some(Bytes::from("a.example [192.0.2.1] (nested)")),
+                by: Some(Bytes::from("b.example")),
+                by_info: Some(Bytes::from("Postfix")),
+                via: Some(Bytes::from("TCP")),
+                with: Some(Bytes::from("ESMTPS")),
+                id: Some(Bytes::from("1A2b")),
+                recipient: Some(Bytes::from("alice@b.example")),
+                date: Some(Bytes::from("1 Jul 2025 12:34:56 +0000")),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::empty("", Received::default())]
+    #[case::no_date("by b", Received { by: Some(Bytes::from("b")), ..Received::default() })]
+    #[case::dangling("from", Received::default())]
+    #[case::unterminated("from a (b", Received {
+        from: Some(Bytes::from("a")),
+        from_info: Some(Bytes::from("b")),
+        ..Received::default()
+    })]
+    fn received_lenient(#[case] value: &'static str, #[case] expected: Received) {
+        assert_eq!(Received::parse(&Bytes::from(value)), expected);
+    }
+
+    #[test]
+    fn received() {
+        let payload = Bytes::from(
+            "Received: from b by c; 1 Jul 2025 12:00:05 +0000\r\n\
+             Subject: Hi\r\n\
+             received: from a\r\n by b; 1 Jul 2025 12:00:00 +0000\r\n\
+             \r\n\
+             Received: in the body",
+        );
+        let hops = super::received(&payload);
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].from, Some(Bytes::from("b")));
+        assert_eq!(hops[1].by, Some(Bytes::from("b")));
+    }
+}
@@ -6,11 +6,19 @@ pub(crate) use super::*;
 
 mod try_from;
 
+mod deferred;
+pub use deferred::*;
+
 mod iterators;
 pub(crate) use iterators::*;
+pub use iterators::{Lines, Tokens};
 
 mod helpers;
-use helpers::*;
+pub(crate) use helpers::*;
+
+pub mod message;
+pub mod sasl;
+pub mod validate;
 
 mod mail;
 mod rcpt;
@@ -20,6 +28,29 @@ mod rfc5321;
 type Result<T> = core::result::Result<T, Error>;
 type CommandResult = Result<Command>;
 
+bitflags::bitflags! {
+    /// # Lenient Parsing Options
+    ///
+    /// Opt-in tolerance for common deviations from the grammar. The default is strict.
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+    pub struct Lenient: u8 {
+        /// Accept a single trailing dot on domains (e.g. `example.com.`), removing it.
+        const TRAILING_DOT = 0b0001;
+        /// Accept a space between `FROM:` or `TO:` and the path, e.g. `MAIL FROM: <bob@example.com>`.
+        const SPACE_AFTER_COLON = 0b0010;
+        /// Accept a path without angle brackets, e.g. `MAIL FROM:bob@example.com`.
+        const BARE_ADDRESS = 0b0100;
+        /// Treat runs of SP and HTAB as a single separator, e.g. `MAIL  FROM:<bob@example.com>`.
+        const WHITESPACE = 0b1000;
+        /// Keep the parameters of `MAIL` and `RCPT` exactly as received, in
+        /// [`Mail::raw_parameters`](crate::mail::Mail::raw_parameters) and
+        /// [`Rcpt::raw_parameters`](crate::rcpt::Rcpt::raw_parameters), so a proxy can forward the
+        /// command byte for byte. Not a deviation from the grammar, but kept here with the other
+        /// parsing options.
+        const RAW_PARAMETERS = 0b1_0000;
+    }
+}
+
 #[non_exhaustive]
 #[derive(thiserror::Error, Debug, Clone, PartialEq, Hash)]
 pub enum Error {
@@ -38,6 +69,21 @@ pub enum Error {
     #[error("Invalid syntax")]
     InvalidSyntax,
 
+    #[error("Invalid domain")]
+    InvalidDomain,
+
+    #[error("Invalid address")]
+    InvalidAddress,
+
+    #[error("Invalid xtext")]
+    InvalidXText,
+
+    #[error("Invalid address literal")]
+    InvalidAddressLiteral,
+
+    #[error("Invalid value for parameter {keyword}")]
+    InvalidParameterValue { keyword: &'static str },
+
     #[error("Empty command")]
     Empty,
 
@@ -54,6 +100,102 @@ pub enum Error {
     ParameterNotImplemented,
 }
 
+/// # Parse Error Kind
+///
+/// The variant of an [`Error`] without its fields, e.g. to count errors by kind.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ErrorKind {
+    InvalidCommand,
+    InvalidParameter,
+    MissingParameter,
+    UnexpectedParameter,
+    InvalidSyntax,
+    InvalidDomain,
+    InvalidAddress,
+    InvalidXText,
+    InvalidAddressLiteral,
+    InvalidParameterValue,
+    Empty,
+    TooLong,
+    Eoi,
+    CommandNotImplemented,
+    ParameterNotImplemented,
+}
+
+impl ErrorKind {
+    /// Number of kinds.
+    pub(crate) const COUNT: usize = 15;
+
+    /// The `snake_case` name of the kind, e.g. `too_long`, for logs and metrics labels.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::InvalidCommand => "invalid_command",
+            Self::InvalidParameter => "invalid_parameter",
+            Self::MissingParameter => "missing_parameter",
+            Self::UnexpectedParameter => "unexpected_parameter",
+            Self::InvalidSyntax => "invalid_syntax",
+            Self::InvalidDomain => "invalid_domain",
+            Self::InvalidAddress => "invalid_address",
+            Self::InvalidXText => "invalid_xtext",
+            Self::InvalidAddressLiteral => "invalid_address_literal",
+            Self::InvalidParameterValue => "invalid_parameter_value",
+            Self::Empty => "empty",
+            Self::TooLong => "too_long",
+            Self::Eoi => "eoi",
+            Self::CommandNotImplemented => "command_not_implemented",
+            Self::ParameterNotImplemented => "parameter_not_implemented",
+        }
+    }
+}
+
+impl Error {
+    /// The kind of this error, without its fields.
+    #[must_use]
+    pub const fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidCommand => ErrorKind::InvalidCommand,
+            Self::InvalidParameter => ErrorKind::InvalidParameter,
+            Self::MissingParameter => ErrorKind::MissingParameter,
+            Self::UnexpectedParameter => ErrorKind::UnexpectedParameter,
+            Self::InvalidSyntax => ErrorKind::InvalidSyntax,
+            Self::InvalidDomain => ErrorKind::InvalidDomain,
+            Self::InvalidAddress => ErrorKind::InvalidAddress,
+            Self::InvalidXText => ErrorKind::InvalidXText,
+            Self::InvalidAddressLiteral => ErrorKind::InvalidAddressLiteral,
+            Self::InvalidParameterValue { .. } => ErrorKind::InvalidParameterValue,
+            Self::Empty => ErrorKind::Empty,
+            Self::TooLong => ErrorKind::TooLong,
+            Self::Eoi => ErrorKind::Eoi,
+            Self::CommandNotImplemented => ErrorKind::CommandNotImplemented,
+            Self::ParameterNotImplemented => ErrorKind::ParameterNotImplemented,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// The closest [`std::io::ErrorKind`] for this error.
+    #[must_use]
+    pub fn io_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+
+        match self {
+            Self::Eoi => ErrorKind::UnexpectedEof,
+            Self::CommandNotImplemented | Self::ParameterNotImplemented => ErrorKind::Unsupported,
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        Self::new(error.io_kind(), error)
+    }
+}
+
 /*
 #[cfg(test)]
 #[allow(non_snake_case)]
@@ -126,3 +268,24 @@ mod tests {
     }
 }
 */
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::io;
+
+    #[rstest]
+    #[case::eoi(Error::Eoi, io::ErrorKind::UnexpectedEof)]
+    #[case::not_implemented(Error::CommandNotImplemented, io::ErrorKind::Unsupported)]
+    #[case::too_long(Error::TooLong, io::ErrorKind::InvalidData)]
+    #[case::syntax(Error::InvalidSyntax, io::ErrorKind::InvalidData)]
+    fn into_io(#[case] error: Error, #[case] kind: io::ErrorKind) {
+        let io = io::Error::from(error.clone());
+        assert_eq!(io.kind(), kind);
+        assert_eq!(
+            io.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&error)
+        );
+    }
+}
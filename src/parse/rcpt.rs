@@ -21,13 +21,46 @@ impl TryFrom<Bytes> for Parameter {
             }
 
             (orcpt, Some(x)) if orcpt.eq_ignore_ascii_case(b"ORCPT") => {
-                Email::try_from(x).map(Parameter::ORcpt)
+                ORcpt::try_from(x).map(Parameter::ORcpt)
+            }
+
+            (conneg, Some(x)) if conneg.eq_ignore_ascii_case(b"CONNEG") => {
+                XText::try_from(x).map(ConNeg).map(Parameter::ConNeg)
             }
             _ => Err(Error::InvalidParameter),
         }
     }
 }
 
+impl TryFrom<Bytes> for ORcpt {
+    type Error = Error;
+
+    /// Parse `addr-type ";" xtext`, accepting any address type.
+    ///
+    /// Only `rfc822` addresses are checked to be `XText`; others are kept as received.
+    fn try_from(mut input: Bytes) -> Result<Self> {
+        let pos = input
+            .find_byte(b';')
+            .ok_or(Error::InvalidParameterValue { keyword: "ORCPT" })?;
+        let addr_type = input.split_to(pos);
+        input.advance(1); // the `;`
+
+        if addr_type.is_empty() || input.is_empty() {
+            return Err(Error::InvalidParameterValue { keyword: "ORCPT" });
+        }
+
+        let orcpt = Self {
+            addr_type,
+            addr: input,
+        };
+        if orcpt.is_rfc822() {
+            XText::try_from(orcpt.addr.clone())?;
+        }
+
+        Ok(orcpt)
+    }
+}
+
 impl TryFrom<Bytes> for Notify {
     type Error = Error;
 
@@ -41,7 +74,7 @@ impl TryFrom<Bytes> for Notify {
             delay if delay.eq_ignore_ascii_case(b"DELAY") => Ok(Self::DELAY),
             failure if failure.eq_ignore_ascii_case(b"FAILURE") => Ok(Self::FAILURE),
             success if success.eq_ignore_ascii_case(b"SUCCESS") => Ok(Self::SUCCESS),
-            _ => Err(Error::InvalidSyntax),
+            _ => Err(Error::InvalidParameterValue { keyword: "NOTIFY" }),
         }) {
             flags |= token?;
         }
@@ -54,11 +87,31 @@ impl Parameters<Result<Parameter>> for Rcpt {
     fn parameters(&mut self, parameters: impl Iterator<Item = Result<Parameter>>) -> Result<()> {
         for parameter in parameters {
             match parameter? {
-                Parameter::ORcpt(email) => self.orcpt = Some(email),
+                Parameter::ORcpt(orcpt) => self.orcpt = Some(orcpt),
                 Parameter::Notify(notify) => self.notify = Some(notify),
+                Parameter::ConNeg(conneg) => self.conneg = Some(conneg),
             }
         }
 
         Ok(())
     }
 }
+
+impl Rcpt {
+    /// The [raw parameters](Rcpt::raw_parameters), if they are set and still parse to the typed
+    /// parameters.
+    pub(crate) fn replay(&self) -> Option<&Bytes> {
+        let raw = self.raw_parameters.as_ref()?;
+        let mut parsed = Self {
+            orcpt: None,
+            notify: None,
+            conneg: None,
+            raw_parameters: None,
+            to: self.to.clone(),
+        };
+        parsed
+            .parameters(Tokens::whitespace(raw.clone()).map(Parameter::try_from))
+            .ok()?;
+        (parsed == *self).then_some(raw)
+    }
+}
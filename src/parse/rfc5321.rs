@@ -1,41 +1,126 @@
+use alloc::boxed::Box;
+
 use btoi::{ParseIntegerErrorKind, btou_radix};
 
 use super::*;
 use crate::mail::{self, Mail, ReversePath};
 use crate::rcpt::{self, Rcpt};
 
-pub(super) fn helo(mut tokens: Tokens) -> CommandResult {
-    match (tokens.next(), tokens.next()) {
-        (Some(d), None) => Domain::try_from(d).map(Host::Domain).map(Command::Helo),
-        (Some(_), Some(_)) => Err(Error::UnexpectedParameter),
-        (None, _) => Err(Error::MissingParameter),
+/// Remove a single trailing dot from `input` if [`Lenient::TRAILING_DOT`] is set.
+fn trailing_dot(input: Bytes, lenient: Lenient) -> Bytes {
+    if lenient.contains(Lenient::TRAILING_DOT) && input.ends_with(b".") {
+        return input.slice(..input.len() - 1);
     }
+
+    input
 }
 
-pub(super) fn ehlo(mut tokens: Tokens) -> CommandResult {
+/// Get the path following `prefix` (e.g. `FROM:`), which is the next token if there is a space
+/// after the colon and [`Lenient::SPACE_AFTER_COLON`] is set.
+fn path(tokens: &mut Tokens, prefix: &[u8], lenient: Lenient) -> Result<Bytes> {
+    let token = tokens.next().ok_or(Error::MissingParameter)?;
+    let path = token.strip_prefix_ci(prefix).ok_or(Error::InvalidSyntax)?;
+
+    if path.is_empty() && lenient.contains(Lenient::SPACE_AFTER_COLON) {
+        return tokens.next().ok_or(Error::MissingParameter);
+    }
+
+    Ok(path)
+}
+
+/// Strip the angle brackets from `path`, which may be omitted if [`Lenient::BARE_ADDRESS`] is
+/// set.
+fn angled(path: Bytes, lenient: Lenient) -> Result<Bytes> {
+    match path.strip_angled() {
+        Some(path) => Ok(path),
+        None if lenient.contains(Lenient::BARE_ADDRESS)
+            && !path.starts_with(b"<")
+            && !path.ends_with(b">") =>
+        {
+            Ok(path)
+        }
+        None => Err(Error::InvalidAddress),
+    }
+}
+
+/// Get the only argument of `HELO` or `EHLO`.
+fn argument(mut tokens: Tokens) -> Result<Bytes> {
     match (tokens.next(), tokens.next()) {
-        (Some(d), None) => Host::try_from(d).map(Command::Ehlo),
+        (Some(argument), None) => Ok(argument),
         (Some(_), Some(_)) => Err(Error::UnexpectedParameter),
         (None, _) => Err(Error::MissingParameter),
     }
 }
 
-pub(super) fn mail(mut tokens: Tokens) -> CommandResult {
-    let token = tokens.next().ok_or(Error::MissingParameter)?;
-    let rp = token
-        .strip_prefix_ci(b"FROM:")
-        .ok_or(Error::InvalidSyntax)?;
-
-    let from = if rp == b"<>"[..] {
-        ReversePath::Null
-    } else {
-        ReversePath::Email(
-            rp.strip_angled()
-                .ok_or(Error::InvalidSyntax)
-                .and_then(Email::try_from)?,
-        )
+/// Get the reverse path of `MAIL`, without the angle brackets, or `None` if it is null.
+fn reverse_path(tokens: &mut Tokens, lenient: Lenient) -> Result<Option<Bytes>> {
+    let rp = path(tokens, b"FROM:", lenient)?;
+    if rp == b"<>"[..] {
+        return Ok(None);
+    }
+
+    angled(rp, lenient).map(|path| Some(trailing_dot(path, lenient)))
+}
+
+/// Get the forward path of `RCPT`, without the angle brackets.
+fn forward_path(tokens: &mut Tokens, lenient: Lenient) -> Result<Bytes> {
+    path(tokens, b"TO:", lenient)
+        .and_then(|path| angled(path, lenient))
+        .map(|path| trailing_dot(path, lenient))
+}
+
+pub(super) fn helo(tokens: Tokens, lenient: Lenient) -> CommandResult {
+    Domain::try_from(trailing_dot(argument(tokens)?, lenient))
+        .map(Host::Domain)
+        .map(Command::Helo)
+}
+
+pub(super) fn helo_deferred(tokens: Tokens, lenient: Lenient) -> Result<Unvalidated> {
+    argument(tokens).map(|domain| Unvalidated::Helo(trailing_dot(domain, lenient)))
+}
+
+pub(super) fn ehlo(tokens: Tokens, lenient: Lenient) -> CommandResult {
+    let host = argument(tokens)?;
+    if host.starts_with(b"[") {
+        return Host::try_from(host).map(Command::Ehlo);
+    }
+
+    Domain::try_from(trailing_dot(host, lenient))
+        .map(Host::Domain)
+        .map(Command::Ehlo)
+}
+
+pub(super) fn ehlo_deferred(tokens: Tokens, lenient: Lenient) -> Result<Unvalidated> {
+    let host = argument(tokens)?;
+    // address literals are cheap to validate
+    if host.starts_with(b"[") {
+        return Host::try_from(host)
+            .map(Command::Ehlo)
+            .map(Unvalidated::Command);
+    }
+
+    Ok(Unvalidated::Ehlo(trailing_dot(host, lenient)))
+}
+
+pub(super) fn mail(mut tokens: Tokens, lenient: Lenient) -> CommandResult {
+    let from = match reverse_path(&mut tokens, lenient)? {
+        Some(path) => ReversePath::Email(Email::try_from(path)?),
+        None => ReversePath::Null,
     };
 
+    new_mail(from, tokens, lenient)
+}
+
+pub(super) fn mail_deferred(mut tokens: Tokens, lenient: Lenient) -> Result<Unvalidated> {
+    let from = reverse_path(&mut tokens, lenient)?;
+    Ok(Unvalidated::Mail {
+        from,
+        parameters: tokens.remainder(),
+    })
+}
+
+/// Build a `MAIL` command from its validated reverse path, parsing its `parameters`.
+pub(super) fn new_mail(from: ReversePath, parameters: Tokens, lenient: Lenient) -> CommandResult {
     let mut mail = Mail {
         from,
         size: None,
@@ -43,31 +128,46 @@ pub(super) fn mail(mut tokens: Tokens) -> CommandResult {
         envid: None,
         auth: None,
         body: None,
+        conperm: None,
+        solicit: None,
+        prdr: false,
+        smtputf8: false,
+        raw_parameters: lenient
+            .contains(Lenient::RAW_PARAMETERS)
+            .then(|| parameters.remainder()),
     };
 
-    mail.parameters(tokens.map(mail::Parameter::try_from))?;
+    mail.parameters(parameters.map(mail::Parameter::try_from))?;
+    Ok(Command::Mail(Box::new(mail)))
+}
 
-    Ok(Command::Mail(mail))
+pub(super) fn rcpt(mut tokens: Tokens, lenient: Lenient) -> CommandResult {
+    let to = forward_path(&mut tokens, lenient).and_then(Email::try_from)?;
+    new_rcpt(to, tokens, lenient)
 }
 
-pub(super) fn rcpt(mut tokens: Tokens) -> CommandResult {
-    let token = tokens.next().ok_or(Error::MissingParameter)?;
-    let to = token
-        .strip_prefix_ci(b"TO:")
-        .as_ref()
-        .and_then(Helpers::strip_angled)
-        .ok_or(Error::InvalidSyntax)
-        .and_then(Email::try_from)?;
+pub(super) fn rcpt_deferred(mut tokens: Tokens, lenient: Lenient) -> Result<Unvalidated> {
+    let to = forward_path(&mut tokens, lenient)?;
+    Ok(Unvalidated::Rcpt {
+        to,
+        parameters: tokens.remainder(),
+    })
+}
 
+/// Build a `RCPT` command from its validated forward path, parsing its `parameters`.
+pub(super) fn new_rcpt(to: Email, parameters: Tokens, lenient: Lenient) -> CommandResult {
     let mut rcpt = Rcpt {
         to,
         orcpt: None,
         notify: None,
+        conneg: None,
+        raw_parameters: lenient
+            .contains(Lenient::RAW_PARAMETERS)
+            .then(|| parameters.remainder()),
     };
 
-    rcpt.parameters(tokens.map(rcpt::Parameter::try_from))?;
-
-    Ok(Command::Rcpt(rcpt))
+    rcpt.parameters(parameters.map(rcpt::Parameter::try_from))?;
+    Ok(Command::Rcpt(Box::new(rcpt)))
 }
 
 pub(super) fn data(mut tokens: Tokens) -> CommandResult {
@@ -136,17 +236,162 @@ pub(super) fn bdat(mut tokens: Tokens) -> CommandResult {
         .ok_or(Error::UnexpectedParameter)
 }
 
-#[allow(unused_variables, unused_mut, reason = "TODO")]
-pub(super) fn vrfy(mut tokens: Tokens) -> CommandResult {
-    todo!();
+pub(super) fn vrfy(tokens: Tokens) -> CommandResult {
+    tokens
+        .rest_as_one()
+        .map(Command::Vrfy)
+        .ok_or(Error::MissingParameter)
 }
 
-#[allow(unused_variables, unused_mut, reason = "TODO")]
-pub(super) fn expn(mut tokens: Tokens) -> CommandResult {
-    todo!();
+pub(super) fn expn(tokens: Tokens) -> CommandResult {
+    tokens
+        .rest_as_one()
+        .map(Command::Expn)
+        .ok_or(Error::MissingParameter)
 }
 
-#[allow(unused_variables, unused_mut, reason = "TODO")]
-pub(super) fn help(mut tokens: Tokens) -> CommandResult {
-    todo!();
+pub(super) fn help(tokens: Tokens) -> CommandResult {
+    Ok(Command::Help(tokens.rest_as_one()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::helo(b"HELO example.com.", Lenient::TRAILING_DOT, Ok("HELO example.com"))]
+    #[case::ehlo(b"EHLO example.com.", Lenient::TRAILING_DOT, Ok("EHLO example.com"))]
+    #[case::mail(
+        b"MAIL FROM:<bob@example.com.>",
+        Lenient::TRAILING_DOT,
+        Ok("MAIL FROM:<bob@example.com>")
+    )]
+    #[case::rcpt(
+        b"RCPT TO:<alice@example.com.>",
+        Lenient::TRAILING_DOT,
+        Ok("RCPT TO:<alice@example.com>")
+    )]
+    #[case::two_dots(
+        b"HELO example.com..",
+        Lenient::TRAILING_DOT,
+        Err(Error::InvalidDomain)
+    )]
+    #[case::only_dot(b"HELO .", Lenient::TRAILING_DOT, Err(Error::InvalidDomain))]
+    #[case::strict(b"HELO example.com.", Lenient::empty(), Err(Error::InvalidDomain))]
+    #[case::mail_space(
+        b"MAIL FROM: <bob@example.com> SIZE=1024",
+        Lenient::SPACE_AFTER_COLON,
+        Ok("MAIL FROM:<bob@example.com> SIZE=1024")
+    )]
+    #[case::mail_space_null(b"MAIL FROM: <>", Lenient::SPACE_AFTER_COLON, Ok("MAIL FROM:<>"))]
+    #[case::rcpt_space(
+        b"RCPT TO: <alice@example.com>",
+        Lenient::SPACE_AFTER_COLON,
+        Ok("RCPT TO:<alice@example.com>")
+    )]
+    #[case::rcpt_space_missing(
+        b"RCPT TO: ",
+        Lenient::SPACE_AFTER_COLON,
+        Err(Error::MissingParameter)
+    )]
+    #[case::mail_bare(
+        b"MAIL FROM:bob@example.com SIZE=1024",
+        Lenient::BARE_ADDRESS,
+        Ok("MAIL FROM:<bob@example.com> SIZE=1024")
+    )]
+    #[case::rcpt_bare(
+        b"RCPT TO:alice@example.com",
+        Lenient::BARE_ADDRESS,
+        Ok("RCPT TO:<alice@example.com>")
+    )]
+    #[case::rcpt_bare_space(b"RCPT TO: alice@example.com", Lenient::BARE_ADDRESS | Lenient::SPACE_AFTER_COLON, Ok("RCPT TO:<alice@example.com>"))]
+    #[case::mail_bare_half_open(
+        b"MAIL FROM:<bob@example.com",
+        Lenient::BARE_ADDRESS,
+        Err(Error::InvalidAddress)
+    )]
+    #[case::mail_bare_empty(b"MAIL FROM:", Lenient::BARE_ADDRESS, Err(Error::InvalidAddress))]
+    #[case::rcpt_bare_strict(
+        b"RCPT TO:alice@example.com",
+        Lenient::empty(),
+        Err(Error::InvalidAddress)
+    )]
+    #[case::mail_double_space(
+        b"MAIL  FROM:<bob@example.com>",
+        Lenient::WHITESPACE,
+        Ok("MAIL FROM:<bob@example.com>")
+    )]
+    #[case::mail_tab(
+        b"MAIL FROM:<bob@example.com>\tSIZE=1024",
+        Lenient::WHITESPACE,
+        Ok("MAIL FROM:<bob@example.com> SIZE=1024")
+    )]
+    #[case::mail_trailing_space(
+        b"MAIL FROM:<bob@example.com>  ",
+        Lenient::WHITESPACE,
+        Ok("MAIL FROM:<bob@example.com>")
+    )]
+    #[case::rcpt_spaces(b"RCPT TO: \t <alice@example.com>", Lenient::WHITESPACE | Lenient::SPACE_AFTER_COLON, Ok("RCPT TO:<alice@example.com>"))]
+    #[case::ehlo_double_space(b"EHLO  example.com", Lenient::WHITESPACE, Ok("EHLO example.com"))]
+    #[case::whitespace_only(b" \t ", Lenient::WHITESPACE, Err(Error::Empty))]
+    #[case::mail_double_space_strict(
+        b"MAIL  FROM:<bob@example.com>",
+        Lenient::empty(),
+        Err(Error::InvalidSyntax)
+    )]
+    #[case::mail_space_strict(
+        b"MAIL FROM: <bob@example.com>",
+        Lenient::empty(),
+        Err(Error::InvalidAddress)
+    )]
+    fn lenient(
+        #[case] input: &'static [u8],
+        #[case] lenient: Lenient,
+        #[case] expected: Result<&'static str>,
+    ) {
+        let result = Command::parse_lenient(Bytes::from_static(input), lenient)
+            .map(|command| command.to_string());
+        assert_eq!(result, expected.map(String::from));
+    }
+
+    #[rstest]
+    #[case::mail(b"MAIL FROM:<bob@example.com> body=8BITMIME SIZE=1024")]
+    #[case::mail_bare(b"MAIL FROM:<>")]
+    #[case::rcpt(b"RCPT TO:<alice@example.com> ORCPT=rfc822;alice@example.com notify=never")]
+    fn raw_parameters(#[case] input: &'static [u8]) {
+        let command =
+            Command::parse_lenient(Bytes::from_static(input), Lenient::RAW_PARAMETERS).unwrap();
+        assert_eq!(command.to_bytes(), [input, b"\r\n"].concat());
+        assert_eq!(command.to_string().as_bytes(), input);
+
+        // not kept by default, and not compared
+        let strict = Command::try_from(Bytes::from_static(input)).unwrap();
+        assert_eq!(strict, command);
+        match strict {
+            Command::Mail(mail) => assert_eq!(mail.raw_parameters, None),
+            Command::Rcpt(rcpt) => assert_eq!(rcpt.raw_parameters, None),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn raw_parameters_edited() {
+        let input = Bytes::from_static(b"MAIL FROM:<> body=8BITMIME SIZE=1024");
+        let Ok(Command::Mail(mut mail)) = Command::parse_lenient(input, Lenient::RAW_PARAMETERS)
+        else {
+            panic!("expected MAIL");
+        };
+
+        mail.size = Some(2048);
+        assert!(mail.raw_parameters.is_some());
+        assert_eq!(
+            mail.to_bytes(),
+            b"MAIL FROM:<> SIZE=2048 BODY=8BITMIME\r\n"[..]
+        );
+        assert_eq!(
+            Command::Mail(mail).to_string(),
+            "MAIL FROM:<> SIZE=2048 BODY=8BITMIME"
+        );
+    }
 }
@@ -0,0 +1,122 @@
+//! # SASL
+//!
+//! Client-side helpers for the `AUTH` exchange, as defined in
+//! [RFC 4954](https://datatracker.ietf.org/doc/html/rfc4954).
+
+use super::*;
+
+/// Decode the challenge of a `334` reply line, **excluding** the trailing CRLF.
+///
+/// Returns `Ok(None)` if the line is not a `334` reply, e.g. the `235` or `535` ending the
+/// exchange, and [`Error::InvalidSyntax`] if the challenge is not valid base64. An empty challenge
+/// decodes to empty `Bytes`.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::sasl;
+/// let challenge = sasl::challenge(Bytes::from("334 VXNlcm5hbWU6"));
+/// assert_eq!(challenge, Ok(Some(Bytes::from("Username:"))));
+/// ```
+pub fn challenge(line: Bytes) -> Result<Option<Bytes>> {
+    let _span = log::info_span!("challenge").entered();
+    log::debug!(line = ?Redact::bytes(&line));
+    if !line.starts_with(b"334") {
+        return Ok(None);
+    }
+
+    match line.get(3) {
+        None => Ok(Some(Bytes::new())),
+        Some(b' ') => Ok(Some(Base64::try_from(line.slice(4..))?.decode().freeze())),
+        Some(_) => Err(Error::InvalidSyntax),
+    }
+}
+
+/// # SASL Mechanism
+///
+/// One side of a SASL exchange, driven by the `AUTH` state machine: the initial response, if
+/// any, then a response to each challenge, then the outcome. Inputs and outputs are decoded; the
+/// base64 encoding is left to the driver.
+///
+/// Implement it to plug in an external SASL library or a proprietary mechanism:
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::sasl::SaslMechanism;
+/// struct Plain(Bytes);
+///
+/// impl SaslMechanism for Plain {
+///     type Error = core::convert::Infallible;
+///
+///     fn name(&self) -> &str {
+///         "PLAIN"
+///     }
+///
+///     fn initial_response(&mut self) -> Result<Option<Bytes>, Self::Error> {
+///         Ok(Some(self.0.clone()))
+///     }
+///
+///     fn step(&mut self, _challenge: &[u8]) -> Result<Bytes, Self::Error> {
+///         Ok(Bytes::new())
+///     }
+/// }
+///
+/// let mut plain = Plain(Bytes::from("\0user\0pencil"));
+/// assert_eq!(plain.initial_response(), Ok(Some(Bytes::from("\0user\0pencil"))));
+/// ```
+pub trait SaslMechanism {
+    /// The error returned when the exchange fails.
+    type Error;
+
+    /// The registered name of the mechanism, as advertised in the `EHLO` reply and sent in
+    /// `AUTH`.
+    fn name(&self) -> &str;
+
+    /// The initial response to send with `AUTH`, for mechanisms where the client goes first.
+    ///
+    /// # Errors
+    ///
+    /// If the mechanism cannot start.
+    fn initial_response(&mut self) -> core::result::Result<Option<Bytes>, Self::Error> {
+        Ok(None)
+    }
+
+    /// The response to the decoded `challenge` of a `334` reply, see [`challenge`].
+    ///
+    /// # Errors
+    ///
+    /// If the challenge is invalid; the driver should cancel the exchange.
+    fn step(&mut self, challenge: &[u8]) -> core::result::Result<Bytes, Self::Error>;
+
+    /// Complete the exchange on a `235` reply, with the decoded additional data, if any.
+    ///
+    /// # Errors
+    ///
+    /// If the server could not be verified, despite accepting the exchange.
+    fn finish(&mut self, _additional: Option<&[u8]>) -> core::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::login_username("334 VXNlcm5hbWU6", Ok(Some(Bytes::from("Username:"))))]
+    #[case::login_password("334 UGFzc3dvcmQ6", Ok(Some(Bytes::from("Password:"))))]
+    #[case::padded("334 Zm8=", Ok(Some(Bytes::from("fo"))))]
+    #[case::empty("334 ", Ok(Some(Bytes::new())))]
+    #[case::bare("334", Ok(Some(Bytes::new())))]
+    #[case::success("235 2.7.0 Authentication successful", Ok(None))]
+    #[case::failure("535 5.7.8 Authentication credentials invalid", Ok(None))]
+    #[case::no_space("334VXNlcm5hbWU6", Err(Error::InvalidSyntax))]
+    #[case::multiline("334-VXNlcm5hbWU6", Err(Error::InvalidSyntax))]
+    #[case::unpadded("334 Zm8", Err(Error::InvalidSyntax))]
+    #[case::too_much_padding("334 Z===", Err(Error::InvalidSyntax))]
+    #[case::inner_padding("334 Zg==Zm8=", Err(Error::InvalidSyntax))]
+    #[case::invalid_char("334 Zm9-", Err(Error::InvalidSyntax))]
+    fn test_challenge(#[case] input: &'static str, #[case] expected: Result<Option<Bytes>>) {
+        assert_eq!(challenge(Bytes::from(input)), expected);
+    }
+}
@@ -7,19 +7,33 @@ use super::*;
 impl TryFrom<Bytes> for Command {
     type Error = Error;
 
-    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn try_from(input: Bytes) -> Result<Self> {
+        Self::parse_lenient(input, Lenient::empty())
+    }
+}
+
+impl Command {
+    /// Parse a command line, **excluding** the trailing CRLF, tolerating the deviations enabled in
+    /// `lenient`.
+    ///
+    /// `Command::try_from` is equivalent to passing [`Lenient::empty`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn parse_lenient(input: Bytes, lenient: Lenient) -> Result<Self> {
         let _span = log::info_span!("Command").entered();
 
-        let mut tokens = Tokens::new(input, b' ');
+        let mut tokens = if lenient.contains(Lenient::WHITESPACE) {
+            Tokens::whitespace(input)
+        } else {
+            Tokens::new(input, b' ')
+        };
         let token = tokens.next().ok_or(Error::Empty)?;
         log::debug!(token = ?token.as_bstr());
 
         match token {
-            helo if helo.eq_ignore_ascii_case(b"HELO") => rfc5321::helo(tokens),
-            ehlo if ehlo.eq_ignore_ascii_case(b"EHLO") => rfc5321::ehlo(tokens),
-            mail if mail.eq_ignore_ascii_case(b"MAIL") => rfc5321::mail(tokens),
-            rcpt if rcpt.eq_ignore_ascii_case(b"RCPT") => rfc5321::rcpt(tokens),
+            helo if helo.eq_ignore_ascii_case(b"HELO") => rfc5321::helo(tokens, lenient),
+            ehlo if ehlo.eq_ignore_ascii_case(b"EHLO") => rfc5321::ehlo(tokens, lenient),
+            mail if mail.eq_ignore_ascii_case(b"MAIL") => rfc5321::mail(tokens, lenient),
+            rcpt if rcpt.eq_ignore_ascii_case(b"RCPT") => rfc5321::rcpt(tokens, lenient),
             data if data.eq_ignore_ascii_case(b"DATA") => rfc5321::data(tokens),
             rset if rset.eq_ignore_ascii_case(b"RSET") => rfc5321::rset(tokens),
             vrfy if vrfy.eq_ignore_ascii_case(b"VRFY") => rfc5321::vrfy(tokens),
@@ -56,13 +70,23 @@ impl TryFrom<Bytes> for Host {
                 );
                 if tag == b"IPv6" {
                     log::debug!("input is an IPv6 address");
+                    // a zone ID (`%25eth0` or `%eth0`) is only meaningful to the client, so
+                    // discard it
+                    let addr = match content.split_once_str(b"%") {
+                        Some((_, b"")) => return Err(Error::InvalidAddressLiteral),
+                        Some((addr, _zone)) => {
+                            log::debug!(zone = ?_zone.as_bstr(), "discarding zone ID");
+                            addr
+                        }
+                        None => content,
+                    };
                     Ok(Self::Ip(IpAddr::V6(
-                        Ipv6Addr::parse_ascii(content).map_err(|_| Error::InvalidSyntax)?,
+                        Ipv6Addr::parse_ascii(addr).map_err(|_| Error::InvalidAddressLiteral)?,
                     )))
                 } else {
                     log::debug!("empty tag");
                     if tag.is_empty() {
-                        return Err(Error::InvalidSyntax);
+                        return Err(Error::InvalidAddressLiteral);
                     }
 
                     unsafe {
@@ -73,7 +97,7 @@ impl TryFrom<Bytes> for Host {
                 }
             } else {
                 log::debug!("input is bracketed, but not an address literal or IP address");
-                Err(Error::InvalidSyntax)
+                Err(Error::InvalidAddressLiteral)
             }
         } else {
             log::debug!("input is not bracketed, so must be a domain");
@@ -87,10 +111,10 @@ impl TryFrom<Bytes> for Email {
 
     fn try_from(input: Bytes) -> Result<Self> {
         let _span = log::info_span!("Email").entered();
-        log::debug!(input = ?input.as_bstr());
-        let (local, host) = input.rsplit_once_str(b"@").ok_or(Error::InvalidSyntax)?;
+        log::debug!(input = ?Redact::email(&input));
+        let (local, host) = input.rsplit_once_str(b"@").ok_or(Error::InvalidAddress)?;
 
-        log::debug!(is_local_part = is_local_part(local), "{}", local.as_bstr());
+        log::debug!(is_local_part = is_local_part(local));
         log::debug!(is_domain = is_domain(host), "{}", host.as_bstr());
 
         if local.len() <= max::LOCAL_PART
@@ -104,7 +128,7 @@ impl TryFrom<Bytes> for Email {
             return unsafe { Ok(Self::new_unchecked(input)) };
         }
 
-        Err(Error::InvalidSyntax)
+        Err(Error::InvalidAddress)
     }
 }
 
@@ -121,7 +145,7 @@ impl TryFrom<Bytes> for Domain {
 
         log::debug!(is_subdomain = is_subdomain(&a), "{}", a.as_bstr());
         if !is_subdomain(a.as_ref()) {
-            return Err(Error::InvalidSyntax);
+            return Err(Error::InvalidDomain);
         }
 
         log::debug!(is_empty = b.is_empty(), "{}", b.as_bstr());
@@ -135,7 +159,29 @@ impl TryFrom<Bytes> for Domain {
             .all(is_subdomain)
             // SAFETY: `is_subdomain` ensures the input is valid.
             .then_some(unsafe { Self::new_unchecked(input) })
-            .ok_or(Error::InvalidSyntax)
+            .ok_or(Error::InvalidDomain)
+    }
+}
+
+impl TryFrom<Bytes> for Base64 {
+    type Error = Error;
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn try_from(input: Bytes) -> Result<Self> {
+        let _span = log::info_span!("Base64").entered();
+        log::debug!(input = ?Redact::bytes(&input));
+        if input.len() % 4 != 0 {
+            return Err(Error::InvalidSyntax);
+        }
+
+        let padding = input.iter().rev().take_while(|&&c| c == b'=').count();
+        let data = &input[..input.len() - padding];
+        if padding > 2 || !data.iter().copied().all(is_base64) {
+            return Err(Error::InvalidSyntax);
+        }
+
+        // SAFETY: `is_base64` and the length and padding checks ensure the input is valid.
+        unsafe { Ok(Self::new_unchecked(input)) }
     }
 }
 
@@ -145,18 +191,18 @@ impl TryFrom<Bytes> for XText {
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn try_from(input: Bytes) -> Result<Self> {
         let _span = log::info_span!("XText").entered();
-        log::debug!(input = ?input.as_bstr());
+        log::debug!(input = ?Redact::bytes(&input));
         let mut i = 0;
         while i < input.len() {
             if i + 2 < input.len() && input[i] == b'+' {
                 if !(input[i + 1].is_ascii_hexdigit() && input[i + 2].is_ascii_hexdigit()) {
-                    return Err(Error::InvalidSyntax);
+                    return Err(Error::InvalidXText);
                 }
                 i += 3;
             } else if is_xchar(input[i]) {
                 i += 1;
             } else {
-                return Err(Error::InvalidSyntax);
+                return Err(Error::InvalidXText);
             }
         }
 
@@ -0,0 +1,25 @@
+//! # Grammar Predicates
+//!
+//! The predicates used by the parser to validate the
+//! [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.2) grammar, for checking
+//! input outside of a command (e.g. form fields or configuration).
+//!
+//! ```rust
+//! # use smtpkit::validate;
+//! assert!(validate::is_domain(b"mail.example.com"));
+//! assert!(validate::is_local_part(b"\"bob smith\""));
+//! assert!(!validate::is_dot_string(b"bob..smith"));
+//! ```
+
+pub use super::helpers::{
+    is_atext, is_base64, is_domain, is_dot_string, is_local_part, is_qtext, is_quoted_pair,
+    is_quoted_string, is_solicitation_keyword, is_subdomain,
+};
+
+/// Whether `input` is an `xchar`, i.e. may appear unencoded in `xtext`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3461#section-4>
+#[must_use]
+pub fn is_xchar(input: u8) -> bool {
+    crate::is_xchar(input)
+}
@@ -1,9 +1,95 @@
 #![cfg(feature = "parse")]
 
+use core::mem;
+
 use bstr::Finder;
 
 use crate::*;
 
+/// Default `max` of a [`Parser`], 25 MiB.
+const DEFAULT_MAX: usize = 1024 * 1024 * 25;
+
+/// # Message Content Sink
+///
+/// Receives `DATA` and `BDAT` content as it arrives when parsing with [`Parser::parse_into`], so
+/// it can be written straight to a spool (file, object store, hash-while-receiving) instead of
+/// accumulating in the parse buffer.
+pub trait DataSink {
+    /// Receive the next piece of content. `DATA` content is still dot-stuffed.
+    fn push(&mut self, chunk: Bytes);
+
+    /// The message is complete: the end of `DATA`, or the last `BDAT` chunk.
+    fn commit(&mut self);
+
+    /// The message was abandoned, e.g. a `DATA` line was too long. Content pushed so far should be
+    /// discarded.
+    fn abort(&mut self);
+}
+
+/// # Parsed Command with Original Bytes
+///
+/// Returned by [`Parser::parse_passthrough`], so a proxy can inspect `command` but forward `raw`
+/// verbatim instead of re-serializing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passthrough {
+    /// The parsed command.
+    pub command: Command,
+    /// The exact bytes consumed for `command`, including the CRLF, and any `DATA` content with its
+    /// terminator or `BDAT` chunk.
+    pub raw: Bytes,
+}
+
+/// # Rejected Command Line
+///
+/// Returned by [`Parser::parse_or_reject`], so the rejected line can be quoted in the reply or
+/// logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedLine {
+    /// Why the line was rejected.
+    pub error: Error,
+    /// The rejected command line, excluding the CRLF and truncated to [`max::COMMAND_LINE`]
+    /// bytes. Empty if the error was not caused by a command line, e.g. a `DATA` line that was
+    /// too long.
+    pub raw: Bytes,
+}
+
+/// # Draining Iterator
+///
+/// Returned by [`Parser::drain`]. Yields the result of each [`Parser::parse`] until more bytes
+/// are needed or the budget is spent.
+#[derive(Debug)]
+pub struct Drain<'a> {
+    parser: &'a mut Parser,
+    buf: &'a mut BytesMut,
+    remaining: usize,
+    yielded: bool,
+}
+
+impl Drain<'_> {
+    /// Whether iteration stopped because the budget was spent with a complete command still
+    /// buffered, rather than because more bytes are needed, so another drain can make progress
+    /// without reading.
+    #[must_use]
+    pub const fn yielded(&self) -> bool {
+        self.yielded
+    }
+}
+
+impl Iterator for Drain<'_> {
+    type Item = Result<Command, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            self.yielded = self.parser.ready(self.buf);
+            return None;
+        }
+
+        let result = self.parser.parse(self.buf).transpose()?;
+        self.remaining -= 1;
+        Some(result)
+    }
+}
+
 /// # `Parser` Internal State
 #[derive(Debug)]
 enum State {
@@ -21,17 +107,101 @@ pub struct Parser {
     max: usize,
     crlf_finder: Finder<'static>,
     data_finder: Finder<'static>,
+    stats: Stats,
+    low: usize,
+    high: usize,
+    paused: bool,
+    lenient: Lenient,
+    limits: Limits,
+    scratch: BytesMut,
+    streamed: usize,
+    line: usize,
+    discard: bool,
+    raw_line: Bytes,
+    rejected: Bytes,
+    budget: usize,
+}
+
+/// # Backpressure Advisory
+///
+/// Returned by [`Parser::pressure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pressure {
+    /// Keep reading.
+    Read,
+    /// Too many bytes are buffered; pause reading until they are parsed.
+    Pause,
+}
+
+/// # `Parser` Statistics
+///
+/// Running counters maintained by a [`Parser`], useful for metrics.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Total bytes consumed from the buffer, including any discarded due to errors.
+    pub bytes: usize,
+    /// Number of commands parsed.
+    pub commands: usize,
+    /// Number of messages completed, via `DATA` or the final `BDAT` chunk.
+    pub messages: usize,
+    /// Size of the largest message seen, in bytes.
+    pub largest_message: usize,
+    errors: [usize; ErrorKind::COUNT],
+    chunked: usize,
+}
+
+impl Stats {
+    /// Number of errors of `kind`.
+    #[must_use]
+    pub const fn errors(&self, kind: ErrorKind) -> usize {
+        self.errors[kind as usize]
+    }
+
+    /// Total number of errors, of any kind.
+    #[must_use]
+    pub fn total_errors(&self) -> usize {
+        self.errors.iter().sum()
+    }
+
+    fn command(&mut self, command: &Command, streamed: usize) {
+        observe::command(command);
+        self.commands += 1;
+        match command {
+            Command::Data(payload) => self.message(payload.len() + streamed),
+            Command::Bdat(bdat) => {
+                self.chunked += bdat.payload.len() + streamed;
+                if bdat.last {
+                    self.message(self.chunked);
+                    self.chunked = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn message(&mut self, len: usize) {
+        observe::message(len);
+        self.messages += 1;
+        self.largest_message = self.largest_message.max(len);
+    }
+
+    fn error(&mut self, error: &Error) {
+        observe::error(error);
+        self.errors[error.kind() as usize] += 1;
+    }
 }
 
 impl Default for Parser {
     /// Create a `Parser` with a default `max` of 25 MiB.
     fn default() -> Self {
-        Self::new(1024 * 1024 * 25)
+        Self::new(DEFAULT_MAX)
     }
 }
 
 impl Parser {
     /// Create a `Parser` with a custom `max`.
+    ///
+    /// The low and high watermarks default to 1/4 and 1/2 of `max`, respectively.
     #[must_use]
     pub fn new(max: usize) -> Self {
         Self {
@@ -39,7 +209,138 @@ impl Parser {
             max,
             crlf_finder: Finder::new(b"\r\n"),
             data_finder: Finder::new(b"\r\n.\r\n"),
+            stats: Stats::default(),
+            low: max / 4,
+            high: max / 2,
+            paused: false,
+            lenient: Lenient::empty(),
+            limits: Limits::default(),
+            scratch: BytesMut::new(),
+            streamed: 0,
+            line: 0,
+            discard: false,
+            raw_line: Bytes::new(),
+            rejected: Bytes::new(),
+            budget: usize::MAX,
+        }
+    }
+
+    /// Set the low and high watermarks used by [`Parser::pressure`].
+    #[must_use]
+    pub fn with_watermarks(mut self, low: usize, high: usize) -> Self {
+        debug_assert!(low <= high, "low watermark exceeds high watermark");
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Tolerate the deviations from the grammar enabled in `lenient`.
+    #[must_use]
+    pub fn with_lenient(mut self, lenient: Lenient) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Enforce `limits` instead of the defaults.
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the number of results yielded by each [`Parser::drain`] before it yields control.
+    #[must_use]
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Get a mutable reference to the [`Limits`], to adjust them as extensions are negotiated.
+    pub fn limits_mut(&mut self) -> &mut Limits {
+        &mut self.limits
+    }
+
+    /// Get a cleared scratch buffer owned by this `Parser`, for use with the `*_into` methods
+    /// (e.g. [`XText::decode_into`] or [`ToBytes::to_bytes_into`]).
+    ///
+    /// The buffer keeps its capacity between calls, so reusing it avoids an allocation per call.
+    pub fn scratch(&mut self) -> &mut BytesMut {
+        self.scratch.clear();
+        &mut self.scratch
+    }
+
+    /// Advise the I/O layer whether to keep reading, based on the bytes buffered but not yet
+    /// parsed.
+    ///
+    /// Once `buf` reaches the high watermark, returns [`Pressure::Pause`] until it drains to the
+    /// low watermark. The content already pushed to a [`DataSink`] for the message in progress
+    /// counts too, so a slow sink can be given time to catch up, e.g. to flush to disk; it stops
+    /// counting once the message is complete. Note that `Ok(None)` from [`Parser::parse`] means
+    /// more bytes are needed to make progress, so reading should resume regardless.
+    pub fn pressure(&mut self, buf: &BytesMut) -> Pressure {
+        let streaming = match self.state {
+            State::Command => 0,
+            State::Data | State::Bdat(_) => self.streamed,
+        };
+        let fill = buf.len().saturating_add(streaming);
+        if fill >= self.high {
+            self.paused = true;
+        } else if fill <= self.low {
+            self.paused = false;
         }
+
+        if self.paused {
+            Pressure::Pause
+        } else {
+            Pressure::Read
+        }
+    }
+
+    /// The timeout for the next read in the current state: [`Timeouts::data_block`] while
+    /// receiving `DATA` or `BDAT` content, otherwise [`Timeouts::server`].
+    #[must_use]
+    pub fn timeout(&self, timeouts: &Timeouts) -> core::time::Duration {
+        match self.state {
+            State::Command => timeouts.server,
+            State::Data | State::Bdat(_) => timeouts.data_block,
+        }
+    }
+
+    /// Whether a `DATA` command line has been parsed and its content is awaited, so a server can
+    /// reply `354` once [`Parser::parse`] needs more bytes.
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::Parser;
+    /// let mut parser = Parser::default();
+    /// let mut buf = BytesMut::from(&b"DATA\r\n"[..]);
+    /// assert_eq!(parser.parse(&mut buf), Ok(None));
+    /// assert!(parser.in_data());
+    /// ```
+    #[must_use]
+    pub const fn in_data(&self) -> bool {
+        matches!(self.state, State::Data)
+    }
+
+    /// Stop awaiting `DATA` content, after the server refused the `DATA` command instead of
+    /// replying `354`.
+    pub fn cancel_data(&mut self) {
+        if self.in_data() {
+            self.state = State::Command;
+        }
+    }
+
+    /// Get the running [`Stats`] for this `Parser`.
+    #[must_use]
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Record how long a call to [`Parser::parse`] took, as measured by the caller.
+    ///
+    /// Only emitted with the `metrics` feature, as `smtpkit_parse_duration_seconds`.
+    pub fn record_duration(&self, duration: core::time::Duration) {
+        observe::duration(duration);
     }
 
     /// Read and parse bytes from the buffer.
@@ -48,7 +349,150 @@ impl Parser {
     /// - Returns `Ok(None)` if more bytes are needed.
     /// - Returns `Err(Error::TooLong)` if the buffer exceeds `max` bytes.
     pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<Command>, Error> {
+        self.parse_with(buf, None)
+    }
+
+    /// Read and parse bytes from the buffer, as [`Parser::parse`], but return the rejected command
+    /// line alongside any error.
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::{Error, Parser};
+    /// let mut parser = Parser::default();
+    /// let mut buf = BytesMut::from(&b"MAIL FROM:<bob>\r\n"[..]);
+    /// let rejected = parser.parse_or_reject(&mut buf).unwrap_err();
+    /// assert_eq!(rejected.error, Error::InvalidAddress);
+    /// assert_eq!(rejected.raw, b"MAIL FROM:<bob>"[..]);
+    /// ```
+    pub fn parse_or_reject(&mut self, buf: &mut BytesMut) -> Result<Option<Command>, RejectedLine> {
+        self.parse(buf).map_err(|error| {
+            let mut raw = mem::take(&mut self.rejected);
+            raw.truncate(max::COMMAND_LINE);
+            RejectedLine { error, raw }
+        })
+    }
+
+    /// Parse every command in the buffer, up to the budget set with [`Parser::with_budget`], so a
+    /// client pipelining thousands of commands in one read can't starve other connections in a
+    /// single-threaded reactor.
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::{Command, Parser};
+    /// let mut parser = Parser::default().with_budget(2);
+    /// let mut buf = BytesMut::from(&b"NOOP\r\nNOOP\r\nNOOP\r\n"[..]);
+    ///
+    /// let mut drain = parser.drain(&mut buf);
+    /// assert_eq!(drain.by_ref().count(), 2);
+    /// // more available; schedule another drain later
+    /// assert!(drain.yielded());
+    ///
+    /// let mut drain = parser.drain(&mut buf);
+    /// assert_eq!(drain.next(), Some(Ok(Command::Noop)));
+    /// assert_eq!(drain.next(), None);
+    /// assert!(!drain.yielded());
+    /// ```
+    pub fn drain<'a>(&'a mut self, buf: &'a mut BytesMut) -> Drain<'a> {
+        Drain {
+            remaining: self.budget,
+            parser: self,
+            buf,
+            yielded: false,
+        }
+    }
+
+    /// Read and parse bytes from the buffer, as [`Parser::parse`], but also return the exact bytes
+    /// that were consumed for the command.
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::{Lenient, Parser};
+    /// let mut parser = Parser::default().with_lenient(Lenient::WHITESPACE);
+    /// let mut buf = BytesMut::from(&b"mail from:<bob@example.com>  size=10\r\n"[..]);
+    /// let passthrough = parser.parse_passthrough(&mut buf).unwrap().unwrap();
+    /// assert_eq!(passthrough.command.to_string(), "MAIL FROM:<bob@example.com> SIZE=10");
+    /// assert_eq!(passthrough.raw, b"mail from:<bob@example.com>  size=10\r\n"[..]);
+    /// ```
+    pub fn parse_passthrough(&mut self, buf: &mut BytesMut) -> Result<Option<Passthrough>, Error> {
+        let Some(command) = self.parse(buf)? else {
+            return Ok(None);
+        };
+
+        let raw = match &command {
+            Command::Data(payload) => [&self.raw_line[..], payload, b"\r\n.\r\n"].concat().into(),
+            Command::Bdat(bdat) => [&self.raw_line[..], &bdat.payload].concat().into(),
+            _ => self.raw_line.clone(),
+        };
+
+        Ok(Some(Passthrough { command, raw }))
+    }
+
+    /// Read and parse bytes from the buffer, as [`Parser::parse`], but push `DATA` and `BDAT`
+    /// content to `sink` as it arrives.
+    ///
+    /// The returned `Command::Data` or `Command::Bdat` has an empty payload, and `max` does not
+    /// apply to `BDAT` chunks, so memory use is bounded regardless of message size.
+    ///
+    /// ```rust
+    /// # use bytes::{Bytes, BytesMut};
+    /// # use smtpkit::{Command, DataSink, Parser};
+    /// #[derive(Default)]
+    /// struct Spool(Vec<Bytes>);
+    ///
+    /// impl DataSink for Spool {
+    ///     fn push(&mut self, chunk: Bytes) {
+    ///         self.0.push(chunk);
+    ///     }
+    ///     fn commit(&mut self) {}
+    ///     fn abort(&mut self) {
+    ///         self.0.clear();
+    ///     }
+    /// }
+    ///
+    /// let mut parser = Parser::default();
+    /// let mut spool = Spool::default();
+    /// let mut buf = BytesMut::from(&b"DATA\r\nHello,\r\n"[..]);
+    /// assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(None));
+    /// assert_eq!(spool.0, [Bytes::from("Hello,")]);
+    ///
+    /// buf.extend_from_slice(b"world!\r\n.\r\n");
+    /// let data = parser.parse_into(&mut buf, &mut spool);
+    /// assert_eq!(data, Ok(Some(Command::Data(Bytes::new()))));
+    /// assert_eq!(spool.0.concat(), b"Hello,\r\nworld!");
+    /// ```
+    pub fn parse_into(
+        &mut self,
+        buf: &mut BytesMut,
+        sink: &mut dyn DataSink,
+    ) -> Result<Option<Command>, Error> {
+        self.parse_with(buf, Some(sink))
+    }
+
+    fn parse_with(
+        &mut self,
+        buf: &mut BytesMut,
+        sink: Option<&mut dyn DataSink>,
+    ) -> Result<Option<Command>, Error> {
+        let len = buf.len();
+        let result = self.parse_inner(buf, sink);
+        self.stats.bytes += len - buf.len();
+
+        match &result {
+            Ok(Some(command)) => self.stats.command(command, self.streamed),
+            Ok(None) => {}
+            Err(e) => self.stats.error(e),
+        }
+
+        result
+    }
+
+    fn parse_inner(
+        &mut self,
+        buf: &mut BytesMut,
+        mut sink: Option<&mut dyn DataSink>,
+    ) -> Result<Option<Command>, Error> {
         let _span = log::debug_span!("parser").entered();
+        self.rejected = Bytes::new();
         loop {
             let _span = log::trace_span!("loop").entered();
             log::trace!(buf_len = buf.len());
@@ -74,21 +518,24 @@ impl Parser {
                         return Ok(None);
                     };
 
-                    if pos > max::COMMAND_LINE {
+                    if pos > self.limits.command_line {
                         log::debug!(
                             len = pos,
-                            max = max::COMMAND_LINE,
+                            max = self.limits.command_line,
                             "Command line too long; advancing"
                         );
-                        buf.advance(pos);
+                        // consume the CRLF too, so it is not parsed as an empty command
+                        self.rejected = buf.split_to(pos + 2).freeze().slice(..pos);
                         return Err(Error::TooLong);
                     }
 
-                    let command = buf.split_to(pos);
-                    // consume CRLF
-                    buf.advance(2);
+                    // keep the CRLF for passthrough
+                    self.raw_line = buf.split_to(pos + 2).freeze();
+                    let command = self.raw_line.slice(..pos);
 
-                    match Command::try_from(command.freeze())? {
+                    match Command::parse_lenient(command.clone(), self.lenient)
+                        .inspect_err(|_| self.rejected = command)?
+                    {
                         Command::Data(payload) => {
                             log::debug!("Parsed DATA");
 
@@ -97,6 +544,7 @@ impl Parser {
                                 "DATA command payload should not have been read yet"
                             );
 
+                            self.streamed = 0;
                             self.state = State::Data;
                         }
 
@@ -108,6 +556,7 @@ impl Parser {
                                 "BDAT command payload should not have been read yet"
                             );
 
+                            self.streamed = 0;
                             self.state = State::Bdat(bdat);
                         }
 
@@ -121,6 +570,10 @@ impl Parser {
                 State::Data => {
                     let _span = log::debug_span!("Data").entered();
 
+                    if let Some(sink) = sink.as_deref_mut() {
+                        return self.stream_data(buf, sink);
+                    }
+
                     let Some(pos) = self.data_finder.find(&buf) else {
                         log::debug!("No CRLF.CRLF found, need more bytes");
                         return Ok(None);
@@ -133,11 +586,11 @@ impl Parser {
                     let mut lines = Lines::new(payload.freeze());
                     #[expect(clippy::unused_enumerate_index, reason = "tracing stub")]
                     for (_i, line) in lines.by_ref().enumerate() {
-                        if line.len() > max::DATA_LINE {
+                        if line.len() > self.limits.data_line {
                             log::debug!(
                                 line = _i,
                                 len = line.len(),
-                                max = max::DATA_LINE,
+                                max = self.limits.data_line,
                                 "DATA line too long"
                             );
                             self.state = State::Command;
@@ -155,6 +608,11 @@ impl Parser {
                 State::Bdat(ref bdat) => {
                     let _span = log::debug_span!("Bdat").entered();
 
+                    if let Some(sink) = sink.as_deref_mut() {
+                        let (size, last) = (bdat.size, bdat.last);
+                        return Ok(self.stream_bdat(buf, sink, size, last));
+                    }
+
                     debug_assert!(
                         bdat.payload.is_empty(),
                         "BDAT command payload should not have been read yet"
@@ -194,4 +652,476 @@ impl Parser {
             }
         }
     }
+
+    /// Whether `buf` holds enough bytes for [`Parser::parse`] to make progress: a complete command
+    /// line, `DATA` content with its terminator, or a whole `BDAT` chunk.
+    fn ready(&self, buf: &BytesMut) -> bool {
+        match &self.state {
+            State::Command => self.crlf_finder.find(buf).is_some(),
+            State::Data => self.data_finder.find(buf).is_some(),
+            State::Bdat(bdat) => buf.len() >= bdat.size,
+        }
+    }
+
+    /// Push `DATA` content to `sink`, holding back any bytes that may be part of the terminator.
+    fn stream_data(
+        &mut self,
+        buf: &mut BytesMut,
+        sink: &mut dyn DataSink,
+    ) -> Result<Option<Command>, Error> {
+        let (end, done) = match self.data_finder.find(&buf) {
+            Some(pos) => (pos, true),
+            None => {
+                // the longest suffix that could be the start of the terminator
+                let hold = (1..5)
+                    .rev()
+                    .find(|&n| buf.ends_with(&b"\r\n.\r\n"[..n]))
+                    .unwrap_or(0);
+                (buf.len() - hold, false)
+            }
+        };
+
+        let chunk = buf.split_to(end).freeze();
+        if done {
+            // consume \r\n.\r\n
+            buf.advance(5);
+        }
+
+        if !self.discard {
+            if self.too_long(&chunk) {
+                log::debug!(
+                    max = self.limits.data_line,
+                    "DATA line too long; discarding"
+                );
+                self.discard = true;
+                sink.abort();
+            } else if !chunk.is_empty() {
+                self.streamed += chunk.len();
+                sink.push(chunk);
+            }
+        }
+
+        if !done {
+            log::debug!("No CRLF.CRLF found, need more bytes");
+            return Ok(None);
+        }
+
+        self.state = State::Command;
+        self.line = 0;
+        if mem::take(&mut self.discard) {
+            return Err(Error::TooLong);
+        }
+
+        sink.commit();
+        let command = Command::Data(Bytes::new());
+        log::debug!(streamed = self.streamed, "Parsed DATA");
+        Ok(Some(command))
+    }
+
+    /// Push `BDAT` content to `sink` until `size` bytes have been streamed.
+    fn stream_bdat(
+        &mut self,
+        buf: &mut BytesMut,
+        sink: &mut dyn DataSink,
+        size: usize,
+        last: bool,
+    ) -> Option<Command> {
+        let chunk = buf.split_to((size - self.streamed).min(buf.len())).freeze();
+        if !chunk.is_empty() {
+            self.streamed += chunk.len();
+            sink.push(chunk);
+        }
+
+        if self.streamed < size {
+            log::debug!(streamed = self.streamed, size, "Need more bytes for BDAT");
+            return None;
+        }
+
+        if last {
+            sink.commit();
+        }
+
+        self.state = State::Command;
+        let command = Command::Bdat(Bdat {
+            size,
+            last,
+            payload: Bytes::new(),
+        });
+        log::debug!(command = ?command, "Parsed");
+        Some(command)
+    }
+
+    /// Whether `chunk` continues or contains a line longer than [`Limits::data_line`].
+    fn too_long(&mut self, chunk: &[u8]) -> bool {
+        for &b in chunk {
+            if b == b'\n' {
+                self.line = 0;
+            } else {
+                // the line so far, including a CR
+                self.line += 1;
+                if self.line > self.limits.data_line + 1 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn stats() {
+        let input = &b"NOOP\r\nFOO\r\nDATA\r\nHi!\r\n.\r\nBDAT 3\r\nabcBDAT 2 LAST\r\nde"[..];
+        let mut parser = Parser::default();
+        let mut buf = BytesMut::from(input);
+        while parser.parse(&mut buf) != Ok(None) {}
+
+        let stats = parser.stats();
+        assert_eq!(stats.bytes, input.len());
+        assert_eq!(stats.commands, 4);
+        assert_eq!(stats.messages, 2);
+        assert_eq!(stats.largest_message, 5);
+        assert_eq!(stats.errors(ErrorKind::CommandNotImplemented), 1);
+        assert_eq!(stats.errors(ErrorKind::TooLong), 0);
+        assert_eq!(stats.total_errors(), 1);
+    }
+
+    #[test]
+    fn parse_or_reject() {
+        let mut parser = Parser::default().with_limits(Limits {
+            command_line: 8,
+            ..Limits::default()
+        });
+        let mut buf = BytesMut::from(&b"NOOP\r\nRCPT TO:<alice@example.com>\r\nFOO\r\n"[..]);
+
+        assert_eq!(parser.parse_or_reject(&mut buf), Ok(Some(Command::Noop)));
+        assert_eq!(
+            parser.parse_or_reject(&mut buf),
+            Err(RejectedLine {
+                error: Error::TooLong,
+                raw: Bytes::from_static(b"RCPT TO:<alice@example.com>"),
+            })
+        );
+        // the CRLF of the long line is consumed with it, rather than parsed as an empty command
+        assert_eq!(
+            parser.parse_or_reject(&mut buf),
+            Err(RejectedLine {
+                error: Error::CommandNotImplemented,
+                raw: Bytes::from_static(b"FOO"),
+            })
+        );
+        assert_eq!(parser.parse_or_reject(&mut buf), Ok(None));
+    }
+
+    #[test]
+    fn drain() {
+        let mut parser = Parser::default().with_budget(3);
+        let mut buf = BytesMut::from(&b"NOOP\r\nFOO\r\nRSET\r\nQUIT\r\n"[..]);
+
+        let mut drain = parser.drain(&mut buf);
+        assert_eq!(drain.next(), Some(Ok(Command::Noop)));
+        assert_eq!(drain.next(), Some(Err(Error::CommandNotImplemented)));
+        assert_eq!(drain.next(), Some(Ok(Command::Rset)));
+        assert_eq!(drain.next(), None);
+        assert!(drain.yielded());
+
+        let mut drain = parser.drain(&mut buf);
+        assert_eq!(drain.next(), Some(Ok(Command::Quit)));
+        assert_eq!(drain.next(), None);
+        assert!(!drain.yielded());
+
+        // a partial line is not worth another drain until more bytes are read
+        buf.extend_from_slice(b"NOOP\r\nNOOP\r\nNOOP\r\nNO");
+        let mut drain = parser.drain(&mut buf);
+        assert_eq!(drain.by_ref().count(), 3);
+        assert!(!drain.yielded());
+
+        buf.extend_from_slice(b"OP\r\nDATA\r\nHi!\r\n.\r\nNOOP\r\nBDAT 3\r\nab");
+        let mut drain = parser.drain(&mut buf);
+        assert_eq!(drain.by_ref().count(), 3);
+        assert!(drain.yielded());
+        assert_eq!(parser.drain(&mut buf).count(), 0);
+        assert!(!parser.ready(&buf));
+        buf.extend_from_slice(b"c");
+        assert!(parser.ready(&buf));
+    }
+
+    #[test]
+    fn passthrough() {
+        let input = &b"noop\r\nDATA\r\n..Hi!\r\n.\r\nbdat 2 last\r\nde"[..];
+        let mut parser = Parser::default();
+        let mut buf = BytesMut::from(input);
+
+        let mut raw = Vec::new();
+        while let Some(passthrough) = parser.parse_passthrough(&mut buf).unwrap() {
+            raw.push(passthrough.raw);
+        }
+
+        assert_eq!(raw.concat(), input);
+        assert_eq!(raw[1], b"DATA\r\n..Hi!\r\n.\r\n"[..]);
+    }
+
+    #[test]
+    fn bdat_zero_last() {
+        let mut parser = Parser::default();
+        let mut buf = BytesMut::from(&b"BDAT 3\r\nabcBDAT 0\r\nBDAT 0 LAST\r\n"[..]);
+
+        let chunk = |size, last, payload: &'static [u8]| {
+            Ok(Some(Command::Bdat(Bdat {
+                size,
+                last,
+                payload: Bytes::from_static(payload),
+            })))
+        };
+
+        assert_eq!(parser.parse(&mut buf), chunk(3, false, b"abc"));
+        // an empty chunk in the middle of a transfer
+        assert_eq!(parser.parse(&mut buf), chunk(0, false, b""));
+        // the final empty chunk is yielded without waiting for more bytes
+        assert_eq!(parser.parse(&mut buf), chunk(0, true, b""));
+        assert!(buf.is_empty());
+        assert_eq!(parser.parse(&mut buf), Ok(None));
+
+        let stats = parser.stats();
+        assert_eq!(stats.messages, 1);
+        assert_eq!(stats.largest_message, 3);
+    }
+
+    #[derive(Debug, Default)]
+    struct Spool {
+        chunks: Vec<Bytes>,
+        commits: usize,
+        aborts: usize,
+    }
+
+    impl DataSink for Spool {
+        fn push(&mut self, chunk: Bytes) {
+            self.chunks.push(chunk);
+        }
+
+        fn commit(&mut self) {
+            self.commits += 1;
+        }
+
+        fn abort(&mut self) {
+            self.chunks.clear();
+            self.aborts += 1;
+        }
+    }
+
+    #[rstest]
+    #[case::whole(usize::MAX)]
+    #[case::bytes(1)]
+    #[case::odd(3)]
+    fn parse_into_data(#[case] feed: usize) {
+        let input = b"DATA\r\nHi\r\n..stuffed\r\n\r\n.\r\nNOOP\r\n";
+        let mut parser = Parser::default();
+        let mut spool = Spool::default();
+        let mut buf = BytesMut::new();
+        let mut commands = Vec::new();
+        for chunk in input.chunks(feed.min(input.len())) {
+            buf.extend_from_slice(chunk);
+            while let Some(command) = parser.parse_into(&mut buf, &mut spool).unwrap() {
+                commands.push(command);
+            }
+        }
+
+        assert_eq!(commands, [Command::Data(Bytes::new()), Command::Noop]);
+        assert_eq!(spool.chunks.concat(), b"Hi\r\n..stuffed\r\n");
+        assert_eq!(spool.commits, 1);
+        assert_eq!(parser.stats().largest_message, 15);
+    }
+
+    #[test]
+    fn parse_into_data_line_limit() {
+        let mut parser = Parser::default().with_limits(Limits {
+            data_line: 4,
+            ..Limits::default()
+        });
+        let mut spool = Spool::default();
+        let mut buf = BytesMut::from(&b"DATA\r\nabcd\r\nabc"[..]);
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(None));
+        assert_eq!(spool.chunks.concat(), b"abcd\r\nabc");
+
+        buf.extend_from_slice(b"de\r\nmore\r\n.\r\nNOOP\r\n");
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Err(Error::TooLong));
+        assert_eq!(spool.aborts, 1);
+        assert_eq!(spool.commits, 0);
+        assert!(spool.chunks.is_empty());
+        assert_eq!(
+            parser.parse_into(&mut buf, &mut spool),
+            Ok(Some(Command::Noop))
+        );
+    }
+
+    #[test]
+    fn parse_into_bdat() {
+        let mut parser = Parser::default();
+        let mut spool = Spool::default();
+        let mut buf = BytesMut::from(&b"BDAT 6\r\nabc"[..]);
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(None));
+
+        buf.extend_from_slice(b"defBDAT 2 LAST\r\ngh");
+        let first = parser.parse_into(&mut buf, &mut spool);
+        let last = parser.parse_into(&mut buf, &mut spool);
+        assert!(matches!(
+            first,
+            Ok(Some(Command::Bdat(Bdat {
+                size: 6,
+                last: false,
+                ..
+            })))
+        ));
+        assert!(matches!(
+            last,
+            Ok(Some(Command::Bdat(Bdat {
+                size: 2,
+                last: true,
+                ..
+            })))
+        ));
+        assert_eq!(spool.chunks.concat(), b"abcdefgh");
+        assert_eq!(spool.commits, 1);
+        assert_eq!(parser.stats().largest_message, 8);
+    }
+
+    #[test]
+    fn timeout() {
+        let timeouts = Timeouts::default();
+        let mut parser = Parser::default();
+        assert_eq!(parser.timeout(&timeouts), timeouts.server);
+
+        let mut buf = BytesMut::from(&b"DATA\r\nHi"[..]);
+        assert_eq!(parser.parse(&mut buf), Ok(None));
+        assert_eq!(parser.timeout(&timeouts), timeouts.data_block);
+        assert_eq!(timeouts.data_block.as_secs(), 180);
+    }
+
+    #[test]
+    fn cancel_data() {
+        let mut parser = Parser::default();
+        let mut buf = BytesMut::from(&b"DATA\r\n"[..]);
+        assert_eq!(parser.parse(&mut buf), Ok(None));
+        assert!(parser.in_data());
+
+        parser.cancel_data();
+        assert!(!parser.in_data());
+        buf.extend_from_slice(b"NOOP\r\n");
+        assert_eq!(parser.parse(&mut buf), Ok(Some(Command::Noop)));
+    }
+
+    #[test]
+    fn scratch() {
+        let mut parser = Parser::default();
+        let xtext = XText::encode(&Bytes::from("bob smith"));
+        xtext.decode_into(parser.scratch());
+        assert_eq!(parser.scratch.as_ref(), b"bob smith");
+
+        let capacity = parser.scratch.capacity();
+        assert!(parser.scratch().is_empty());
+        assert_eq!(parser.scratch.capacity(), capacity);
+    }
+
+    #[test]
+    fn limits() {
+        let line = [&b"MAIL FROM:<"[..], &[b'a'; 512], b"@example.com>\r\n"].concat();
+
+        let mut parser = Parser::default();
+        let mut buf = BytesMut::from(&line[..]);
+        assert_eq!(parser.parse(&mut buf), Err(Error::TooLong));
+
+        parser.limits_mut().command_line += max::AUTH_MAIL;
+        let mut buf = BytesMut::from(&line[..]);
+        // the line is accepted, but the local part is still too long
+        assert_eq!(parser.parse(&mut buf), Err(Error::InvalidAddress));
+    }
+
+    #[test]
+    fn data_line_limit() {
+        let mut parser = Parser::default().with_limits(Limits {
+            data_line: 4,
+            ..Limits::default()
+        });
+
+        let mut buf = BytesMut::from(&b"DATA\r\nabcd\r\nx\r\n.\r\nDATA\r\nabcde\r\nx\r\n.\r\n"[..]);
+        assert_eq!(
+            parser.parse(&mut buf),
+            Ok(Some(Command::Data(Bytes::from_static(b"abcd\r\nx"))))
+        );
+        assert_eq!(parser.parse(&mut buf), Err(Error::TooLong));
+    }
+
+    #[test]
+    fn config() {
+        let limits = Limits {
+            command_line: 1000,
+            ..Limits::default()
+        };
+        let parser = Parser::new(1024)
+            .with_watermarks(16, 32)
+            .with_lenient(Lenient::BARE_ADDRESS)
+            .with_limits(limits);
+
+        assert_eq!(parser.max, 1024);
+        assert_eq!((parser.low, parser.high), (16, 32));
+        assert_eq!(parser.lenient, Lenient::BARE_ADDRESS);
+        assert_eq!(parser.limits, limits);
+
+        let parser = Parser::new(1024);
+        assert_eq!((parser.low, parser.high), (256, 512));
+    }
+
+    #[test]
+    fn pressure() {
+        let mut parser = Parser::default().with_watermarks(4, 8);
+        let mut buf = BytesMut::from(&b"NOOP\r\n"[..]);
+        assert_eq!(parser.pressure(&buf), Pressure::Read);
+
+        buf.extend_from_slice(b"RSET\r\n");
+        assert_eq!(parser.pressure(&buf), Pressure::Pause);
+
+        // still above the low watermark
+        assert_eq!(parser.parse(&mut buf), Ok(Some(Command::Noop)));
+        assert_eq!(parser.pressure(&buf), Pressure::Pause);
+
+        assert_eq!(parser.parse(&mut buf), Ok(Some(Command::Rset)));
+        assert_eq!(parser.pressure(&buf), Pressure::Read);
+    }
+
+    #[rstest]
+    #[case::data(b"DATA\r\n", b"0123456789\r\n", b".\r\n", Command::Data(Bytes::new()))]
+    #[case::bdat(b"BDAT 16 LAST\r\n", b"0123456789", b"abcdef", Command::Bdat(Bdat {
+        size: 16,
+        last: true,
+        payload: Bytes::new(),
+    }))]
+    fn pressure_streaming(
+        #[case] command: &[u8],
+        #[case] body: &[u8],
+        #[case] end: &[u8],
+        #[case] expected: Command,
+    ) {
+        let mut parser = Parser::default().with_watermarks(4, 8);
+        let mut spool = Spool::default();
+        let mut buf = BytesMut::from(command);
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(None));
+        assert_eq!(parser.pressure(&buf), Pressure::Read);
+
+        // the body is pushed to the sink, but still counts until the message is complete
+        buf.extend_from_slice(body);
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(None));
+        assert!(buf.len() < 4, "{buf:?}");
+        assert_eq!(parser.pressure(&buf), Pressure::Pause);
+
+        buf.extend_from_slice(end);
+        assert_eq!(parser.parse_into(&mut buf, &mut spool), Ok(Some(expected)));
+        assert!(buf.is_empty());
+        assert_eq!(parser.pressure(&buf), Pressure::Read);
+        assert_eq!(spool.commits, 1);
+    }
 }
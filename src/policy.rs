@@ -0,0 +1,147 @@
+use crate::*;
+use mail::Mail;
+use rcpt::Rcpt;
+
+/// # Policy Decision
+///
+/// Returned by the [`Policy`] callbacks. The optional text replaces the default reply text.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// Carry on.
+    #[default]
+    Accept,
+    /// Refuse permanently.
+    Reject(Option<Bytes>),
+    /// Refuse temporarily; the client may retry later.
+    TempFail(Option<Bytes>),
+}
+
+impl Decision {
+    /// The recommended reply code, or `None` for [`Decision::Accept`].
+    ///
+    /// Refusals of the message content use `554` and `451`, and all others `550` and `450`.
+    #[must_use]
+    pub const fn code(&self, content: bool) -> Option<u16> {
+        match (self, content) {
+            (Self::Accept, _) => None,
+            (Self::Reject(_), false) => Some(550),
+            (Self::Reject(_), true) => Some(554),
+            (Self::TempFail(_), false) => Some(450),
+            (Self::TempFail(_), true) => Some(451),
+        }
+    }
+
+    /// Whether this is [`Decision::Accept`].
+    #[must_use]
+    pub const fn is_accept(&self) -> bool {
+        matches!(self, Self::Accept)
+    }
+}
+
+/// # Server Policy Hooks
+///
+/// Callbacks invoked by [`Session::handle`] once a command has passed [`Session::check`], so
+/// access control and filtering can slot in without reimplementing the protocol flow. Every
+/// callback accepts by default.
+///
+/// Each callback is passed the [`ConnectionInfo`] of the session.
+///
+/// The callbacks are synchronous; an async server can resolve its decision first and return it
+/// from a `Policy` holding the result.
+///
+/// ```rust
+/// # use smtpkit::{ConnectionInfo, Decision, Policy, mail::Mail};
+/// struct NoNullSender;
+///
+/// impl Policy for NoNullSender {
+///     fn on_mail(&mut self, _connection: &ConnectionInfo, mail: &Mail) -> Decision {
+///         match mail.from {
+///             smtpkit::mail::ReversePath::Null => Decision::Reject(None),
+///             _ => Decision::Accept,
+///         }
+///     }
+/// }
+/// ```
+pub trait Policy {
+    /// Called for `HELO` and `EHLO`.
+    fn on_ehlo(&mut self, connection: &ConnectionInfo, host: &Host) -> Decision {
+        let _ = (connection, host);
+        Decision::Accept
+    }
+
+    /// Called for `MAIL`.
+    fn on_mail(&mut self, connection: &ConnectionInfo, mail: &Mail) -> Decision {
+        let _ = (connection, mail);
+        Decision::Accept
+    }
+
+    /// Called for `RCPT`.
+    fn on_rcpt(&mut self, connection: &ConnectionInfo, rcpt: &Rcpt) -> Decision {
+        let _ = (connection, rcpt);
+        Decision::Accept
+    }
+
+    /// Called before the message content, for `DATA` and the first `BDAT` chunk.
+    fn on_data_start(&mut self, connection: &ConnectionInfo) -> Decision {
+        let _ = connection;
+        Decision::Accept
+    }
+
+    /// Called with the message content: the payload of `DATA`, or each `BDAT` chunk in turn, with
+    /// `last` set on the final chunk.
+    fn on_message(&mut self, connection: &ConnectionInfo, content: &Bytes, last: bool) -> Decision {
+        let _ = (connection, content, last);
+        Decision::Accept
+    }
+}
+
+/// A `Policy` accepting everything.
+impl Policy for () {}
+
+/// # Command Refusal
+///
+/// Returned by [`Session::handle`].
+#[derive(Debug, derive_more::Display, derive_more::Error, Clone, PartialEq, Eq, Hash)]
+pub enum Refusal {
+    /// The command was sent out of order or exceeded a limit.
+    #[display("Command violates the session state")]
+    Violation(Violation),
+    /// The [`Policy`] refused the command.
+    #[display("Command refused by policy")]
+    Policy(#[error(not(source))] Decision),
+}
+
+impl Refusal {
+    /// The reply a server should send: the violation's, or the policy's text, if any and valid,
+    /// with the [`Decision::code`] for a refusal of the message `content` or not.
+    #[must_use]
+    pub fn reply(&self, content: bool) -> Reply {
+        let decision = match self {
+            Self::Violation(violation) => return violation.reply(),
+            Self::Policy(decision) => decision,
+        };
+
+        let code = decision.code(content).unwrap_or(550);
+        let default = match decision {
+            Decision::TempFail(_) => Bytes::from_static(b"4.7.1 Try again later"),
+            Decision::Accept | Decision::Reject(_) => {
+                Bytes::from_static(b"5.7.1 Rejected by policy")
+            }
+        };
+        let mut reply = match decision {
+            Decision::Reject(Some(text)) | Decision::TempFail(Some(text)) => {
+                Reply::new(code, text.clone()).ok()
+            }
+            _ => None,
+        }
+        .unwrap_or_else(|| Reply::new(code, default).expect("the default text is valid"));
+        reply.split_enhanced_code();
+        reply
+    }
+}
+
+impl From<Violation> for Refusal {
+    fn from(violation: Violation) -> Self {
+        Self::Violation(violation)
+    }
+}
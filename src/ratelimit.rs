@@ -0,0 +1,228 @@
+use core::time::Duration;
+
+use crate::*;
+
+/// # Token Bucket
+///
+/// Allows bursts of up to `burst` events, refilled at one per `interval`.
+///
+/// There is no clock: the caller supplies the current time as a [`Duration`] since any fixed
+/// point, e.g. `Instant::elapsed` of when the server started.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenBucket {
+    burst: u32,
+    interval: Duration,
+    /// When the bucket will be full again.
+    full_at: Duration,
+}
+
+impl TokenBucket {
+    /// Create a full `TokenBucket`.
+    #[must_use]
+    pub const fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            full_at: Duration::ZERO,
+        }
+    }
+
+    /// How long until an event at `now` would be allowed, or [`Duration::ZERO`] if it is allowed
+    /// now.
+    #[must_use]
+    pub fn wait(&self, now: Duration) -> Duration {
+        let debt = self.full_at.saturating_sub(now) + self.interval;
+        let allowance = self
+            .interval
+            .checked_mul(self.burst)
+            .unwrap_or(Duration::MAX);
+        debt.saturating_sub(allowance)
+    }
+
+    /// Take a token at `now`, returning how long the event should be delayed to stay within the
+    /// rate, as [`TokenBucket::wait`].
+    ///
+    /// The token is taken even if the event must wait, so it is reserved for the delayed event.
+    pub fn take(&mut self, now: Duration) -> Duration {
+        let wait = self.wait(now);
+        self.full_at = self.full_at.max(now) + self.interval;
+        wait
+    }
+}
+
+/// # Rate Limiter Advice
+///
+/// Returned by [`RateLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Throttle {
+    /// Within the rate; carry on.
+    Proceed,
+    /// Over the rate; delay the reply by this long, then carry on.
+    SlowDown(Duration),
+    /// Far over the rate; refuse temporarily.
+    TempFail,
+}
+
+impl Throttle {
+    /// The [`Decision`] to return from a [`Policy`]: [`Decision::TempFail`] for
+    /// [`Throttle::TempFail`], otherwise [`Decision::Accept`].
+    #[must_use]
+    pub const fn decision(self) -> Decision {
+        match self {
+            Self::Proceed | Self::SlowDown(_) => Decision::Accept,
+            Self::TempFail => Decision::TempFail(None),
+        }
+    }
+}
+
+/// # Rate Limiter
+///
+/// Per-connection budgets for commands, recipients, and messages, each a [`TokenBucket`]. Over
+/// budget, the advice is to slow down by up to `max_delay`, and beyond that to fail temporarily.
+/// Budgets that are not set are unlimited.
+///
+/// Consult it from a [`Policy`]:
+///
+/// ```rust
+/// # use std::time::{Duration, Instant};
+/// # use smtpkit::{ConnectionInfo, Decision, Policy, RateLimiter, TokenBucket, rcpt::Rcpt};
+/// struct Limited {
+///     start: Instant,
+///     limiter: RateLimiter,
+/// }
+///
+/// impl Policy for Limited {
+///     fn on_rcpt(&mut self, _connection: &ConnectionInfo, _rcpt: &Rcpt) -> Decision {
+///         self.limiter.recipient(self.start.elapsed()).decision()
+///     }
+/// }
+///
+/// let limiter = RateLimiter::new(Duration::from_secs(5))
+///     .with_recipients(TokenBucket::new(100, Duration::from_secs(1)));
+/// let policy = Limited {
+///     start: Instant::now(),
+///     limiter,
+/// };
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RateLimiter {
+    commands: Option<TokenBucket>,
+    recipients: Option<TokenBucket>,
+    messages: Option<TokenBucket>,
+    max_delay: Duration,
+}
+
+impl RateLimiter {
+    /// Create a `RateLimiter` with no budgets, advising delays of up to `max_delay`.
+    #[must_use]
+    pub const fn new(max_delay: Duration) -> Self {
+        Self {
+            commands: None,
+            recipients: None,
+            messages: None,
+            max_delay,
+        }
+    }
+
+    /// Limit the rate of commands.
+    #[must_use]
+    pub const fn with_commands(mut self, bucket: TokenBucket) -> Self {
+        self.commands = Some(bucket);
+        self
+    }
+
+    /// Limit the rate of recipients.
+    #[must_use]
+    pub const fn with_recipients(mut self, bucket: TokenBucket) -> Self {
+        self.recipients = Some(bucket);
+        self
+    }
+
+    /// Limit the rate of messages.
+    #[must_use]
+    pub const fn with_messages(mut self, bucket: TokenBucket) -> Self {
+        self.messages = Some(bucket);
+        self
+    }
+
+    /// Account for a command received at `now`.
+    pub fn command(&mut self, now: Duration) -> Throttle {
+        Self::throttle(self.commands.as_mut(), self.max_delay, now)
+    }
+
+    /// Account for a recipient received at `now`.
+    pub fn recipient(&mut self, now: Duration) -> Throttle {
+        Self::throttle(self.recipients.as_mut(), self.max_delay, now)
+    }
+
+    /// Account for a message received at `now`.
+    pub fn message(&mut self, now: Duration) -> Throttle {
+        Self::throttle(self.messages.as_mut(), self.max_delay, now)
+    }
+
+    fn throttle(bucket: Option<&mut TokenBucket>, max_delay: Duration, now: Duration) -> Throttle {
+        let Some(bucket) = bucket else {
+            return Throttle::Proceed;
+        };
+
+        // a refusal does not take a token
+        match bucket.wait(now) {
+            wait if wait > max_delay => Throttle::TempFail,
+            _ => match bucket.take(now) {
+                Duration::ZERO => Throttle::Proceed,
+                wait => Throttle::SlowDown(wait),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn token_bucket() {
+        let mut bucket = TokenBucket::new(2, SECOND);
+        assert_eq!(bucket.take(Duration::ZERO), Duration::ZERO);
+        assert_eq!(bucket.take(Duration::ZERO), Duration::ZERO);
+        assert_eq!(bucket.wait(Duration::ZERO), SECOND);
+
+        // reserved for later
+        assert_eq!(bucket.take(Duration::ZERO), SECOND);
+        assert_eq!(bucket.wait(Duration::ZERO), 2 * SECOND);
+
+        // refilled
+        assert_eq!(bucket.wait(10 * SECOND), Duration::ZERO);
+        assert_eq!(bucket.take(10 * SECOND), Duration::ZERO);
+        assert_eq!(bucket.take(10 * SECOND), Duration::ZERO);
+        assert_eq!(bucket.wait(10 * SECOND), SECOND);
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let mut limiter =
+            RateLimiter::new(SECOND * 3 / 5).with_recipients(TokenBucket::new(1, SECOND / 2));
+        assert_eq!(limiter.recipient(Duration::ZERO), Throttle::Proceed);
+        assert_eq!(
+            limiter.recipient(Duration::ZERO),
+            Throttle::SlowDown(SECOND / 2)
+        );
+        assert_eq!(limiter.recipient(Duration::ZERO), Throttle::TempFail);
+        assert_eq!(
+            limiter.recipient(Duration::ZERO).decision(),
+            Decision::TempFail(None)
+        );
+        assert_eq!(
+            limiter.recipient(SECOND * 3 / 5),
+            Throttle::SlowDown(SECOND * 2 / 5)
+        );
+
+        // unlimited
+        for _ in 0..100 {
+            assert_eq!(limiter.command(Duration::ZERO), Throttle::Proceed);
+            assert_eq!(limiter.message(Duration::ZERO), Throttle::Proceed);
+        }
+    }
+}
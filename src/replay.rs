@@ -0,0 +1,157 @@
+//! # Transcript Replay
+//!
+//! Drive a [`Parser`] and [`Session`] over a captured byte stream (e.g. a pcap export or a proxy
+//! log), producing an annotated event log. Replay is deterministic: the same bytes produce the
+//! same log regardless of how they are split into chunks, which makes it useful for debugging
+//! interop reports and regression testing against real traffic.
+//!
+//! ```rust
+//! # use smtpkit::replay::{Event, Replay};
+//! # use smtpkit::{Command, Violation};
+//! let mut replay = Replay::default();
+//! replay.feed(b"EHLO client.example.com\r\nRCPT TO:<al");
+//! replay.feed(b"ice@example.com>\r\nQUIT\r\n");
+//!
+//! let events: Vec<_> = replay.log().iter().map(|entry| &entry.event).collect();
+//! assert!(matches!(events[0], Event::Command(Command::Ehlo(_))));
+//! assert!(matches!(events[1], Event::Violation(Command::Rcpt(_), Violation::NoMail)));
+//! assert_eq!(events[2], &Event::Command(Command::Quit));
+//! ```
+
+#![cfg(feature = "parse")]
+
+use alloc::vec::Vec;
+
+use crate::*;
+
+/// # Replay Event
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A command that passed [`Session::check`] and was accepted.
+    Command(Command),
+    /// A command refused by [`Session::check`].
+    Violation(Command, Violation),
+    /// A parse error.
+    Error(Error),
+}
+
+/// # Replay Log Entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Offset of the first byte after the event in the stream.
+    pub offset: usize,
+    /// What happened.
+    pub event: Event,
+}
+
+/// # Transcript Replayer
+///
+/// Feeds bytes to a [`Parser`], checks each command against a [`Session`], and accepts every
+/// permitted command, as a server that never refuses by policy would.
+#[derive(Debug, Default)]
+pub struct Replay {
+    parser: Parser,
+    session: Session,
+    buf: BytesMut,
+    log: Vec<Entry>,
+}
+
+impl Replay {
+    /// Create a `Replay` using the given `Parser` and `Session`.
+    #[must_use]
+    pub fn new(parser: Parser, session: Session) -> Self {
+        Self {
+            parser,
+            session,
+            buf: BytesMut::new(),
+            log: Vec::new(),
+        }
+    }
+
+    /// Feed the next chunk of the stream, returning the entries it produced.
+    pub fn feed(&mut self, chunk: &[u8]) -> &[Entry] {
+        let start = self.log.len();
+        self.buf.extend_from_slice(chunk);
+
+        loop {
+            let event = match self.parser.parse(&mut self.buf) {
+                Ok(None) => break,
+                Ok(Some(command)) => match self.session.check(&command) {
+                    Ok(()) => {
+                        self.session.accept(&command);
+                        Event::Command(command)
+                    }
+                    Err(violation) => Event::Violation(command, violation),
+                },
+                Err(e) => Event::Error(e),
+            };
+
+            self.log.push(Entry {
+                offset: self.parser.stats().bytes,
+                event,
+            });
+        }
+
+        &self.log[start..]
+    }
+
+    /// Get the entries produced so far.
+    #[must_use]
+    pub fn log(&self) -> &[Entry] {
+        &self.log
+    }
+
+    /// Get the bytes fed but not yet parsed, e.g. an incomplete command at the end of a capture.
+    #[must_use]
+    pub fn pending(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Get a reference to the underlying `Session`.
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Consume the `Replay`, returning the log.
+    #[must_use]
+    pub fn into_log(self) -> Vec<Entry> {
+        self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const TRANSCRIPT: &[u8] = b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\nRCPT TO:<alice@example.com>\r\nBOGUS\r\nDATA\r\nHi Alice!\r\n.\r\nRCPT TO:<alice@example.com>\r\nQUI";
+
+    #[rstest]
+    #[case::whole(TRANSCRIPT.len())]
+    #[case::bytes(1)]
+    #[case::odd(7)]
+    fn deterministic(#[case] chunk_size: usize) {
+        let mut replay = Replay::default();
+        for chunk in TRANSCRIPT.chunks(chunk_size) {
+            replay.feed(chunk);
+        }
+
+        let offsets: Vec<_> = replay.log().iter().map(|entry| entry.offset).collect();
+        assert_eq!(offsets, [25, 54, 83, 90, 110, 139]);
+        assert!(matches!(
+            replay.log()[3].event,
+            Event::Error(Error::CommandNotImplemented)
+        ));
+        assert_eq!(
+            replay.log()[4].event,
+            Event::Command(Command::Data(Bytes::from("Hi Alice!")))
+        );
+        assert!(matches!(
+            replay.log()[5].event,
+            Event::Violation(Command::Rcpt(_), Violation::NoMail)
+        ));
+        assert_eq!(replay.pending(), b"QUI");
+        assert!(!replay.session().in_transaction());
+    }
+}
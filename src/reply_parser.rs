@@ -0,0 +1,351 @@
+#![cfg(feature = "parse")]
+
+use alloc::vec::Vec;
+use core::mem;
+
+use bstr::Finder;
+use derive_more::Display;
+
+use crate::*;
+
+/// Default `max` of a [`ReplyParser`], 64 KiB.
+const DEFAULT_MAX: usize = 64 * 1024;
+
+/// # Reply Parse Error
+///
+/// Returned by [`ReplyParser::parse`]. The reply in progress, if any, is discarded.
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplyParseError {
+    /// A line does not start with three digits followed by a space, a `-`, or the CRLF.
+    #[display("Invalid reply syntax")]
+    InvalidSyntax,
+    /// A line of a multiline reply has a different code than the first, in strict mode; see
+    /// [`ReplyParser::with_strict`].
+    #[display("Reply code changed within a multiline reply")]
+    MismatchedCode,
+    /// The last line of a reply has no space after the code, in strict mode; see
+    /// [`ReplyParser::with_strict`].
+    #[display("Reply line without a separator")]
+    MissingSeparator,
+    /// A line exceeds [`max::REPLY_LINE`], or the reply exceeds the parser's `max`.
+    #[display("Reply too long")]
+    TooLong,
+    /// The reply is not a valid [`Reply`].
+    #[display("Invalid reply")]
+    Invalid(ReplyError),
+}
+
+impl From<ReplyError> for ReplyParseError {
+    fn from(error: ReplyError) -> Self {
+        Self::Invalid(error)
+    }
+}
+
+/// # Reply Parser State Machine
+///
+/// The client-side counterpart of [`Parser`]: consumes bytes read from the server and yields
+/// complete [`Reply`]s, accumulating the lines of a multiline reply across reads.
+///
+/// ```rust
+/// # use bytes::BytesMut;
+/// # use smtpkit::ReplyParser;
+/// let mut parser = ReplyParser::default();
+/// let mut buf = BytesMut::from(&b"250-mx.example.com\r\n250-PIPE"[..]);
+/// assert_eq!(parser.parse(&mut buf), Ok(None));
+/// assert!(parser.in_reply());
+///
+/// buf.extend_from_slice(b"LINING\r\n250 SIZE\r\n");
+/// let reply = parser.parse(&mut buf)?.unwrap();
+/// assert_eq!(reply.code(), 250);
+/// assert_eq!(reply.lines(), ["mx.example.com", "PIPELINING", "SIZE"]);
+/// # Ok::<(), smtpkit::ReplyParseError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplyParser {
+    max: usize,
+    crlf_finder: Finder<'static>,
+    /// The code of the multiline reply in progress.
+    code: Option<u16>,
+    lines: Vec<Bytes>,
+    len: usize,
+    enhanced_codes: bool,
+    strict: bool,
+}
+
+impl Default for ReplyParser {
+    /// Create a `ReplyParser` with a default `max` of 64 KiB.
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX)
+    }
+}
+
+impl ReplyParser {
+    /// Create a `ReplyParser` that buffers at most `max` bytes of one reply before
+    /// [`ReplyParseError::TooLong`].
+    #[must_use]
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            crlf_finder: Finder::new(b"\r\n"),
+            code: None,
+            lines: Vec::new(),
+            len: 0,
+            enhanced_codes: false,
+            strict: false,
+        }
+    }
+
+    /// Set whether to split enhanced status codes from the text of replies, once the server has
+    /// advertised `ENHANCEDSTATUSCODES`; see [`Reply::split_enhanced_code`].
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::{EnhancedCode, ReplyParser};
+    /// let mut parser = ReplyParser::default().with_enhanced_codes(true);
+    /// let mut buf = BytesMut::from(&b"250 2.1.0 OK\r\n"[..]);
+    /// let reply = parser.parse(&mut buf)?.unwrap();
+    /// assert_eq!(reply.enhanced_code(), EnhancedCode::new(2, 1, 0));
+    /// assert_eq!(reply.lines(), ["OK"]);
+    /// # Ok::<(), smtpkit::ReplyParseError>(())
+    /// ```
+    #[must_use]
+    pub const fn with_enhanced_codes(mut self, enhanced_codes: bool) -> Self {
+        self.enhanced_codes = enhanced_codes;
+        self
+    }
+
+    /// Set whether to refuse replies violating the multiline reply syntax of
+    /// [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321#section-4.2.1): a line with a
+    /// different code than the first, with [`ReplyParseError::MismatchedCode`], or a last line
+    /// with no space after the code, with [`ReplyParseError::MissingSeparator`].
+    ///
+    /// By default, the reply takes the code of its last line, and a bare code is accepted.
+    ///
+    /// ```rust
+    /// # use bytes::BytesMut;
+    /// # use smtpkit::{ReplyParseError, ReplyParser};
+    /// let mut buf = BytesMut::from(&b"250-mx.example.com\r\n251 OK\r\n"[..]);
+    /// let reply = ReplyParser::default().parse(&mut buf.clone())?.unwrap();
+    /// assert_eq!(reply.code(), 251);
+    ///
+    /// let mut parser = ReplyParser::default().with_strict(true);
+    /// assert_eq!(parser.parse(&mut buf), Err(ReplyParseError::MismatchedCode));
+    /// # Ok::<(), ReplyParseError>(())
+    /// ```
+    #[must_use]
+    pub const fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Whether some lines of a multiline reply have been parsed, but not the last.
+    #[must_use]
+    pub const fn in_reply(&self) -> bool {
+        self.code.is_some()
+    }
+
+    /// Read and parse bytes from the buffer, returning the next complete reply.
+    ///
+    /// - Returns `Ok(None)` if more bytes are needed.
+    /// - Returns `Err(ReplyParseError::TooLong)` if a line or the reply is too long; the line is
+    ///   consumed, or the buffer is cleared if it exceeds `max` without a CRLF.
+    /// - On any other error, the offending line is consumed. Later lines of the same reply are
+    ///   parsed as a new reply.
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<Reply>, ReplyParseError> {
+        loop {
+            let Some(pos) = self.crlf_finder.find(&buf) else {
+                if buf.len() > self.max {
+                    log::debug!(
+                        buf_len = buf.len(),
+                        max = self.max,
+                        "Reply too long; clearing"
+                    );
+                    buf.clear();
+                    self.reset();
+                    return Err(ReplyParseError::TooLong);
+                }
+
+                return Ok(None);
+            };
+
+            let line = buf.split_to(pos + 2).freeze().slice(..pos);
+            self.len += pos;
+            if pos > max::REPLY_LINE || self.len > self.max {
+                log::debug!(len = pos, "Reply line too long; advancing");
+                self.reset();
+                return Err(ReplyParseError::TooLong);
+            }
+
+            let (code, last, text) = split_line(&line).inspect_err(|_| self.reset())?;
+            if self.strict && self.code.is_some_and(|c| c != code) {
+                self.reset();
+                return Err(ReplyParseError::MismatchedCode);
+            }
+            if self.strict && last && line.len() == 3 {
+                self.reset();
+                return Err(ReplyParseError::MissingSeparator);
+            }
+
+            self.lines.push(text);
+            if last {
+                let lines = mem::take(&mut self.lines);
+                self.reset();
+                let mut reply = Reply::multiline(code, lines)?;
+                if self.enhanced_codes {
+                    reply.split_enhanced_code();
+                }
+                return Ok(Some(reply));
+            }
+            self.code = Some(code);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.code = None;
+        self.lines.clear();
+        self.len = 0;
+    }
+}
+
+/// Split a reply line into its code, whether it is the last line, and its text.
+fn split_line(line: &Bytes) -> Result<(u16, bool, Bytes), ReplyParseError> {
+    let Some((code, rest)) = line.split_first_chunk::<3>() else {
+        return Err(ReplyParseError::InvalidSyntax);
+    };
+    if !code.iter().all(u8::is_ascii_digit) {
+        return Err(ReplyParseError::InvalidSyntax);
+    }
+    let code = code
+        .iter()
+        .fold(0, |code, digit| code * 10 + u16::from(digit - b'0'));
+
+    match rest.first() {
+        None => Ok((code, true, Bytes::new())),
+        Some(b' ') => Ok((code, true, line.slice(4..))),
+        Some(b'-') => Ok((code, false, line.slice(4..))),
+        Some(_) => Err(ReplyParseError::InvalidSyntax),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::single(b"250 2.0.0 OK\r\n", 250, &[&b"2.0.0 OK"[..]])]
+    #[case::bare(b"250\r\n", 250, &[b""])]
+    #[case::empty_continuation(b"250-\r\n250 OK\r\n", 250, &[&b""[..], b"OK"])]
+    #[case::multiline(
+        b"250-mx.example.com\r\n250-PIPELINING\r\n250 SIZE 1000\r\n",
+        250,
+        &[&b"mx.example.com"[..], b"PIPELINING", b"SIZE 1000"]
+    )]
+    fn parse(#[case] input: &[u8], #[case] code: u16, #[case] lines: &[&[u8]]) {
+        let mut parser = ReplyParser::default();
+        let mut buf = BytesMut::from(input);
+        let reply = parser.parse(&mut buf).unwrap().unwrap();
+        assert_eq!(reply.code(), code);
+        assert_eq!(reply.lines(), lines);
+        assert!(buf.is_empty());
+        assert!(!parser.in_reply());
+    }
+
+    #[rstest]
+    #[case::short(b"25\r\n", ReplyParseError::InvalidSyntax)]
+    #[case::letters(b"2x0 OK\r\n", ReplyParseError::InvalidSyntax)]
+    #[case::sign(b"+50 OK\r\n", ReplyParseError::InvalidSyntax)]
+    #[case::separator(b"250:OK\r\n", ReplyParseError::InvalidSyntax)]
+    #[case::code(b"199 OK\r\n", ReplyParseError::Invalid(ReplyError::InvalidCode))]
+    #[case::cr(b"250 O\rK\r\n", ReplyParseError::Invalid(ReplyError::InvalidText))]
+    fn invalid(#[case] input: &[u8], #[case] expected: ReplyParseError) {
+        let mut parser = ReplyParser::default();
+        let mut buf = BytesMut::from(input);
+        assert_eq!(parser.parse(&mut buf), Err(expected));
+        assert!(!parser.in_reply());
+    }
+
+    #[rstest]
+    #[case::mismatched(b"250-a\r\n251 b\r\n", 251, Err(ReplyParseError::MismatchedCode))]
+    #[case::mismatched_continuation(
+        b"250-a\r\n251-b\r\n250 c\r\n",
+        250,
+        Err(ReplyParseError::MismatchedCode)
+    )]
+    #[case::bare(b"250\r\n", 250, Err(ReplyParseError::MissingSeparator))]
+    #[case::bare_last(b"250-a\r\n250\r\n", 250, Err(ReplyParseError::MissingSeparator))]
+    #[case::valid(b"250-a\r\n250 b\r\n", 250, Ok(()))]
+    fn strict(
+        #[case] input: &[u8],
+        #[case] lenient_code: u16,
+        #[case] expected: Result<(), ReplyParseError>,
+    ) {
+        // lenient by default, taking the last code
+        let mut buf = BytesMut::from(input);
+        let reply = ReplyParser::default().parse(&mut buf).unwrap().unwrap();
+        assert_eq!(reply.code(), lenient_code);
+        assert!(buf.is_empty());
+
+        let mut parser = ReplyParser::default().with_strict(true);
+        let mut buf = BytesMut::from(input);
+        assert_eq!(parser.parse(&mut buf).map(|_| ()), expected);
+        assert!(!parser.in_reply());
+    }
+
+    #[test]
+    fn partial() {
+        let mut parser = ReplyParser::default();
+        let mut buf = BytesMut::new();
+        for chunk in [
+            &b"220-mx.exa"[..],
+            b"mple.com\r\n",
+            b"220",
+            b" ESMTP\r",
+            b"\n221 Bye\r\n",
+        ] {
+            buf.extend_from_slice(chunk);
+            if let Some(reply) = parser.parse(&mut buf).unwrap() {
+                assert_eq!(reply.code(), 220);
+                assert_eq!(reply.lines(), ["mx.example.com", "ESMTP"]);
+                break;
+            }
+        }
+
+        // the next reply is left in the buffer
+        let reply = parser.parse(&mut buf).unwrap().unwrap();
+        assert_eq!(reply, Reply::new(221, Bytes::from("Bye")).unwrap());
+    }
+
+    #[test]
+    fn too_long() {
+        let mut parser = ReplyParser::default();
+        let mut line = BytesMut::from(&b"250 "[..]);
+        line.resize(max::REPLY_LINE + 1, b'a');
+        line.extend_from_slice(b"\r\n250 OK\r\n");
+        assert_eq!(parser.parse(&mut line), Err(ReplyParseError::TooLong));
+        assert_eq!(parser.parse(&mut line).unwrap().unwrap().lines(), ["OK"]);
+
+        let mut parser = ReplyParser::new(16);
+        let mut buf = BytesMut::from(&b"250-0123456789\r\n250-0123456789\r\n"[..]);
+        assert_eq!(parser.parse(&mut buf), Err(ReplyParseError::TooLong));
+
+        let mut buf = BytesMut::from(&[b'a'; 17][..]);
+        assert_eq!(parser.parse(&mut buf), Err(ReplyParseError::TooLong));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn enhanced_codes() {
+        let mut parser = ReplyParser::default().with_enhanced_codes(true);
+        let mut buf = BytesMut::from(&b"550-5.1.1 No such user\r\n550 5.1.1\r\n354 Go on\r\n"[..]);
+
+        let reply = parser.parse(&mut buf).unwrap().unwrap();
+        assert_eq!(reply.enhanced_code(), EnhancedCode::new(5, 1, 1));
+        assert_eq!(reply.lines(), ["No such user", ""]);
+        assert_eq!(reply.to_bytes(), "550-5.1.1 No such user\r\n550 5.1.1\r\n");
+
+        let reply = parser.parse(&mut buf).unwrap().unwrap();
+        assert_eq!(reply.enhanced_code(), None);
+        assert_eq!(reply.lines(), ["Go on"]);
+    }
+}
@@ -0,0 +1,972 @@
+//! # SCRAM
+//!
+//! The `SCRAM-SHA-1` and `SCRAM-SHA-256` SASL mechanisms, as defined in
+//! [RFC 5802](https://datatracker.ietf.org/doc/html/rfc5802) and
+//! [RFC 7677](https://datatracker.ietf.org/doc/html/rfc7677), and their `-PLUS` variants with
+//! channel binding.
+//!
+//! Nonces are generated from a caller-supplied RNG, which must be cryptographically secure.
+//! Usernames and passwords are used as given; SASLprep is left to the caller.
+
+#![cfg(feature = "scram")]
+
+use alloc::vec::Vec;
+
+use hmac::{
+    Hmac, Mac,
+    digest::{Digest, KeyInit},
+};
+
+use crate::*;
+
+/// Bytes of randomness in a nonce, before base64 encoding.
+const NONCE_LEN: usize = 18;
+
+/// Default maximum iteration count accepted by a [`Client`].
+const DEFAULT_MAX_ITERATIONS: u32 = 1_000_000;
+
+/// # SCRAM Error
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Error {
+    #[error("Malformed SCRAM message")]
+    InvalidMessage,
+
+    #[error("Server nonce does not extend the client nonce")]
+    InvalidNonce,
+
+    #[error("Server signature does not match")]
+    InvalidSignature,
+
+    #[error("Server reported an error: {0:?}")]
+    Server(Bytes),
+
+    #[error("Client proof does not match")]
+    InvalidProof,
+
+    #[error("Channel binding does not match")]
+    InvalidChannelBinding,
+
+    #[error("Iteration count exceeds the maximum")]
+    TooManyIterations,
+}
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// # SCRAM Hash Function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// `SCRAM-SHA-1`
+    Sha1,
+    /// `SCRAM-SHA-256`
+    Sha256,
+}
+
+impl Algorithm {
+    /// The SASL mechanism using this hash function.
+    #[must_use]
+    pub const fn mechanism(self) -> Mechanism {
+        match self {
+            Self::Sha1 => Mechanism::ScramSha1,
+            Self::Sha256 => Mechanism::ScramSha256,
+        }
+    }
+
+    /// The `-PLUS` SASL mechanism using this hash function, with channel binding.
+    #[must_use]
+    pub const fn mechanism_plus(self) -> Mechanism {
+        match self {
+            Self::Sha1 => Mechanism::ScramSha1Plus,
+            Self::Sha256 => Mechanism::ScramSha256Plus,
+        }
+    }
+
+    /// `H()`
+    fn hash(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1 => sha1::Sha1::digest(data).to_vec(),
+            Self::Sha256 => sha2::Sha256::digest(data).to_vec(),
+        }
+    }
+
+    /// `HMAC()`
+    fn hmac(self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1 => mac::<Hmac<sha1::Sha1>>(key, data),
+            Self::Sha256 => mac::<Hmac<sha2::Sha256>>(key, data),
+        }
+    }
+
+    /// `Hi()`, i.e. PBKDF2 with `HMAC()` as the PRF.
+    fn hi(self, password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+        let mut u = self.hmac(password, &[salt, &1u32.to_be_bytes()[..]].concat());
+        let mut hi = u.clone();
+        for _ in 1..iterations {
+            u = self.hmac(password, &u);
+            xor(&mut hi, &u);
+        }
+
+        hi
+    }
+}
+
+fn mac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn xor(a: &mut [u8], b: &[u8]) {
+    for (a, b) in a.iter_mut().zip(b) {
+        *a ^= b;
+    }
+}
+
+/// A base64 nonce from `rng`.
+fn nonce(rng: &mut dyn FnMut(&mut [u8])) -> Vec<u8> {
+    let mut random = [0; NONCE_LEN];
+    rng(&mut random);
+    Base64::encode(&random).into_bytes().to_vec()
+}
+
+/// Decode a base64 attribute value.
+fn base64(value: &[u8]) -> Result<Vec<u8>> {
+    Base64::try_from(Bytes::copy_from_slice(value))
+        .map(|b| b.decode().to_vec())
+        .map_err(|_| Error::InvalidMessage)
+}
+
+/// Get the value of the next attribute of `message`, which must be `key`.
+fn attribute<'a>(message: &mut impl Iterator<Item = &'a [u8]>, key: u8) -> Result<&'a [u8]> {
+    match message.next() {
+        Some([k, b'=', value @ ..]) if *k == key => Ok(value),
+        _ => Err(Error::InvalidMessage),
+    }
+}
+
+/// Compare `a` and `b` in constant time, for equal lengths.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Unescape `=2C` and `=3D` in a username.
+fn unescape(saslname: &[u8]) -> Result<Bytes> {
+    let mut unescaped = Vec::with_capacity(saslname.len());
+    let mut rest = saslname;
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = match (b, tail) {
+            (b'=', [b'2', b'C', tail @ ..]) => {
+                unescaped.push(b',');
+                tail
+            }
+            (b'=', [b'3', b'D', tail @ ..]) => {
+                unescaped.push(b'=');
+                tail
+            }
+            (b'=', _) => return Err(Error::InvalidMessage),
+            (b, tail) => {
+                unescaped.push(b);
+                tail
+            }
+        };
+    }
+
+    Ok(unescaped.into())
+}
+
+/// Escape `,` and `=` in a username.
+fn saslname(username: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(username.len());
+    for &b in username {
+        match b {
+            b',' => escaped.extend_from_slice(b"=2C"),
+            b'=' => escaped.extend_from_slice(b"=3D"),
+            b => escaped.push(b),
+        }
+    }
+
+    escaped
+}
+
+/// # Channel Binding Type
+///
+/// As registered in the IANA Channel-Binding Types registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BindingType {
+    /// `tls-unique`, for TLS 1.2 and earlier.
+    TlsUnique,
+    /// `tls-server-end-point`
+    TlsServerEndPoint,
+    /// `tls-exporter`, for TLS 1.3.
+    TlsExporter,
+}
+
+impl BindingType {
+    /// The registered name.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::TlsUnique => "tls-unique",
+            Self::TlsServerEndPoint => "tls-server-end-point",
+            Self::TlsExporter => "tls-exporter",
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Option<Self> {
+        [Self::TlsUnique, Self::TlsServerEndPoint, Self::TlsExporter]
+            .into_iter()
+            .find(|kind| kind.name().as_bytes() == name)
+    }
+}
+
+/// # Client Channel Binding
+///
+/// Sent in the GS2 header, so the server can detect a downgrade from a `-PLUS` mechanism.
+#[derive(derive_more::Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum ChannelBinding {
+    /// The client does not support channel binding (`n`).
+    #[default]
+    Unsupported,
+    /// The client supports channel binding, but the server did not advertise a `-PLUS` mechanism
+    /// (`y`).
+    NotAdvertised,
+    /// Bind to the TLS connection, using `data` of `kind` supplied by the TLS layer (`p=`). Use
+    /// with the `-PLUS` mechanisms.
+    Tls {
+        /// The type of channel binding.
+        kind: BindingType,
+        /// The channel binding data.
+        #[debug(skip)]
+        data: Bytes,
+    },
+}
+
+impl ChannelBinding {
+    /// The `gs2-cbind-flag`.
+    fn flag(&self) -> Vec<u8> {
+        match self {
+            Self::Unsupported => b"n".to_vec(),
+            Self::NotAdvertised => b"y".to_vec(),
+            Self::Tls { kind, .. } => [&b"p="[..], kind.name().as_bytes()].concat(),
+        }
+    }
+}
+
+/// # SCRAM Client
+///
+/// Drives the client side of the exchange:
+///
+/// 1. Send [`Client::command`].
+/// 2. Pass the challenge of the `334` reply (see [`sasl::challenge`]) to [`Client::respond`],
+///    and send the base64 response.
+/// 3. Pass the final challenge to [`Client::verify`] to authenticate the server, and send an
+///    empty response.
+///
+/// ```rust
+/// # use smtpkit::{Command, scram::{Algorithm, Client}};
+/// let mut rng = |buf: &mut [u8]| buf.fill(4); // use a CSPRNG
+/// let client = Client::new(Algorithm::Sha256, b"user", b"pencil", &mut rng);
+/// assert_eq!(
+///     client.command().to_string(),
+///     "AUTH SCRAM-SHA-256 biwsbj11c2VyLHI9QkFRRUJBUUVCQVFFQkFRRUJBUUVCQVFF"
+/// );
+/// ```
+#[derive(derive_more::Debug, Clone)]
+pub struct Client {
+    algorithm: Algorithm,
+    #[debug(skip)]
+    password: Vec<u8>,
+    channel_binding: ChannelBinding,
+    gs2_header: Vec<u8>,
+    first_bare: Vec<u8>,
+    nonce: Vec<u8>,
+    server_signature: Option<Vec<u8>>,
+    verified: bool,
+    max_iterations: u32,
+}
+
+impl Client {
+    /// Create a `Client`, generating a nonce with `rng`.
+    pub fn new(
+        algorithm: Algorithm,
+        username: &[u8],
+        password: &[u8],
+        rng: &mut dyn FnMut(&mut [u8]),
+    ) -> Self {
+        Self::with_nonce(algorithm, username, password, nonce(rng))
+    }
+
+    fn with_nonce(algorithm: Algorithm, username: &[u8], password: &[u8], nonce: Vec<u8>) -> Self {
+        let first_bare = [&b"n="[..], &saslname(username), b",r=", &nonce].concat();
+        Self {
+            algorithm,
+            password: password.to_vec(),
+            channel_binding: ChannelBinding::Unsupported,
+            gs2_header: b"n,,".to_vec(),
+            first_bare,
+            nonce,
+            server_signature: None,
+            verified: false,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+
+    /// Refuse a `server-first-message` asking for more than `max_iterations`, instead of
+    /// 1,000,000, so a malicious server cannot make the client spin.
+    #[must_use]
+    pub const fn with_max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Send `channel_binding` instead of [`ChannelBinding::Unsupported`].
+    ///
+    /// With [`ChannelBinding::Tls`], [`Client::command`] selects the `-PLUS` mechanism.
+    #[must_use]
+    pub fn with_channel_binding(mut self, channel_binding: ChannelBinding) -> Self {
+        self.gs2_header = [&channel_binding.flag()[..], b",,"].concat();
+        self.channel_binding = channel_binding;
+        self
+    }
+
+    /// The `client-first-message`.
+    #[must_use]
+    pub fn first(&self) -> Bytes {
+        [&self.gs2_header[..], &self.first_bare].concat().into()
+    }
+
+    /// The `AUTH` command, with the `client-first-message` as the initial response.
+    #[must_use]
+    pub fn command(&self) -> Command {
+        Command::Auth(auth::Auth {
+            mechanism: self.mechanism(),
+            initial_response: Some(Base64::encode(&self.first())),
+        })
+    }
+
+    const fn mechanism(&self) -> Mechanism {
+        match self.channel_binding {
+            ChannelBinding::Tls { .. } => self.algorithm.mechanism_plus(),
+            _ => self.algorithm.mechanism(),
+        }
+    }
+
+    /// Compute the `client-final-message` from the `server-first-message`, returning it encoded
+    /// to send.
+    pub fn respond(&mut self, server_first: &[u8]) -> Result<Base64> {
+        let mut attributes = server_first.split(|&b| b == b',');
+        let nonce = attribute(&mut attributes, b'r')?;
+        let salt = base64(attribute(&mut attributes, b's')?)?;
+        let iterations = attribute(&mut attributes, b'i')
+            .and_then(|i| u32::from_ascii(i).map_err(|_| Error::InvalidMessage))?;
+
+        if iterations == 0 {
+            return Err(Error::InvalidMessage);
+        }
+
+        if iterations > self.max_iterations {
+            return Err(Error::TooManyIterations);
+        }
+
+        if nonce.len() <= self.nonce.len() || !nonce.starts_with(&self.nonce) {
+            return Err(Error::InvalidNonce);
+        }
+
+        let cbind_input = match &self.channel_binding {
+            ChannelBinding::Tls { data, .. } => [&self.gs2_header[..], data].concat(),
+            _ => self.gs2_header.clone(),
+        };
+        let cbind_input = Base64::encode(&cbind_input);
+        let without_proof = [&b"c="[..], cbind_input.bytes(), b",r=", nonce].concat();
+        let auth_message = [
+            &self.first_bare[..],
+            b",",
+            server_first,
+            b",",
+            &without_proof,
+        ]
+        .concat();
+
+        let salted = self.algorithm.hi(&self.password, &salt, iterations);
+        let client_key = self.algorithm.hmac(&salted, b"Client Key");
+        let stored_key = self.algorithm.hash(&client_key);
+        let mut proof = self.algorithm.hmac(&stored_key, &auth_message);
+        xor(&mut proof, &client_key);
+
+        let server_key = self.algorithm.hmac(&salted, b"Server Key");
+        self.server_signature = Some(self.algorithm.hmac(&server_key, &auth_message));
+
+        let proof = Base64::encode(&proof);
+        let client_final = [&without_proof[..], b",p=", proof.bytes()].concat();
+        Ok(Base64::encode(&client_final))
+    }
+
+    /// Verify the `server-final-message`, authenticating the server.
+    pub fn verify(&self, server_final: &[u8]) -> Result<()> {
+        let Some(expected) = &self.server_signature else {
+            return Err(Error::InvalidMessage);
+        };
+
+        match server_final {
+            [b'e', b'=', error @ ..] => Err(Error::Server(Bytes::copy_from_slice(error))),
+            [b'v', b'=', signature @ ..] if base64(signature)? == *expected => Ok(()),
+            [b'v', b'=', ..] => Err(Error::InvalidSignature),
+            _ => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+impl sasl::SaslMechanism for Client {
+    type Error = Error;
+
+    fn name(&self) -> &str {
+        self.mechanism().name()
+    }
+
+    fn initial_response(&mut self) -> Result<Option<Bytes>> {
+        Ok(Some(self.first()))
+    }
+
+    /// Respond to the `server-first-message`, then verify the `server-final-message` if it is
+    /// sent as a challenge, responding with nothing.
+    fn step(&mut self, challenge: &[u8]) -> Result<Bytes> {
+        if self.server_signature.is_none() {
+            return Ok(self.respond(challenge)?.decode().freeze());
+        }
+
+        self.verify(challenge)?;
+        self.verified = true;
+        Ok(Bytes::new())
+    }
+
+    /// Verify the `server-final-message`, unless it was already sent as a challenge.
+    fn finish(&mut self, additional: Option<&[u8]>) -> Result<()> {
+        match additional {
+            Some(server_final) => self.verify(server_final),
+            None if self.verified => Ok(()),
+            None => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+/// # Stored SCRAM Credentials
+///
+/// What a server stores for a user instead of the password.
+#[derive(derive_more::Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    /// The salt.
+    pub salt: Bytes,
+    /// The iteration count.
+    pub iterations: u32,
+    /// `StoredKey`, which verifies the client proof.
+    #[debug(skip)]
+    pub stored_key: Bytes,
+    /// `ServerKey`, which signs the `server-final-message`.
+    #[debug(skip)]
+    pub server_key: Bytes,
+}
+
+impl Credentials {
+    /// Derive the `Credentials` to store for `password`.
+    #[must_use]
+    pub fn new(algorithm: Algorithm, password: &[u8], salt: Bytes, iterations: u32) -> Self {
+        let salted = algorithm.hi(password, &salt, iterations);
+        let client_key = algorithm.hmac(&salted, b"Client Key");
+        Self {
+            salt,
+            iterations,
+            stored_key: algorithm.hash(&client_key).into(),
+            server_key: algorithm.hmac(&salted, b"Server Key").into(),
+        }
+    }
+}
+
+/// # Parsed `client-first-message`
+///
+/// Parsed by the server to look up the [`Credentials`] of the user before creating a [`Server`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientFirst {
+    flag: Flag,
+    gs2_header: Bytes,
+    bare: Bytes,
+    username: Bytes,
+    authzid: Option<Bytes>,
+    nonce: Bytes,
+}
+
+impl ClientFirst {
+    /// Parse the `client-first-message`, i.e. the decoded initial response of `AUTH`.
+    pub fn parse(message: Bytes) -> Result<Self> {
+        let mut commas = message
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &b)| (b == b',').then_some(i));
+        let (Some(flag_end), Some(header_end)) = (commas.next(), commas.next()) else {
+            return Err(Error::InvalidMessage);
+        };
+
+        let flag = match &message[..flag_end] {
+            b"n" => Flag::Unsupported,
+            b"y" => Flag::NotAdvertised,
+            [b'p', b'=', name @ ..] => BindingType::from_name(name)
+                .map(Flag::Tls)
+                .ok_or(Error::InvalidChannelBinding)?,
+            _ => return Err(Error::InvalidMessage),
+        };
+
+        let authzid = match &message[flag_end + 1..header_end] {
+            [] => None,
+            [b'a', b'=', authzid @ ..] => Some(unescape(authzid)?),
+            _ => return Err(Error::InvalidMessage),
+        };
+
+        let bare = message.slice(header_end + 1..);
+        let mut attributes = bare.split(|&b| b == b',');
+        let username = unescape(attribute(&mut attributes, b'n')?)?;
+        let nonce = Bytes::copy_from_slice(attribute(&mut attributes, b'r')?);
+        if nonce.is_empty() {
+            return Err(Error::InvalidMessage);
+        }
+
+        Ok(Self {
+            flag,
+            gs2_header: message.slice(..=header_end),
+            bare,
+            username,
+            authzid,
+            nonce,
+        })
+    }
+
+    /// The username to authenticate as.
+    #[must_use]
+    pub fn username(&self) -> &Bytes {
+        &self.username
+    }
+
+    /// The identity to act as, if different from the username.
+    #[must_use]
+    pub fn authzid(&self) -> Option<&Bytes> {
+        self.authzid.as_ref()
+    }
+
+    /// The type of channel binding selected by the client, which is required with the `-PLUS`
+    /// mechanisms and not allowed otherwise.
+    #[must_use]
+    pub const fn channel_binding(&self) -> Option<BindingType> {
+        match self.flag {
+            Flag::Tls(kind) => Some(kind),
+            Flag::Unsupported | Flag::NotAdvertised => None,
+        }
+    }
+}
+
+/// The `gs2-cbind-flag` of a [`ClientFirst`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Flag {
+    Unsupported,
+    NotAdvertised,
+    Tls(BindingType),
+}
+
+/// # SCRAM Server
+///
+/// Drives the server side of the exchange:
+///
+/// 1. Parse the initial response of `AUTH` with [`ClientFirst::parse`], and look up the
+///    [`Credentials`] of [`ClientFirst::username`].
+/// 2. Send [`Server::first`] as the challenge of a `334` reply.
+/// 3. Pass the response to [`Server::finish`], and send the result with the `235` reply, or
+///    reply `535` on error.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::scram::{Algorithm, Client, ClientFirst, Credentials, Server};
+/// let mut rng = |buf: &mut [u8]| buf.fill(4); // use a CSPRNG
+/// let credentials = Credentials::new(Algorithm::Sha256, b"pencil", Bytes::from("salt"), 4096);
+///
+/// let mut client = Client::new(Algorithm::Sha256, b"user", b"pencil", &mut rng);
+/// let client_first = ClientFirst::parse(client.first())?;
+/// assert_eq!(client_first.username(), "user");
+///
+/// let server = Server::new(Algorithm::Sha256, client_first, credentials, &mut rng);
+/// let client_final = client.respond(server.first())?;
+/// let server_final = server.finish(&client_final.decode())?;
+/// client.verify(&server_final)?;
+/// # Ok::<(), smtpkit::scram::Error>(())
+/// ```
+#[derive(derive_more::Debug, Clone)]
+pub struct Server {
+    algorithm: Algorithm,
+    #[debug(skip)]
+    credentials: Credentials,
+    client_first: ClientFirst,
+    nonce: Vec<u8>,
+    first: Bytes,
+    #[debug(skip)]
+    channel_binding: Option<(BindingType, Bytes)>,
+}
+
+impl Server {
+    /// Create a `Server`, generating its part of the nonce with `rng`.
+    pub fn new(
+        algorithm: Algorithm,
+        client_first: ClientFirst,
+        credentials: Credentials,
+        rng: &mut dyn FnMut(&mut [u8]),
+    ) -> Self {
+        let nonce = [&client_first.nonce[..], &nonce(rng)].concat();
+        Self::with_nonce(algorithm, client_first, credentials, nonce)
+    }
+
+    fn with_nonce(
+        algorithm: Algorithm,
+        client_first: ClientFirst,
+        credentials: Credentials,
+        nonce: Vec<u8>,
+    ) -> Self {
+        let salt = Base64::encode(&credentials.salt);
+        let mut iterations = itoa::Buffer::new();
+        let iterations = iterations.format(credentials.iterations);
+        let first = [
+            &b"r="[..],
+            &nonce,
+            b",s=",
+            salt.bytes(),
+            b",i=",
+            iterations.as_bytes(),
+        ]
+        .concat()
+        .into();
+
+        Self {
+            algorithm,
+            credentials,
+            client_first,
+            nonce,
+            first,
+            channel_binding: None,
+        }
+    }
+
+    /// Support channel binding of `kind`, with `data` supplied by the TLS layer, as when
+    /// advertising the `-PLUS` mechanisms.
+    ///
+    /// A client that supports channel binding but thinks the server does not is then refused, as
+    /// the advertisement may have been stripped.
+    #[must_use]
+    pub fn with_channel_binding(mut self, kind: BindingType, data: Bytes) -> Self {
+        self.channel_binding = Some((kind, data));
+        self
+    }
+
+    /// The `server-first-message`.
+    #[must_use]
+    pub fn first(&self) -> &Bytes {
+        &self.first
+    }
+
+    /// Verify the `client-final-message`, i.e. the decoded response to [`Server::first`],
+    /// returning the `server-final-message`.
+    pub fn finish(&self, client_final: &[u8]) -> Result<Bytes> {
+        let pos = client_final
+            .windows(3)
+            .rposition(|w| w == b",p=")
+            .ok_or(Error::InvalidMessage)?;
+        let without_proof = &client_final[..pos];
+        let proof = base64(&client_final[pos + 3..])?;
+
+        let cbind_input = match (self.client_first.flag, &self.channel_binding) {
+            (Flag::Unsupported, _) | (Flag::NotAdvertised, None) => {
+                self.client_first.gs2_header.to_vec()
+            }
+            (Flag::Tls(kind), Some((supported, data))) if kind == *supported => {
+                [&self.client_first.gs2_header[..], data].concat()
+            }
+            // a downgrade, or an unsupported type
+            (Flag::NotAdvertised, Some(_)) | (Flag::Tls(_), _) => {
+                return Err(Error::InvalidChannelBinding);
+            }
+        };
+
+        let mut attributes = without_proof.split(|&b| b == b',');
+        if base64(attribute(&mut attributes, b'c')?)? != cbind_input {
+            return Err(Error::InvalidChannelBinding);
+        }
+
+        if attribute(&mut attributes, b'r')? != self.nonce {
+            return Err(Error::InvalidNonce);
+        }
+
+        let auth_message = [
+            &self.client_first.bare[..],
+            b",",
+            &self.first,
+            b",",
+            without_proof,
+        ]
+        .concat();
+
+        let mut client_key = proof;
+        let signature = self
+            .algorithm
+            .hmac(&self.credentials.stored_key, &auth_message);
+        if client_key.len() != signature.len() {
+            return Err(Error::InvalidProof);
+        }
+
+        xor(&mut client_key, &signature);
+        if !ct_eq(
+            &self.algorithm.hash(&client_key),
+            &self.credentials.stored_key,
+        ) {
+            return Err(Error::InvalidProof);
+        }
+
+        let signature = self
+            .algorithm
+            .hmac(&self.credentials.server_key, &auth_message);
+        Ok([&b"v="[..], Base64::encode(&signature).bytes()]
+            .concat()
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    // RFC 5802 §5
+    #[case::sha1(
+        Algorithm::Sha1,
+        "fyko+d2lbbFgONRv9qkxdawL",
+        "r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,s=QSXCR+Q6sek8bf92,i=4096",
+        "c=biws,r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,p=v0X8v3Bz2T0CJGbJQyF0X+HI4Ts=",
+        "v=rmF9pqV8S7suAoZWja4dJRkFsKQ="
+    )]
+    // RFC 7677 §3
+    #[case::sha256(
+        Algorithm::Sha256,
+        "rOprNGfwEbeRWgbNEkqO",
+        "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+        "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=",
+        "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="
+    )]
+    fn client(
+        #[case] algorithm: Algorithm,
+        #[case] nonce: &str,
+        #[case] server_first: &str,
+        #[case] client_final: &str,
+        #[case] server_final: &str,
+    ) {
+        let mut client = Client::with_nonce(algorithm, b"user", b"pencil", nonce.into());
+        assert_eq!(client.first(), format!("n,,n=user,r={nonce}"));
+        assert_eq!(
+            client.verify(server_final.as_bytes()),
+            Err(Error::InvalidMessage)
+        );
+
+        let response = client.respond(server_first.as_bytes()).unwrap();
+        assert_eq!(response.decode(), client_final.as_bytes());
+        assert_eq!(client.verify(server_final.as_bytes()), Ok(()));
+        assert_eq!(client.verify(b"v=AAAA"), Err(Error::InvalidSignature));
+        assert_eq!(
+            client.verify(b"e=invalid-proof"),
+            Err(Error::Server(Bytes::from("invalid-proof")))
+        );
+    }
+
+    #[rstest]
+    #[case::nonce_prefix(b"r=abc,s=QSXCR+Q6sek8bf92,i=4096", Error::InvalidNonce)]
+    #[case::nonce_same(b"r=fyko,s=QSXCR+Q6sek8bf92,i=4096", Error::InvalidNonce)]
+    #[case::order(b"s=QSXCR+Q6sek8bf92,r=fykoabc,i=4096", Error::InvalidMessage)]
+    #[case::salt(b"r=fykoabc,s=QSX,i=4096", Error::InvalidMessage)]
+    #[case::iterations(b"r=fykoabc,s=QSXCR+Q6sek8bf92,i=0", Error::InvalidMessage)]
+    // mandatory extensions are not supported
+    #[case::extension(b"m=x,r=fykoabc,s=QSXCR+Q6sek8bf92,i=4096", Error::InvalidMessage)]
+    fn client_invalid(#[case] server_first: &[u8], #[case] expected: Error) {
+        let mut client = Client::with_nonce(Algorithm::Sha1, b"user", b"pencil", b"fyko".to_vec());
+        assert_eq!(client.respond(server_first), Err(expected));
+    }
+
+    #[test]
+    fn client_max_iterations() {
+        let server_first = b"r=fykoabc,s=QSXCR+Q6sek8bf92,i=4294967295";
+        let mut client = Client::with_nonce(Algorithm::Sha1, b"user", b"pencil", b"fyko".to_vec());
+        assert_eq!(client.respond(server_first), Err(Error::TooManyIterations));
+
+        let server_first = b"r=fykoabc,s=QSXCR+Q6sek8bf92,i=4097";
+        let mut client = Client::with_nonce(Algorithm::Sha1, b"user", b"pencil", b"fyko".to_vec())
+            .with_max_iterations(4096);
+        assert_eq!(client.respond(server_first), Err(Error::TooManyIterations));
+        assert!(
+            client
+                .respond(b"r=fykoabc,s=QSXCR+Q6sek8bf92,i=4096")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn server() {
+        // RFC 7677 §3
+        let client_first = Bytes::from("n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+        let nonce = "rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0";
+        let salt = base64(b"W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        let credentials = Credentials::new(Algorithm::Sha256, b"pencil", salt.into(), 4096);
+
+        let client_first = ClientFirst::parse(client_first).unwrap();
+        let server = Server::with_nonce(Algorithm::Sha256, client_first, credentials, nonce.into());
+        assert_eq!(
+            server.first(),
+            "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096"
+        );
+
+        let client_final = "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+        assert_eq!(
+            server.finish(client_final.as_bytes()),
+            Ok(Bytes::from(
+                "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="
+            ))
+        );
+
+        let wrong_proof = client_final.replace("p=dH", "p=eH");
+        assert_eq!(
+            server.finish(wrong_proof.as_bytes()),
+            Err(Error::InvalidProof)
+        );
+        let wrong_nonce = client_final.replace("$k0", "$k1");
+        assert_eq!(
+            server.finish(wrong_nonce.as_bytes()),
+            Err(Error::InvalidNonce)
+        );
+        let wrong_header = client_final.replace("c=biws", "c=eSws");
+        assert_eq!(
+            server.finish(wrong_header.as_bytes()),
+            Err(Error::InvalidChannelBinding)
+        );
+    }
+
+    #[rstest]
+    #[case::plain("n,,n=user,r=abc", Ok(("user", None)))]
+    #[case::escaped("y,,n=a=2Cb=3Dc,r=abc", Ok(("a,b=c", None)))]
+    #[case::authzid("n,a=admin,n=user,r=abc", Ok(("user", Some("admin"))))]
+    #[case::bad_escape("n,,n=a=b,r=abc", Err(Error::InvalidMessage))]
+    #[case::no_header("n=user,r=abc", Err(Error::InvalidMessage))]
+    #[case::no_nonce("n,,n=user,r=", Err(Error::InvalidMessage))]
+    #[case::extension("n,,m=x,n=user,r=abc", Err(Error::InvalidMessage))]
+    #[case::binding("p=tls-exporter,,n=user,r=abc", Ok(("user", None)))]
+    #[case::binding_unknown("p=tls-foo,,n=user,r=abc", Err(Error::InvalidChannelBinding))]
+    #[case::flag("x,,n=user,r=abc", Err(Error::InvalidMessage))]
+    fn client_first(
+        #[case] input: &'static str,
+        #[case] expected: core::result::Result<(&str, Option<&str>), Error>,
+    ) {
+        let result = ClientFirst::parse(Bytes::from(input)).map(|first| {
+            (
+                String::from_utf8(first.username().to_vec()).unwrap(),
+                first
+                    .authzid()
+                    .map(|a| String::from_utf8(a.to_vec()).unwrap()),
+            )
+        });
+        let expected = expected.map(|(u, a)| (u.to_owned(), a.map(str::to_owned)));
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case::bound(
+        ChannelBinding::Tls { kind: BindingType::TlsExporter, data: Bytes::from("tls") },
+        Some(BindingType::TlsExporter),
+        Ok(())
+    )]
+    #[case::unsupported(ChannelBinding::Unsupported, None, Ok(()))]
+    #[case::unsupported_by_server(ChannelBinding::NotAdvertised, None, Ok(()))]
+    #[case::downgrade(
+        ChannelBinding::NotAdvertised,
+        Some(BindingType::TlsExporter),
+        Err(Error::InvalidChannelBinding)
+    )]
+    #[case::kind(
+        ChannelBinding::Tls { kind: BindingType::TlsUnique, data: Bytes::from("tls") },
+        Some(BindingType::TlsExporter),
+        Err(Error::InvalidChannelBinding)
+    )]
+    #[case::data(
+        ChannelBinding::Tls { kind: BindingType::TlsExporter, data: Bytes::from("mitm") },
+        Some(BindingType::TlsExporter),
+        Err(Error::InvalidChannelBinding)
+    )]
+    fn channel_binding(
+        #[case] client_binding: ChannelBinding,
+        #[case] server_binding: Option<BindingType>,
+        #[case] expected: Result<()>,
+    ) {
+        let mut rng = |buf: &mut [u8]| buf.fill(1);
+        let credentials = Credentials::new(Algorithm::Sha1, b"pencil", Bytes::from("salt"), 1);
+        let mut client = Client::new(Algorithm::Sha1, b"user", b"pencil", &mut rng)
+            .with_channel_binding(client_binding);
+
+        let client_first = ClientFirst::parse(client.first()).unwrap();
+        let mut server = Server::new(Algorithm::Sha1, client_first, credentials, &mut rng);
+        if let Some(kind) = server_binding {
+            server = server.with_channel_binding(kind, Bytes::from("tls"));
+        }
+
+        let client_final = client.respond(server.first()).unwrap();
+        let result = server
+            .finish(&client_final.decode())
+            .and_then(|server_final| client.verify(&server_final));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn sasl_mechanism() {
+        use sasl::SaslMechanism;
+
+        let mut rng = |buf: &mut [u8]| buf.fill(1);
+        let credentials = Credentials::new(Algorithm::Sha256, b"pencil", Bytes::from("salt"), 1);
+        let mut client = Client::new(Algorithm::Sha256, b"user", b"pencil", &mut rng);
+        assert_eq!(client.name(), "SCRAM-SHA-256");
+
+        let client_first = client.initial_response().unwrap().unwrap();
+        let client_first = ClientFirst::parse(client_first).unwrap();
+        let server = Server::new(Algorithm::Sha256, client_first, credentials, &mut rng);
+        let client_final = client.step(server.first()).unwrap();
+        let server_final = server.finish(&client_final).unwrap();
+
+        // in the 235 reply
+        assert_eq!(client.clone().finish(None), Err(Error::InvalidMessage));
+        assert_eq!(client.clone().finish(Some(&server_final)), Ok(()));
+
+        // as a challenge
+        assert_eq!(client.step(&server_final), Ok(Bytes::new()));
+        assert_eq!(client.finish(None), Ok(()));
+    }
+
+    #[test]
+    fn plus_command() {
+        let mut rng = |buf: &mut [u8]| buf.fill(1);
+        let client = Client::new(Algorithm::Sha256, b"user", b"pencil", &mut rng);
+        let Command::Auth(auth::Auth { mechanism, .. }) = client.command() else {
+            unreachable!();
+        };
+        assert_eq!(mechanism, Mechanism::ScramSha256);
+
+        let client = client.with_channel_binding(ChannelBinding::Tls {
+            kind: BindingType::TlsExporter,
+            data: Bytes::from("tls"),
+        });
+        assert!(client.first().starts_with(b"p=tls-exporter,,n=user,r="));
+        let Command::Auth(auth::Auth { mechanism, .. }) = client.command() else {
+            unreachable!();
+        };
+        assert_eq!(mechanism, Mechanism::ScramSha256Plus);
+    }
+
+    #[test]
+    fn saslname() {
+        assert_eq!(super::saslname(b"a,b=c"), b"a=2Cb=3Dc");
+    }
+}
@@ -0,0 +1,638 @@
+#![cfg(feature = "server")]
+
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio_util::codec::Decoder;
+
+use crate::*;
+
+/// # Minimal SMTP Server
+///
+/// A small, opinionated runtime: accepts connections from a [`TcpListener`] and runs each
+/// through a [`ServerCodec`] and [`Session`], consulting a [`Policy`] and handing accepted
+/// messages to a [`DataSink`]. The core stays sans-I/O; this is for when a working server is all
+/// you need.
+///
+/// Replies are fixed: `EHLO` advertises `PIPELINING`, `8BITMIME`, `SIZE`, `ENHANCEDSTATUSCODES`,
+/// and `CHUNKING` if enabled. `STARTTLS`, `AUTH`, and `EXPN` are not implemented, and are left
+/// out of the reply to `HELP`.
+///
+/// ```rust,no_run
+/// # use bytes::Bytes;
+/// # use smtpkit::{DataSink, Domain, Server};
+/// struct Stdout(Vec<Bytes>);
+///
+/// impl DataSink for Stdout {
+///     fn push(&mut self, chunk: Bytes) {
+///         self.0.push(chunk);
+///     }
+///     fn commit(&mut self) {
+///         println!("{}", String::from_utf8_lossy(&self.0.concat()));
+///         self.0.clear();
+///     }
+///     fn abort(&mut self) {
+///         self.0.clear();
+///     }
+/// }
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:2525").await?;
+/// let server = Server::new(Domain::try_from(Bytes::from("mx.example.com")).unwrap());
+/// server
+///     // e.g. `tokio::signal::ctrl_c()`, with tokio's `signal` feature
+///     .serve(listener, |_| ((), Stdout(Vec::new())), std::future::pending::<()>())
+///     .await
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Server {
+    hostname: Domain,
+    limits: Limits,
+    timeouts: Timeouts,
+    chunking: bool,
+    max: Option<usize>,
+}
+
+impl Server {
+    /// Create a `Server` greeting clients as `hostname`, with the default [`Limits`] and
+    /// [`Timeouts`].
+    #[must_use]
+    pub fn new(hostname: Domain) -> Self {
+        Self {
+            hostname,
+            limits: Limits::default(),
+            timeouts: Timeouts::default(),
+            chunking: false,
+            max: None,
+        }
+    }
+
+    /// Set the [`Limits`] enforced by each connection's [`ServerCodec`] and [`Session`].
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the [`Timeouts`]; see [`Parser::timeout`].
+    #[must_use]
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Set whether `CHUNKING` is advertised.
+    #[must_use]
+    pub fn with_chunking(mut self, chunking: bool) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Set the maximum size of each connection's read buffer; see [`Parser::new`].
+    #[must_use]
+    pub fn with_max(mut self, max: usize) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Accept connections from `listener` until `shutdown` completes, creating a [`Policy`] and
+    /// [`DataSink`] for each with `make`.
+    ///
+    /// On shutdown, no more connections are accepted, and open ones are closed with `421` once
+    /// any transaction in progress completes. Returns once every connection is closed.
+    pub async fn serve<F, P, D>(
+        &self,
+        listener: TcpListener,
+        mut make: F,
+        shutdown: impl Future,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&ConnectionInfo) -> (P, D),
+        P: Policy + Send + 'static,
+        D: DataSink + Send + 'static,
+    {
+        let (closing, closed) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => {
+                    // e.g. out of file descriptors; carry on with the open connections
+                    let Ok((stream, peer)) = accepted else {
+                        continue;
+                    };
+
+                    let session = self.session(peer, stream.local_addr().ok());
+                    let (mut policy, mut sink) = make(session.connection());
+                    let server = self.clone();
+                    let closed = closed.clone();
+                    connections.spawn(async move {
+                        server.connection(stream, session, &mut policy, &mut sink, closed).await
+                    });
+                }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+
+        let _ = closing.send(true);
+        while connections.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Run one connection over `stream` to completion, e.g. for a listener other than TCP or a
+    /// stream already wrapped in TLS.
+    ///
+    /// The connection is closed with `421` once `closed` is `true` and no transaction is in
+    /// progress.
+    pub async fn connection<S, P, D>(
+        &self,
+        mut stream: S,
+        session: Session,
+        policy: &mut P,
+        sink: &mut D,
+        mut closed: watch::Receiver<bool>,
+    ) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        P: Policy + ?Sized,
+        D: DataSink + ?Sized,
+    {
+        let mut connection = Connection {
+            server: self,
+            session,
+            codec: self.codec(),
+            policy,
+            sink,
+            out: BytesMut::new(),
+            pending: false,
+            data: false,
+        };
+        let mut buf = BytesMut::with_capacity(4096);
+
+        connection.line(&[b"220 ", self.hostname.as_ref(), b" ESMTP"]);
+        stream.write_all(&connection.out.split()).await?;
+        connection.session.greeting_sent();
+        if *closed.borrow() {
+            connection.session.begin_shutdown();
+        }
+
+        loop {
+            let quit = connection.parse(&mut buf)?;
+            if connection.session.is_closing() && !quit {
+                connection.line(&[
+                    b"421 4.3.2 ",
+                    self.hostname.as_ref(),
+                    b" Service shutting down",
+                ]);
+            }
+            stream.write_all(&connection.out.split()).await?;
+            if quit || connection.session.is_closing() {
+                connection.abort();
+                return stream.shutdown().await;
+            }
+
+            let timeout = connection.codec.parser().timeout(&self.timeouts);
+            tokio::select! {
+                read = tokio::time::timeout(timeout, stream.read_buf(&mut buf)) => match read {
+                    Ok(Ok(0)) => {
+                        connection.abort();
+                        return Ok(());
+                    }
+                    Ok(Ok(_)) => connection.session.received(),
+                    Ok(Err(e)) => {
+                        connection.abort();
+                        return Err(e);
+                    }
+                    Err(_) => {
+                        connection.abort();
+                        connection.line(&[b"421 4.4.2 ", self.hostname.as_ref(), b" Timeout"]);
+                        stream.write_all(&connection.out.split()).await?;
+                        return stream.shutdown().await;
+                    }
+                },
+                Ok(()) = closed.changed() => connection.session.begin_shutdown(),
+            }
+        }
+    }
+
+    fn codec(&self) -> ServerCodec {
+        let parser = match self.max {
+            Some(max) => Parser::new(max),
+            None => Parser::default(),
+        };
+        ServerCodec::new(parser.with_limits(self.limits))
+    }
+
+    fn session(&self, peer: SocketAddr, local: Option<SocketAddr>) -> Session {
+        let mut verbs = Verbs::all() - Verbs::EXPN - Verbs::STARTTLS - Verbs::AUTH;
+        if !self.chunking {
+            verbs -= Verbs::BDAT;
+        }
+
+        let session = Session::new()
+            .with_verbs(verbs)
+            .with_limits(self.limits)
+            .with_chunking(self.chunking)
+            .with_hostname(self.hostname.clone())
+            .with_peer(peer);
+        match local {
+            Some(local) => session.with_local(local),
+            None => session,
+        }
+    }
+}
+
+/// The state of one connection of a [`Server`].
+struct Connection<'a, P: ?Sized, D: ?Sized> {
+    server: &'a Server,
+    session: Session,
+    codec: ServerCodec,
+    policy: &'a mut P,
+    sink: &'a mut D,
+    /// Replies to write.
+    out: BytesMut,
+    /// Whether `BDAT` content has been pushed to the sink but not committed.
+    pending: bool,
+    /// Whether `354` has been sent for the `DATA` in progress.
+    data: bool,
+}
+
+impl<P: Policy + ?Sized, D: DataSink + ?Sized> Connection<'_, P, D> {
+    /// Decode and reply to every command in `buf`, returning whether the client sent `QUIT`.
+    fn parse(&mut self, buf: &mut BytesMut) -> io::Result<bool> {
+        loop {
+            match self.codec.decode(buf)?.transpose() {
+                Ok(Some(Command::Quit)) => {
+                    self.line(&[b"221 2.0.0 ", self.server.hostname.as_ref(), b" Bye"]);
+                    return Ok(true);
+                }
+                // the content arrived along with the command line, before `354`
+                Ok(Some(command @ Command::Data(_))) if !self.data => {
+                    if self.start_data() {
+                        self.data = false;
+                        self.command(&command);
+                    }
+                }
+                Ok(Some(command)) => {
+                    self.data = false;
+                    self.command(&command);
+                }
+                Ok(None) if self.codec.parser().in_data() && !self.data => {
+                    if !self.start_data() {
+                        // parse what was sent after the refused `DATA` as commands
+                        self.codec.parser_mut().cancel_data();
+                    }
+                }
+                Ok(None) => return Ok(false),
+                Err(error) => {
+                    self.data = false;
+                    self.error(&error);
+                }
+            }
+        }
+    }
+
+    /// Reply `354` if the session permits `DATA`, otherwise with the violation, returning whether
+    /// it was permitted.
+    fn start_data(&mut self) -> bool {
+        match self.session.check(&Command::Data(Bytes::new())) {
+            Ok(()) => {
+                self.line(&[b"354 End data with <CR><LF>.<CR><LF>"]);
+                self.data = true;
+                true
+            }
+            Err(violation) => {
+                self.violation(violation);
+                false
+            }
+        }
+    }
+
+    fn command(&mut self, command: &Command) {
+        if matches!(command, Command::Vrfy(_)) {
+            return self.line(&[b"252 2.5.0 Cannot VRFY user"]);
+        }
+
+        let content = matches!(command, Command::Data(_) | Command::Bdat(_));
+        match self.session.handle(command, self.policy) {
+            Ok(()) => self.accepted(command),
+            Err(Refusal::Violation(violation)) => self.violation(violation),
+            Err(refusal) => {
+                // the session ends the transaction
+                if content {
+                    self.abort();
+                }
+
+                refusal.reply(content).to_bytes_into(&mut self.out);
+            }
+        }
+    }
+
+    fn accepted(&mut self, command: &Command) {
+        // the session reset the transaction, so any `BDAT` content pushed for it is abandoned
+        if matches!(command, Command::Helo(_) | Command::Ehlo(_) | Command::Rset)
+            || self.session.implicitly_reset()
+        {
+            self.abort();
+        }
+
+        match command {
+            Command::Helo(_) => self.line(&[b"250 ", self.server.hostname.as_ref()]),
+            Command::Ehlo(_) => {
+                let mut capabilities = Capabilities::new(self.server.hostname.bytes().clone());
+                capabilities.push(Extension::Pipelining);
+                capabilities.push(Extension::EightBitMime);
+                // no fixed maximum is advertised as a bare `SIZE`
+                capabilities.push(Extension::Size(match self.server.limits.message {
+                    usize::MAX => 0,
+                    message => message,
+                }));
+                if self.server.chunking {
+                    capabilities.push(Extension::Chunking);
+                }
+                capabilities.push(Extension::EnhancedStatusCodes);
+                capabilities
+                    .reply()
+                    .expect("the hostname is a valid reply line")
+                    .to_bytes_into(&mut self.out);
+            }
+            Command::Help(topic) => self
+                .session
+                .help(topic.as_deref())
+                .to_bytes_into(&mut self.out),
+            Command::Mail(_) => self.line(&[b"250 2.1.0 OK"]),
+            Command::Rcpt(_) => self.line(&[b"250 2.1.5 OK"]),
+            Command::Data(payload) => {
+                self.sink.push(payload.clone());
+                self.sink.commit();
+                self.line(&[b"250 2.0.0 OK: queued"]);
+            }
+            Command::Bdat(bdat) => {
+                self.sink.push(bdat.payload.clone());
+                self.pending = !bdat.last;
+                if bdat.last {
+                    self.sink.commit();
+                    self.line(&[b"250 2.0.0 OK: queued"]);
+                } else {
+                    let mut size = itoa::Buffer::new();
+                    self.line(&[
+                        b"250 2.0.0 ",
+                        size.format(bdat.size).as_bytes(),
+                        b" octets received",
+                    ]);
+                }
+            }
+            _ => self.line(&[b"250 2.0.0 OK"]),
+        }
+    }
+
+    /// Reply to a command that could not be parsed. The transaction, and any `BDAT` content pushed
+    /// for it, carries on.
+    fn error(&mut self, error: &Error) {
+        let code: &[u8] = match error {
+            Error::InvalidCommand | Error::Empty | Error::TooLong => b"500 5.5.2 ",
+            Error::CommandNotImplemented => b"502 5.5.1 ",
+            Error::ParameterNotImplemented => b"504 5.5.4 ",
+            _ => b"501 5.5.4 ",
+        };
+        self.line(&[code, error.to_string().as_bytes()]);
+    }
+
+    fn violation(&mut self, violation: Violation) {
+        self.line(&[violation.to_string().as_bytes()]);
+    }
+
+    /// Discard `BDAT` content pushed for an abandoned transaction.
+    fn abort(&mut self) {
+        if self.pending {
+            self.sink.abort();
+            self.pending = false;
+        }
+    }
+
+    /// Append a reply line, adding the CRLF.
+    fn line(&mut self, parts: &[&[u8]]) {
+        for part in parts {
+            self.out.extend_from_slice(part);
+        }
+        self.out.extend_from_slice(b"\r\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    #[derive(Default)]
+    struct Spool {
+        chunks: Vec<Bytes>,
+        messages: Vec<Bytes>,
+    }
+
+    impl DataSink for Spool {
+        fn push(&mut self, chunk: Bytes) {
+            self.chunks.push(chunk);
+        }
+
+        fn commit(&mut self) {
+            self.messages.push(self.chunks.concat().into());
+            self.chunks.clear();
+        }
+
+        fn abort(&mut self) {
+            self.chunks.clear();
+        }
+    }
+
+    async fn converse(server: &Server, input: &'static [u8]) -> (String, Spool) {
+        let session = server.session(SocketAddr::from(([192, 0, 2, 1], 25)), None);
+        converse_session(server, session, input).await
+    }
+
+    async fn converse_session(
+        server: &Server,
+        session: Session,
+        input: &'static [u8],
+    ) -> (String, Spool) {
+        let (mut client, stream) = duplex(64 * 1024);
+        let (_closing, closed) = watch::channel(false);
+        let mut spool = Spool::default();
+
+        client.write_all(input).await.unwrap();
+        server
+            .connection(stream, session, &mut (), &mut spool, closed)
+            .await
+            .unwrap();
+
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        (output, spool)
+    }
+
+    fn server() -> Server {
+        Server::new(Domain::try_from(Bytes::from("mx.example.com")).unwrap()).with_chunking(true)
+    }
+
+    #[tokio::test]
+    async fn data() {
+        let (output, spool) = converse(
+            &server(),
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nDATA\r\nHi!\r\n.\r\nQUIT\r\n",
+        )
+        .await;
+
+        assert_eq!(
+            output,
+            "220 mx.example.com ESMTP\r\n\
+             250-mx.example.com\r\n250-PIPELINING\r\n250-8BITMIME\r\n250-SIZE\r\n\
+             250-CHUNKING\r\n250 ENHANCEDSTATUSCODES\r\n\
+             250 2.1.0 OK\r\n250 2.1.5 OK\r\n\
+             354 End data with <CR><LF>.<CR><LF>\r\n\
+             250 2.0.0 OK: queued\r\n\
+             221 2.0.0 mx.example.com Bye\r\n"
+        );
+        assert_eq!(spool.messages, [Bytes::from("Hi!")]);
+    }
+
+    #[tokio::test]
+    async fn help() {
+        let (output, _) = converse(
+            &server(),
+            b"HELP\r\nHELP mail\r\nHELP expn\r\nSTARTTLS\r\nQUIT\r\n",
+        )
+        .await;
+
+        assert_eq!(
+            output,
+            "220 mx.example.com ESMTP\r\n\
+             214-2.0.0 Commands supported:\r\n\
+             214 2.0.0 HELO EHLO MAIL RCPT DATA BDAT RSET VRFY HELP NOOP QUIT\r\n\
+             214 2.0.0 MAIL FROM:<reverse-path> [<parameters>]\r\n\
+             504 5.5.4 HELP topic unknown\r\n\
+             502 5.5.1 Command not implemented\r\n\
+             221 2.0.0 mx.example.com Bye\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_data() {
+        let (output, spool) = converse(
+            &server(),
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\nDATA\r\nQUIT\r\n",
+        )
+        .await;
+
+        assert!(output.ends_with(
+            "250 2.1.0 OK\r\n503 5.5.1 Need RCPT command\r\n221 2.0.0 mx.example.com Bye\r\n"
+        ));
+        assert!(spool.messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bdat_reset() {
+        let (output, spool) = converse(
+            &server(),
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3\r\nHi!RSET\r\nQUIT\r\n",
+        )
+        .await;
+
+        assert!(output.contains("250 2.0.0 3 octets received\r\n250 2.0.0 OK\r\n"));
+        assert!(spool.messages.is_empty());
+        assert!(spool.chunks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bdat_parse_error() {
+        let (output, spool) = converse(
+            &server(),
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3\r\nHi!FOO\r\nBDAT 3 LAST\r\nYo!QUIT\r\n",
+        )
+        .await;
+
+        assert!(output.contains("502 5.5.1 "));
+        assert_eq!(spool.messages, [Bytes::from("Hi!Yo!")]);
+    }
+
+    #[tokio::test]
+    async fn bdat_nested_mail() {
+        let (output, spool) = converse(
+            &server(),
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3\r\nHi!MAIL FROM:<carol@example.com>\r\n\
+              BDAT 3 LAST\r\nYo!QUIT\r\n",
+        )
+        .await;
+
+        assert!(output.contains("503 5.5.1 Nested MAIL command\r\n"));
+        assert_eq!(spool.messages, [Bytes::from("Hi!Yo!")]);
+    }
+
+    #[tokio::test]
+    async fn bdat_invalid_identity() {
+        let server = server();
+        let session = server
+            .session(SocketAddr::from(([192, 0, 2, 1], 25)), None)
+            .with_identity(Identity::REJECT_LITERAL);
+        let (output, spool) = converse_session(
+            &server,
+            session,
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3\r\nHi!EHLO [192.0.2.1]\r\n\
+              BDAT 3 LAST\r\nYo!QUIT\r\n",
+        )
+        .await;
+
+        assert!(output.contains("550 5.7.1 HELO/EHLO identity rejected\r\n"));
+        assert_eq!(spool.messages, [Bytes::from("Hi!Yo!")]);
+    }
+
+    #[tokio::test]
+    async fn bdat_implicit_reset() {
+        let server = server();
+        let session = server
+            .session(SocketAddr::from(([192, 0, 2, 1], 25)), None)
+            .with_implicit_reset(true);
+        let (_, spool) = converse_session(
+            &server,
+            session,
+            b"EHLO client.example.com\r\nMAIL FROM:<bob@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3\r\nHi!MAIL FROM:<carol@example.com>\r\n\
+              RCPT TO:<alice@example.com>\r\nBDAT 3 LAST\r\nYo!QUIT\r\n",
+        )
+        .await;
+
+        assert_eq!(spool.messages, [Bytes::from("Yo!")]);
+    }
+
+    #[tokio::test]
+    async fn shutdown() {
+        let (mut client, stream) = duplex(1024);
+        let (closing, closed) = watch::channel(false);
+        closing.send(true).unwrap();
+
+        let server = server();
+        let session = server.session(SocketAddr::from(([192, 0, 2, 1], 25)), None);
+        server
+            .connection(stream, session, &mut (), &mut Spool::default(), closed)
+            .await
+            .unwrap();
+
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        assert_eq!(
+            output,
+            "220 mx.example.com ESMTP\r\n421 4.3.2 mx.example.com Service shutting down\r\n"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+#![cfg(feature = "tower")]
+
+use core::convert::Infallible;
+use core::future::{Ready, ready};
+use core::task::{Context, Poll};
+
+use tower_service::Service;
+
+use crate::*;
+
+/// # Session Service
+///
+/// The server side of a connection as a [`tower_service::Service`] from [`Command`] to [`Reply`],
+/// so middleware from the `tower` ecosystem, e.g. for rate limiting, authentication, or logging,
+/// can wrap SMTP handling. Each command is sequenced by a [`Session`] and its [`Policy`] with
+/// [`Session::handle`], and answered with the refusal, or else the default reply.
+///
+/// The service holds no I/O: the caller sends the greeting, reads commands, e.g. with a
+/// [`Parser`], and writes the replies. The message content of `DATA` and `BDAT` is only seen by
+/// [`Policy::on_message`] and any wrapping layer. `STARTTLS` is answered with `220`, after which
+/// the caller performs the handshake and calls [`Session::tls_established`] on
+/// [`SessionService::session_mut`]. `AUTH` is not implemented, and is refused with `502` unless a
+/// layer answers it.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Capabilities, Command, Domain, Host, Session, SessionService, ToBytes};
+/// use tower_service::Service;
+///
+/// let capabilities = Capabilities::new(Bytes::from("mx.example.com"));
+/// let mut service = SessionService::new(Session::new(), (), &capabilities)?;
+///
+/// let ehlo = Command::Ehlo(Host::Domain(Domain::from_static("client.example.com")));
+/// let reply = service.call(ehlo).into_inner().unwrap();
+/// assert_eq!(reply.to_bytes(), "250 mx.example.com\r\n");
+///
+/// let reply = service.call(Command::Data(Bytes::from("Hi!"))).into_inner().unwrap();
+/// assert_eq!(reply.to_bytes(), "503 5.5.1 Need MAIL command\r\n");
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionService<P> {
+    session: Session,
+    policy: P,
+    helo: Reply,
+    ehlo: Reply,
+}
+
+impl<P: Policy> SessionService<P> {
+    /// Create a `SessionService` sequencing commands with `session` and `policy`, and advertising
+    /// `capabilities` in reply to `EHLO`.
+    ///
+    /// # Errors
+    ///
+    /// If the domain or an extension of `capabilities` is not a valid reply line, see
+    /// [`Capabilities::reply`].
+    pub fn new(
+        session: Session,
+        policy: P,
+        capabilities: &Capabilities,
+    ) -> Result<Self, ReplyError> {
+        Ok(Self {
+            session,
+            policy,
+            helo: Reply::new(250, capabilities.domain().clone())?,
+            ehlo: capabilities.reply()?,
+        })
+    }
+
+    /// The session.
+    #[must_use]
+    pub const fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The session, e.g. to call [`Session::tls_established`] or [`Session::authenticated`].
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+
+    /// The policy.
+    #[must_use]
+    pub const fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// The policy.
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Handle `command`, returning the reply. As [`Service::call`], without the future.
+    pub fn reply(&mut self, command: &Command) -> Reply {
+        if matches!(command, Command::Auth(_)) {
+            return Violation::Disabled.reply();
+        }
+
+        let content = matches!(command, Command::Data(_) | Command::Bdat(_));
+        if let Err(refusal) = self.session.handle(command, &mut self.policy) {
+            return refusal.reply(content);
+        }
+
+        let (code, text): (u16, &'static [u8]) = match command {
+            Command::Helo(_) => return self.helo.clone(),
+            Command::Ehlo(_) => return self.ehlo.clone(),
+            Command::Help(topic) => return self.session.help(topic.as_deref()),
+            Command::Bdat(bdat) if !bdat.last => {
+                let mut size = itoa::Buffer::new();
+                let text = [size.format(bdat.size).as_bytes(), b" octets received"].concat();
+                return Reply::new(250, Bytes::from(text))
+                    .and_then(|reply| reply.with_enhanced_code(EnhancedCode::OK))
+                    .expect("a size is a valid reply line");
+            }
+            Command::Mail(_) => (250, b"2.1.0 OK"),
+            Command::Rcpt(_) => (250, b"2.1.5 OK"),
+            Command::Data(_) | Command::Bdat(_) => (250, b"2.0.0 OK: queued"),
+            Command::Vrfy(_) => (252, b"2.5.0 Cannot VRFY user"),
+            Command::StartTls => (220, b"2.0.0 Ready to start TLS"),
+            Command::Quit => (221, b"2.0.0 Bye"),
+            _ => (250, b"2.0.0 OK"),
+        };
+        let mut reply =
+            Reply::new(code, Bytes::from_static(text)).expect("the default replies are valid");
+        reply.split_enhanced_code();
+        reply
+    }
+}
+
+impl<P: Policy> Service<Command> for SessionService<P> {
+    type Response = Reply;
+    type Error = Infallible;
+    type Future = Ready<Result<Reply, Infallible>>;
+
+    /// Always ready: each command is handled synchronously.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, command: Command) -> Self::Future {
+        ready(Ok(self.reply(&command)))
+    }
+}
+
+#[cfg(all(test, feature = "parse"))]
+mod tests {
+    use super::*;
+
+    struct NoData;
+
+    impl Policy for NoData {
+        fn on_data_start(&mut self, _connection: &ConnectionInfo) -> Decision {
+            Decision::TempFail(Some(Bytes::from("4.3.0 Queue full")))
+        }
+    }
+
+    fn service<P: Policy>(policy: P) -> SessionService<P> {
+        let mut capabilities = Capabilities::new(Bytes::from("mx.example.com"));
+        capabilities.push(Extension::Pipelining);
+        SessionService::new(Session::new(), policy, &capabilities).unwrap()
+    }
+
+    fn call<P: Policy>(service: &mut SessionService<P>, command: &'static str) -> Bytes {
+        let command = Command::try_from(Bytes::from(command)).unwrap();
+        service
+            .call(command)
+            .into_inner()
+            .unwrap()
+            .to_bytes()
+            .freeze()
+    }
+
+    #[test]
+    fn transaction() {
+        let mut service = service(());
+        assert_eq!(
+            call(&mut service, "EHLO client.example.com"),
+            "250-mx.example.com\r\n250 PIPELINING\r\n"
+        );
+        assert_eq!(
+            call(&mut service, "MAIL FROM:<bob@example.com>"),
+            "250 2.1.0 OK\r\n"
+        );
+        assert_eq!(
+            call(&mut service, "RCPT TO:<alice@example.com>"),
+            "250 2.1.5 OK\r\n"
+        );
+        let data = Command::Data(Bytes::from("Hi!"));
+        assert_eq!(
+            service.call(data).into_inner().unwrap().to_bytes(),
+            "250 2.0.0 OK: queued\r\n"
+        );
+        assert_eq!(service.session().counters().messages, 1);
+        assert_eq!(call(&mut service, "QUIT"), "221 2.0.0 Bye\r\n");
+    }
+
+    #[test]
+    fn refused() {
+        let mut service = service(NoData);
+        assert_eq!(
+            call(&mut service, "MAIL FROM:<bob@example.com>"),
+            "503 5.5.1 Send HELO/EHLO first\r\n"
+        );
+        assert_eq!(
+            call(&mut service, "HELO client.example.com"),
+            "250 mx.example.com\r\n"
+        );
+        call(&mut service, "MAIL FROM:<bob@example.com>");
+        call(&mut service, "RCPT TO:<alice@example.com>");
+        let data = Command::Data(Bytes::from("Hi!"));
+        assert_eq!(
+            service.call(data).into_inner().unwrap().to_bytes(),
+            "451 4.3.0 Queue full\r\n"
+        );
+        assert!(!service.session().in_transaction());
+    }
+}
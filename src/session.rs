@@ -0,0 +1,1641 @@
+use alloc::{string::ToString, vec, vec::Vec};
+use core::net::SocketAddr;
+
+use derive_more::Display;
+
+use crate::*;
+use mail::Body;
+
+bitflags::bitflags! {
+    /// # `HELO`/`EHLO` Identity Policy
+    ///
+    /// Opt-in checks on the identity a client claims. The default accepts any valid identity.
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+    pub struct Identity: u8 {
+        /// Reject address literals, e.g. `EHLO [192.0.2.1]`.
+        const REJECT_LITERAL = 0b0001;
+        /// Reject an IP address literal that does not match the peer's address.
+        const REJECT_MISMATCH = 0b0010;
+        /// Reject the server's own hostname.
+        const REJECT_SELF = 0b0100;
+    }
+}
+
+bitflags::bitflags! {
+    /// # Enabled Commands
+    ///
+    /// The commands a [`Session`] accepts; any other is refused with [`Violation::Disabled`] and
+    /// left out of [`Session::help`]. The default enables them all.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+    pub struct Verbs: u16 {
+        const HELO = 1 << 0;
+        const EHLO = 1 << 1;
+        const MAIL = 1 << 2;
+        const RCPT = 1 << 3;
+        const DATA = 1 << 4;
+        const BDAT = 1 << 5;
+        const RSET = 1 << 6;
+        const VRFY = 1 << 7;
+        const EXPN = 1 << 8;
+        const HELP = 1 << 9;
+        const NOOP = 1 << 10;
+        const QUIT = 1 << 11;
+        const STARTTLS = 1 << 12;
+        const AUTH = 1 << 13;
+    }
+}
+
+impl Default for Verbs {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Verbs {
+    /// The verb of `command`.
+    #[must_use]
+    pub const fn of(command: &Command) -> Self {
+        match command {
+            Command::Helo(_) => Self::HELO,
+            Command::Ehlo(_) => Self::EHLO,
+            Command::Mail(_) => Self::MAIL,
+            Command::Rcpt(_) => Self::RCPT,
+            Command::Data(_) => Self::DATA,
+            Command::Bdat(_) => Self::BDAT,
+            Command::Rset => Self::RSET,
+            Command::Vrfy(_) => Self::VRFY,
+            Command::Expn(_) => Self::EXPN,
+            Command::Help(_) => Self::HELP,
+            Command::Noop => Self::NOOP,
+            Command::Quit => Self::QUIT,
+            Command::StartTls => Self::STARTTLS,
+            Command::Auth(_) => Self::AUTH,
+        }
+    }
+
+    /// The syntax of a single verb, for `HELP <verb>`.
+    fn syntax(self) -> &'static [u8] {
+        const SYNTAX: [&[u8]; 14] = [
+            b"HELO <domain>",
+            b"EHLO <domain>",
+            b"MAIL FROM:<reverse-path> [<parameters>]",
+            b"RCPT TO:<forward-path> [<parameters>]",
+            b"DATA",
+            b"BDAT <size> [LAST]",
+            b"RSET",
+            b"VRFY <string>",
+            b"EXPN <string>",
+            b"HELP [<verb>]",
+            b"NOOP",
+            b"QUIT",
+            b"STARTTLS",
+            b"AUTH <mechanism> [<initial-response>]",
+        ];
+
+        SYNTAX[self.bits().trailing_zeros() as usize]
+    }
+}
+
+/// # Sequence Violation
+///
+/// A command sent out of order, or exceeding the session's [`Limits`]. Displays as the reply a
+/// server should send instead, **excluding** the trailing CRLF.
+#[non_exhaustive]
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Violation {
+    /// `MAIL` or `AUTH` before `HELO`/`EHLO`.
+    #[display("503 5.5.1 Send HELO/EHLO first")]
+    NoHelo,
+    /// `RCPT`, `DATA`, or `BDAT` before `MAIL`.
+    #[display("503 5.5.1 Need MAIL command")]
+    NoMail,
+    /// `DATA` or `BDAT` without any accepted recipients.
+    #[display("503 5.5.1 Need RCPT command")]
+    NoRcpt,
+    /// `MAIL` while a transaction is already open, unless the session resets it implicitly; see
+    /// [`Session::with_implicit_reset`].
+    #[display("503 5.5.1 Nested MAIL command")]
+    NestedMail,
+    /// `STARTTLS` while a transaction is open.
+    #[display("503 5.5.1 STARTTLS not permitted during a mail transaction")]
+    StartTlsInTransaction,
+    /// `STARTTLS` after TLS has been established.
+    #[display("503 5.5.1 TLS already active")]
+    TlsActive,
+    /// `DATA` after `BDAT` in the same transaction.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3030#section-2>
+    #[display("503 5.5.1 DATA not permitted after BDAT")]
+    DataAfterBdat,
+    /// `DATA` in a `BODY=BINARYMIME` transaction.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3030#section-3>
+    #[display("503 5.5.1 DATA not permitted with BODY=BINARYMIME")]
+    DataWithBinaryMime,
+    /// `MAIL` with `BODY=BINARYMIME` when `CHUNKING` is not available.
+    #[display("555 5.5.4 BODY=BINARYMIME requires CHUNKING")]
+    BinaryMimeWithoutChunking,
+    /// `MAIL` with `PRDR` when `PRDR` is not advertised; see [`Session::with_prdr`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/draft-hall-prdr-00#section-4>
+    #[display("555 5.5.4 PRDR not supported")]
+    PrdrUnavailable,
+    /// `DATA` with a header block that is not well-formed UTF-8 in a `SMTPUTF8` transaction; see
+    /// [`Session::with_utf8_check`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6532#section-3.2>
+    #[display("554 5.6.0 Malformed UTF-8 in message header")]
+    InvalidUtf8,
+    /// More `RCPT` commands than [`Limits::recipients`].
+    ///
+    /// The client should retry the remaining recipients in another transaction.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.5.3.1.10>
+    #[display("452 4.5.3 Too many recipients")]
+    TooManyRecipients,
+    /// More `RCPT` commands than [`Limits::null_sender_recipients`] with a null reverse path.
+    #[display("550 5.5.3 Too many recipients for null sender")]
+    TooManyNullSenderRecipients,
+    /// More `BDAT` chunks than [`Limits::chunks`].
+    #[display("552 5.3.4 Too many BDAT chunks")]
+    TooManyChunks,
+    /// A `MAIL` declaring a `SIZE`, or a message, larger than [`Limits::message`], or `BDAT`
+    /// chunks adding up to more than the declared `SIZE`.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870#section-6.1>
+    #[display("552 5.3.4 Message size exceeds fixed maximum message size")]
+    MessageTooBig,
+    /// A `BDAT` chunk, other than the last, smaller than [`Limits::min_chunk`].
+    #[display("554 5.5.0 BDAT chunk too small")]
+    ChunkTooSmall,
+    /// `AUTH` while a transaction is open.
+    #[display("503 5.5.1 AUTH not permitted during a mail transaction")]
+    AuthInTransaction,
+    /// `HELO`/`EHLO` with an identity refused by the session's [`Identity`] policy.
+    #[display("550 5.7.1 HELO/EHLO identity rejected")]
+    InvalidIdentity,
+    /// A command other than `QUIT` outside a transaction, after [`Session::begin_shutdown`].
+    #[display("421 4.3.2 Service shutting down")]
+    ShuttingDown,
+    /// A command beyond one of the per-connection [`Limits`]: `MAIL` after
+    /// [`Limits::transactions_per_connection`] or [`Limits::messages_per_connection`], `RCPT`
+    /// after [`Limits::recipients_per_connection`], or any command but `QUIT` after
+    /// [`Limits::commands_per_connection`].
+    ///
+    /// The server should close the connection; the client may reconnect to carry on.
+    #[display("421 4.7.0 Too much mail on this connection, please reconnect")]
+    ConnectionLimit,
+    /// A command other than `EHLO`, `STARTTLS`, `NOOP`, or `QUIT` before TLS, when TLS is required.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207#section-4>
+    #[display("530 5.7.0 Must issue a STARTTLS command first")]
+    TlsRequired,
+    /// A command not enabled by [`Session::with_verbs`].
+    #[display("502 5.5.1 Command not implemented")]
+    Disabled,
+}
+
+impl Violation {
+    /// The recommended reply code.
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::TlsRequired => 530,
+            Self::Disabled => 502,
+            Self::ShuttingDown | Self::ConnectionLimit => 421,
+            Self::InvalidIdentity | Self::TooManyNullSenderRecipients => 550,
+            Self::BinaryMimeWithoutChunking | Self::PrdrUnavailable => 555,
+            Self::TooManyRecipients => 452,
+            Self::TooManyChunks | Self::MessageTooBig => 552,
+            Self::ChunkTooSmall | Self::InvalidUtf8 => 554,
+            _ => 503,
+        }
+    }
+
+    /// The reply a server should send, as displayed.
+    #[must_use]
+    pub fn reply(self) -> Reply {
+        let line = self.to_string();
+        let mut reply = Reply::new(self.code(), Bytes::copy_from_slice(&line.as_bytes()[4..]))
+            .expect("every violation displays as a valid reply");
+        reply.split_enhanced_code();
+        reply
+    }
+
+    /// The `snake_case` name of the violation, e.g. `no_helo`, for logs and metrics labels.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::NoHelo => "no_helo",
+            Self::NoMail => "no_mail",
+            Self::NoRcpt => "no_rcpt",
+            Self::NestedMail => "nested_mail",
+            Self::StartTlsInTransaction => "starttls_in_transaction",
+            Self::TlsActive => "tls_active",
+            Self::DataAfterBdat => "data_after_bdat",
+            Self::DataWithBinaryMime => "data_with_binary_mime",
+            Self::BinaryMimeWithoutChunking => "binary_mime_without_chunking",
+            Self::PrdrUnavailable => "prdr_unavailable",
+            Self::InvalidUtf8 => "invalid_utf8",
+            Self::TooManyRecipients => "too_many_recipients",
+            Self::TooManyNullSenderRecipients => "too_many_null_sender_recipients",
+            Self::TooManyChunks => "too_many_chunks",
+            Self::MessageTooBig => "message_too_big",
+            Self::ChunkTooSmall => "chunk_too_small",
+            Self::AuthInTransaction => "auth_in_transaction",
+            Self::InvalidIdentity => "invalid_identity",
+            Self::ShuttingDown => "shutting_down",
+            Self::ConnectionLimit => "connection_limit",
+            Self::TlsRequired => "tls_required",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+/// # Per-Connection Counters
+///
+/// Totals over the life of a [`Session`], checked against the per-connection [`Limits`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Counters {
+    /// Mail transactions ended by a message, whether or not it was accepted.
+    pub transactions: usize,
+    /// Messages accepted.
+    pub messages: usize,
+    /// Recipients accepted.
+    pub recipients: usize,
+    /// Commands accepted.
+    pub commands: usize,
+}
+
+/// # Connection Metadata
+///
+/// What is known about the connection of a [`Session`], passed to each [`Policy`] callback so
+/// decisions can take it into account without a side table keyed by socket.
+#[derive(derive_more::Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectionInfo {
+    /// The client's address.
+    pub peer: Option<SocketAddr>,
+    /// The server's address the client connected to.
+    pub local: Option<SocketAddr>,
+    /// Whether TLS has been established.
+    pub tls: bool,
+    /// The server name the client requested in the TLS handshake, if supplied.
+    pub sni: Option<Bytes>,
+    /// The application protocol negotiated in the TLS handshake, if supplied.
+    pub alpn: Option<Bytes>,
+    /// The identity the client authenticated as.
+    #[debug("{:?}", authenticated.as_ref().map(|a| Redact::bytes(a)))]
+    pub authenticated: Option<Bytes>,
+}
+
+/// # Server Session State Machine
+///
+/// Tracks the command sequence of a server connection and enforces the ordering rules of
+/// [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.4). Use
+/// [`Session::check`] before acting on a command, and [`Session::accept`] once the server has
+/// accepted it, so that rejected commands (e.g. a `RCPT` refused by policy) do not advance the
+/// state.
+#[derive(Debug, Default, Clone)]
+pub struct Session {
+    greeted: bool,
+    connection: ConnectionInfo,
+    require_tls: bool,
+    chunking: bool,
+    prdr: bool,
+    utf8_check: bool,
+    limits: Limits,
+    transaction: Option<Transaction>,
+    implicit_reset: bool,
+    reset: bool,
+    shutdown: bool,
+    greeting: bool,
+    early_talker: bool,
+    identity: Identity,
+    hostname: Option<Domain>,
+    counters: Counters,
+    verbs: Verbs,
+    /// Accepted recipients of the last message, if it was sent with `PRDR`.
+    prdr_recipients: Option<Vec<Email>>,
+}
+
+/// # `Session` Mail Transaction
+#[derive(Debug, Default, Clone)]
+struct Transaction {
+    rcpts: usize,
+    null_sender: bool,
+    chunks: usize,
+    binary: bool,
+    /// `SMTPUTF8`, and not `BODY=BINARYMIME`.
+    utf8: bool,
+    /// Declared with `SIZE`.
+    declared: Option<usize>,
+    /// Received so far in `BDAT` chunks.
+    received: usize,
+    /// Accepted recipients, tracked only with `PRDR`.
+    prdr: Option<Vec<Email>>,
+}
+
+impl Transaction {
+    /// Whether `more` bytes would exceed the declared `SIZE` or `limit`.
+    fn too_big(&self, more: usize, limit: usize) -> bool {
+        let total = self.received.saturating_add(more);
+        total > limit || self.declared.is_some_and(|declared| total > declared)
+    }
+}
+
+impl Session {
+    /// Create a `Session` awaiting `HELO`/`EHLO`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `STARTTLS` before any command other than `EHLO`, `STARTTLS`, `NOOP`, or `QUIT`.
+    #[must_use]
+    pub fn with_require_tls(mut self, require_tls: bool) -> Self {
+        self.require_tls = require_tls;
+        self
+    }
+
+    /// Start with TLS already established, for implicit TLS (SMTPS, usually port 465), so
+    /// `STARTTLS` is refused.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc8314#section-3>
+    #[must_use]
+    pub fn with_implicit_tls(mut self, implicit_tls: bool) -> Self {
+        self.connection.tls = implicit_tls;
+        self
+    }
+
+    /// Set whether `CHUNKING` is advertised, which `BODY=BINARYMIME` requires.
+    #[must_use]
+    pub fn with_chunking(mut self, chunking: bool) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Set whether `PRDR` is advertised, which `MAIL ... PRDR` requires. See
+    /// [`Session::prdr_replies`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/draft-hall-prdr-00>
+    #[must_use]
+    pub fn with_prdr(mut self, prdr: bool) -> Self {
+        self.prdr = prdr;
+        self
+    }
+
+    /// Refuse `DATA` with [`Violation::InvalidUtf8`] if its header block is not well-formed UTF-8,
+    /// in `SMTPUTF8` transactions other than `BODY=BINARYMIME`; see [`message::invalid_utf8`].
+    ///
+    /// `BDAT` chunks are not checked, as the header block may span several.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn with_utf8_check(mut self, utf8_check: bool) -> Self {
+        self.utf8_check = utf8_check;
+        self
+    }
+
+    /// Reset an open transaction on `MAIL`, discarding its envelope, instead of refusing it with
+    /// [`Violation::NestedMail`], as some MTAs do for clients that omit `RSET`. See
+    /// [`Session::implicitly_reset`].
+    #[must_use]
+    pub fn with_implicit_reset(mut self, implicit_reset: bool) -> Self {
+        self.implicit_reset = implicit_reset;
+        self
+    }
+
+    /// Enforce `limits` instead of the defaults.
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Check `HELO`/`EHLO` identities against `identity`.
+    #[must_use]
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Accept only the commands in `verbs`, e.g. without [`Verbs::EXPN`] for a server that does
+    /// not implement it.
+    #[must_use]
+    pub fn with_verbs(mut self, verbs: Verbs) -> Self {
+        self.verbs = verbs;
+        self
+    }
+
+    /// Set the server's own hostname, for [`Identity::REJECT_SELF`].
+    #[must_use]
+    pub fn with_hostname(mut self, hostname: Domain) -> Self {
+        self.hostname = Some(hostname);
+        self
+    }
+
+    /// Set the peer's address, for [`Identity::REJECT_MISMATCH`].
+    #[must_use]
+    pub fn with_peer(mut self, peer: SocketAddr) -> Self {
+        self.connection.peer = Some(peer);
+        self
+    }
+
+    /// Set the server's address the client connected to.
+    #[must_use]
+    pub fn with_local(mut self, local: SocketAddr) -> Self {
+        self.connection.local = Some(local);
+        self
+    }
+
+    /// What is known about the connection.
+    #[must_use]
+    pub fn connection(&self) -> &ConnectionInfo {
+        &self.connection
+    }
+
+    /// Record that the client authenticated as `identity`.
+    pub fn authenticated(&mut self, identity: Bytes) {
+        self.connection.authenticated = Some(identity);
+    }
+
+    /// The commands the session accepts.
+    #[must_use]
+    pub fn verbs(&self) -> Verbs {
+        self.verbs
+    }
+
+    /// The `214` reply to `HELP`, listing the enabled commands, or the syntax of `topic` if it is
+    /// one of them. An unknown or disabled `topic` gets a `504`.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.8>
+    #[must_use]
+    pub fn help(&self, topic: Option<&[u8]>) -> Reply {
+        let (code, enhanced, lines) = match topic {
+            None => {
+                let mut verbs = Vec::new();
+                for (name, _) in self.verbs.iter_names() {
+                    if !verbs.is_empty() {
+                        verbs.push(b' ');
+                    }
+                    verbs.extend_from_slice(name.as_bytes());
+                }
+                let lines = vec![
+                    Bytes::from_static(b"Commands supported:"),
+                    Bytes::from(verbs),
+                ];
+                (214, EnhancedCode::OK, lines)
+            }
+            Some(topic) => match self
+                .verbs
+                .iter_names()
+                .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(topic))
+            {
+                Some((_, verb)) => (
+                    214,
+                    EnhancedCode::OK,
+                    vec![Bytes::from_static(verb.syntax())],
+                ),
+                None => (
+                    504,
+                    EnhancedCode::new(5, 5, 4).expect("valid enhanced status code"),
+                    vec![Bytes::from_static(b"HELP topic unknown")],
+                ),
+            },
+        };
+
+        Reply::multiline(code, lines)
+            .and_then(|reply| reply.with_enhanced_code(enhanced))
+            .expect("HELP replies are valid")
+    }
+
+    /// Whether a mail transaction is open.
+    #[must_use]
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Number of recipients accepted in the open transaction.
+    #[must_use]
+    pub fn recipients(&self) -> usize {
+        self.transaction.as_ref().map_or(0, |t| t.rcpts)
+    }
+
+    /// The recipients accepted in the transaction of the message just accepted, in `RCPT` order,
+    /// if it was sent with `PRDR`, so the server owes a reply for each; see
+    /// [`Session::prdr_replies`].
+    #[must_use]
+    pub fn prdr_recipients(&self) -> Option<&[Email]> {
+        self.prdr_recipients.as_deref()
+    }
+
+    /// The reply sequence after the final `.` (or `BDAT ... LAST`) of a message sent with `PRDR`:
+    /// `353`, then the reply `decide` returns for each accepted recipient in `RCPT` order, then the
+    /// message reply, which is `250` if any recipient got a positive completion reply, otherwise
+    /// `550`. Returns `None` unless [`Session::prdr_recipients`] has recipients pending.
+    ///
+    /// A message refused as a whole by [`Policy::on_message`] gets the single refusal instead.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/draft-hall-prdr-00#section-4>
+    pub fn prdr_replies(&mut self, mut decide: impl FnMut(&Email) -> Reply) -> Option<Vec<Reply>> {
+        let recipients = self.prdr_recipients.take()?;
+        let reply = |code, text: &'static [u8]| {
+            let mut reply =
+                Reply::new(code, Bytes::from_static(text)).expect("PRDR replies are valid");
+            reply.split_enhanced_code();
+            reply
+        };
+
+        let mut replies = Vec::with_capacity(recipients.len() + 2);
+        replies.push(reply(353, b"Content analysis has started"));
+        replies.extend(recipients.iter().map(&mut decide));
+        let accepted = replies[1..].iter().any(Reply::is_positive_completion);
+        replies.push(if accepted {
+            reply(250, b"2.0.0 OK: queued")
+        } else {
+            reply(550, b"5.7.1 No recipients accepted the message")
+        });
+        Some(replies)
+    }
+
+    /// Whether the last accepted command was a `MAIL` that reset an open transaction, so the
+    /// server can discard the envelope collected so far.
+    #[must_use]
+    pub fn implicitly_reset(&self) -> bool {
+        self.reset
+    }
+
+    /// Totals over the life of the session.
+    #[must_use]
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Bytes of message content received in `BDAT` chunks of the open transaction, for progress
+    /// and quota checks.
+    #[must_use]
+    pub fn message_size(&self) -> usize {
+        self.transaction.as_ref().map_or(0, |t| t.received)
+    }
+
+    /// Whether TLS has been established.
+    #[must_use]
+    pub fn is_tls(&self) -> bool {
+        self.connection.tls
+    }
+
+    /// Record that the server's `220` greeting has been sent.
+    pub fn greeting_sent(&mut self) {
+        self.greeting = true;
+    }
+
+    /// Record that bytes were received from the client.
+    ///
+    /// If the greeting has not been sent yet, the client is flagged as an early talker; see
+    /// [`Session::is_early_talker`].
+    pub fn received(&mut self) {
+        if !self.greeting {
+            self.early_talker = true;
+        }
+    }
+
+    /// Whether the client sent bytes before the server's greeting, which is commonly used to
+    /// reject or tarpit spam bots. The flag is sticky for the life of the session.
+    #[must_use]
+    pub fn is_early_talker(&self) -> bool {
+        self.early_talker
+    }
+
+    /// Begin shutting down the service.
+    ///
+    /// An open transaction may complete, after which every command but `QUIT` is refused with
+    /// [`Violation::ShuttingDown`] and the connection should be closed; see
+    /// [`Session::is_closing`].
+    pub fn begin_shutdown(&mut self) {
+        self.shutdown = true;
+    }
+
+    /// Whether the service is shutting down and no transaction is open, so the server should send
+    /// `421` and close the connection.
+    #[must_use]
+    pub fn is_closing(&self) -> bool {
+        self.shutdown && self.transaction.is_none()
+    }
+
+    /// Record that the TLS handshake following `STARTTLS` has completed, with the `sni` and
+    /// `alpn` of the handshake if the TLS layer supplies them.
+    ///
+    /// Everything learned from the client before the handshake is discarded, including any
+    /// authenticated identity, and the client must send `EHLO` again.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207#section-4.2>
+    pub fn tls_established(&mut self, sni: Option<Bytes>, alpn: Option<Bytes>) {
+        self.connection.tls = true;
+        self.connection.sni = sni;
+        self.connection.alpn = alpn;
+        self.connection.authenticated = None;
+        self.greeted = false;
+        self.transaction = None;
+        self.reset = false;
+        self.prdr_recipients = None;
+    }
+
+    /// Check whether `command` is permitted in the current state.
+    pub fn check(&self, command: &Command) -> Result<(), Violation> {
+        self.check_inner(command)
+            .inspect_err(|&v| observe::violation(v))
+    }
+
+    fn check_inner(&self, command: &Command) -> Result<(), Violation> {
+        if self.is_closing() && !matches!(command, Command::Quit) {
+            return Err(Violation::ShuttingDown);
+        }
+
+        if !matches!(command, Command::Quit)
+            && self.counters.commands >= self.limits.commands_per_connection
+        {
+            return Err(Violation::ConnectionLimit);
+        }
+
+        if !self.verbs.contains(Verbs::of(command)) {
+            return Err(Violation::Disabled);
+        }
+
+        if self.require_tls
+            && !self.connection.tls
+            && !matches!(
+                command,
+                Command::Ehlo(_) | Command::StartTls | Command::Noop | Command::Quit
+            )
+        {
+            return Err(Violation::TlsRequired);
+        }
+
+        match (command, &self.transaction) {
+            (Command::Helo(host) | Command::Ehlo(host), _) if !self.valid_identity(host) => {
+                Err(Violation::InvalidIdentity)
+            }
+            (Command::Mail(_) | Command::Auth(_), _) if !self.greeted => Err(Violation::NoHelo),
+            (Command::Mail(_), Some(_)) if !self.implicit_reset => Err(Violation::NestedMail),
+            (Command::Mail(_), _)
+                if self.counters.transactions >= self.limits.transactions_per_connection
+                    || self.counters.messages >= self.limits.messages_per_connection =>
+            {
+                Err(Violation::ConnectionLimit)
+            }
+            (Command::Mail(mail), _) if mail.body == Some(Body::BinaryMime) && !self.chunking => {
+                Err(Violation::BinaryMimeWithoutChunking)
+            }
+            (Command::Mail(mail), _) if mail.prdr && !self.prdr => Err(Violation::PrdrUnavailable),
+            (Command::Mail(mail), _)
+                if mail.size.is_some_and(|size| size > self.limits.message) =>
+            {
+                Err(Violation::MessageTooBig)
+            }
+            (Command::Rcpt(_) | Command::Data(_) | Command::Bdat(_), None) => {
+                Err(Violation::NoMail)
+            }
+            (Command::Rcpt(_), Some(_))
+                if self.counters.recipients >= self.limits.recipients_per_connection =>
+            {
+                Err(Violation::ConnectionLimit)
+            }
+            (Command::Rcpt(_), Some(t)) if t.rcpts >= self.limits.recipients => {
+                Err(Violation::TooManyRecipients)
+            }
+            (Command::Rcpt(_), Some(t))
+                if t.null_sender && t.rcpts >= self.limits.null_sender_recipients =>
+            {
+                Err(Violation::TooManyNullSenderRecipients)
+            }
+            (Command::Data(_) | Command::Bdat(_), Some(t)) if t.rcpts == 0 => {
+                Err(Violation::NoRcpt)
+            }
+            (Command::Data(_), Some(t)) if t.chunks > 0 => Err(Violation::DataAfterBdat),
+            (Command::Bdat(_), Some(t)) if t.chunks >= self.limits.chunks => {
+                Err(Violation::TooManyChunks)
+            }
+            (Command::Bdat(bdat), Some(_)) if !bdat.last && bdat.size < self.limits.min_chunk => {
+                Err(Violation::ChunkTooSmall)
+            }
+            (Command::Data(_), Some(t)) if t.binary => Err(Violation::DataWithBinaryMime),
+            (Command::Data(payload), Some(t)) if t.too_big(payload.len(), self.limits.message) => {
+                Err(Violation::MessageTooBig)
+            }
+            #[cfg(feature = "parse")]
+            (Command::Data(payload), Some(t))
+                if self.utf8_check && t.utf8 && message::invalid_utf8(payload).is_some() =>
+            {
+                Err(Violation::InvalidUtf8)
+            }
+            (Command::Bdat(bdat), Some(t)) if t.too_big(bdat.size, self.limits.message) => {
+                Err(Violation::MessageTooBig)
+            }
+            (Command::StartTls, Some(_)) => Err(Violation::StartTlsInTransaction),
+            (Command::StartTls, None) if self.connection.tls => Err(Violation::TlsActive),
+            (Command::Auth(_), Some(_)) => Err(Violation::AuthInTransaction),
+            _ => Ok(()),
+        }
+    }
+
+    fn valid_identity(&self, host: &Host) -> bool {
+        match host {
+            Host::Ip(_) | Host::Address(_) if self.identity.contains(Identity::REJECT_LITERAL) => {
+                false
+            }
+            Host::Ip(ip) if self.identity.contains(Identity::REJECT_MISMATCH) => {
+                self.connection.peer.is_none_or(|peer| peer.ip() == *ip)
+            }
+            Host::Domain(domain) if self.identity.contains(Identity::REJECT_SELF) => !self
+                .hostname
+                .as_ref()
+                .is_some_and(|hostname| hostname.bytes().eq_ignore_ascii_case(domain.bytes())),
+            _ => true,
+        }
+    }
+
+    /// [`Session::check`] `command`, consult `policy`, and [`Session::accept`] it if neither
+    /// refused it.
+    ///
+    /// A refused `DATA` or `BDAT` ends the transaction, since the content has been sent anyway.
+    pub fn handle<P: Policy + ?Sized>(
+        &mut self,
+        command: &Command,
+        policy: &mut P,
+    ) -> Result<(), Refusal> {
+        self.check(command).map_err(Refusal::Violation)?;
+
+        let first_chunk = self.transaction.as_ref().is_some_and(|t| t.chunks == 0);
+        let connection = &self.connection;
+        let decision = match command {
+            Command::Helo(host) | Command::Ehlo(host) => policy.on_ehlo(connection, host),
+            Command::Mail(mail) => policy.on_mail(connection, mail),
+            Command::Rcpt(rcpt) => policy.on_rcpt(connection, rcpt),
+            Command::Data(payload) => match policy.on_data_start(connection) {
+                Decision::Accept => policy.on_message(connection, payload, true),
+                refused => refused,
+            },
+            Command::Bdat(bdat) => {
+                let start = if first_chunk {
+                    policy.on_data_start(connection)
+                } else {
+                    Decision::Accept
+                };
+                match start {
+                    Decision::Accept => policy.on_message(connection, &bdat.payload, bdat.last),
+                    refused => refused,
+                }
+            }
+            _ => Decision::Accept,
+        };
+
+        if decision.is_accept() {
+            self.accept(command);
+            return Ok(());
+        }
+
+        if matches!(command, Command::Data(_) | Command::Bdat(_)) {
+            self.end_transaction(false);
+        }
+
+        Err(Refusal::Policy(decision))
+    }
+
+    /// Advance the state after the server accepted `command`.
+    ///
+    /// `command` should have passed [`Session::check`].
+    pub fn accept(&mut self, command: &Command) {
+        self.reset = matches!(command, Command::Mail(_)) && self.transaction.is_some();
+        self.prdr_recipients = None;
+        self.counters.commands += 1;
+        match command {
+            Command::Helo(_) | Command::Ehlo(_) => {
+                self.greeted = true;
+                self.transaction = None;
+            }
+            Command::Mail(mail) => {
+                self.transaction = Some(Transaction {
+                    null_sender: matches!(mail.from, mail::ReversePath::Null),
+                    binary: mail.body == Some(Body::BinaryMime),
+                    utf8: mail.smtputf8 && mail.body != Some(Body::BinaryMime),
+                    declared: mail.size,
+                    prdr: mail.prdr.then(Vec::new),
+                    ..Transaction::default()
+                });
+            }
+            Command::Rcpt(rcpt) => {
+                if let Some(t) = &mut self.transaction {
+                    t.rcpts += 1;
+                    self.counters.recipients += 1;
+                    if let Some(recipients) = &mut t.prdr {
+                        recipients.push(rcpt.to.clone());
+                    }
+                }
+            }
+            Command::Data(_) => {
+                self.prdr_recipients = self.transaction.as_mut().and_then(|t| t.prdr.take());
+                self.end_transaction(true);
+            }
+            Command::Bdat(bdat) if bdat.last => {
+                self.prdr_recipients = self.transaction.as_mut().and_then(|t| t.prdr.take());
+                self.end_transaction(true);
+            }
+            Command::Rset => self.transaction = None,
+            Command::Bdat(bdat) => {
+                if let Some(t) = &mut self.transaction {
+                    t.chunks += 1;
+                    t.received = t.received.saturating_add(bdat.size);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// End the open transaction with a message, counting it.
+    fn end_transaction(&mut self, accepted: bool) {
+        if self.transaction.take().is_some() {
+            self.counters.transactions += 1;
+            self.counters.messages += usize::from(accepted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail::{Mail, ReversePath};
+    use rcpt::Rcpt;
+    use rstest::rstest;
+
+    fn email(s: &'static str) -> Email {
+        unsafe { Email::new_unchecked(Bytes::from(s)) }
+    }
+
+    fn ehlo() -> Command {
+        Command::Ehlo(Host::Domain(unsafe {
+            Domain::new_unchecked(Bytes::from("example.com"))
+        }))
+    }
+
+    fn mail() -> Command {
+        mail_body(None)
+    }
+
+    fn mail_body(body: Option<Body>) -> Command {
+        Command::Mail(Box::new(Mail {
+            size: None,
+            ret: None,
+            envid: None,
+            auth: None,
+            body,
+            conperm: None,
+            solicit: None,
+            prdr: false,
+            smtputf8: false,
+            raw_parameters: None,
+            from: ReversePath::Email(email("bob@example.com")),
+        }))
+    }
+
+    fn mail_prdr() -> Command {
+        let Command::Mail(mut mail) = mail() else {
+            unreachable!()
+        };
+        mail.prdr = true;
+        Command::Mail(mail)
+    }
+
+    #[cfg(feature = "parse")]
+    fn mail_smtputf8(body: Option<Body>) -> Command {
+        let Command::Mail(mut mail) = mail_body(body) else {
+            unreachable!()
+        };
+        mail.smtputf8 = true;
+        Command::Mail(mail)
+    }
+
+    fn rcpt() -> Command {
+        rcpt_to("alice@example.com")
+    }
+
+    fn rcpt_to(to: &'static str) -> Command {
+        Command::Rcpt(Box::new(Rcpt::builder(email(to)).build()))
+    }
+
+    fn data() -> Command {
+        Command::Data(Bytes::from("Hi!"))
+    }
+
+    fn bdat(last: bool) -> Command {
+        Command::Bdat(Bdat {
+            size: 3,
+            last,
+            payload: Bytes::from("Hi!"),
+        })
+    }
+
+    fn auth() -> Command {
+        Command::Auth(auth::Auth::new(Mechanism::Plain))
+    }
+
+    #[rstest]
+    #[case::data(vec![ehlo(), mail(), rcpt(), rcpt(), data()])]
+    #[case::bdat(vec![ehlo(), mail(), rcpt(), bdat(false), bdat(true)])]
+    #[case::two_transactions(vec![ehlo(), mail(), rcpt(), data(), mail(), rcpt(), data()])]
+    #[case::rset(vec![ehlo(), mail(), Command::Rset, mail()])]
+    #[case::auth(vec![ehlo(), auth(), mail()])]
+    #[case::starttls(vec![ehlo(), Command::StartTls])]
+    #[case::noop(vec![Command::Noop, ehlo(), mail(), Command::Noop, rcpt()])]
+    fn valid(#[case] commands: Vec<Command>) {
+        let mut session = Session::new();
+        for command in &commands {
+            assert_eq!(session.check(command), Ok(()), "{command:?}");
+            session.accept(command);
+        }
+    }
+
+    #[rstest]
+    #[case::mail_before_ehlo(vec![], mail(), Violation::NoHelo)]
+    #[case::auth_before_ehlo(vec![], auth(), Violation::NoHelo)]
+    #[case::rcpt_before_mail(vec![ehlo()], rcpt(), Violation::NoMail)]
+    #[case::data_before_mail(vec![ehlo()], data(), Violation::NoMail)]
+    #[case::bdat_before_mail(vec![ehlo()], bdat(true), Violation::NoMail)]
+    #[case::data_without_rcpt(vec![ehlo(), mail()], data(), Violation::NoRcpt)]
+    #[case::bdat_without_rcpt(vec![ehlo(), mail()], bdat(false), Violation::NoRcpt)]
+    #[case::nested_mail(vec![ehlo(), mail()], mail(), Violation::NestedMail)]
+    #[case::starttls_in_transaction(vec![ehlo(), mail()], Command::StartTls, Violation::StartTlsInTransaction)]
+    #[case::auth_in_transaction(vec![ehlo(), mail(), rcpt()], auth(), Violation::AuthInTransaction)]
+    #[case::data_after_data(vec![ehlo(), mail(), rcpt(), data()], data(), Violation::NoMail)]
+    #[case::data_after_bdat(vec![ehlo(), mail(), rcpt(), bdat(false)], data(), Violation::DataAfterBdat)]
+    #[case::ehlo_resets(vec![ehlo(), mail(), rcpt(), ehlo()], data(), Violation::NoMail)]
+    fn invalid(
+        #[case] commands: Vec<Command>,
+        #[case] command: Command,
+        #[case] expected: Violation,
+    ) {
+        let mut session = Session::new();
+        for previous in &commands {
+            session.accept(previous);
+        }
+
+        assert_eq!(session.check(&command), Err(expected));
+        assert_eq!(expected.code(), 503);
+    }
+
+    #[test]
+    fn binarymime() {
+        let mut session = Session::new();
+        session.accept(&ehlo());
+        assert_eq!(
+            session.check(&mail_body(Some(Body::BinaryMime))),
+            Err(Violation::BinaryMimeWithoutChunking)
+        );
+
+        let mut session = Session::new().with_chunking(true);
+        for command in [ehlo(), mail_body(Some(Body::BinaryMime)), rcpt()] {
+            assert_eq!(session.check(&command), Ok(()));
+            session.accept(&command);
+        }
+
+        assert_eq!(session.check(&data()), Err(Violation::DataWithBinaryMime));
+        assert_eq!(session.check(&bdat(true)), Ok(()));
+        assert_eq!(Violation::BinaryMimeWithoutChunking.code(), 555);
+        assert_eq!(Violation::DataWithBinaryMime.code(), 503);
+    }
+
+    #[test]
+    fn require_tls() {
+        let mut session = Session::new().with_require_tls(true);
+        assert_eq!(session.check(&ehlo()), Ok(()));
+        session.accept(&ehlo());
+        assert_eq!(session.check(&mail()), Err(Violation::TlsRequired));
+        assert_eq!(session.check(&auth()), Err(Violation::TlsRequired));
+        assert_eq!(Violation::TlsRequired.code(), 530);
+
+        assert_eq!(session.check(&Command::StartTls), Ok(()));
+        session.accept(&Command::StartTls);
+        session.authenticated(Bytes::from("bob"));
+        session.tls_established(Some(Bytes::from("mx.example.com")), None);
+        assert!(session.is_tls());
+        assert_eq!(session.connection().authenticated, None);
+        assert_eq!(
+            session.connection().sni,
+            Some(Bytes::from("mx.example.com"))
+        );
+
+        // the client must greet again after the handshake
+        assert_eq!(session.check(&mail()), Err(Violation::NoHelo));
+        session.accept(&ehlo());
+        assert_eq!(session.check(&mail()), Ok(()));
+        assert_eq!(session.check(&Command::StartTls), Err(Violation::TlsActive));
+    }
+
+    #[test]
+    fn recipient_limit() {
+        let mut session = Session::new();
+        for command in [ehlo(), mail()] {
+            session.accept(&command);
+        }
+
+        for _ in 0..max::RECIPIENTS {
+            assert_eq!(session.check(&rcpt()), Ok(()));
+            session.accept(&rcpt());
+        }
+        assert_eq!(session.check(&rcpt()), Err(Violation::TooManyRecipients));
+        assert_eq!(Violation::TooManyRecipients.code(), 452);
+        assert_eq!(
+            Violation::TooManyRecipients.to_string(),
+            "452 4.5.3 Too many recipients"
+        );
+
+        // the limit is per transaction
+        session.accept(&data());
+        session.accept(&mail());
+        assert_eq!(session.check(&rcpt()), Ok(()));
+
+        let mut session = Session::new().with_limits(Limits {
+            recipients: 1,
+            ..Limits::default()
+        });
+        for command in [ehlo(), mail(), rcpt()] {
+            session.accept(&command);
+        }
+        assert_eq!(session.check(&rcpt()), Err(Violation::TooManyRecipients));
+    }
+
+    #[test]
+    fn null_sender_limit() {
+        let limits = Limits {
+            null_sender_recipients: 1,
+            ..Limits::default()
+        };
+        let mut session = Session::new().with_limits(limits);
+        let Command::Mail(mut null) = mail() else {
+            unreachable!()
+        };
+        null.from = ReversePath::Null;
+        for command in [ehlo(), Command::Mail(null), rcpt()] {
+            session.accept(&command);
+        }
+        assert_eq!(
+            session.check(&rcpt()),
+            Err(Violation::TooManyNullSenderRecipients)
+        );
+        assert_eq!(Violation::TooManyNullSenderRecipients.code(), 550);
+
+        // other senders are not limited
+        session.accept(&Command::Rset);
+        for command in [mail(), rcpt()] {
+            session.accept(&command);
+        }
+        assert_eq!(session.check(&rcpt()), Ok(()));
+    }
+
+    #[test]
+    fn connection_limits() {
+        let limits = Limits {
+            messages_per_connection: 1,
+            recipients_per_connection: 3,
+            ..Limits::default()
+        };
+        let mut session = Session::new().with_limits(limits);
+        for command in [ehlo(), mail(), rcpt(), rcpt(), data()] {
+            assert_eq!(session.check(&command), Ok(()));
+            session.accept(&command);
+        }
+        assert_eq!(
+            session.counters(),
+            Counters {
+                transactions: 1,
+                messages: 1,
+                recipients: 2,
+                commands: 5,
+            }
+        );
+        assert_eq!(session.check(&mail()), Err(Violation::ConnectionLimit));
+        assert_eq!(Violation::ConnectionLimit.code(), 421);
+
+        // a refused message counts as a transaction, but not a message
+        let limits = Limits {
+            transactions_per_connection: 1,
+            ..limits
+        };
+        let mut session = Session::new().with_limits(limits);
+        let mut policy = Recorder {
+            reject: Some("message_last"),
+            ..Recorder::default()
+        };
+        for command in [ehlo(), mail(), rcpt(), rcpt(), rcpt()] {
+            session.handle(&command, &mut ()).unwrap();
+        }
+        assert_eq!(session.check(&rcpt()), Err(Violation::ConnectionLimit));
+        assert!(session.handle(&data(), &mut policy).is_err());
+        assert_eq!(session.counters().transactions, 1);
+        assert_eq!(session.counters().messages, 0);
+        assert_eq!(session.check(&mail()), Err(Violation::ConnectionLimit));
+
+        let mut session = Session::new().with_limits(Limits {
+            commands_per_connection: 1,
+            ..Limits::default()
+        });
+        session.accept(&ehlo());
+        assert_eq!(
+            session.check(&Command::Noop),
+            Err(Violation::ConnectionLimit)
+        );
+        assert_eq!(session.check(&Command::Quit), Ok(()));
+    }
+
+    #[test]
+    fn chunk_limits() {
+        let limits = Limits {
+            chunks: 2,
+            min_chunk: 3,
+            ..Limits::default()
+        };
+        let mut session = Session::new().with_limits(limits);
+        for command in [ehlo(), mail(), rcpt()] {
+            session.accept(&command);
+        }
+
+        let small = Command::Bdat(Bdat {
+            size: 2,
+            last: false,
+            payload: Bytes::from("Hi"),
+        });
+        assert_eq!(session.check(&small), Err(Violation::ChunkTooSmall));
+        assert_eq!(Violation::ChunkTooSmall.code(), 554);
+
+        // the last chunk may be small
+        let small_last = Command::Bdat(Bdat {
+            size: 2,
+            last: true,
+            payload: Bytes::from("Hi"),
+        });
+        assert_eq!(session.check(&small_last), Ok(()));
+
+        for _ in 0..2 {
+            assert_eq!(session.check(&bdat(false)), Ok(()));
+            session.accept(&bdat(false));
+        }
+        assert_eq!(session.check(&bdat(true)), Err(Violation::TooManyChunks));
+        assert_eq!(Violation::TooManyChunks.code(), 552);
+    }
+
+    #[test]
+    fn implicit_reset() {
+        let mut session = Session::new().with_implicit_reset(true);
+        for command in [ehlo(), mail(), rcpt()] {
+            session.accept(&command);
+        }
+        assert!(!session.implicitly_reset());
+
+        assert_eq!(session.check(&mail()), Ok(()));
+        session.accept(&mail());
+        assert!(session.implicitly_reset());
+        assert_eq!(session.recipients(), 0);
+        assert_eq!(session.check(&data()), Err(Violation::NoRcpt));
+
+        session.accept(&rcpt());
+        assert!(!session.implicitly_reset());
+        assert_eq!(
+            session.check(&mail_body(Some(Body::BinaryMime))),
+            Err(Violation::BinaryMimeWithoutChunking)
+        );
+    }
+
+    #[test]
+    fn message_size() {
+        let limits = Limits {
+            message: 8,
+            ..Limits::default()
+        };
+        let mut session = Session::new().with_limits(limits);
+        for command in [ehlo(), mail(), rcpt()] {
+            session.accept(&command);
+        }
+
+        for received in [3, 6] {
+            assert_eq!(session.check(&bdat(false)), Ok(()));
+            session.accept(&bdat(false));
+            assert_eq!(session.message_size(), received);
+        }
+        assert_eq!(session.check(&bdat(true)), Err(Violation::MessageTooBig));
+        assert_eq!(Violation::MessageTooBig.code(), 552);
+
+        let big = Command::Data(Bytes::from("Too big!!"));
+        session.accept(&Command::Rset);
+        for command in [mail(), rcpt()] {
+            session.accept(&command);
+        }
+        assert_eq!(session.check(&big), Err(Violation::MessageTooBig));
+        assert_eq!(session.check(&data()), Ok(()));
+
+        // declared
+        let Command::Mail(mut mail) = mail() else {
+            unreachable!();
+        };
+        mail.size = Some(9);
+        let mail = Command::Mail(mail);
+        session.accept(&Command::Rset);
+        assert_eq!(session.check(&mail), Err(Violation::MessageTooBig));
+
+        let mut session = Session::new();
+        for command in [ehlo(), mail.clone(), rcpt()] {
+            session.accept(&command);
+        }
+        for _ in 0..3 {
+            session.accept(&bdat(false));
+        }
+        assert_eq!(session.message_size(), 9);
+        assert_eq!(session.check(&bdat(true)), Err(Violation::MessageTooBig));
+    }
+
+    #[rstest]
+    #[case::domain(Identity::all(), "client.example.com", true)]
+    #[case::literal(Identity::REJECT_LITERAL, "[192.0.2.1]", false)]
+    #[case::literal_allowed(Identity::REJECT_MISMATCH, "[192.0.2.1]", true)]
+    #[case::mismatch(Identity::REJECT_MISMATCH, "[192.0.2.2]", false)]
+    #[case::mismatch_allowed(Identity::empty(), "[192.0.2.2]", true)]
+    #[case::address(Identity::REJECT_LITERAL, "[test:1234]", false)]
+    #[case::own_hostname(Identity::REJECT_SELF, "MX.example.com", false)]
+    #[case::own_hostname_allowed(Identity::REJECT_LITERAL, "mx.example.com", true)]
+    fn identity(#[case] identity: Identity, #[case] host: &'static str, #[case] valid: bool) {
+        let session = Session::new()
+            .with_identity(identity)
+            .with_hostname(Domain::from_static("mx.example.com"))
+            .with_peer(SocketAddr::from(([192, 0, 2, 1], 25)));
+        let host = match host.strip_prefix('[').and_then(|ip| ip.strip_suffix(']')) {
+            Some(ip) => ip.parse().map_or_else(
+                |_| Host::Address(unsafe { Address::new_unchecked(Bytes::from(host)) }),
+                Host::Ip,
+            ),
+            None => Host::Domain(unsafe { Domain::new_unchecked(Bytes::from(host)) }),
+        };
+
+        let expected = if valid {
+            Ok(())
+        } else {
+            Err(Violation::InvalidIdentity)
+        };
+        assert_eq!(session.check(&Command::Ehlo(host.clone())), expected);
+        assert_eq!(session.check(&Command::Helo(host)), expected);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<&'static str>,
+        reject: Option<&'static str>,
+    }
+
+    impl Recorder {
+        fn decide(&mut self, call: &'static str) -> Decision {
+            self.calls.push(call);
+            if self.reject == Some(call) {
+                Decision::Reject(Some(Bytes::from("No thanks")))
+            } else {
+                Decision::Accept
+            }
+        }
+    }
+
+    impl Policy for Recorder {
+        fn on_ehlo(&mut self, _: &ConnectionInfo, _: &Host) -> Decision {
+            self.decide("ehlo")
+        }
+
+        fn on_mail(&mut self, _: &ConnectionInfo, _: &Mail) -> Decision {
+            self.decide("mail")
+        }
+
+        fn on_rcpt(&mut self, _: &ConnectionInfo, _: &Rcpt) -> Decision {
+            self.decide("rcpt")
+        }
+
+        fn on_data_start(&mut self, _: &ConnectionInfo) -> Decision {
+            self.decide("data_start")
+        }
+
+        fn on_message(&mut self, _: &ConnectionInfo, _: &Bytes, last: bool) -> Decision {
+            self.decide(if last { "message_last" } else { "message" })
+        }
+    }
+
+    #[test]
+    fn policy() {
+        let mut session = Session::new().with_chunking(true);
+        let mut policy = Recorder::default();
+        for command in [ehlo(), mail(), rcpt(), bdat(false), bdat(false), bdat(true)] {
+            assert_eq!(session.handle(&command, &mut policy), Ok(()));
+        }
+        assert_eq!(
+            policy.calls,
+            [
+                "ehlo",
+                "mail",
+                "rcpt",
+                "data_start",
+                "message",
+                "message",
+                "message_last"
+            ]
+        );
+        assert!(!session.in_transaction());
+
+        // a violation is reported before the policy is consulted
+        let mut policy = Recorder::default();
+        assert_eq!(
+            session.handle(&rcpt(), &mut policy),
+            Err(Refusal::Violation(Violation::NoMail))
+        );
+        assert!(policy.calls.is_empty());
+
+        // a refused RCPT does not count
+        let mut policy = Recorder {
+            reject: Some("rcpt"),
+            ..Recorder::default()
+        };
+        session.handle(&mail(), &mut policy).unwrap();
+        let refusal = session.handle(&rcpt(), &mut policy).unwrap_err();
+        assert_eq!(
+            refusal,
+            Refusal::Policy(Decision::Reject(Some(Bytes::from("No thanks"))))
+        );
+        assert_eq!(session.recipients(), 0);
+
+        // a refused message ends the transaction
+        session.handle(&rcpt(), &mut ()).unwrap();
+        policy.reject = Some("message_last");
+        assert!(session.handle(&data(), &mut policy).is_err());
+        assert!(!session.in_transaction());
+    }
+
+    #[test]
+    fn connection() {
+        struct Authenticated(Option<Bytes>);
+
+        impl Policy for Authenticated {
+            fn on_mail(&mut self, connection: &ConnectionInfo, _: &Mail) -> Decision {
+                self.0.clone_from(&connection.authenticated);
+                Decision::Accept
+            }
+        }
+
+        let peer = SocketAddr::from(([192, 0, 2, 1], 49152));
+        let mut session = Session::new().with_peer(peer);
+        session.authenticated(Bytes::from("bob"));
+        assert_eq!(session.connection().peer, Some(peer));
+
+        let mut policy = Authenticated(None);
+        session.handle(&ehlo(), &mut policy).unwrap();
+        session.handle(&mail(), &mut policy).unwrap();
+        assert_eq!(policy.0, Some(Bytes::from("bob")));
+    }
+
+    #[rstest]
+    #[case::accept(Decision::Accept, None, None)]
+    #[case::reject(Decision::Reject(None), Some(550), Some(554))]
+    #[case::tempfail(Decision::TempFail(None), Some(450), Some(451))]
+    fn decision_code(
+        #[case] decision: Decision,
+        #[case] command: Option<u16>,
+        #[case] content: Option<u16>,
+    ) {
+        assert_eq!(decision.code(false), command);
+        assert_eq!(decision.code(true), content);
+    }
+
+    #[test]
+    fn implicit_tls() {
+        let mut session = Session::new()
+            .with_implicit_tls(true)
+            .with_require_tls(true);
+        assert!(session.is_tls());
+        assert_eq!(session.check(&ehlo()), Ok(()));
+        session.accept(&ehlo());
+        assert_eq!(session.check(&Command::StartTls), Err(Violation::TlsActive));
+        assert_eq!(session.check(&mail()), Ok(()));
+        assert!(Session::new().with_implicit_tls(true).is_tls());
+    }
+
+    #[test]
+    fn early_talker() {
+        let mut session = Session::new();
+        session.greeting_sent();
+        session.received();
+        assert!(!session.is_early_talker());
+
+        let mut session = Session::new();
+        session.received();
+        session.greeting_sent();
+        session.received();
+        assert!(session.is_early_talker());
+    }
+
+    #[test]
+    fn shutdown() {
+        let mut session = Session::new();
+        for command in [ehlo(), mail(), rcpt()] {
+            session.accept(&command);
+        }
+
+        // the open transaction may complete
+        session.begin_shutdown();
+        assert!(!session.is_closing());
+        assert_eq!(session.check(&rcpt()), Ok(()));
+        assert_eq!(session.check(&data()), Ok(()));
+        session.accept(&data());
+
+        assert!(session.is_closing());
+        assert_eq!(session.check(&mail()), Err(Violation::ShuttingDown));
+        assert_eq!(session.check(&Command::Noop), Err(Violation::ShuttingDown));
+        assert_eq!(session.check(&Command::Quit), Ok(()));
+        assert_eq!(Violation::ShuttingDown.code(), 421);
+    }
+
+    #[test]
+    fn recipients() {
+        let mut session = Session::new();
+        for command in [ehlo(), mail(), rcpt(), rcpt()] {
+            session.accept(&command);
+        }
+
+        assert!(session.in_transaction());
+        assert_eq!(session.recipients(), 2);
+
+        session.accept(&data());
+        assert!(!session.in_transaction());
+        assert_eq!(session.recipients(), 0);
+    }
+
+    #[rstest]
+    #[case::data(data())]
+    #[case::bdat(bdat(true))]
+    fn prdr(#[case] message: Command) {
+        let mut session = Session::new();
+        session.accept(&ehlo());
+        assert_eq!(session.check(&mail_prdr()), Err(Violation::PrdrUnavailable));
+        assert_eq!(Violation::PrdrUnavailable.code(), 555);
+
+        let mut session = Session::new().with_prdr(true).with_chunking(true);
+        for command in [
+            ehlo(),
+            mail_prdr(),
+            rcpt_to("alice@example.com"),
+            rcpt_to("carol@example.com"),
+            rcpt_to("dave@example.com"),
+            message,
+        ] {
+            assert_eq!(session.handle(&command, &mut ()), Ok(()), "{command:?}");
+            if matches!(command, Command::Rcpt(_)) {
+                assert_eq!(session.prdr_recipients(), None);
+            }
+        }
+        assert!(!session.in_transaction());
+        assert_eq!(session.counters().messages, 1);
+        assert_eq!(
+            session.prdr_recipients(),
+            Some(
+                &[
+                    email("alice@example.com"),
+                    email("carol@example.com"),
+                    email("dave@example.com")
+                ][..]
+            )
+        );
+
+        let replies = session
+            .prdr_replies(|to| {
+                let text = if to.bytes() == "carol@example.com" {
+                    "5.7.1 Mailbox full"
+                } else {
+                    "2.1.5 OK"
+                };
+                let code = if text.starts_with('2') { 250 } else { 550 };
+                let mut reply = Reply::new(code, Bytes::from(text)).unwrap();
+                reply.split_enhanced_code();
+                reply
+            })
+            .unwrap();
+        let replies: Vec<_> = replies.iter().map(ToBytes::to_bytes).collect();
+        assert_eq!(
+            replies,
+            [
+                "353 Content analysis has started\r\n",
+                "250 2.1.5 OK\r\n",
+                "550 5.7.1 Mailbox full\r\n",
+                "250 2.1.5 OK\r\n",
+                "250 2.0.0 OK: queued\r\n",
+            ]
+        );
+        assert_eq!(session.prdr_recipients(), None);
+        assert_eq!(session.prdr_replies(|_| unreachable!()), None);
+    }
+
+    #[test]
+    fn prdr_all_rejected() {
+        let mut session = Session::new().with_prdr(true);
+        for command in [ehlo(), mail_prdr(), rcpt(), data()] {
+            session.accept(&command);
+        }
+        let replies = session
+            .prdr_replies(|_| Reply::new(550, Bytes::from("Mailbox unavailable")).unwrap())
+            .unwrap();
+        assert_eq!(
+            replies.last().unwrap().to_bytes(),
+            "550 5.7.1 No recipients accepted the message\r\n"
+        );
+
+        // the pending recipients are dropped by the next command, or without PRDR
+        for command in [mail_prdr(), rcpt(), data(), Command::Noop] {
+            session.accept(&command);
+        }
+        assert_eq!(session.prdr_recipients(), None);
+        for command in [mail(), rcpt(), data()] {
+            session.accept(&command);
+        }
+        assert_eq!(session.prdr_recipients(), None);
+    }
+
+    #[cfg(feature = "parse")]
+    #[rstest]
+    #[case::valid(mail_smtputf8(None), b"Subject: \xce\xb1\r\n\r\n\xce", true, Ok(()))]
+    #[case::invalid(
+        mail_smtputf8(Some(Body::EightBitMime)),
+        b"Subject: \xce\r\n\r\nHi!",
+        true,
+        Err(Violation::InvalidUtf8)
+    )]
+    #[case::disabled(mail_smtputf8(None), b"Subject: \xce\r\n\r\nHi!", false, Ok(()))]
+    #[case::not_smtputf8(mail(), b"Subject: \xce\r\n\r\nHi!", true, Ok(()))]
+    fn utf8_check(
+        #[case] mail: Command,
+        #[case] payload: &'static [u8],
+        #[case] utf8_check: bool,
+        #[case] expected: Result<(), Violation>,
+    ) {
+        let mut session = Session::new().with_utf8_check(utf8_check);
+        for command in [ehlo(), mail, rcpt()] {
+            session.accept(&command);
+        }
+        let data = Command::Data(Bytes::from_static(payload));
+        assert_eq!(session.check(&data), expected);
+        assert_eq!(Violation::InvalidUtf8.code(), 554);
+    }
+
+    #[test]
+    fn verbs() {
+        let session = Session::new().with_verbs(Verbs::all() - Verbs::EXPN - Verbs::AUTH);
+        assert_eq!(
+            session.check(&Command::Expn(Bytes::from("list"))),
+            Err(Violation::Disabled)
+        );
+        assert_eq!(session.check(&auth()), Err(Violation::Disabled));
+        assert_eq!(session.check(&ehlo()), Ok(()));
+        assert_eq!(Violation::Disabled.code(), 502);
+    }
+
+    #[rstest]
+    #[case::list(
+        None,
+        "214-2.0.0 Commands supported:\r\n214 2.0.0 HELO EHLO MAIL RCPT DATA QUIT\r\n"
+    )]
+    #[case::verb(Some(&b"rcpt"[..]), "214 2.0.0 RCPT TO:<forward-path> [<parameters>]\r\n")]
+    #[case::disabled(Some(&b"BDAT"[..]), "504 5.5.4 HELP topic unknown\r\n")]
+    #[case::unknown(Some(&b"FOO"[..]), "504 5.5.4 HELP topic unknown\r\n")]
+    fn help(#[case] topic: Option<&[u8]>, #[case] expected: &str) {
+        let verbs =
+            Verbs::HELO | Verbs::EHLO | Verbs::MAIL | Verbs::RCPT | Verbs::DATA | Verbs::QUIT;
+        let session = Session::new().with_verbs(verbs);
+        assert_eq!(session.help(topic).to_bytes(), expected);
+    }
+
+    #[rstest]
+    #[case::violation(
+        Refusal::Violation(Violation::NoMail),
+        false,
+        "503 5.5.1 Need MAIL command\r\n"
+    )]
+    #[case::reject(
+        Refusal::Policy(Decision::Reject(None)),
+        false,
+        "550 5.7.1 Rejected by policy\r\n"
+    )]
+    #[case::temp_fail(
+        Refusal::Policy(Decision::TempFail(None)),
+        true,
+        "451 4.7.1 Try again later\r\n"
+    )]
+    #[case::text(
+        Refusal::Policy(Decision::Reject(Some(Bytes::from("5.7.1 Go away")))),
+        true,
+        "554 5.7.1 Go away\r\n"
+    )]
+    #[case::invalid_text(
+        Refusal::Policy(Decision::Reject(Some(Bytes::from("Go away\r\n250 OK")))),
+        false,
+        "550 5.7.1 Rejected by policy\r\n"
+    )]
+    fn refusal_reply(#[case] refusal: Refusal, #[case] content: bool, #[case] expected: &str) {
+        assert_eq!(refusal.reply(content).to_bytes(), expected);
+    }
+}
@@ -0,0 +1,330 @@
+//! # Conformance Test Vectors
+//!
+//! A curated corpus of command lines, drawn from RFC examples and real-world quirks, along with
+//! the result `smtpkit` produces for each. Alternative implementations can run the same checks by
+//! comparing their parse results against [`COMMANDS`].
+
+use crate::Error;
+
+/// A single command line test vector.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Vector {
+    /// Short, unique name describing the case.
+    pub name: &'static str,
+
+    /// The command line, **excluding** the trailing CRLF.
+    pub input: &'static [u8],
+
+    /// The canonical form of the parsed command, or the expected error.
+    pub expected: Result<&'static str, Error>,
+}
+
+const fn ok(name: &'static str, input: &'static [u8], canonical: &'static str) -> Vector {
+    Vector {
+        name,
+        input,
+        expected: Ok(canonical),
+    }
+}
+
+const fn err(name: &'static str, input: &'static [u8], error: Error) -> Vector {
+    Vector {
+        name,
+        input,
+        expected: Err(error),
+    }
+}
+
+/// Command line test vectors.
+pub const COMMANDS: &[Vector] = &[
+    // HELO
+    ok("helo", b"HELO example.com", "HELO example.com"),
+    ok("helo_lowercase", b"helo example.com", "HELO example.com"),
+    err("helo_missing", b"HELO", Error::MissingParameter),
+    err(
+        "helo_extra",
+        b"HELO example.com foo",
+        Error::UnexpectedParameter,
+    ),
+    err(
+        "helo_leading_hyphen",
+        b"HELO -example.com",
+        Error::InvalidDomain,
+    ),
+    err(
+        "helo_empty_label",
+        b"HELO example..com",
+        Error::InvalidDomain,
+    ),
+    // rooted FQDN, only accepted with `Lenient::TRAILING_DOT`
+    err(
+        "helo_trailing_dot",
+        b"HELO example.com.",
+        Error::InvalidDomain,
+    ),
+    // RFC 5321 only allows a domain with HELO
+    err("helo_ipv4", b"HELO [192.0.2.1]", Error::InvalidDomain),
+    // EHLO
+    ok("ehlo", b"EHLO example.com", "EHLO example.com"),
+    ok("ehlo_ipv4", b"EHLO [192.0.2.1]", "EHLO [192.0.2.1]"),
+    ok(
+        "ehlo_ipv6",
+        b"EHLO [IPv6:2001:db8::1]",
+        "EHLO [IPv6:2001:db8::1]",
+    ),
+    // zone IDs are discarded
+    ok(
+        "ehlo_ipv6_zone",
+        b"EHLO [IPv6:fe80::1%25eth0]",
+        "EHLO [IPv6:fe80::1]",
+    ),
+    ok(
+        "ehlo_ipv6_zone_raw",
+        b"EHLO [IPv6:fe80::1%eth0]",
+        "EHLO [IPv6:fe80::1]",
+    ),
+    err(
+        "ehlo_ipv6_zone_empty",
+        b"EHLO [IPv6:fe80::1%]",
+        Error::InvalidAddressLiteral,
+    ),
+    ok(
+        "ehlo_address",
+        b"EHLO [x-tag:content]",
+        "EHLO [x-tag:content]",
+    ),
+    err("ehlo_missing", b"EHLO", Error::MissingParameter),
+    err(
+        "ehlo_bad_ipv4",
+        b"EHLO [300.0.0.1]",
+        Error::InvalidAddressLiteral,
+    ),
+    err(
+        "ehlo_bad_ipv6",
+        b"EHLO [IPv6:2001:db8::zz]",
+        Error::InvalidAddressLiteral,
+    ),
+    err(
+        "ehlo_empty_tag",
+        b"EHLO [:content]",
+        Error::InvalidAddressLiteral,
+    ),
+    // MAIL
+    ok(
+        "mail",
+        b"MAIL FROM:<bob@example.com>",
+        "MAIL FROM:<bob@example.com>",
+    ),
+    ok("mail_null", b"MAIL FROM:<>", "MAIL FROM:<>"),
+    ok(
+        "mail_mixed_case",
+        b"Mail From:<bob@example.com>",
+        "MAIL FROM:<bob@example.com>",
+    ),
+    ok(
+        "mail_size_body",
+        b"MAIL FROM:<bob@example.com> SIZE=1024 BODY=8BITMIME",
+        "MAIL FROM:<bob@example.com> SIZE=1024 BODY=8BITMIME",
+    ),
+    // RFC 3461 section 6.2
+    ok(
+        "mail_dsn",
+        b"MAIL FROM:<bob@example.com> RET=hdrs ENVID=QQ314159",
+        "MAIL FROM:<bob@example.com> RET=HDRS ENVID=QQ314159",
+    ),
+    ok(
+        "mail_auth_anonymous",
+        b"MAIL FROM:<bob@example.com> AUTH=<>",
+        "MAIL FROM:<bob@example.com> AUTH=<>",
+    ),
+    // RFC 4141
+    ok(
+        "mail_conperm",
+        b"MAIL FROM:<bob@example.com> CONPERM=image/tiff",
+        "MAIL FROM:<bob@example.com> CONPERM=image/tiff",
+    ),
+    // RFC 3865
+    ok(
+        "mail_solicit",
+        b"MAIL FROM:<bob@example.com> SOLICIT=org.example:ADV,net.example:SPAM",
+        "MAIL FROM:<bob@example.com> SOLICIT=org.example:ADV,net.example:SPAM",
+    ),
+    err(
+        "mail_bad_solicit",
+        b"MAIL FROM:<bob@example.com> SOLICIT=ADV,,SPAM",
+        Error::InvalidParameterValue { keyword: "SOLICIT" },
+    ),
+    ok(
+        "mail_prdr",
+        b"MAIL FROM:<bob@example.com> SIZE=1024 prdr",
+        "MAIL FROM:<bob@example.com> SIZE=1024 PRDR",
+    ),
+    err(
+        "mail_prdr_value",
+        b"MAIL FROM:<bob@example.com> PRDR=YES",
+        Error::InvalidParameter,
+    ),
+    // RFC 6531 section 3.4
+    ok(
+        "mail_smtputf8",
+        b"MAIL FROM:<bob@example.com> BODY=8BITMIME smtputf8",
+        "MAIL FROM:<bob@example.com> BODY=8BITMIME SMTPUTF8",
+    ),
+    err(
+        "mail_smtputf8_value",
+        b"MAIL FROM:<bob@example.com> SMTPUTF8=YES",
+        Error::InvalidParameter,
+    ),
+    err("mail_missing", b"MAIL", Error::MissingParameter),
+    err(
+        "mail_to",
+        b"MAIL TO:<bob@example.com>",
+        Error::InvalidSyntax,
+    ),
+    err(
+        "mail_no_angles",
+        b"MAIL FROM:bob@example.com",
+        Error::InvalidAddress,
+    ),
+    // sent by some versions of Outlook
+    err(
+        "mail_space",
+        b"MAIL FROM: <bob@example.com>",
+        Error::InvalidAddress,
+    ),
+    err(
+        "mail_empty_local",
+        b"MAIL FROM:<@example.com>",
+        Error::InvalidAddress,
+    ),
+    err(
+        "mail_bad_size",
+        b"MAIL FROM:<bob@example.com> SIZE=big",
+        Error::InvalidParameterValue { keyword: "SIZE" },
+    ),
+    err(
+        "mail_bad_ret",
+        b"MAIL FROM:<bob@example.com> RET=PARTIAL",
+        Error::InvalidParameterValue { keyword: "RET" },
+    ),
+    err(
+        "mail_bad_body",
+        b"MAIL FROM:<bob@example.com> BODY=9BIT",
+        Error::InvalidParameterValue { keyword: "BODY" },
+    ),
+    err(
+        "mail_bad_envid",
+        b"MAIL FROM:<bob@example.com> ENVID=a+zz",
+        Error::InvalidXText,
+    ),
+    err(
+        "mail_unknown",
+        b"MAIL FROM:<bob@example.com> FOO=BAR",
+        Error::InvalidParameter,
+    ),
+    // RCPT
+    ok(
+        "rcpt",
+        b"RCPT TO:<alice@example.com>",
+        "RCPT TO:<alice@example.com>",
+    ),
+    ok(
+        "rcpt_dsn",
+        b"RCPT TO:<alice@example.com> NOTIFY=success,failure ORCPT=rfc822;alice@example.com",
+        "RCPT TO:<alice@example.com> NOTIFY=FAILURE,SUCCESS ORCPT=rfc822;alice@example.com",
+    ),
+    ok(
+        "rcpt_never",
+        b"RCPT TO:<alice@example.com> NOTIFY=NEVER",
+        "RCPT TO:<alice@example.com> NOTIFY=NEVER",
+    ),
+    ok(
+        "rcpt_conneg",
+        b"RCPT TO:<alice@example.com> CONNEG=image/tiff",
+        "RCPT TO:<alice@example.com> CONNEG=image/tiff",
+    ),
+    err("rcpt_missing", b"RCPT", Error::MissingParameter),
+    err(
+        "rcpt_from",
+        b"RCPT FROM:<alice@example.com>",
+        Error::InvalidSyntax,
+    ),
+    err(
+        "rcpt_no_angles",
+        b"RCPT TO:alice@example.com",
+        Error::InvalidAddress,
+    ),
+    // RFC 3461 section 4.1: NEVER must appear alone
+    err(
+        "rcpt_never_success",
+        b"RCPT TO:<alice@example.com> NOTIFY=NEVER,SUCCESS",
+        Error::InvalidParameterValue { keyword: "NOTIFY" },
+    ),
+    ok(
+        "rcpt_orcpt_type",
+        b"RCPT TO:<alice@example.com> ORCPT=x400;alice",
+        "RCPT TO:<alice@example.com> ORCPT=x400;alice",
+    ),
+    // RFC 6533 section 3
+    ok(
+        "rcpt_orcpt_utf8",
+        b"RCPT TO:<alice@example.com> ORCPT=utf-8;j\\x{E9}r\\x{F4}me@example.com",
+        "RCPT TO:<alice@example.com> ORCPT=utf-8;j\\x{E9}r\\x{F4}me@example.com",
+    ),
+    err(
+        "rcpt_orcpt_no_type",
+        b"RCPT TO:<alice@example.com> ORCPT=alice@example.com",
+        Error::InvalidParameterValue { keyword: "ORCPT" },
+    ),
+    err(
+        "rcpt_orcpt_bad_xtext",
+        b"RCPT TO:<alice@example.com> ORCPT=rfc822;a+zz",
+        Error::InvalidXText,
+    ),
+    // DATA
+    err("data_extra", b"DATA foo", Error::UnexpectedParameter),
+    // BDAT
+    err("bdat_missing", b"BDAT", Error::MissingParameter),
+    err("bdat_bad_size", b"BDAT ten", Error::InvalidSyntax),
+    err("bdat_first", b"BDAT 10 FIRST", Error::UnexpectedParameter),
+    err(
+        "bdat_extra",
+        b"BDAT 10 LAST foo",
+        Error::UnexpectedParameter,
+    ),
+    err(
+        "bdat_overflow",
+        b"BDAT 99999999999999999999999",
+        Error::TooLong,
+    ),
+    // others
+    ok("rset", b"RSET", "RSET"),
+    ok("noop", b"NOOP", "NOOP"),
+    ok("quit", b"QUIT", "QUIT"),
+    ok("quit_lowercase", b"quit", "QUIT"),
+    ok("vrfy", b"VRFY Smith", "VRFY Smith"),
+    ok("expn", b"EXPN Example-People", "EXPN Example-People"),
+    err("vrfy_missing", b"VRFY", Error::MissingParameter),
+    ok("help", b"HELP", "HELP"),
+    ok("help_topic", b"HELP mail", "HELP mail"),
+    err("rset_extra", b"RSET foo", Error::UnexpectedParameter),
+    err("quit_extra", b"QUIT foo", Error::UnexpectedParameter),
+    err("empty", b"", Error::Empty),
+    err("unknown", b"FOO", Error::CommandNotImplemented),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bytes, Command};
+
+    #[test]
+    fn commands() {
+        for vector in COMMANDS {
+            let result = Command::try_from(Bytes::from_static(vector.input))
+                .map(|command| command.to_string());
+            let expected = vector.expected.clone().map(String::from);
+            assert_eq!(result, expected, "{}", vector.name);
+        }
+    }
+}
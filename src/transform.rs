@@ -0,0 +1,220 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use derive_more::Display;
+use mail::Body;
+
+use crate::*;
+
+/// # Message Transform
+///
+/// Rewrites the message content before it is surfaced or relayed, e.g. to add a header field or
+/// a DKIM signature. Run in order by a [`Pipeline`].
+///
+/// Content is canonical: no dot-stuffing, and every line ends in CRLF, including the last. A
+/// transform must return canonical content; [`Pipeline`] refuses bare CR or LF in its output and
+/// applies dot-stuffing again for `DATA`.
+pub trait Transform {
+    /// Transform the complete message `content`.
+    fn transform(&mut self, content: Bytes) -> Bytes;
+}
+
+impl<F: FnMut(Bytes) -> Bytes> Transform for F {
+    fn transform(&mut self, content: Bytes) -> Bytes {
+        self(content)
+    }
+}
+
+/// # Transform Error
+///
+/// Returned by [`Pipeline`].
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransformError {
+    /// A transform returned a CR or LF that is not part of a CRLF, or content not ending in CRLF.
+    #[display("Transform broke CRLF line endings")]
+    LineEnding,
+    /// The transformed content cannot be sent with `DATA`.
+    #[display("Transformed content cannot be sent with DATA")]
+    Join(JoinError),
+}
+
+impl From<JoinError> for TransformError {
+    fn from(error: JoinError) -> Self {
+        Self::Join(error)
+    }
+}
+
+/// # Message Transform Pipeline
+///
+/// Passes the message content through each [`Transform`] in turn.
+///
+/// `DATA` payloads are dot-stuffed, so use [`Pipeline::data`]. For `BDAT`, join the chunk
+/// payloads, pass them to [`Pipeline::content`], then split the result with [`Bdat::chunks`].
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::Pipeline;
+/// let mut pipeline = Pipeline::new().with(|content: Bytes| -> Bytes {
+///     [&b"Received: from client\r\n"[..], &content[..]].concat().into()
+/// });
+///
+/// let payload = Bytes::from("Subject: Hi!\r\n\r\n..");
+/// assert_eq!(
+///     pipeline.data(&payload),
+///     Ok(Bytes::from("Received: from client\r\nSubject: Hi!\r\n\r\n.."))
+/// );
+/// ```
+#[derive(derive_more::Debug, Default)]
+pub struct Pipeline {
+    #[debug(skip)]
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl Pipeline {
+    /// Create an empty `Pipeline`, which leaves the content unchanged.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Append `transform` to the pipeline.
+    #[must_use]
+    pub fn with(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Whether there are no transforms.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Transform canonical `content`, such as joined `BDAT` chunks.
+    ///
+    /// Line endings are not checked for `BINARYMIME` content.
+    pub fn content(
+        &mut self,
+        mut content: Bytes,
+        body: Option<Body>,
+    ) -> Result<Bytes, TransformError> {
+        for transform in &mut self.transforms {
+            content = transform.transform(content);
+            if body != Some(Body::BinaryMime) && !canonical(&content) {
+                return Err(TransformError::LineEnding);
+            }
+        }
+
+        Ok(content)
+    }
+
+    /// Transform a dot-stuffed `DATA` payload, as in [`Command::Data`], returning it dot-stuffed.
+    pub fn data(&mut self, payload: &Bytes) -> Result<Bytes, TransformError> {
+        if self.is_empty() {
+            return Ok(payload.clone());
+        }
+
+        let content = self.content(unstuff(payload), None)?;
+        let chunk = Bdat {
+            size: content.len(),
+            last: true,
+            payload: content,
+        };
+        Ok(Bdat::join([chunk], None)?)
+    }
+}
+
+/// Remove dot-stuffing from a `DATA` payload, and end its last line in CRLF.
+fn unstuff(payload: &Bytes) -> Bytes {
+    if payload.is_empty() {
+        return Bytes::new();
+    }
+
+    let mut content = BytesMut::with_capacity(payload.len() + 2);
+    for line in payload.split_str("\r\n") {
+        content.extend_from_slice(line.strip_prefix(b".").unwrap_or(line));
+        content.extend_from_slice(b"\r\n");
+    }
+
+    content.freeze()
+}
+
+/// Whether every CR and LF in `content` is part of a CRLF, and it is empty or ends in CRLF.
+fn canonical(content: &[u8]) -> bool {
+    let mut bytes = content.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'\r' if bytes.next_if_eq(&&b'\n').is_none() => return false,
+            b'\n' => return false,
+            _ => {}
+        }
+    }
+
+    content.is_empty() || content.ends_with(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn received(content: Bytes) -> Bytes {
+        [&b"Received: x\r\n"[..], &content[..]].concat().into()
+    }
+
+    #[rstest]
+    #[case::empty("", "Received: x")]
+    #[case::stuffed("Hi\r\n..\r\n..x", "Received: x\r\nHi\r\n..\r\n..x")]
+    fn data(#[case] payload: &'static str, #[case] expected: &'static str) {
+        let mut pipeline = Pipeline::new().with(received);
+        assert_eq!(
+            pipeline.data(&Bytes::from(payload)),
+            Ok(Bytes::from(expected))
+        );
+    }
+
+    #[test]
+    fn order() {
+        let mut pipeline = Pipeline::new()
+            .with(received)
+            .with(|content: Bytes| content.slice(10..));
+        assert_eq!(
+            pipeline.content(Bytes::from("Hi\r\n"), None),
+            Ok(Bytes::from("x\r\nHi\r\n"))
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let mut pipeline = Pipeline::new();
+        let payload = Bytes::from("bare\nLF");
+        assert_eq!(pipeline.data(&payload), Ok(payload));
+    }
+
+    #[rstest]
+    #[case::bare_lf("Hi\n", None, Err(TransformError::LineEnding))]
+    #[case::bare_cr("Hi\r", None, Err(TransformError::LineEnding))]
+    #[case::unterminated("Hi", None, Err(TransformError::LineEnding))]
+    #[case::binary("Hi\n", Some(Body::BinaryMime), Ok(()))]
+    fn line_endings(
+        #[case] output: &'static str,
+        #[case] body: Option<Body>,
+        #[case] expected: Result<(), TransformError>,
+    ) {
+        let mut pipeline = Pipeline::new().with(move |_: Bytes| Bytes::from(output));
+        assert_eq!(pipeline.content(Bytes::new(), body).map(|_| ()), expected);
+    }
+
+    #[test]
+    fn too_long() {
+        let mut long = alloc::vec![b'a'; max::DATA_LINE + 1];
+        long.extend_from_slice(b"\r\n");
+        let long = Bytes::from(long);
+        let mut pipeline = Pipeline::new().with(move |_: Bytes| long.clone());
+        assert_eq!(
+            pipeline.data(&Bytes::from("Hi")),
+            Err(TransformError::Join(JoinError::TooLong))
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use derive_more::Display;
+
+use crate::*;
+
+/// `AUTH` Command
+///
+/// <https://datatracker.ietf.org/doc/html/rfc4954#section-4>
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Auth {
+    /// The SASL mechanism.
+    pub mechanism: Mechanism,
+    /// The initial response, sent with the command to save a round trip.
+    pub initial_response: Option<Base64>,
+}
+
+impl Auth {
+    /// The line cancelling an exchange, in place of a response.
+    pub const CANCEL: &'static [u8] = b"*\r\n";
+
+    /// Create an `Auth` without an initial response.
+    #[must_use]
+    pub const fn new(mechanism: Mechanism) -> Self {
+        Self {
+            mechanism,
+            initial_response: None,
+        }
+    }
+
+    /// Create an `Auth` with `response` as the initial response, encoding it.
+    ///
+    /// Returns `None` if `mechanism` does not allow an initial response.
+    ///
+    /// ```rust
+    /// # use smtpkit::{Mechanism, auth::Auth};
+    /// let auth = Auth::with_initial_response(Mechanism::Plain, b"\0user\0pencil").unwrap();
+    /// assert_eq!(auth.to_string(), "AUTH PLAIN AHVzZXIAcGVuY2ls");
+    /// assert_eq!(Auth::with_initial_response(Mechanism::CramMd5, b""), None);
+    /// ```
+    #[must_use]
+    pub fn with_initial_response(mechanism: Mechanism, response: &[u8]) -> Option<Self> {
+        let auth = Self {
+            mechanism,
+            initial_response: Some(Base64::encode(response)),
+        };
+        auth.is_valid().then_some(auth)
+    }
+
+    /// Whether the initial response, if any, is allowed for the mechanism. Mechanisms where the
+    /// server goes first, e.g. `CRAM-MD5`, do not allow one.
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.initial_response.is_none() || self.mechanism.client_first()
+    }
+
+    /// Encode `response` as the line answering a `334` challenge, including the CRLF.
+    #[must_use]
+    pub fn continuation(response: &[u8]) -> Bytes {
+        let mut buf = BytesMut::new();
+        Base64::encode_into(response, &mut buf);
+        buf.extend_from_slice(b"\r\n");
+        buf.freeze()
+    }
+}
+
+impl From<Mechanism> for Auth {
+    fn from(mechanism: Mechanism) -> Self {
+        Self::new(mechanism)
+    }
+}
+
+impl fmt::Display for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AUTH {}", self.mechanism.name())?;
+        match &self.initial_response {
+            // an empty initial response is sent as `=`
+            Some(ir) if ir.bytes().is_empty() => write!(f, " ="),
+            Some(ir) => write!(f, " {ir}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// # Mechanism Negotiation Error
+///
+/// Returned by [`negotiate`].
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NegotiateError {
+    /// None of the preferred mechanisms were advertised.
+    #[display("No mutually supported mechanism")]
+    Unsupported,
+    /// The only mutually supported mechanisms send credentials in the clear, which is not
+    /// allowed.
+    #[display("Mechanism requires TLS")]
+    TlsRequired,
+}
+
+/// Choose the mechanism to authenticate with: the first of `preference` that the server
+/// `advertised`, as the space-separated parameters of the `AUTH` keyword of its `EHLO` reply.
+///
+/// Unless `plaintext` is set, e.g. because TLS is established, mechanisms that send credentials
+/// in the clear are skipped; see [`Mechanism::plaintext`].
+///
+/// ```rust
+/// # use smtpkit::{Mechanism, auth::{self, NegotiateError}};
+/// let preference = [Mechanism::ScramSha256, Mechanism::Plain];
+/// let negotiated = auth::negotiate(b"LOGIN PLAIN", &preference, true);
+/// assert_eq!(negotiated, Ok(Mechanism::Plain));
+///
+/// let negotiated = auth::negotiate(b"LOGIN PLAIN", &preference, false);
+/// assert_eq!(negotiated, Err(NegotiateError::TlsRequired));
+/// ```
+pub fn negotiate(
+    advertised: &[u8],
+    preference: &[Mechanism],
+    plaintext: bool,
+) -> Result<Mechanism, NegotiateError> {
+    let offered = |mechanism: &&Mechanism| {
+        advertised
+            .fields_with(|c| c == ' ')
+            .any(|name| Mechanism::from_name(name) == Some(**mechanism))
+    };
+
+    let mut mutual = preference.iter().filter(offered).peekable();
+    if mutual.peek().is_none() {
+        return Err(NegotiateError::Unsupported);
+    }
+
+    mutual
+        .copied()
+        .find(|mechanism| plaintext || !mechanism.plaintext())
+        .ok_or(NegotiateError::TlsRequired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::plain(Mechanism::Plain, true)]
+    #[case::login(Mechanism::Login, true)]
+    #[case::scram(Mechanism::ScramSha256Plus, true)]
+    #[case::cram_md5(Mechanism::CramMd5, false)]
+    #[case::digest_md5(Mechanism::DigestMd5, false)]
+    fn is_valid(#[case] mechanism: Mechanism, #[case] valid: bool) {
+        assert!(Auth::new(mechanism).is_valid());
+        assert_eq!(
+            Auth::with_initial_response(mechanism, b"x").is_some(),
+            valid
+        );
+    }
+
+    #[test]
+    fn empty_initial_response() {
+        let auth = Auth::with_initial_response(Mechanism::Anonymous, b"").unwrap();
+        assert_eq!(auth.to_string(), "AUTH ANONYMOUS =");
+        assert_eq!(auth.to_bytes(), "AUTH ANONYMOUS =\r\n");
+    }
+
+    #[rstest]
+    #[case::first(b"PLAIN SCRAM-SHA-256", true, Ok(Mechanism::ScramSha256))]
+    #[case::preference(b"PLAIN LOGIN", true, Ok(Mechanism::Plain))]
+    #[case::case(b"plain", true, Ok(Mechanism::Plain))]
+    #[case::plaintext(b"PLAIN LOGIN", false, Err(NegotiateError::TlsRequired))]
+    #[case::secure(b"PLAIN SCRAM-SHA-256", false, Ok(Mechanism::ScramSha256))]
+    #[case::unknown(b"X-CUSTOM CRAM-MD5", true, Err(NegotiateError::Unsupported))]
+    #[case::empty(b"", true, Err(NegotiateError::Unsupported))]
+    fn negotiate(
+        #[case] advertised: &[u8],
+        #[case] plaintext: bool,
+        #[case] expected: Result<Mechanism, NegotiateError>,
+    ) {
+        let preference = [Mechanism::ScramSha256, Mechanism::Plain, Mechanism::Login];
+        assert_eq!(
+            super::negotiate(advertised, &preference, plaintext),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::text(b"user", "dXNlcg==\r\n")]
+    #[case::empty(b"", "\r\n")]
+    fn continuation(#[case] response: &[u8], #[case] expected: &str) {
+        assert_eq!(Auth::continuation(response), expected);
+    }
+}
@@ -0,0 +1,148 @@
+use super::*;
+
+/// # Pipelined Command Batch
+///
+/// Buffers commands for a server that advertised `PIPELINING` into one contiguous write, ending
+/// each batch where the client must await a reply before sending more.
+///
+/// The content of `DATA` cannot be sent until the server replies `354`, so [`Batch::push`] writes
+/// only the `DATA` command line; write the content afterwards with [`Batch::push_data`].
+///
+/// <https://datatracker.ietf.org/doc/html/rfc2920#section-3.1>
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Batch, Command};
+/// let mut batch = Batch::new();
+/// for command in ["MAIL FROM:<bob@example.com>", "RCPT TO:<alice@example.com>", "DATA"] {
+///     let command = Command::try_from(Bytes::from(command))?;
+///     if batch.push(&command) {
+///         break;
+///     }
+/// }
+///
+/// assert_eq!(
+///     batch.take(),
+///     "MAIL FROM:<bob@example.com>\r\nRCPT TO:<alice@example.com>\r\nDATA\r\n"
+/// );
+/// assert_eq!(batch.commands(), 0);
+/// # Ok::<(), smtpkit::Error>(())
+/// ```
+#[derive(derive_more::Debug, Default, Clone)]
+pub struct Batch {
+    #[debug(skip)]
+    buf: BytesMut,
+    commands: usize,
+}
+
+impl Batch {
+    /// Create an empty `Batch`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `command`, returning whether the batch should now be sent, as the reply to
+    /// `command` must be awaited before sending anything more.
+    pub fn push(&mut self, command: &Command) -> bool {
+        match command {
+            Command::Data(_) => self.buf.extend_from_slice(b"DATA\r\n"),
+            command => command.to_bytes_into(&mut self.buf),
+        }
+        self.commands += 1;
+        ends_group(command)
+    }
+
+    /// Append the dot-stuffed `payload` of a `DATA` command and the terminating `.`, once the
+    /// server has replied `354`. The batch should then be sent.
+    pub fn push_data(&mut self, payload: &Bytes) {
+        self.buf.extend_from_slice(payload);
+        self.buf.extend_from_slice(b"\r\n.\r\n");
+    }
+
+    /// The number of commands in the batch, each awaiting a reply once sent.
+    #[must_use]
+    pub const fn commands(&self) -> usize {
+        self.commands
+    }
+
+    /// Whether the batch is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Take the bytes to write, leaving the `Batch` empty.
+    pub fn take(&mut self) -> Bytes {
+        self.commands = 0;
+        self.buf.split().freeze()
+    }
+}
+
+/// Whether the client must await the reply to `command` before sending anything more, with
+/// `PIPELINING`.
+///
+/// `MAIL`, `RCPT`, `RSET`, and `BDAT` may be followed by more commands; any other command ends
+/// the group.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc2920#section-3.1>
+#[must_use]
+pub const fn ends_group(command: &Command) -> bool {
+    !matches!(
+        command,
+        Command::Mail(_) | Command::Rcpt(_) | Command::Rset | Command::Bdat(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rcpt() -> Command {
+        let to = unsafe { Email::new_unchecked(Bytes::from("alice@example.com")) };
+        Command::Rcpt(Box::new(rcpt::Rcpt::builder(to).build()))
+    }
+
+    #[test]
+    fn batch() {
+        let mut batch = Batch::new();
+        assert!(batch.is_empty());
+        assert!(!batch.push(&Command::Rset));
+        assert!(!batch.push(&rcpt()));
+        assert!(!batch.push(&rcpt()));
+        assert!(batch.push(&Command::Data(Bytes::from("Hi!"))));
+        assert_eq!(batch.commands(), 4);
+        assert_eq!(
+            batch.take(),
+            "RSET\r\nRCPT TO:<alice@example.com>\r\nRCPT TO:<alice@example.com>\r\nDATA\r\n"
+        );
+        assert!(batch.is_empty());
+
+        batch.push_data(&Bytes::from("Hi!"));
+        assert!(batch.push(&Command::Quit));
+        assert_eq!(batch.take(), "Hi!\r\n.\r\nQUIT\r\n");
+    }
+
+    #[test]
+    fn commands() {
+        let mut batch = Batch::new();
+        assert!(batch.push(&Command::StartTls));
+        assert!(batch.push(&Command::Help(Some(Bytes::from("MAIL")))));
+        assert!(batch.push(&Command::Vrfy(Bytes::from("alice"))));
+        assert!(batch.push(&Command::Expn(Bytes::from("staff"))));
+        assert!(batch.push(&Command::Noop));
+        assert_eq!(
+            batch.take(),
+            "STARTTLS\r\nHELP MAIL\r\nVRFY alice\r\nEXPN staff\r\nNOOP\r\n"
+        );
+    }
+
+    #[test]
+    fn bdat() {
+        let mut batch = Batch::new();
+        for chunk in Bdat::chunks(Bytes::from("Hello!"), NonZeroUsize::new(4).unwrap()) {
+            assert!(!batch.push(&Command::Bdat(chunk)));
+        }
+        assert_eq!(batch.take(), "BDAT 4\r\nHellBDAT 2 LAST\r\no!");
+    }
+}
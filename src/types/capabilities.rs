@@ -0,0 +1,455 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+/// # `EHLO` Extension
+///
+/// One keyword line of a reply to `EHLO`, with its parameters. Serialized with [`ToBytes`] as the
+/// text of the line, without the reply code.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Extension, Mechanism, ToBytes};
+/// let auth = Extension::parse(&Bytes::from("auth PLAIN LOGIN"));
+/// assert_eq!(auth, Extension::Auth(vec![Mechanism::Plain, Mechanism::Login]));
+/// assert_eq!(auth.to_bytes(), "AUTH PLAIN LOGIN");
+/// assert_eq!(Extension::Size(35_882_577).to_bytes(), "SIZE 35882577");
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// `PIPELINING`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc2920>
+    Pipelining,
+    /// `STARTTLS`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3207>
+    StartTls,
+    /// `SIZE`, with the maximum message size, `0` if there is no fixed maximum.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870>
+    Size(usize),
+    /// `8BITMIME`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6152>
+    EightBitMime,
+    /// `BINARYMIME`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3030>
+    BinaryMime,
+    /// `CHUNKING`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3030>
+    Chunking,
+    /// `DSN`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3461>
+    Dsn,
+    /// `SMTPUTF8`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6531>
+    SmtpUtf8,
+    /// `ENHANCEDSTATUSCODES`
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc2034>
+    EnhancedStatusCodes,
+    /// `AUTH`, with the mechanisms, in order.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc4954>
+    Auth(Vec<Mechanism>),
+    /// Any other keyword, as advertised, with its parameters, which may be empty.
+    Other(Bytes, Bytes),
+}
+
+impl Extension {
+    /// Parse the text of an `EHLO` reply line.
+    ///
+    /// The keyword is matched ignoring case, and parameters of keywords that take none are
+    /// ignored. `SIZE` with parameters that cannot be parsed is [`Extension::Other`]. `AUTH`
+    /// mechanisms this crate does not know are ignored, and the obsolete `AUTH=` form is
+    /// accepted.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn parse(line: &Bytes) -> Self {
+        let (keyword, params) = match line.find_byteset(b" =") {
+            Some(i) => (line.slice(..i), line.slice(i + 1..)),
+            None => (line.clone(), Bytes::new()),
+        };
+
+        match keyword.to_ascii_uppercase().as_slice() {
+            b"PIPELINING" => Self::Pipelining,
+            b"STARTTLS" => Self::StartTls,
+            b"8BITMIME" => Self::EightBitMime,
+            b"BINARYMIME" => Self::BinaryMime,
+            b"CHUNKING" => Self::Chunking,
+            b"DSN" => Self::Dsn,
+            b"SMTPUTF8" => Self::SmtpUtf8,
+            b"ENHANCEDSTATUSCODES" => Self::EnhancedStatusCodes,
+            b"SIZE" if params.is_empty() => Self::Size(0),
+            b"SIZE" => match usize::from_ascii(&params) {
+                Ok(size) => Self::Size(size),
+                Err(_) => Self::Other(keyword, params),
+            },
+            b"AUTH" => {
+                let mut mechanisms = Vec::new();
+                for mechanism in params.fields().filter_map(Mechanism::from_name) {
+                    if !mechanisms.contains(&mechanism) {
+                        mechanisms.push(mechanism);
+                    }
+                }
+                Self::Auth(mechanisms)
+            }
+            _ => Self::Other(keyword, params),
+        }
+    }
+
+    /// The keyword: as advertised for [`Extension::Other`], otherwise uppercase.
+    #[must_use]
+    pub fn keyword(&self) -> &[u8] {
+        match self {
+            Self::Pipelining => b"PIPELINING",
+            Self::StartTls => b"STARTTLS",
+            Self::Size(_) => b"SIZE",
+            Self::EightBitMime => b"8BITMIME",
+            Self::BinaryMime => b"BINARYMIME",
+            Self::Chunking => b"CHUNKING",
+            Self::Dsn => b"DSN",
+            Self::SmtpUtf8 => b"SMTPUTF8",
+            Self::EnhancedStatusCodes => b"ENHANCEDSTATUSCODES",
+            Self::Auth(_) => b"AUTH",
+            Self::Other(keyword, _) => keyword,
+        }
+    }
+}
+
+/// # `EHLO` Capabilities
+///
+/// The [`Extension`]s a server advertised in its reply to `EHLO`, so a client can decide which it
+/// may use, or a server will advertise, see [`Capabilities::reply`].
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Capabilities, Mechanism, Reply};
+/// let reply = Reply::multiline(
+///     250,
+///     ["mx.example.com Hello", "SIZE 35882577", "AUTH PLAIN LOGIN", "X-FOO bar"].map(Bytes::from),
+/// )?;
+/// let capabilities = Capabilities::parse(&reply).unwrap();
+/// assert_eq!(capabilities.domain(), "mx.example.com");
+/// assert_eq!(capabilities.size(), Some(35_882_577));
+/// assert_eq!(capabilities.auth(), [Mechanism::Plain, Mechanism::Login]);
+/// assert!(!capabilities.pipelining());
+/// assert!(capabilities.contains(b"x-foo"));
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    domain: Bytes,
+    extensions: Vec<Extension>,
+}
+
+impl Capabilities {
+    /// Create `Capabilities` for a server identifying itself as `domain`, with no extensions.
+    #[must_use]
+    pub const fn new(domain: Bytes) -> Self {
+        Self {
+            domain,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Parse a reply to `EHLO`, or `None` if it is not a [positive
+    /// completion](Reply::is_positive_completion). See [`Extension::parse`].
+    ///
+    /// The mechanisms of repeated `AUTH` lines are merged into the first.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn parse(reply: &Reply) -> Option<Self> {
+        if !reply.is_positive_completion() {
+            return None;
+        }
+
+        let (greeting, lines) = reply.lines().split_first()?;
+        let mut capabilities =
+            Self::new(greeting.slice(..greeting.find_byte(b' ').unwrap_or(greeting.len())));
+        for line in lines {
+            capabilities.push(Extension::parse(line));
+        }
+
+        Some(capabilities)
+    }
+
+    /// Add `extension`, merging the mechanisms of an [`Extension::Auth`] into any already added.
+    pub fn push(&mut self, extension: Extension) {
+        let Extension::Auth(mechanisms) = extension else {
+            return self.extensions.push(extension);
+        };
+
+        match self
+            .extensions
+            .iter_mut()
+            .find_map(|extension| match extension {
+                Extension::Auth(auth) => Some(auth),
+                _ => None,
+            }) {
+            Some(auth) => {
+                for mechanism in mechanisms {
+                    if !auth.contains(&mechanism) {
+                        auth.push(mechanism);
+                    }
+                }
+            }
+            None => self.extensions.push(Extension::Auth(mechanisms)),
+        }
+    }
+
+    /// The `250` reply to `EHLO` advertising these capabilities.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{Capabilities, Extension, ToBytes};
+    /// let mut capabilities = Capabilities::new(Bytes::from("mx.example.com"));
+    /// capabilities.push(Extension::Pipelining);
+    /// capabilities.push(Extension::Size(1024));
+    /// assert_eq!(
+    ///     capabilities.reply()?.to_bytes(),
+    ///     "250-mx.example.com\r\n250-PIPELINING\r\n250 SIZE 1024\r\n"
+    /// );
+    /// # Ok::<(), smtpkit::ReplyError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the domain or an extension is not a valid reply line, see [`ReplyError`].
+    pub fn reply(&self) -> Result<Reply, ReplyError> {
+        Reply::multiline(
+            250,
+            [self.domain.clone()].into_iter().chain(
+                self.extensions
+                    .iter()
+                    .map(|extension| extension.to_bytes().freeze()),
+            ),
+        )
+    }
+
+    /// The domain the server identified itself with, the first word of the reply.
+    #[must_use]
+    pub const fn domain(&self) -> &Bytes {
+        &self.domain
+    }
+
+    /// The extensions, in the order advertised.
+    #[must_use]
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Whether `keyword` was advertised, ignoring case, whether known or not.
+    #[must_use]
+    pub fn contains(&self, keyword: &[u8]) -> bool {
+        self.extensions
+            .iter()
+            .any(|extension| extension.keyword().eq_ignore_ascii_case(keyword))
+    }
+
+    /// Whether `PIPELINING` was advertised.
+    #[must_use]
+    pub fn pipelining(&self) -> bool {
+        self.extensions.contains(&Extension::Pipelining)
+    }
+
+    /// Whether `STARTTLS` was advertised.
+    #[must_use]
+    pub fn starttls(&self) -> bool {
+        self.extensions.contains(&Extension::StartTls)
+    }
+
+    /// The maximum message size advertised with `SIZE`, `0` if there is no fixed maximum, or
+    /// `None` if `SIZE` was not advertised.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc1870#section-4>
+    #[must_use]
+    pub fn size(&self) -> Option<usize> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                Extension::Size(size) => Some(*size),
+                _ => None,
+            })
+    }
+
+    /// Whether `8BITMIME` was advertised.
+    #[must_use]
+    pub fn eight_bit_mime(&self) -> bool {
+        self.extensions.contains(&Extension::EightBitMime)
+    }
+
+    /// Whether `BINARYMIME` was advertised.
+    #[must_use]
+    pub fn binary_mime(&self) -> bool {
+        self.extensions.contains(&Extension::BinaryMime)
+    }
+
+    /// Whether `CHUNKING` was advertised.
+    #[must_use]
+    pub fn chunking(&self) -> bool {
+        self.extensions.contains(&Extension::Chunking)
+    }
+
+    /// Whether `DSN` was advertised.
+    #[must_use]
+    pub fn dsn(&self) -> bool {
+        self.extensions.contains(&Extension::Dsn)
+    }
+
+    /// Whether `SMTPUTF8` was advertised.
+    #[must_use]
+    pub fn smtputf8(&self) -> bool {
+        self.extensions.contains(&Extension::SmtpUtf8)
+    }
+
+    /// Whether `ENHANCEDSTATUSCODES` was advertised, so replies should be parsed with
+    /// [`ReplyParser::with_enhanced_codes`](crate::ReplyParser::with_enhanced_codes).
+    #[must_use]
+    pub fn enhanced_status_codes(&self) -> bool {
+        self.extensions.contains(&Extension::EnhancedStatusCodes)
+    }
+
+    /// The `AUTH` mechanisms advertised, in order, empty if `AUTH` was not advertised.
+    #[must_use]
+    pub fn auth(&self) -> &[Mechanism] {
+        self.extensions
+            .iter()
+            .find_map(|extension| match extension {
+                Extension::Auth(mechanisms) => Some(mechanisms.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The keywords not known to this crate, with their parameters, as advertised.
+    pub fn unknown(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.extensions
+            .iter()
+            .filter_map(|extension| match extension {
+                Extension::Other(keyword, params) => Some((keyword, params)),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn ehlo(lines: &[&'static str]) -> Capabilities {
+        let reply = Reply::multiline(250, lines.iter().copied().map(Bytes::from)).unwrap();
+        Capabilities::parse(&reply).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let capabilities = ehlo(&[
+            "mx.example.com",
+            "pipelining",
+            "STARTTLS",
+            "SIZE",
+            "8BITMIME",
+            "BINARYMIME",
+            "CHUNKING",
+            "DSN",
+            "SMTPUTF8",
+            "ENHANCEDSTATUSCODES",
+            "AUTH=LOGIN",
+            "AUTH PLAIN LOGIN X-UNKNOWN",
+            "X-FOO bar baz",
+        ]);
+
+        assert_eq!(capabilities.domain(), "mx.example.com");
+        assert!(capabilities.pipelining());
+        assert!(capabilities.starttls());
+        assert_eq!(capabilities.size(), Some(0));
+        assert!(capabilities.eight_bit_mime());
+        assert!(capabilities.binary_mime());
+        assert!(capabilities.chunking());
+        assert!(capabilities.dsn());
+        assert!(capabilities.smtputf8());
+        assert!(capabilities.enhanced_status_codes());
+        assert_eq!(capabilities.auth(), [Mechanism::Login, Mechanism::Plain]);
+        assert_eq!(
+            capabilities.unknown().collect::<Vec<_>>(),
+            [(&Bytes::from("X-FOO"), &Bytes::from("bar baz"))]
+        );
+        assert!(capabilities.contains(b"Pipelining"));
+        assert!(capabilities.contains(b"x-foo"));
+        assert!(!capabilities.contains(b"X-BAR"));
+    }
+
+    #[test]
+    fn minimal() {
+        let capabilities = ehlo(&["mx.example.com Hello client.example.com [192.0.2.1]"]);
+        assert_eq!(capabilities.domain(), "mx.example.com");
+        assert_eq!(
+            capabilities,
+            Capabilities::new(Bytes::from("mx.example.com"))
+        );
+        assert!(capabilities.auth().is_empty());
+        assert!(!capabilities.contains(b"AUTH"));
+    }
+
+    #[test]
+    fn invalid_size() {
+        let capabilities = ehlo(&["mx.example.com", "SIZE lots"]);
+        assert_eq!(capabilities.size(), None);
+        assert!(capabilities.contains(b"SIZE"));
+    }
+
+    #[test]
+    fn not_ehlo() {
+        let reply = Reply::new(502, Bytes::from("Command not implemented")).unwrap();
+        assert_eq!(Capabilities::parse(&reply), None);
+    }
+
+    #[rstest]
+    #[case::flag("PIPELINING", Extension::Pipelining, "PIPELINING")]
+    #[case::flag_params("8bitmime x", Extension::EightBitMime, "8BITMIME")]
+    #[case::size("SIZE 1000", Extension::Size(1000), "SIZE 1000")]
+    #[case::size_unlimited("SIZE 0", Extension::Size(0), "SIZE")]
+    #[case::size_invalid(
+        "SIZE lots",
+        Extension::Other(Bytes::from("SIZE"), Bytes::from("lots")),
+        "SIZE lots"
+    )]
+    #[case::auth(
+        "AUTH=PLAIN X-UNKNOWN PLAIN",
+        Extension::Auth(vec![Mechanism::Plain]),
+        "AUTH PLAIN"
+    )]
+    #[case::auth_empty("AUTH", Extension::Auth(Vec::new()), "AUTH")]
+    #[case::other(
+        "X-Foo bar",
+        Extension::Other(Bytes::from("X-Foo"), Bytes::from("bar")),
+        "X-Foo bar"
+    )]
+    #[case::other_bare("X-FOO", Extension::Other(Bytes::from("X-FOO"), Bytes::new()), "X-FOO")]
+    fn extension(
+        #[case] line: &'static str,
+        #[case] expected: Extension,
+        #[case] serialized: &str,
+    ) {
+        let extension = Extension::parse(&Bytes::from(line));
+        assert_eq!(extension, expected);
+        assert_eq!(extension.to_bytes(), serialized);
+    }
+
+    #[test]
+    fn reply() {
+        let capabilities = ehlo(&["mx.example.com", "PIPELINING", "AUTH LOGIN", "X-FOO bar"]);
+        assert_eq!(
+            capabilities.reply().unwrap().to_bytes(),
+            "250-mx.example.com\r\n250-PIPELINING\r\n250-AUTH LOGIN\r\n250 X-FOO bar\r\n"
+        );
+    }
+}
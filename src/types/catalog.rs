@@ -0,0 +1,280 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::*;
+
+/// # Reply Situation
+///
+/// A situation a server replies to, with a default code, [`EnhancedCode`], and text, which a
+/// [`ReplyCatalog`] may override.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Situation {
+    /// `220 {hostname} ESMTP`
+    Greeting,
+    /// `221 2.0.0 {hostname} Bye`, the reply to `QUIT`.
+    Closing,
+    /// `250 2.0.0 OK`
+    Ok,
+    /// `250 2.0.0 OK: queued as {queue_id}`, the reply to the end of a message.
+    Queued,
+    /// `550 5.1.1 <{recipient}>: Mailbox unavailable`
+    MailboxUnavailable,
+    /// `550 5.7.1 Rejected by policy`
+    PolicyRejection,
+    /// `451 4.7.1 Try again later`
+    TempFail,
+    /// `421 4.3.2 {hostname} Service not available, closing transmission channel`
+    ServiceUnavailable,
+}
+
+impl Situation {
+    /// The reply code.
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::Greeting => 220,
+            Self::Closing => 221,
+            Self::Ok | Self::Queued => 250,
+            Self::MailboxUnavailable | Self::PolicyRejection => 550,
+            Self::TempFail => 451,
+            Self::ServiceUnavailable => 421,
+        }
+    }
+
+    /// The enhanced status code, if any; the greeting has none.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc2034#section-3>
+    #[must_use]
+    pub const fn enhanced_code(self) -> Option<EnhancedCode> {
+        match self {
+            Self::Greeting => None,
+            Self::Closing | Self::Ok | Self::Queued => Some(EnhancedCode::OK),
+            Self::MailboxUnavailable => EnhancedCode::new(5, 1, 1),
+            Self::PolicyRejection => EnhancedCode::new(5, 7, 1),
+            Self::TempFail => EnhancedCode::new(4, 7, 1),
+            Self::ServiceUnavailable => EnhancedCode::new(4, 3, 2),
+        }
+    }
+
+    /// The default template.
+    const fn template(self) -> &'static [u8] {
+        match self {
+            Self::Greeting => b"{hostname} ESMTP",
+            Self::Closing => b"{hostname} Bye",
+            Self::Ok => b"OK",
+            Self::Queued => b"OK: queued as {queue_id}",
+            Self::MailboxUnavailable => b"<{recipient}>: Mailbox unavailable",
+            Self::PolicyRejection => b"Rejected by policy",
+            Self::TempFail => b"Try again later",
+            Self::ServiceUnavailable => {
+                b"{hostname} Service not available, closing transmission channel"
+            }
+        }
+    }
+}
+
+/// # Placeholder Values
+///
+/// The values substituted for the placeholders of a [`ReplyCatalog`] template. Any left empty
+/// are substituted as empty.
+#[derive(derive_more::Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Placeholders<'a> {
+    /// `{hostname}`: the server's own hostname.
+    #[debug("{:?}", hostname.as_bstr())]
+    pub hostname: &'a [u8],
+    /// `{client}`: the client's host, e.g. the identity it sent with `EHLO`.
+    #[debug("{:?}", client.as_bstr())]
+    pub client: &'a [u8],
+    /// `{recipient}`: the recipient being replied to.
+    #[debug("{:?}", Redact::email(recipient))]
+    pub recipient: &'a [u8],
+    /// `{queue_id}`: the queue ID assigned to the message.
+    #[debug("{:?}", queue_id.as_bstr())]
+    pub queue_id: &'a [u8],
+}
+
+impl Placeholders<'_> {
+    /// The value of the placeholder `name`, the text between the braces.
+    fn get(&self, name: &[u8]) -> Option<&[u8]> {
+        match name {
+            b"hostname" => Some(self.hostname),
+            b"client" => Some(self.client),
+            b"recipient" => Some(self.recipient),
+            b"queue_id" => Some(self.queue_id),
+            _ => None,
+        }
+    }
+}
+
+/// # Reply Text Catalog
+///
+/// Maps each [`Situation`] to templated text, so operators can brand or localize replies. A
+/// template may contain the placeholders of [`Placeholders`], e.g. `{hostname}`; an unknown
+/// placeholder is kept as written.
+///
+/// The code and enhanced status code of a situation are fixed, and substituted values are
+/// sanitized, so every reply rendered is valid on the wire whatever the values hold: CR and LF
+/// are removed, and text too long for a reply line is truncated.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Placeholders, ReplyCatalog, Situation, ToBytes};
+/// let catalog = ReplyCatalog::new()
+///     .with_template(Situation::Greeting, Bytes::from("{hostname} ESMTP ready for {client}"))?;
+/// let placeholders = Placeholders {
+///     hostname: b"mx.example.com",
+///     client: b"192.0.2.1\r\n",
+///     ..Placeholders::default()
+/// };
+/// assert_eq!(
+///     catalog.reply(Situation::Greeting, &placeholders).to_bytes(),
+///     "220 mx.example.com ESMTP ready for 192.0.2.1\r\n"
+/// );
+/// assert_eq!(
+///     catalog.reply(Situation::Closing, &placeholders).to_bytes(),
+///     "221 2.0.0 mx.example.com Bye\r\n"
+/// );
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ReplyCatalog {
+    templates: BTreeMap<Situation, Bytes>,
+}
+
+impl ReplyCatalog {
+    /// Create a `ReplyCatalog` with the default template of every situation.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `template` for `situation`.
+    ///
+    /// # Errors
+    ///
+    /// If `template` contains a CR or LF, [`ReplyError::InvalidText`].
+    pub fn with_template(
+        mut self,
+        situation: Situation,
+        template: Bytes,
+    ) -> Result<Self, ReplyError> {
+        if template.find_byteset(b"\r\n").is_some() {
+            return Err(ReplyError::InvalidText);
+        }
+
+        self.templates.insert(situation, template);
+        Ok(self)
+    }
+
+    /// The template for `situation`.
+    #[must_use]
+    pub fn template(&self, situation: Situation) -> &[u8] {
+        self.templates
+            .get(&situation)
+            .map_or(situation.template(), |template| &template[..])
+    }
+
+    /// Render the reply for `situation`, substituting `placeholders`.
+    #[must_use]
+    pub fn reply(&self, situation: Situation, placeholders: &Placeholders) -> Reply {
+        // room for the code, separator, and the longest enhanced status code, `x.999.999 `
+        const MAX_TEXT: usize = max::REPLY_LINE - 4 - 10;
+
+        let mut template = self.template(situation);
+        let mut text = Vec::with_capacity(template.len());
+        while let Some(start) = template.find_byte(b'{') {
+            text.extend_from_slice(&template[..start]);
+            let rest = &template[start..];
+            match rest
+                .find_byte(b'}')
+                .and_then(|end| Some((end, placeholders.get(&rest[1..end])?)))
+            {
+                Some((end, value)) => {
+                    text.extend(value.iter().filter(|&&b| !matches!(b, b'\r' | b'\n')));
+                    template = &rest[end + 1..];
+                }
+                None => {
+                    text.push(b'{');
+                    template = &rest[1..];
+                }
+            }
+        }
+        text.extend_from_slice(template);
+        text.truncate(MAX_TEXT);
+
+        let mut reply = Reply::new(situation.code(), Bytes::from(text))
+            .expect("sanitized text and a fixed code are a valid reply");
+        reply
+            .set_enhanced_code(situation.enhanced_code())
+            .expect("the enhanced status code matches the code");
+        reply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const PLACEHOLDERS: Placeholders = Placeholders {
+        hostname: b"mx.example.com",
+        client: b"client.example.com",
+        recipient: b"alice@example.com",
+        queue_id: b"4F2A1",
+    };
+
+    #[rstest]
+    #[case::greeting(Situation::Greeting, "220 mx.example.com ESMTP\r\n")]
+    #[case::queued(Situation::Queued, "250 2.0.0 OK: queued as 4F2A1\r\n")]
+    #[case::mailbox(
+        Situation::MailboxUnavailable,
+        "550 5.1.1 <alice@example.com>: Mailbox unavailable\r\n"
+    )]
+    #[case::temp_fail(Situation::TempFail, "451 4.7.1 Try again later\r\n")]
+    fn defaults(#[case] situation: Situation, #[case] expected: &str) {
+        let reply = ReplyCatalog::new().reply(situation, &PLACEHOLDERS);
+        assert_eq!(reply.to_bytes(), expected);
+    }
+
+    #[rstest]
+    #[case::substituted(
+        b"Hello {client}, {hostname} here",
+        "Hello client.example.com, mx.example.com here"
+    )]
+    #[case::unknown(b"{unknown} {hostname}", "{unknown} mx.example.com")]
+    #[case::unclosed(b"{hostname {queue_id}", "{hostname 4F2A1")]
+    #[case::braces(b"{}{{queue_id}}", "{}{4F2A1}")]
+    fn templates(#[case] template: &'static [u8], #[case] expected: &str) {
+        let catalog = ReplyCatalog::new()
+            .with_template(Situation::Ok, Bytes::from_static(template))
+            .unwrap();
+        let reply = catalog.reply(Situation::Ok, &PLACEHOLDERS);
+        assert_eq!(reply.lines(), [expected]);
+    }
+
+    #[test]
+    fn sanitized() {
+        assert_eq!(
+            ReplyCatalog::new().with_template(Situation::Ok, Bytes::from("OK\r\n250 Injected")),
+            Err(ReplyError::InvalidText)
+        );
+
+        let long = vec![b'a'; 1024];
+        let placeholders = Placeholders {
+            client: b"evil\r\n250 Injected",
+            queue_id: &long,
+            ..PLACEHOLDERS
+        };
+        let catalog = ReplyCatalog::new()
+            .with_template(Situation::Ok, Bytes::from("{client}"))
+            .unwrap();
+        assert_eq!(
+            catalog.reply(Situation::Ok, &placeholders).to_bytes(),
+            "250 2.0.0 evil250 Injected\r\n"
+        );
+
+        let reply = ReplyCatalog::new().reply(Situation::Queued, &placeholders);
+        assert_eq!(reply.lines()[0].len(), max::REPLY_LINE - 14);
+    }
+}
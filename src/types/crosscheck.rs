@@ -0,0 +1,309 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+/// # Parameter Inconsistency
+///
+/// A rule between the parameters of a transaction, the extensions offered, and the message
+/// content that a transaction breaks, found by the `crosscheck` methods of [`Mail`], [`Rcpt`],
+/// and [`Envelope`].
+///
+/// `RET` and `ENVID` are only meaningful on `MAIL`; a `RCPT` cannot carry them, as they are
+/// refused when parsing it.
+#[non_exhaustive]
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Inconsistency {
+    /// `RET`, `ENVID`, `ORCPT`, or `NOTIFY` when `DSN` was not offered.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3461#section-4>
+    #[display("DSN parameters without DSN")]
+    DsnNotOffered,
+    /// `AUTH=` when `AUTH` was not offered.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc4954#section-5>
+    #[display("AUTH parameter without AUTH")]
+    AuthNotOffered,
+    /// `BODY=8BITMIME` when `8BITMIME` was not offered.
+    #[display("BODY=8BITMIME without 8BITMIME")]
+    EightBitMimeNotOffered,
+    /// `BODY=BINARYMIME` when `BINARYMIME` and `CHUNKING` were not both offered.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc3030#section-3>
+    #[display("BODY=BINARYMIME without BINARYMIME and CHUNKING")]
+    BinaryMimeNotOffered,
+    /// Content with 8-bit bytes, declared `7BIT` or with no `BODY`.
+    #[display("8-bit content without BODY=8BITMIME")]
+    EightBitContent,
+    /// Content with NUL, a bare CR or LF, or a line longer than [`max::DATA_LINE`], not declared
+    /// `BODY=BINARYMIME`.
+    #[display("Binary content without BODY=BINARYMIME")]
+    BinaryContent,
+}
+
+impl Inconsistency {
+    /// Whether a conforming server refuses the transaction, usually with `555`, rather than
+    /// commonly tolerating it, as with 8-bit content declared `7BIT`. A policy may treat any
+    /// inconsistency as it sees fit.
+    #[must_use]
+    pub const fn is_error(self) -> bool {
+        !matches!(self, Self::EightBitContent | Self::BinaryContent)
+    }
+}
+
+impl Body {
+    /// The least `BODY` the `content` requires, one segment at a time, e.g. the segments of a
+    /// [`Payload`].
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6152#section-3>
+    #[must_use]
+    pub fn required<'a>(content: impl IntoIterator<Item = &'a Bytes>) -> Self {
+        let mut body = Self::SevenBit;
+        let mut line = 0;
+        let mut cr = false;
+        for &b in content.into_iter().flat_map(|segment| segment.iter()) {
+            // a CR not followed by LF, or an LF not preceded by CR
+            if cr != (b == b'\n') {
+                return Self::BinaryMime;
+            }
+
+            match b {
+                b'\r' => {}
+                b'\n' => line = 0,
+                0 => return Self::BinaryMime,
+                _ => {
+                    line += 1;
+                    if line > max::DATA_LINE {
+                        return Self::BinaryMime;
+                    }
+                    if !b.is_ascii() {
+                        body = Self::EightBitMime;
+                    }
+                }
+            }
+            cr = b == b'\r';
+        }
+
+        match cr {
+            true => Self::BinaryMime,
+            false => body,
+        }
+    }
+}
+
+impl Mail {
+    /// Check the parameters against the extensions `offered`, returning the inconsistencies found.
+    #[must_use]
+    pub fn crosscheck(&self, offered: &Capabilities) -> Vec<Inconsistency> {
+        let mut inconsistencies = Vec::new();
+        if (self.ret.is_some() || self.envid.is_some()) && !offered.dsn() {
+            inconsistencies.push(Inconsistency::DsnNotOffered);
+        }
+        if self.auth.is_some() && !offered.contains(b"AUTH") {
+            inconsistencies.push(Inconsistency::AuthNotOffered);
+        }
+        match self.body {
+            Some(Body::EightBitMime) if !offered.eight_bit_mime() => {
+                inconsistencies.push(Inconsistency::EightBitMimeNotOffered);
+            }
+            Some(Body::BinaryMime) if !(offered.binary_mime() && offered.chunking()) => {
+                inconsistencies.push(Inconsistency::BinaryMimeNotOffered);
+            }
+            _ => {}
+        }
+
+        inconsistencies
+    }
+}
+
+impl Rcpt {
+    /// Check the parameters against the extensions `offered`, returning the inconsistencies found.
+    #[must_use]
+    pub fn crosscheck(&self, offered: &Capabilities) -> Vec<Inconsistency> {
+        match (self.orcpt.is_some() || self.notify.is_some()) && !offered.dsn() {
+            true => Vec::from([Inconsistency::DsnNotOffered]),
+            false => Vec::new(),
+        }
+    }
+}
+
+impl Envelope {
+    /// Check the parameters of the `MAIL` and every `RCPT` against the extensions `offered`, and
+    /// the `BODY` against the payload, returning each inconsistency found once.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{Capabilities, Command, Envelope, Inconsistency, Payload};
+    /// let Command::Mail(mail) = Command::try_from(Bytes::from("MAIL FROM:<> RET=HDRS"))? else {
+    ///     unreachable!()
+    /// };
+    /// let envelope = Envelope {
+    ///     mail: *mail,
+    ///     rcpts: Vec::new(),
+    ///     payload: Payload::from(Bytes::from("Subject: caf\u{e9}\r\n\r\nHi!")),
+    /// };
+    /// let inconsistencies = envelope.crosscheck(&Capabilities::default());
+    /// assert_eq!(
+    ///     inconsistencies,
+    ///     [Inconsistency::DsnNotOffered, Inconsistency::EightBitContent]
+    /// );
+    /// assert!(inconsistencies[0].is_error());
+    /// assert!(!inconsistencies[1].is_error());
+    /// # Ok::<(), smtpkit::Error>(())
+    /// ```
+    #[must_use]
+    pub fn crosscheck(&self, offered: &Capabilities) -> Vec<Inconsistency> {
+        let mut inconsistencies = self.mail.crosscheck(offered);
+        for inconsistency in self.rcpts.iter().flat_map(|rcpt| rcpt.crosscheck(offered)) {
+            if !inconsistencies.contains(&inconsistency) {
+                inconsistencies.push(inconsistency);
+            }
+        }
+
+        match (Body::required(self.payload.segments()), self.mail.body) {
+            (Body::BinaryMime, Some(Body::BinaryMime)) | (Body::SevenBit, _) => {}
+            (Body::BinaryMime, _) => inconsistencies.push(Inconsistency::BinaryContent),
+            (Body::EightBitMime, None | Some(Body::SevenBit)) => {
+                inconsistencies.push(Inconsistency::EightBitContent);
+            }
+            (Body::EightBitMime, _) => {}
+        }
+
+        inconsistencies
+    }
+}
+
+#[cfg(all(test, feature = "parse"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn capabilities(lines: &[&'static str]) -> Capabilities {
+        let lines = ["mx.example.com"]
+            .iter()
+            .chain(lines)
+            .copied()
+            .map(Bytes::from);
+        Capabilities::parse(&Reply::multiline(250, lines).unwrap()).unwrap()
+    }
+
+    fn mail(command: &'static str) -> Mail {
+        match Command::try_from(Bytes::from(command)).unwrap() {
+            Command::Mail(mail) => *mail,
+            _ => unreachable!(),
+        }
+    }
+
+    fn rcpt(command: &'static str) -> Rcpt {
+        match Command::try_from(Bytes::from(command)).unwrap() {
+            Command::Rcpt(rcpt) => *rcpt,
+            _ => unreachable!(),
+        }
+    }
+
+    #[rstest]
+    #[case::none("MAIL FROM:<>", &[], &[])]
+    #[case::ret("MAIL FROM:<> RET=FULL", &[], &[Inconsistency::DsnNotOffered])]
+    #[case::envid("MAIL FROM:<> ENVID=abc", &[], &[Inconsistency::DsnNotOffered])]
+    #[case::dsn("MAIL FROM:<> RET=FULL ENVID=abc", &["DSN"], &[])]
+    #[case::auth("MAIL FROM:<> AUTH=<>", &["DSN"], &[Inconsistency::AuthNotOffered])]
+    #[case::auth_offered("MAIL FROM:<> AUTH=<>", &["AUTH X-UNKNOWN"], &[])]
+    #[case::eight_bit("MAIL FROM:<> BODY=8BITMIME", &[], &[Inconsistency::EightBitMimeNotOffered])]
+    #[case::eight_bit_offered("MAIL FROM:<> BODY=8BITMIME", &["8BITMIME"], &[])]
+    #[case::binary(
+        "MAIL FROM:<> BODY=BINARYMIME",
+        &["BINARYMIME"],
+        &[Inconsistency::BinaryMimeNotOffered]
+    )]
+    #[case::binary_offered("MAIL FROM:<> BODY=BINARYMIME", &["BINARYMIME", "CHUNKING"], &[])]
+    #[case::all(
+        "MAIL FROM:<> RET=HDRS AUTH=<> BODY=8BITMIME",
+        &[],
+        &[
+            Inconsistency::DsnNotOffered,
+            Inconsistency::AuthNotOffered,
+            Inconsistency::EightBitMimeNotOffered,
+        ]
+    )]
+    fn mail_parameters(
+        #[case] command: &'static str,
+        #[case] offered: &[&'static str],
+        #[case] expected: &[Inconsistency],
+    ) {
+        assert_eq!(mail(command).crosscheck(&capabilities(offered)), expected);
+    }
+
+    #[rstest]
+    #[case::none("RCPT TO:<alice@example.com>", &[], &[])]
+    #[case::notify(
+        "RCPT TO:<alice@example.com> NOTIFY=NEVER",
+        &[],
+        &[Inconsistency::DsnNotOffered]
+    )]
+    #[case::orcpt(
+        "RCPT TO:<alice@example.com> ORCPT=rfc822;alice@example.com",
+        &[],
+        &[Inconsistency::DsnNotOffered]
+    )]
+    #[case::dsn("RCPT TO:<alice@example.com> NOTIFY=NEVER", &["DSN"], &[])]
+    fn rcpt_parameters(
+        #[case] command: &'static str,
+        #[case] offered: &[&'static str],
+        #[case] expected: &[Inconsistency],
+    ) {
+        assert_eq!(rcpt(command).crosscheck(&capabilities(offered)), expected);
+    }
+
+    #[rstest]
+    #[case::seven_bit(&[b"Hi!\r\n"], Body::SevenBit)]
+    #[case::empty(&[], Body::SevenBit)]
+    #[case::eight_bit(&[b"caf\xc3\xa9\r\n"], Body::EightBitMime)]
+    #[case::split_crlf(&[b"Hi!\r", b"\nYo!"], Body::SevenBit)]
+    #[case::nul(&[b"Hi\0"], Body::BinaryMime)]
+    #[case::bare_lf(&[b"Hi!\nYo!"], Body::BinaryMime)]
+    #[case::bare_cr(&[b"Hi!\rYo!"], Body::BinaryMime)]
+    #[case::trailing_cr(&[b"Hi!\r"], Body::BinaryMime)]
+    #[case::long_line(&[&[b'a'; max::DATA_LINE + 1]], Body::BinaryMime)]
+    #[case::max_line(&[&[b'a'; max::DATA_LINE], b"\r\n"], Body::SevenBit)]
+    fn required(#[case] segments: &[&'static [u8]], #[case] expected: Body) {
+        let segments: Vec<_> = segments.iter().copied().map(Bytes::from_static).collect();
+        assert_eq!(Body::required(&segments), expected);
+    }
+
+    #[rstest]
+    #[case::declared("MAIL FROM:<> BODY=8BITMIME", b"caf\xc3\xa9", &[])]
+    #[case::undeclared("MAIL FROM:<>", b"caf\xc3\xa9", &[Inconsistency::EightBitContent])]
+    #[case::binary("MAIL FROM:<> BODY=8BITMIME", b"\0", &[Inconsistency::BinaryContent])]
+    #[case::binary_declared("MAIL FROM:<> BODY=BINARYMIME", b"\0", &[])]
+    fn envelope(
+        #[case] command: &'static str,
+        #[case] payload: &'static [u8],
+        #[case] expected: &[Inconsistency],
+    ) {
+        let offered = capabilities(&["8BITMIME", "BINARYMIME", "CHUNKING", "DSN"]);
+        let envelope = Envelope {
+            mail: mail(command),
+            rcpts: Vec::from([
+                rcpt("RCPT TO:<alice@example.com>"),
+                rcpt("RCPT TO:<bob@example.com>"),
+            ]),
+            payload: Payload::from(Bytes::from_static(payload)),
+        };
+        assert_eq!(envelope.crosscheck(&offered), expected);
+    }
+
+    #[test]
+    fn envelope_dedup() {
+        let envelope = Envelope {
+            mail: mail("MAIL FROM:<>"),
+            rcpts: Vec::from([
+                rcpt("RCPT TO:<alice@example.com> NOTIFY=NEVER"),
+                rcpt("RCPT TO:<bob@example.com> NOTIFY=NEVER"),
+            ]),
+            payload: Payload::from(Bytes::from("Hi!")),
+        };
+        assert_eq!(
+            envelope.crosscheck(&capabilities(&[])),
+            [Inconsistency::DsnNotOffered]
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use alloc::boxed::Box;
+#[cfg(feature = "parse")]
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+
+#[cfg(feature = "parse")]
+use derive_more::Display;
+
+use super::*;
+
+/// The first line of a saved [`Envelope`], including the format version.
+const VERSION: &[u8] = b"SMTPKIT-ENVELOPE/1";
+
+/// # Mail Envelope
+///
+/// A complete mail transaction: the `MAIL` command, its recipients, and the message payload.
+#[derive(derive_more::Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Envelope {
+    /// The `MAIL` command, including the reverse path.
+    pub mail: Mail,
+
+    /// The `RCPT` commands, in the order they were (or will be) sent.
+    pub rcpts: Vec<Rcpt>,
+
+    /// Message payload.
+    #[debug(skip)]
+    pub payload: Payload,
+}
+
+impl Envelope {
+    /// Expand the `Envelope` into the commands needed to transmit it to a server that advertised
+    /// `capabilities`, in order.
+    ///
+    /// Parameters of `MAIL` and `RCPT` for extensions the server did not advertise are dropped:
+    /// `SIZE`; `BODY=7BIT` and `BODY=8BITMIME` without `8BITMIME`, and `BODY=BINARYMIME` without
+    /// `BINARYMIME` and `CHUNKING`; `SMTPUTF8`; `RET`, `ENVID`, `NOTIFY`, and `ORCPT` without
+    /// `DSN`; and `AUTH`, `CONPERM`, `SOLICIT`, `PRDR`, and `CONNEG`. Whether the message can be
+    /// sent without them is up to the caller.
+    ///
+    /// If the server advertised `CHUNKING`, the payload is sent as `BDAT` chunks of at most
+    /// `chunk_size` bytes. Otherwise, it is sent with `DATA`.
+    #[must_use]
+    pub fn commands(&self, capabilities: &Capabilities, chunk_size: NonZeroUsize) -> Vec<Command> {
+        let mut commands = Vec::with_capacity(self.rcpts.len() + 2);
+        commands.push(Command::Mail(Box::new(negotiate_mail(
+            self.mail.clone(),
+            capabilities,
+        ))));
+        commands.extend(
+            self.rcpts
+                .iter()
+                .map(|rcpt| negotiate_rcpt(rcpt.clone(), capabilities))
+                .map(Box::new)
+                .map(Command::Rcpt),
+        );
+
+        if capabilities.chunking() {
+            commands.extend(Bdat::chunks(self.payload.clone(), chunk_size).map(Command::Bdat));
+        } else {
+            commands.push(Command::Data(self.payload.clone().into_bytes()));
+        }
+
+        commands
+    }
+
+    /// Remove recipients with the same address as an earlier one, returning how many were
+    /// removed, so a message is not delivered twice to the same mailbox.
+    ///
+    /// Addresses are compared by [`Email::normalized`]. The first `RCPT` for an address is kept,
+    /// along with its parameters. Check the recipient limit against the remaining count.
+    #[cfg(feature = "parse")]
+    pub fn dedup_recipients(&mut self, case_insensitive: bool) -> usize {
+        let before = self.rcpts.len();
+        let mut seen = BTreeSet::new();
+        self.rcpts
+            .retain(|rcpt| seen.insert(rcpt.to.normalized(case_insensitive).bytes().clone()));
+        before - self.rcpts.len()
+    }
+
+    /// Save the `Envelope` and its payload into `buf`, e.g. for a queue to reload after a
+    /// restart with [`Envelope::restore`].
+    ///
+    /// The format is versioned: a header line, the `MAIL` and `RCPT` command lines, then the
+    /// length of the payload and the payload itself.
+    pub fn save(&self, buf: &mut BytesMut) {
+        self.save_commands(buf);
+        Self::save_section(b"PAYLOAD", self.payload.len(), buf);
+        self.payload.to_bytes_into(buf);
+    }
+
+    /// Save the `Envelope` into `buf` as [`Envelope::save`], but with `reference` to a payload
+    /// stored elsewhere, e.g. a spool file name, instead of the payload.
+    pub fn save_reference(&self, reference: &[u8], buf: &mut BytesMut) {
+        self.save_commands(buf);
+        Self::save_section(b"REFERENCE", reference.len(), buf);
+        buf.extend_from_slice(reference);
+    }
+
+    fn save_commands(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(VERSION);
+        buf.extend_from_slice(b"\r\n");
+        self.mail.to_bytes_into(buf);
+        for rcpt in &self.rcpts {
+            rcpt.to_bytes_into(buf);
+        }
+    }
+
+    fn save_section(name: &[u8], len: usize, buf: &mut BytesMut) {
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(b" ");
+        buf.extend_from_slice(itoa::Buffer::new().format(len).as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    /// Restore an `Envelope` saved with [`Envelope::save`] or [`Envelope::save_reference`].
+    ///
+    /// Returns the payload reference, if one was saved instead of the payload, in which case the
+    /// payload is empty. The payload is zero-copy.
+    ///
+    /// ```rust
+    /// # use bytes::{Bytes, BytesMut};
+    /// # use smtpkit::{Command, Envelope};
+    /// # let Ok(Command::Mail(mail)) = Command::try_from(Bytes::from("MAIL FROM:<bob@example.com>"))
+    /// # else {
+    /// #     unreachable!();
+    /// # };
+    /// let envelope = Envelope {
+    ///     mail: *mail,
+    ///     rcpts: Vec::new(),
+    ///     payload: Bytes::from("Hi!\r\n").into(),
+    /// };
+    ///
+    /// let mut buf = BytesMut::new();
+    /// envelope.save_reference(b"spool/1234", &mut buf);
+    /// let (restored, reference) = Envelope::restore(buf.freeze())?;
+    /// assert_eq!(restored.mail.from, envelope.mail.from);
+    /// assert_eq!(reference, Some(Bytes::from("spool/1234")));
+    /// # Ok::<(), smtpkit::RestoreError>(())
+    /// ```
+    #[cfg(feature = "parse")]
+    pub fn restore(mut saved: Bytes) -> Result<(Self, Option<Bytes>), RestoreError> {
+        if restore_line(&mut saved)? != VERSION {
+            return Err(RestoreError::Version);
+        }
+
+        let Command::Mail(mail) = Command::try_from(restore_line(&mut saved)?)? else {
+            return Err(RestoreError::Invalid(Error::InvalidCommand));
+        };
+
+        let mut rcpts = Vec::new();
+        loop {
+            let line = restore_line(&mut saved)?;
+            let Some((name, len)) = line.split_once_str(" ") else {
+                return Err(RestoreError::Invalid(Error::InvalidSyntax));
+            };
+
+            if name == b"RCPT" {
+                let Command::Rcpt(rcpt) = Command::try_from(line)? else {
+                    unreachable!();
+                };
+                rcpts.push(*rcpt);
+                continue;
+            }
+
+            let len = usize::from_ascii(len).map_err(|_| Error::InvalidSyntax)?;
+            if len > saved.len() {
+                return Err(RestoreError::Truncated);
+            }
+            let section = saved.split_to(len);
+
+            let mut envelope = Self {
+                mail: *mail,
+                rcpts,
+                payload: Payload::new(),
+            };
+            return match name {
+                b"PAYLOAD" => {
+                    envelope.payload = section.into();
+                    Ok((envelope, None))
+                }
+                b"REFERENCE" => Ok((envelope, Some(section))),
+                _ => Err(RestoreError::Invalid(Error::InvalidSyntax)),
+            };
+        }
+    }
+}
+
+/// Drop the parameters of `mail` for extensions not in `capabilities`.
+fn negotiate_mail(mut mail: Mail, capabilities: &Capabilities) -> Mail {
+    if capabilities.size().is_none() {
+        mail.size = None;
+    }
+    mail.body = mail.body.filter(|body| match body {
+        Body::SevenBit | Body::EightBitMime => capabilities.eight_bit_mime(),
+        Body::BinaryMime => capabilities.binary_mime() && capabilities.chunking(),
+    });
+    mail.smtputf8 &= capabilities.smtputf8();
+    if !capabilities.dsn() {
+        mail.ret = None;
+        mail.envid = None;
+    }
+    if capabilities.auth().is_empty() {
+        mail.auth = None;
+    }
+    if !capabilities.contains(b"CONPERM") {
+        mail.conperm = None;
+    }
+    if !capabilities.contains(b"SOLICIT") {
+        mail.solicit = None;
+    }
+    mail.prdr &= capabilities.contains(b"PRDR");
+    mail
+}
+
+/// Drop the parameters of `rcpt` for extensions not in `capabilities`.
+fn negotiate_rcpt(mut rcpt: Rcpt, capabilities: &Capabilities) -> Rcpt {
+    if !capabilities.dsn() {
+        rcpt.notify = None;
+        rcpt.orcpt = None;
+    }
+    if !capabilities.contains(b"CONNEG") {
+        rcpt.conneg = None;
+    }
+    rcpt
+}
+
+/// Split the next line off `saved`, **excluding** the CRLF.
+#[cfg(feature = "parse")]
+fn restore_line(saved: &mut Bytes) -> Result<Bytes, RestoreError> {
+    let end = saved.find(b"\r\n").ok_or(RestoreError::Truncated)?;
+    let mut line = saved.split_to(end + 2);
+    line.truncate(end);
+    Ok(line)
+}
+
+/// # `Envelope` Restore Error
+///
+/// Returned by [`Envelope::restore`].
+#[cfg(feature = "parse")]
+#[derive(Debug, Display, derive_more::Error, Clone, PartialEq, Hash)]
+pub enum RestoreError {
+    /// Not a saved `Envelope`, or saved in an unsupported version of the format.
+    #[display("Unsupported envelope format")]
+    Version,
+    /// The saved `Envelope` ended unexpectedly.
+    #[display("Saved envelope is truncated")]
+    Truncated,
+    /// A saved command or length is invalid.
+    #[display("Saved envelope is invalid")]
+    Invalid(Error),
+}
+
+#[cfg(feature = "parse")]
+impl From<Error> for RestoreError {
+    fn from(error: Error) -> Self {
+        Self::Invalid(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn email(s: &'static str) -> Email {
+        unsafe { Email::new_unchecked(Bytes::from(s)) }
+    }
+
+    fn bdat(payload: &'static [u8], last: bool) -> Command {
+        Command::Bdat(Bdat {
+            size: payload.len(),
+            last,
+            payload: Bytes::from_static(payload),
+        })
+    }
+
+    fn envelope(payload: &'static [u8]) -> Envelope {
+        Envelope {
+            mail: Mail {
+                size: None,
+                ret: None,
+                envid: None,
+                auth: None,
+                body: None,
+                conperm: None,
+                solicit: None,
+                prdr: false,
+                smtputf8: false,
+                raw_parameters: None,
+                from: ReversePath::Email(email("bob@example.com")),
+            },
+            rcpts: vec![
+                Rcpt::builder(email("alice@example.com")).build(),
+                Rcpt::builder(email("carol@example.com")).build(),
+            ],
+            payload: Bytes::from_static(payload).into(),
+        }
+    }
+
+    #[cfg(feature = "parse")]
+    #[rstest]
+    #[case::none(&["alice@example.com", "bob@example.com"], false, 0)]
+    #[case::exact(&["alice@example.com", "bob@example.com", "alice@example.com"], false, 1)]
+    #[case::domain(&["alice@example.com", "alice@EXAMPLE.COM"], false, 1)]
+    #[case::quoted(&["alice@example.com", "\"alice\"@example.com"], false, 1)]
+    #[case::local_case(&["alice@example.com", "ALICE@example.com"], false, 0)]
+    #[case::case_insensitive(&["alice@example.com", "ALICE@example.com"], true, 1)]
+    fn dedup_recipients(
+        #[case] rcpts: &[&'static str],
+        #[case] case_insensitive: bool,
+        #[case] removed: usize,
+    ) {
+        let mut envelope = envelope(b"Hi!");
+        envelope.rcpts = rcpts
+            .iter()
+            .map(|&to| Rcpt::builder(email(to)).build())
+            .collect();
+
+        assert_eq!(envelope.dedup_recipients(case_insensitive), removed);
+        assert_eq!(envelope.rcpts.len(), rcpts.len() - removed);
+        assert_eq!(envelope.rcpts[0].to, email(rcpts[0]));
+    }
+
+    #[cfg(feature = "parse")]
+    #[test]
+    fn save() {
+        let mut original = envelope(b"Hi!\r\n");
+        original.payload.push_front(Bytes::from("Received: x\r\n"));
+
+        let mut buf = BytesMut::new();
+        original.save(&mut buf);
+        assert_eq!(
+            buf,
+            &b"SMTPKIT-ENVELOPE/1\r\nMAIL FROM:<bob@example.com>\r\nRCPT TO:<alice@example.com>\r\nRCPT TO:<carol@example.com>\r\nPAYLOAD 18\r\nReceived: x\r\nHi!\r\n"[..]
+        );
+
+        let (restored, reference) = Envelope::restore(buf.clone().freeze()).unwrap();
+        assert_eq!(reference, None);
+        assert_eq!(restored.payload, original.payload);
+        assert_eq!(restored.rcpts.len(), 2);
+
+        // saving again gives the same bytes
+        let mut again = BytesMut::new();
+        restored.save(&mut again);
+        assert_eq!(again, buf);
+    }
+
+    #[cfg(feature = "parse")]
+    #[test]
+    fn save_reference() {
+        let original = envelope(b"Hi!\r\n");
+        let mut buf = BytesMut::new();
+        original.save_reference(b"1234", &mut buf);
+
+        let (restored, reference) = Envelope::restore(buf.freeze()).unwrap();
+        assert_eq!(reference, Some(Bytes::from("1234")));
+        assert!(restored.payload.is_empty());
+        assert_eq!(restored.rcpts[1].to, original.rcpts[1].to);
+    }
+
+    #[cfg(feature = "parse")]
+    #[rstest]
+    #[case::version("SMTPKIT-ENVELOPE/2\r\n", RestoreError::Version)]
+    #[case::empty("", RestoreError::Truncated)]
+    #[case::no_mail(
+        "SMTPKIT-ENVELOPE/1\r\nRCPT TO:<alice@example.com>\r\n",
+        RestoreError::Invalid(Error::InvalidCommand)
+    )]
+    #[case::no_payload("SMTPKIT-ENVELOPE/1\r\nMAIL FROM:<>\r\n", RestoreError::Truncated)]
+    #[case::short(
+        "SMTPKIT-ENVELOPE/1\r\nMAIL FROM:<>\r\nPAYLOAD 4\r\nHi!",
+        RestoreError::Truncated
+    )]
+    #[case::section(
+        "SMTPKIT-ENVELOPE/1\r\nMAIL FROM:<>\r\nBODY 0\r\n",
+        RestoreError::Invalid(Error::InvalidSyntax)
+    )]
+    #[case::length(
+        "SMTPKIT-ENVELOPE/1\r\nMAIL FROM:<>\r\nPAYLOAD x\r\n",
+        RestoreError::Invalid(Error::InvalidSyntax)
+    )]
+    fn restore_invalid(#[case] saved: &'static str, #[case] expected: RestoreError) {
+        assert_eq!(Envelope::restore(Bytes::from(saved)).unwrap_err(), expected);
+    }
+
+    fn capabilities(extensions: Vec<Extension>) -> Capabilities {
+        let mut capabilities = Capabilities::new(Bytes::from("mx.example.com"));
+        for extension in extensions {
+            capabilities.push(extension);
+        }
+        capabilities
+    }
+
+    fn other(keyword: &'static str) -> Extension {
+        Extension::Other(Bytes::from(keyword), Bytes::new())
+    }
+
+    #[rstest]
+    #[case::data(b"Hi!", vec![], 3, vec![Command::Data(Bytes::from_static(b"Hi!"))])]
+    #[case::no_chunking(b"Hi!", vec![], 1, vec![Command::Data(Bytes::from_static(b"Hi!"))])]
+    #[case::bdat_one(b"Hi!", vec![Extension::Chunking], 3, vec![bdat(b"Hi!", true)])]
+    #[case::bdat_many(b"Hello!", vec![Extension::Chunking], 4, vec![bdat(b"Hell", false), bdat(b"o!", true)])]
+    #[case::bdat_empty(b"", vec![Extension::Chunking], 4, vec![bdat(b"", true)])]
+    fn commands(
+        #[case] payload: &'static [u8],
+        #[case] extensions: Vec<Extension>,
+        #[case] chunk_size: usize,
+        #[case] transfer: Vec<Command>,
+    ) {
+        let envelope = envelope(payload);
+        let chunk_size = NonZeroUsize::new(chunk_size).unwrap();
+        let commands = envelope.commands(&capabilities(extensions), chunk_size);
+
+        assert_eq!(commands[0], Command::Mail(Box::new(envelope.mail.clone())));
+        assert_eq!(
+            commands[1],
+            Command::Rcpt(Box::new(envelope.rcpts[0].clone()))
+        );
+        assert_eq!(
+            commands[2],
+            Command::Rcpt(Box::new(envelope.rcpts[1].clone()))
+        );
+        assert_eq!(commands[3..], transfer);
+    }
+
+    #[rstest]
+    #[case::none(vec![], "", "")]
+    #[case::size(vec![Extension::Size(0)], " SIZE=1024", "")]
+    #[case::eight_bit_mime(vec![Extension::EightBitMime], " BODY=8BITMIME", "")]
+    #[case::smtputf8(vec![Extension::SmtpUtf8], " SMTPUTF8", "")]
+    #[case::dsn(
+        vec![Extension::Dsn],
+        " RET=HDRS ENVID=QQ314159",
+        " NOTIFY=NEVER ORCPT=rfc822;alice@example.com"
+    )]
+    #[case::auth(vec![Extension::Auth(vec![Mechanism::Plain])], " AUTH=<>", "")]
+    #[case::conperm(vec![other("CONPERM")], " CONPERM=image/tiff", "")]
+    #[case::solicit(vec![other("solicit")], " SOLICIT=org.example:ADV", "")]
+    #[case::prdr(vec![other("PRDR")], " PRDR", "")]
+    #[case::conneg(vec![other("CONNEG")], "", " CONNEG=image/tiff")]
+    #[case::all(
+        vec![
+            Extension::Size(1024),
+            Extension::EightBitMime,
+            Extension::SmtpUtf8,
+            Extension::Dsn,
+            Extension::Auth(vec![Mechanism::Plain]),
+            other("CONPERM"),
+            other("SOLICIT"),
+            other("PRDR"),
+            other("CONNEG"),
+        ],
+        " SIZE=1024 RET=HDRS ENVID=QQ314159 AUTH=<> BODY=8BITMIME CONPERM=image/tiff \
+         SOLICIT=org.example:ADV PRDR SMTPUTF8",
+        " NOTIFY=NEVER ORCPT=rfc822;alice@example.com CONNEG=image/tiff"
+    )]
+    fn parameters(
+        #[case] extensions: Vec<Extension>,
+        #[case] mail_params: &str,
+        #[case] rcpt_params: &str,
+    ) {
+        let mut envelope = envelope(b"Hi!");
+        let xtext = |s: &'static str| unsafe { XText::new_unchecked(Bytes::from(s)) };
+        envelope.mail = Mail {
+            size: Some(1024),
+            ret: Some(mail::Ret::Headers),
+            envid: Some(mail::EnvId(xtext("QQ314159"))),
+            auth: Some(mail::Auth::Anonymous),
+            body: Some(Body::EightBitMime),
+            conperm: Some(mail::ConPerm(xtext("image/tiff"))),
+            solicit: Some(unsafe { mail::Solicit::new_unchecked(Bytes::from("org.example:ADV")) }),
+            prdr: true,
+            smtputf8: true,
+            ..envelope.mail
+        };
+        let mut alice = Rcpt::builder(email("alice@example.com"))
+            .never()
+            .orcpt(email("alice@example.com"))
+            .build();
+        alice.conneg = Some(rcpt::ConNeg(xtext("image/tiff")));
+        envelope.rcpts = vec![alice];
+
+        let chunk_size = NonZeroUsize::new(1024).unwrap();
+        let commands = envelope.commands(&capabilities(extensions), chunk_size);
+        assert_eq!(
+            commands[0].to_bytes(),
+            format!("MAIL FROM:<bob@example.com>{mail_params}\r\n").as_str()
+        );
+        assert_eq!(
+            commands[1].to_bytes(),
+            format!("RCPT TO:<alice@example.com>{rcpt_params}\r\n").as_str()
+        );
+    }
+
+    #[rstest]
+    #[case::advertised(vec![Extension::BinaryMime, Extension::Chunking], Some(Body::BinaryMime))]
+    #[case::no_chunking(vec![Extension::BinaryMime], None)]
+    #[case::no_binary_mime(vec![Extension::Chunking], None)]
+    #[case::seven_bit(vec![Extension::EightBitMime], None)]
+    fn binary_mime(#[case] extensions: Vec<Extension>, #[case] body: Option<Body>) {
+        let mut envelope = envelope(b"Hi!");
+        envelope.mail.body = Some(Body::BinaryMime);
+        let commands = envelope.commands(&capabilities(extensions), NonZeroUsize::MIN);
+        let Command::Mail(mail) = &commands[0] else {
+            unreachable!()
+        };
+        assert_eq!(mail.body, body);
+    }
+}
@@ -1,9 +1,12 @@
-use derive_more::Display;
+use derive_more::{AsRef, Display};
 
 use crate::*;
 
 /// `MAIL` Command Parameters
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+///
+/// [`Mail::raw_parameters`] is ignored when comparing and hashing, so commands differing only in
+/// the order or case of their parameters are equal.
+#[derive(Debug, Clone)]
 pub struct Mail {
     /// `SIZE`
     pub size: Option<usize>,
@@ -15,10 +18,83 @@ pub struct Mail {
     pub auth: Option<mail::Auth>,
     /// `BODY`
     pub body: Option<mail::Body>,
+    /// `CONPERM`
+    pub conperm: Option<ConPerm>,
+    /// `SOLICIT`
+    pub solicit: Option<Solicit>,
+    /// `PRDR`
+    pub prdr: bool,
+    /// `SMTPUTF8`
+    pub smtputf8: bool,
+    /// The parameters exactly as received, in their original order and case, when parsed with
+    /// [`Lenient::RAW_PARAMETERS`](crate::Lenient::RAW_PARAMETERS).
+    ///
+    /// While they still parse to the typed fields above, serialization and `Display` emit these
+    /// instead, so a proxy can forward the command faithfully. Once a typed field is changed, the
+    /// typed fields are emitted.
+    pub raw_parameters: Option<Bytes>,
     /// `FROM:`
     pub from: ReversePath,
 }
 
+impl PartialEq for Mail {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            size,
+            ret,
+            envid,
+            auth,
+            body,
+            conperm,
+            solicit,
+            prdr,
+            smtputf8,
+            raw_parameters: _,
+            from,
+        } = self;
+        *size == other.size
+            && *ret == other.ret
+            && *envid == other.envid
+            && *auth == other.auth
+            && *body == other.body
+            && *conperm == other.conperm
+            && *solicit == other.solicit
+            && *prdr == other.prdr
+            && *smtputf8 == other.smtputf8
+            && *from == other.from
+    }
+}
+
+impl Eq for Mail {}
+
+impl core::hash::Hash for Mail {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        let Self {
+            size,
+            ret,
+            envid,
+            auth,
+            body,
+            conperm,
+            solicit,
+            prdr,
+            smtputf8,
+            raw_parameters: _,
+            from,
+        } = self;
+        size.hash(state);
+        ret.hash(state);
+        envid.hash(state);
+        auth.hash(state);
+        body.hash(state);
+        conperm.hash(state);
+        solicit.hash(state);
+        prdr.hash(state);
+        smtputf8.hash(state);
+        from.hash(state);
+    }
+}
+
 /// # `MAIL` Command Parameter
 #[derive(Debug, Display, PartialEq, Eq, Clone, Hash)]
 #[non_exhaustive]
@@ -33,6 +109,20 @@ pub enum Parameter {
     Auth(Auth),
     #[display("BODY={_0}")]
     Body(Body),
+    #[display("CONPERM={_0}")]
+    ConPerm(ConPerm),
+    #[display("SOLICIT={_0}")]
+    Solicit(Solicit),
+    /// Request per-recipient data responses.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/draft-hall-prdr-00>
+    #[display("PRDR")]
+    Prdr,
+    /// The message may contain UTF-8 in its envelope and header fields.
+    ///
+    /// <https://datatracker.ietf.org/doc/html/rfc6531#section-3.4>
+    #[display("SMTPUTF8")]
+    SmtpUtf8,
 }
 
 /// Envelope ID
@@ -74,6 +164,32 @@ pub enum Auth {
     Identity(XText),
 }
 
+#[cfg(feature = "parse")]
+impl Auth {
+    /// Decode the identity and parse it as a mailbox, for comparing identities structurally.
+    ///
+    /// Returns `None` for [`Auth::Anonymous`], and the decoded bytes if the identity is not a valid
+    /// address. Surrounding angle brackets, sent by some clients, are ignored.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{Email, XText, mail::Auth};
+    /// let auth = Auth::Identity(XText::encode(&Bytes::from("e=mc2@example.com")));
+    /// let email = Email::try_from(Bytes::from("e=mc2@example.com")).unwrap();
+    /// assert_eq!(auth.decode_mailbox(), Some(Ok(email)));
+    /// ```
+    #[must_use]
+    pub fn decode_mailbox(&self) -> Option<Result<Email, Bytes>> {
+        let Self::Identity(xtext) = self else {
+            return None;
+        };
+
+        let decoded = xtext.decode().freeze();
+        let mailbox = decoded.strip_angled().unwrap_or_else(|| decoded.clone());
+        Some(Email::try_from(mailbox).map_err(|_| decoded))
+    }
+}
+
 /// # Body
 ///
 /// The body type of the message.
@@ -91,6 +207,58 @@ pub enum Body {
     BinaryMime,
 }
 
+/// # Content Conversion Permission
+///
+/// The sender's permission for content conversion of the message, `XText` encoded.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc4141>
+#[derive(derive_more::Debug, Display, PartialEq, Eq, Clone, Hash)]
+#[debug("{_0:?}")]
+pub struct ConPerm(pub XText);
+
+/// # Solicitation Class Keywords
+///
+/// Comma-separated keywords identifying the message as a solicitation.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3865>
+#[derive(derive_more::Debug, AsRef, Display, PartialEq, Eq, Clone, Hash)]
+#[as_ref([u8])]
+#[debug("{:?}", self.0.as_bstr())]
+#[display("{}", self.0.as_bstr())]
+pub struct Solicit(Bytes);
+
+impl Solicit {
+    /// Returns an iterator over the individual keywords.
+    pub fn keywords(&self) -> impl Iterator<Item = Bytes> + '_ {
+        self.0.split(|&b| b == b',').map(|k| self.0.slice_ref(k))
+    }
+
+    /// Get a reference to the inner `Bytes`.
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    #[must_use]
+    pub fn bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    /// Consume the `Solicit`, returning the inner `Bytes`.
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    #[must_use]
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+
+    /// Create a new `Solicit` from the given `Bytes`.
+    ///
+    /// # Safety
+    ///
+    /// The inner `Bytes` must be a comma-separated list of valid solicitation class keywords.
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    #[must_use]
+    pub const unsafe fn new_unchecked(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+}
+
 /// # Reverse Path
 ///
 /// The reverse path (from address) of the message.
@@ -111,6 +279,26 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    #[cfg(feature = "parse")]
+    #[rstest]
+    #[case::plain("bob@example.com", Some(Ok("bob@example.com")))]
+    #[case::encoded("e+3Dmc2@example.com", Some(Ok("e=mc2@example.com")))]
+    #[case::angled("<bob@example.com>", Some(Ok("bob@example.com")))]
+    #[case::not_mailbox("bob", Some(Err("bob")))]
+    #[case::not_mailbox_angled("<bob>", Some(Err("<bob>")))]
+    fn decode_mailbox(
+        #[case] identity: &'static str,
+        #[case] expected: Option<Result<&'static str, &'static str>>,
+    ) {
+        let auth = Auth::Identity(unsafe { XText::new_unchecked(identity.into()) });
+        let expected = expected.map(|r| {
+            r.map(|email| unsafe { Email::new_unchecked(email.into()) })
+                .map_err(Bytes::from)
+        });
+        assert_eq!(auth.decode_mailbox(), expected);
+        assert_eq!(Auth::Anonymous.decode_mailbox(), None);
+    }
+
     #[rstest]
     #[case::size(Parameter::Size(1024), "SIZE=1024")]
     #[case::ret_headers(Parameter::Ret(Ret::Headers), "RET=HDRS")]
@@ -121,7 +309,23 @@ mod tests {
     #[case::body_7bit(Parameter::Body(Body::SevenBit), "BODY=7BIT")]
     #[case::body_8bit_mime(Parameter::Body(Body::EightBitMime), "BODY=8BITMIME")]
     #[case::body_binary_mime(Parameter::Body(Body::BinaryMime), "BODY=BINARYMIME")]
+    #[case::solicit(Parameter::Solicit(unsafe { Solicit::new_unchecked("org.example:ADV,net.example:SPAM".into()) }), "SOLICIT=org.example:ADV,net.example:SPAM")]
+    #[case::prdr(Parameter::Prdr, "PRDR")]
+    #[case::smtputf8(Parameter::SmtpUtf8, "SMTPUTF8")]
+    #[case::conperm(Parameter::ConPerm(unsafe { ConPerm(XText::new_unchecked("image/tiff".into())) }), "CONPERM=image/tiff")]
     fn mail_param_display(#[case] param: Parameter, #[case] expected: &str) {
         assert_eq!(&param.to_string(), expected);
     }
+
+    #[test]
+    fn solicit_keywords() {
+        let solicit = unsafe { Solicit::new_unchecked("org.example:ADV,net.example:SPAM".into()) };
+        assert_eq!(
+            solicit.keywords().collect::<Vec<_>>(),
+            [
+                Bytes::from_static(b"org.example:ADV"),
+                Bytes::from_static(b"net.example:SPAM")
+            ]
+        );
+    }
 }
@@ -1,15 +1,44 @@
-use core::net::IpAddr;
+use alloc::boxed::Box;
+use core::iter::FusedIterator;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::num::NonZeroUsize;
 
 use derive_more::{AsRef, Display};
 
 use crate::*;
 
 pub mod mail;
-use mail::{Mail, ReversePath};
+use mail::{Body, Mail, ReversePath};
 
 pub mod rcpt;
 use rcpt::Rcpt;
 
+pub mod auth;
+
+mod envelope;
+pub use envelope::*;
+
+mod payload;
+pub use payload::*;
+
+mod batch;
+pub use batch::*;
+
+mod reply;
+pub use reply::*;
+
+mod capabilities;
+pub use capabilities::*;
+
+mod verification;
+pub use verification::*;
+
+mod catalog;
+pub use catalog::*;
+
+mod crosscheck;
+pub use crosscheck::*;
+
 mod serialize;
 pub use serialize::*;
 
@@ -32,12 +61,12 @@ pub enum Command {
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.2>
     #[debug("{_0:?}")]
-    Mail(Mail),
+    Mail(Box<Mail>),
     /// Identify the recipient of the mail transaction.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.3>
     #[debug("{_0:?}")]
-    Rcpt(Rcpt),
+    Rcpt(Box<Rcpt>),
     /// Initiate the transfer of the message data.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.4>
@@ -52,18 +81,20 @@ pub enum Command {
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.5>
     Rset,
-    /// Verify an email address.
+    /// Verify that a string identifies a user or mailbox; see [`Verification`] for the reply.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.6>
-    Vrfy,
-    /// Expand a mailing list.
+    #[debug("Vrfy({:?})", Redact::bytes(_0))]
+    Vrfy(Bytes),
+    /// Expand a mailing list; see [`Verification`] for the reply.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.7>
-    Expn,
-    /// Request help from the server.
+    #[debug("Expn({:?})", Redact::bytes(_0))]
+    Expn(Bytes),
+    /// Request help from the server, optionally on a topic such as a verb.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.8>
-    Help,
+    Help(Option<Bytes>),
     /// Do nothing.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.1.1.9>
@@ -79,10 +110,8 @@ pub enum Command {
     /// Authenticate the client to the server.
     ///
     /// <https://datatracker.ietf.org/doc/html/rfc4954>
-    Auth {
-        mechanism: Mechanism,
-        initial_response: Option<Base64>,
-    },
+    #[debug("{_0:?}")]
+    Auth(auth::Auth),
 }
 
 /// # Binary Data Chunk
@@ -101,6 +130,148 @@ pub struct Bdat {
     pub payload: Bytes,
 }
 
+impl Bdat {
+    /// Split a message payload into `BDAT` chunks of at most `chunk_size` bytes.
+    ///
+    /// The final chunk has `last` set. An empty payload yields a single empty chunk (`BDAT 0
+    /// LAST`). Chunks within one segment of a [`Payload`] are zero-copy.
+    #[must_use]
+    pub fn chunks(payload: impl Into<Payload>, chunk_size: NonZeroUsize) -> Chunks {
+        Chunks {
+            payload: Some(payload.into()),
+            chunk_size,
+        }
+    }
+
+    /// Join `BDAT` chunks into a payload that can be sent with `DATA`, applying dot-stuffing.
+    ///
+    /// Fails if `body` is `BINARYMIME`, which can only be sent with `BDAT`, or if any line would
+    /// exceed [`max::DATA_LINE`].
+    pub fn join(
+        chunks: impl IntoIterator<Item = Self>,
+        body: Option<Body>,
+    ) -> Result<Bytes, JoinError> {
+        if body == Some(Body::BinaryMime) {
+            return Err(JoinError::BinaryMime);
+        }
+
+        let mut content = BytesMut::new();
+        for chunk in chunks {
+            content.extend_from_slice(&chunk.payload);
+        }
+
+        // `DATA` adds its own CRLF before the terminating `.`
+        if content.ends_with(b"\r\n") {
+            content.truncate(content.len() - 2);
+        }
+
+        let mut data = BytesMut::with_capacity(content.len());
+        for (i, line) in content.split_str("\r\n").enumerate() {
+            let stuff = line.starts_with(b".");
+            if line.len() + usize::from(stuff) > max::DATA_LINE {
+                return Err(JoinError::TooLong);
+            }
+
+            if i > 0 {
+                data.extend_from_slice(b"\r\n");
+            }
+
+            if stuff {
+                data.extend_from_slice(b".");
+            }
+
+            data.extend_from_slice(line);
+        }
+
+        Ok(data.freeze())
+    }
+}
+
+/// # `BDAT` Join Error
+///
+/// Returned by [`Bdat::join`].
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinError {
+    /// The message is `BODY=BINARYMIME`, which can only be sent with `BDAT`.
+    #[display("BINARYMIME content cannot be sent with DATA")]
+    BinaryMime,
+    /// A line exceeds [`max::DATA_LINE`].
+    #[display("Line too long")]
+    TooLong,
+}
+
+/// # `BDAT` Chunks
+///
+/// An iterator over the `BDAT` chunks of a message payload, created by [`Bdat::chunks`].
+#[derive(Debug, Clone)]
+pub struct Chunks {
+    payload: Option<Payload>,
+    chunk_size: NonZeroUsize,
+}
+
+impl Iterator for Chunks {
+    type Item = Bdat;
+
+    /// Return the next chunk.
+    fn next(&mut self) -> Option<Self::Item> {
+        let payload = self.payload.as_mut()?;
+        let chunk = payload.split_to(self.chunk_size.get().min(payload.len()));
+        let last = payload.is_empty();
+        if last {
+            self.payload = None;
+        }
+
+        Some(Bdat {
+            size: chunk.len(),
+            last,
+            payload: chunk,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.payload.as_ref().map_or(0, |payload| {
+            payload.len().div_ceil(self.chunk_size.get()).max(1)
+        });
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Chunks {}
+
+impl FusedIterator for Chunks {}
+
+impl Command {
+    /// The command verb, e.g. `MAIL`.
+    #[must_use]
+    pub const fn verb(&self) -> &'static str {
+        match self {
+            Self::Helo(_) => "HELO",
+            Self::Ehlo(_) => "EHLO",
+            Self::Mail(_) => "MAIL",
+            Self::Rcpt(_) => "RCPT",
+            Self::Data(_) => "DATA",
+            Self::Bdat(_) => "BDAT",
+            Self::Rset => "RSET",
+            Self::Vrfy(_) => "VRFY",
+            Self::Expn(_) => "EXPN",
+            Self::Help(_) => "HELP",
+            Self::Noop => "NOOP",
+            Self::Quit => "QUIT",
+            Self::StartTls => "STARTTLS",
+            Self::Auth(_) => "AUTH",
+        }
+    }
+}
+
+/// Write the raw parameters of a `MAIL` or `RCPT` command, as `ToBytes` does.
+#[cfg(feature = "parse")]
+fn write_raw(f: &mut fmt::Formatter, raw: &Bytes) -> fmt::Result {
+    match raw.is_empty() {
+        true => Ok(()),
+        false => write!(f, " {}", raw.as_bstr()),
+    }
+}
+
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -113,6 +284,11 @@ impl fmt::Display for Command {
                     ReversePath::Null => write!(f, "<>")?,
                 }
 
+                #[cfg(feature = "parse")]
+                if let Some(raw) = mail.replay() {
+                    return write_raw(f, raw);
+                }
+
                 if let Some(size) = mail.size {
                     write!(f, " SIZE={size}")?;
                 }
@@ -133,18 +309,43 @@ impl fmt::Display for Command {
                     write!(f, " BODY={body}")?;
                 }
 
+                if let Some(conperm) = &mail.conperm {
+                    write!(f, " CONPERM={conperm}")?;
+                }
+
+                if let Some(solicit) = &mail.solicit {
+                    write!(f, " SOLICIT={solicit}")?;
+                }
+
+                if mail.prdr {
+                    write!(f, " PRDR")?;
+                }
+
+                if mail.smtputf8 {
+                    write!(f, " SMTPUTF8")?;
+                }
+
                 Ok(())
             }
 
             Self::Rcpt(rcpt) => {
                 write!(f, "RCPT TO:<{}>", rcpt.to)?;
 
+                #[cfg(feature = "parse")]
+                if let Some(raw) = rcpt.replay() {
+                    return write_raw(f, raw);
+                }
+
                 if let Some(notify) = rcpt.notify {
-                    write!(f, " NOTIFY={notify}")?;
+                    write!(f, " {notify}")?;
                 }
 
                 if let Some(orcpt) = &rcpt.orcpt {
-                    write!(f, " ORCPT=<{orcpt}>")?;
+                    write!(f, " ORCPT={orcpt}")?;
+                }
+
+                if let Some(conneg) = &rcpt.conneg {
+                    write!(f, " CONNEG={conneg}")?;
                 }
 
                 Ok(())
@@ -164,26 +365,19 @@ impl fmt::Display for Command {
             Self::Noop => write!(f, "NOOP"),
             Self::StartTls => write!(f, "STARTTLS"),
 
-            Self::Auth {
-                mechanism,
-                initial_response,
-            } => {
-                write!(f, "AUTH {mechanism}")?;
-                if let Some(initial_response) = initial_response {
-                    write!(f, " {initial_response}")?;
-                }
-                Ok(())
-            }
+            Self::Auth(auth) => write!(f, "{auth}"),
 
-            Self::Expn => write!(f, "EXPN"),
-            Self::Help => write!(f, "HELP"),
-            Self::Vrfy => write!(f, "VRFY"),
+            Self::Expn(list) => write!(f, "EXPN {}", list.as_bstr()),
+            Self::Help(None) => write!(f, "HELP"),
+            Self::Help(Some(topic)) => write!(f, "HELP {}", topic.as_bstr()),
+            Self::Vrfy(user) => write!(f, "VRFY {}", user.as_bstr()),
         }
     }
 }
 
 /// Base64-Encoded String
-#[derive(Debug, AsRef, Display, PartialEq, Eq, Clone, Hash)]
+#[derive(derive_more::Debug, AsRef, Display, PartialEq, Eq, Clone, Hash)]
+#[debug("Base64({:?})", Redact::bytes(&self.0))]
 #[display("{}", self.0.as_bstr())]
 #[as_ref([u8])]
 pub struct Base64(Bytes);
@@ -213,12 +407,63 @@ impl Base64 {
     pub const unsafe fn new_unchecked(bytes: Bytes) -> Self {
         Self(bytes)
     }
+
+    /// Decode the `Base64` string into the provided `BytesMut`.
+    pub fn decode_into(&self, buf: &mut BytesMut) {
+        for quad in self.0.chunks(4) {
+            let len = quad.iter().take_while(|&&c| c != b'=').count();
+            let n = quad[..len]
+                .iter()
+                .fold(0u32, |n, &c| (n << 6) | u32::from(decode_base64(c)))
+                << (6 * (4 - len));
+            buf.extend_from_slice(&n.to_be_bytes()[1..len]);
+        }
+    }
+
+    /// Return a `BytesMut` containing the decoded bytes of the `Base64` string.
+    ///
+    /// This is a convenience method that allocates a new `BytesMut` and calls `decode_into`.
+    #[must_use]
+    pub fn decode(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(self.0.len() / 4 * 3);
+        self.decode_into(&mut buf);
+        buf
+    }
+
+    /// Encode the input as padded base64 into the provided `BytesMut`.
+    pub fn encode_into(input: &[u8], buf: &mut BytesMut) {
+        buf.reserve(input.len().div_ceil(3) * 4);
+
+        for chunk in input.chunks(3) {
+            let n =
+                chunk.iter().fold(0u32, |n, &b| (n << 8) | u32::from(b)) << (8 * (3 - chunk.len()));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    buf.extend_from_slice(&[encode_base64((n >> (18 - 6 * i)) as u8 & 0x3F)]);
+                } else {
+                    buf.extend_from_slice(b"=");
+                }
+            }
+        }
+    }
+
+    /// Encode the input, returning a new padded `Base64` string.
+    ///
+    /// This is a convenience method that allocates a new `BytesMut` and calls `encode_into`.
+    #[must_use]
+    pub fn encode(input: &[u8]) -> Self {
+        let mut buf = BytesMut::new();
+        Self::encode_into(input, &mut buf);
+        Self(buf.freeze())
+    }
 }
 
 /// Domain, IP address, or address literaly identifying an SMTP client to the server.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum Host {
     Domain(Domain),
+    /// IP address literal. When parsing, an IPv6 zone ID (e.g. `[IPv6:fe80::1%25eth0]`) is
+    /// discarded, since it is only meaningful to the client.
     Ip(IpAddr),
     Address(Address),
 }
@@ -255,6 +500,87 @@ impl Domain {
     pub const unsafe fn new_unchecked(bytes: Bytes) -> Self {
         Self(bytes)
     }
+
+    /// Create a new `Domain` from a `&'static str`, usable in a `const`.
+    ///
+    /// ```rust
+    /// # use smtpkit::Domain;
+    /// const HOSTNAME: Domain = Domain::from_static("mx.example.com");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `domain` is not a valid domain name. In a `const`, this is a compile error.
+    #[must_use]
+    pub const fn from_static(domain: &'static str) -> Self {
+        let bytes = domain.as_bytes();
+        assert!(
+            bytes.len() <= max::DOMAIN && is_static_domain(bytes, 0, bytes.len()),
+            "invalid domain name"
+        );
+        Self(Bytes::from_static(bytes))
+    }
+
+    /// Iterate over the labels of the `Domain`, from left to right.
+    pub fn labels(&self) -> impl DoubleEndedIterator<Item = Bytes> + '_ {
+        self.0
+            .split(|&c| c == b'.')
+            .map(|label| self.0.slice_ref(label))
+    }
+
+    /// Get the `Domain` with the leftmost label removed, or `None` if there is only one label.
+    #[must_use]
+    pub fn parent(&self) -> Option<Self> {
+        self.0.find_byte(b'.').map(|i| Self(self.0.slice(i + 1..)))
+    }
+
+    /// Get the rightmost label of the `Domain`.
+    #[must_use]
+    pub fn tld(&self) -> Bytes {
+        self.0
+            .rfind_byte(b'.')
+            .map_or_else(|| self.0.clone(), |i| self.0.slice(i + 1..))
+    }
+
+    /// Whether the `Domain` is a strict subdomain of `other`, ignoring case.
+    ///
+    /// A `Domain` is not a subdomain of itself.
+    #[must_use]
+    pub fn is_subdomain_of(&self, other: &Self) -> bool {
+        let Some(prefix) = self.0.len().checked_sub(other.0.len() + 1) else {
+            return false;
+        };
+
+        self.0[prefix] == b'.' && self.0[prefix + 1..].eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Host {
+    /// Create a `Host` from the IP address of `addr`, discarding the port.
+    ///
+    /// Useful for a client identifying itself by its local address in `EHLO`.
+    #[must_use]
+    pub fn from_socket_addr(addr: SocketAddr) -> Self {
+        Self::Ip(addr.ip())
+    }
+}
+
+impl From<IpAddr> for Host {
+    fn from(ip: IpAddr) -> Self {
+        Self::Ip(ip)
+    }
+}
+
+impl From<Ipv4Addr> for Host {
+    fn from(ip: Ipv4Addr) -> Self {
+        Self::Ip(IpAddr::V4(ip))
+    }
+}
+
+impl From<Ipv6Addr> for Host {
+    fn from(ip: Ipv6Addr) -> Self {
+        Self::Ip(IpAddr::V6(ip))
+    }
 }
 
 impl fmt::Display for Host {
@@ -339,18 +665,83 @@ pub enum Mechanism {
     Plain,
     #[display("SCRAM-SHA-1")]
     ScramSha1,
+    #[display("SCRAM-SHA-1-PLUS")]
+    ScramSha1Plus,
     #[display("SCRAM-SHA-256")]
     ScramSha256,
+    #[display("SCRAM-SHA-256-PLUS")]
+    ScramSha256Plus,
     #[display("XOAUTH2")]
     XOAuth2,
 }
 
+impl Mechanism {
+    /// The registered SASL name.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Anonymous => "ANONYMOUS",
+            Self::CramMd5 => "CRAM-MD5",
+            Self::DigestMd5 => "DIGEST-MD5",
+            Self::GssApi => "GSSAPI",
+            Self::Login => "LOGIN",
+            Self::Ntlm => "NTLM",
+            Self::OAuthBearer => "OAUTHBEARER",
+            Self::Plain => "PLAIN",
+            Self::ScramSha1 => "SCRAM-SHA-1",
+            Self::ScramSha1Plus => "SCRAM-SHA-1-PLUS",
+            Self::ScramSha256 => "SCRAM-SHA-256",
+            Self::ScramSha256Plus => "SCRAM-SHA-256-PLUS",
+            Self::XOAuth2 => "XOAUTH2",
+        }
+    }
+
+    /// Look up a mechanism by its registered SASL `name`, ignoring case.
+    #[must_use]
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        [
+            Self::Anonymous,
+            Self::CramMd5,
+            Self::DigestMd5,
+            Self::GssApi,
+            Self::Login,
+            Self::Ntlm,
+            Self::OAuthBearer,
+            Self::Plain,
+            Self::ScramSha1,
+            Self::ScramSha1Plus,
+            Self::ScramSha256,
+            Self::ScramSha256Plus,
+            Self::XOAuth2,
+        ]
+        .into_iter()
+        .find(|mechanism| mechanism.name().as_bytes().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the mechanism sends the password or bearer token in the clear, so should only be
+    /// used over TLS.
+    #[must_use]
+    pub const fn plaintext(self) -> bool {
+        matches!(
+            self,
+            Self::Plain | Self::Login | Self::OAuthBearer | Self::XOAuth2
+        )
+    }
+
+    /// Whether the client sends the first message, so an initial response may be sent with
+    /// `AUTH`.
+    #[must_use]
+    pub const fn client_first(self) -> bool {
+        !matches!(self, Self::CramMd5 | Self::DigestMd5)
+    }
+}
+
 /// # `XText` String
 ///
 /// As defined in [RFC 3461](https://datatracker.ietf.org/doc/html/rfc3461#section-4).
 #[derive(derive_more::Debug, AsRef, Display, PartialEq, Eq, Clone, Hash)]
 #[as_ref([u8])]
-#[debug("{:?}", self.0.as_bstr())]
+#[debug("{:?}", Redact::bytes(&self.0))]
 #[display("{}", self.0.as_bstr())]
 pub struct XText(Bytes);
 
@@ -382,18 +773,22 @@ impl XText {
 
     /// Decode hexchars in the `XText` string into the provided `BytesMut`.
     pub fn decode_into(&self, buf: &mut BytesMut) {
-        let mut i = 0;
-        while i < self.0.len() {
-            if i + 2 < self.0.len() && self.0[i] == b'+' {
-                let high = decode_hex(self.0[i + 1]);
-                let low = decode_hex(self.0[i + 2]);
-                buf.extend_from_slice(&[(high << 4) | low]);
-                i += 3;
-            } else {
-                buf.extend_from_slice(&[self.0[i]]);
-                i += 1;
-            }
-        }
+        buf.reserve(self.0.len());
+        buf.extend(self.decoded());
+    }
+
+    /// Return an iterator over the decoded bytes of the `XText` string, without allocating.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::XText;
+    /// let xtext = XText::encode(&Bytes::from("bob+smith"));
+    /// assert_eq!(xtext.bytes(), "bob+2Bsmith");
+    /// assert!(xtext.decoded().eq(*b"bob+smith"));
+    /// ```
+    #[must_use]
+    pub fn decoded(&self) -> Decoded<'_> {
+        Decoded { bytes: &self.0 }
     }
 
     /// Return a `BytesMut` containing the decoded bytes of the `XText` string.
@@ -406,32 +801,72 @@ impl XText {
         buf
     }
 
-    /// Encode the input into hexchars where necessary, returning a new `XText` string.
-    #[must_use]
-    pub fn encode(input: &Bytes) -> Self {
-        let mut ret = BytesMut::with_capacity(input.len() * 3);
+    /// Encode the input into hexchars where necessary, into the provided `BytesMut`.
+    pub fn encode_into(input: &[u8], buf: &mut BytesMut) {
+        buf.reserve(input.len());
 
         for &byte in input {
             if is_xchar(byte) {
-                ret.extend_from_slice(&[byte]);
+                buf.extend_from_slice(&[byte]);
                 continue;
             }
 
-            ret.extend_from_slice(b"+");
-            ret.extend_from_slice(&[encode_hex(byte >> 4)]);
-            ret.extend_from_slice(&[encode_hex(byte & 0x0F)]);
+            buf.extend_from_slice(b"+");
+            buf.extend_from_slice(&[encode_hex(byte >> 4)]);
+            buf.extend_from_slice(&[encode_hex(byte & 0x0F)]);
+        }
+    }
+
+    /// Encode the input into hexchars where necessary, returning a new `XText` string.
+    ///
+    /// This is a convenience method that allocates a new `BytesMut` and calls `encode_into`.
+    #[must_use]
+    pub fn encode(input: &Bytes) -> Self {
+        let mut buf = BytesMut::with_capacity(input.len() * 3);
+        Self::encode_into(input, &mut buf);
+        Self(buf.freeze())
+    }
+}
+
+/// # Decoded `XText`
+///
+/// An iterator over the decoded bytes of an `XText` string, created by [`XText::decoded`].
+#[derive(Debug, Clone)]
+pub struct Decoded<'a> {
+    bytes: &'a [u8],
+}
+
+impl Iterator for Decoded<'_> {
+    type Item = u8;
+
+    /// Return the next decoded byte.
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self.bytes {
+            [b'+', high, low, ref rest @ ..] => {
+                self.bytes = rest;
+                Some((decode_hex(high) << 4) | decode_hex(low))
+            }
+            [byte, ref rest @ ..] => {
+                self.bytes = rest;
+                Some(byte)
+            }
+            [] => None,
         }
+    }
 
-        Self(ret.freeze())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.bytes.len().div_ceil(3), Some(self.bytes.len()))
     }
 }
 
+impl FusedIterator for Decoded<'_> {}
+
 /// # Email Address
 ///
 /// As defined in [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321).
 #[derive(AsRef, derive_more::Debug, Display, PartialEq, Eq, Clone, Hash)]
 #[as_ref([u8])]
-#[debug("{:?}", self.0.as_bstr())]
+#[debug("{:?}", Redact::email(&self.0))]
 #[display("{}", self.0.as_bstr())]
 pub struct Email(Bytes);
 
@@ -443,6 +878,13 @@ impl Email {
         self.0
     }
 
+    /// Get a reference to the inner `Bytes`.
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    #[must_use]
+    pub fn bytes(&self) -> &Bytes {
+        &self.0
+    }
+
     /// Create a new `Email` from the given `Bytes`.
     ///
     /// # Safety
@@ -453,6 +895,184 @@ impl Email {
     pub const unsafe fn new_unchecked(bytes: Bytes) -> Self {
         Self(bytes)
     }
+
+    /// Create a new `Email` from a `&'static str`, usable in a `const`.
+    ///
+    /// ```rust
+    /// # use smtpkit::Email;
+    /// const POSTMASTER: Email = Email::from_static("postmaster@example.com");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `email` is not a valid address. In a `const`, this is a compile error.
+    #[must_use]
+    pub const fn from_static(email: &'static str) -> Self {
+        assert!(is_static_email(email.as_bytes()), "invalid email address");
+        Self(Bytes::from_static(email.as_bytes()))
+    }
+
+    /// Returns the local part of the address, before the last `@`.
+    #[must_use]
+    pub fn local_part(&self) -> Bytes {
+        self.0.slice(..self.at())
+    }
+
+    /// Returns the domain of the address, after the last `@`.
+    #[must_use]
+    pub fn domain(&self) -> Bytes {
+        self.0.slice(self.at() + 1..)
+    }
+
+    /// Split a subaddress (e.g. `bob+tag@example.com`) from the local part at the first
+    /// `separator`.
+    ///
+    /// Returns the base address and the tag, or `None` if the local part has no subaddress. Quoted
+    /// local parts are never considered to have a subaddress.
+    #[must_use]
+    pub fn subaddress(&self, separator: u8) -> Option<(Self, Bytes)> {
+        let local = self.local_part();
+        if local.starts_with(b"\"") {
+            return None;
+        }
+
+        let pos = local.find_byte(separator).filter(|&pos| pos > 0)?;
+        let mut base = BytesMut::with_capacity(self.0.len() - (local.len() - pos));
+        base.extend_from_slice(&local[..pos]);
+        base.extend_from_slice(&self.0[local.len()..]);
+
+        Some((Self(base.freeze()), local.slice(pos + 1..)))
+    }
+
+    /// Returns the address with any subaddress removed from the local part.
+    ///
+    /// See [`Email::subaddress`].
+    #[must_use]
+    pub fn strip_subaddress(&self, separator: u8) -> Self {
+        self.subaddress(separator)
+            .map_or_else(|| self.clone(), |(base, _)| base)
+    }
+
+    /// Returns the address in a canonical form, suitable for comparisons or use as a map key.
+    ///
+    /// The domain is lowercased, and a quoted local part is unquoted when quoting is unnecessary.
+    /// If `case_insensitive` is set, an unquoted local part is also lowercased.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn normalized(&self, case_insensitive: bool) -> Self {
+        let local = self.local_part();
+        let mut normalized = BytesMut::with_capacity(self.0.len());
+
+        match strip_quotes(&local) {
+            Some(quoted) => {
+                let mut unquoted = BytesMut::with_capacity(quoted.len());
+                let mut escaped = false;
+                for &b in quoted {
+                    if b == b'\\' && !escaped {
+                        escaped = true;
+                        continue;
+                    }
+
+                    escaped = false;
+                    unquoted.extend_from_slice(&[b]);
+                }
+
+                if is_dot_string(&unquoted) {
+                    normalized.extend_from_slice(&unquoted);
+                } else {
+                    normalized.extend_from_slice(&local);
+                }
+            }
+            None => normalized.extend_from_slice(&local),
+        }
+
+        if case_insensitive && !normalized.starts_with(b"\"") {
+            normalized.make_ascii_lowercase();
+        }
+
+        normalized.extend_from_slice(b"@");
+        let domain = normalized.len();
+        normalized.extend_from_slice(&self.domain());
+        normalized[domain..].make_ascii_lowercase();
+
+        Self(normalized.freeze())
+    }
+
+    fn at(&self) -> usize {
+        self.0
+            .rfind_byte(b'@')
+            // an `Email` always contains an `@`; see `new_unchecked`.
+            .unwrap()
+    }
+}
+
+/// `const` equivalent of `is_domain` for `input[start..end]`, for `from_static`.
+const fn is_static_domain(input: &[u8], start: usize, end: usize) -> bool {
+    let mut label = start;
+    let mut i = start;
+    while i <= end {
+        if i == end || input[i] == b'.' {
+            // empty or overlong labels, and labels starting or ending with `-` are invalid
+            if i == label || i - label > max::LABEL || input[label] == b'-' || input[i - 1] == b'-'
+            {
+                return false;
+            }
+            label = i + 1;
+        } else if !(input[i].is_ascii_alphanumeric() || input[i] == b'-') {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// `const` equivalent of `TryFrom<Bytes> for Email`, for `from_static`.
+const fn is_static_email(input: &[u8]) -> bool {
+    let mut at = input.len();
+    while at > 0 && input[at - 1] != b'@' {
+        at -= 1;
+    }
+    if at == 0 || input.len() > max::EMAIL {
+        return false;
+    }
+    let at = at - 1;
+
+    if at > max::LOCAL_PART || !is_static_domain(input, at + 1, input.len()) {
+        return false;
+    }
+
+    if input[0] == b'"' {
+        // Quoted-string
+        if at < 2 || input[at - 1] != b'"' {
+            return false;
+        }
+        let mut i = 1;
+        while i < at - 1 {
+            match input[i] {
+                b'\\' if i + 1 < at - 1 && matches!(input[i + 1], b' '..=b'~') => i += 2,
+                b' '..=b'!' | b'#'..=b'[' | b']'..=b'~' => i += 1,
+                _ => return false,
+            }
+        }
+        return true;
+    }
+
+    // Dot-string
+    let mut i = 0;
+    while i < at {
+        let atext = input[i].is_ascii_alphanumeric()
+            || matches!(
+                input[i],
+                b'!' | b'#'..=b'\'' | b'*'..=b'+' | b'-' | b'/' | b'=' | b'?' | b'^' | b'_' | b'`'
+                    | b'{'..=b'}'
+            );
+        let dot = input[i] == b'.' && i > 0 && i + 1 < at && input[i + 1] != b'.';
+        if !atext && !dot {
+            return false;
+        }
+        i += 1;
+    }
+    at > 0
 }
 
 /// Encode a hex value into a hex character.
@@ -474,6 +1094,30 @@ fn decode_hex(c: u8) -> u8 {
     }
 }
 
+/// Encode a 6-bit value into a base64 character.
+fn encode_base64(byte: u8) -> u8 {
+    match byte {
+        0..=25 => b'A' + byte,
+        26..=51 => b'a' + (byte - 26),
+        52..=61 => b'0' + (byte - 52),
+        62 => b'+',
+        63 => b'/',
+        _ => unreachable!("Invalid sextet"),
+    }
+}
+
+/// Decode a base64 character into a 6-bit value.
+fn decode_base64(c: u8) -> u8 {
+    match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => unreachable!("Invalid base64 character"),
+    }
+}
+
 #[cfg(test)]
 #[expect(non_snake_case)]
 mod tests {
@@ -481,6 +1125,50 @@ mod tests {
     use bstr::{BStr, ByteSlice};
     use rstest::*;
 
+    fn domain(s: &'static str) -> Domain {
+        Domain(Bytes::from(s))
+    }
+
+    #[test]
+    fn domain_labels() {
+        let mail = domain("mail.example.com");
+        assert!(
+            mail.labels()
+                .eq(["mail", "example", "com"].map(Bytes::from))
+        );
+        assert!(
+            mail.labels()
+                .rev()
+                .eq(["com", "example", "mail"].map(Bytes::from))
+        );
+        assert_eq!(mail.tld(), Bytes::from("com"));
+        assert_eq!(domain("localhost").tld(), Bytes::from("localhost"));
+    }
+
+    #[test]
+    fn domain_parent() {
+        let mail = domain("mail.example.com");
+        let parents: Vec<_> = core::iter::successors(mail.parent(), Domain::parent).collect();
+        assert_eq!(parents, [domain("example.com"), domain("com")]);
+        assert_eq!(domain("localhost").parent(), None);
+    }
+
+    #[rstest]
+    #[case::child("mail.example.com", "example.com", true)]
+    #[case::grandchild("a.mail.example.com", "example.com", true)]
+    #[case::case_insensitive("mail.EXAMPLE.com", "example.COM", true)]
+    #[case::same("example.com", "example.com", false)]
+    #[case::suffix("badexample.com", "example.com", false)]
+    #[case::parent("example.com", "mail.example.com", false)]
+    #[case::unrelated("example.org", "example.com", false)]
+    fn domain_is_subdomain_of(
+        #[case] domain_: &'static str,
+        #[case] other: &'static str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(domain(domain_).is_subdomain_of(&domain(other)), expected);
+    }
+
     #[test]
     fn test_address_parts() {
         let addr = Address(Bytes::from("[test:1234]"));
@@ -502,6 +1190,9 @@ mod tests {
     fn xtext_decode(#[case] input: &'static [u8], #[case] expected: &BStr) {
         let encoded = XText(Bytes::from(input));
         assert_eq!(encoded.decode().as_ref().as_bstr(), expected);
+        assert!(encoded.decoded().eq(expected.iter().copied()));
+        let (lower, upper) = encoded.decoded().size_hint();
+        assert!(lower <= expected.len() && upper >= Some(expected.len()));
     }
 
     #[rstest]
@@ -520,6 +1211,40 @@ mod tests {
     fn xtext_roundtrip_encode(#[case] input: &'static [u8]) {
         let hex = XText::encode(&Bytes::from(input.as_bytes())).decode();
         assert_eq!(hex.as_ref().as_bstr(), input);
+
+        // appends, rather than overwriting
+        let mut buf = BytesMut::from(&b"x"[..]);
+        XText::encode_into(input, &mut buf);
+        assert_eq!(
+            &buf[1..],
+            XText::encode(&Bytes::from(input.as_bytes())).bytes()
+        );
+    }
+
+    #[rstest]
+    #[case::empty(b"", b"".as_bstr())]
+    #[case::f(b"f", b"Zg==".as_bstr())]
+    #[case::fo(b"fo", b"Zm8=".as_bstr())]
+    #[case::foo(b"foo", b"Zm9v".as_bstr())]
+    #[case::foobar(b"foobar", b"Zm9vYmFy".as_bstr())]
+    #[case::binary(b"\x00\xFF\xFE", b"AP/+".as_bstr())]
+    fn base64_encode(#[case] input: &'static [u8], #[case] expected: &BStr) {
+        assert_eq!(Base64::encode(input).as_ref().as_bstr(), expected);
+    }
+
+    #[rstest]
+    #[case::empty(b"")]
+    #[case::f(b"f")]
+    #[case::fo(b"fo")]
+    #[case::foob(b"foob")]
+    #[case::binary(b"\x00\xFF\xFE")]
+    #[case::username(b"Username:")]
+    fn base64_roundtrip(#[case] input: &'static [u8]) {
+        assert_eq!(Base64::encode(input).decode().as_ref(), input);
+
+        let mut buf = BytesMut::from(&b"x"[..]);
+        Base64::encode_into(input, &mut buf);
+        assert_eq!(&buf[1..], Base64::encode(input).bytes());
     }
 
     #[rstest]
@@ -561,6 +1286,134 @@ mod tests {
         let _ = encode_hex(16);
     }
 
+    #[rstest]
+    #[case::simple(b"bob@example.com", b"bob", b"example.com")]
+    #[case::quoted_at(b"\"bob@home\"@example.com", b"\"bob@home\"", b"example.com")]
+    #[case::literal(b"bob@[127.0.0.1]", b"bob", b"[127.0.0.1]")]
+    fn email_parts(
+        #[case] input: &'static [u8],
+        #[case] local_part: &'static [u8],
+        #[case] domain: &'static [u8],
+    ) {
+        let email = Email(Bytes::from_static(input));
+        assert_eq!(email.local_part().as_ref(), local_part);
+        assert_eq!(email.domain().as_ref(), domain);
+    }
+
+    #[rstest]
+    #[case::plus(b"bob+tag@example.com", b'+', Some((&b"bob@example.com"[..], &b"tag"[..])))]
+    #[case::first(b"bob+a+b@example.com", b'+', Some((&b"bob@example.com"[..], &b"a+b"[..])))]
+    #[case::empty_tag(b"bob+@example.com", b'+', Some((&b"bob@example.com"[..], &b""[..])))]
+    #[case::hyphen(b"bob-tag@example.com", b'-', Some((&b"bob@example.com"[..], &b"tag"[..])))]
+    #[case::other_separator(b"bob-tag@example.com", b'+', None)]
+    #[case::none(b"bob@example.com", b'+', None)]
+    #[case::empty_base(b"+tag@example.com", b'+', None)]
+    #[case::quoted(b"\"bob+tag\"@example.com", b'+', None)]
+    fn email_subaddress(
+        #[case] input: &'static [u8],
+        #[case] separator: u8,
+        #[case] expected: Option<(&'static [u8], &'static [u8])>,
+    ) {
+        let email = Email(Bytes::from_static(input));
+        let subaddress = email.subaddress(separator);
+        assert_eq!(
+            subaddress
+                .as_ref()
+                .map(|(base, tag)| (base.as_ref(), tag.as_ref())),
+            expected
+        );
+        assert_eq!(
+            email.strip_subaddress(separator).as_ref(),
+            expected.map_or(input, |(base, _)| base)
+        );
+    }
+
+    #[cfg(feature = "parse")]
+    #[rstest]
+    #[case::domain(b"Bob@Example.COM", false, b"Bob@example.com")]
+    #[case::case_insensitive(b"Bob@Example.COM", true, b"bob@example.com")]
+    #[case::unquote(b"\"Bob.Smith\"@example.com", false, b"Bob.Smith@example.com")]
+    #[case::unquote_case_insensitive(b"\"Bob.Smith\"@example.com", true, b"bob.smith@example.com")]
+    #[case::unquote_escaped(b"\"\\b\\o\\b\"@example.com", false, b"bob@example.com")]
+    #[case::keep_quoted(b"\"Bob Smith\"@example.com", true, b"\"Bob Smith\"@example.com")]
+    #[case::keep_quoted_dots(b"\"bob..smith\"@example.com", false, b"\"bob..smith\"@example.com")]
+    fn email_normalized(
+        #[case] input: &'static [u8],
+        #[case] case_insensitive: bool,
+        #[case] expected: &'static [u8],
+    ) {
+        let email = Email(Bytes::from_static(input));
+        assert_eq!(email.normalized(case_insensitive).as_ref(), expected);
+    }
+
+    #[rstest]
+    #[case::exact(b"abcdef", 3, &[(&b"abc"[..], false), (&b"def"[..], true)])]
+    #[case::remainder(b"abcdefg", 3, &[(&b"abc"[..], false), (&b"def"[..], false), (&b"g"[..], true)])]
+    #[case::single(b"abc", 10, &[(&b"abc"[..], true)])]
+    #[case::empty(b"", 10, &[(&b""[..], true)])]
+    fn bdat_chunks(
+        #[case] payload: &'static [u8],
+        #[case] chunk_size: usize,
+        #[case] expected: &[(&'static [u8], bool)],
+    ) {
+        let chunks = Bdat::chunks(
+            Bytes::from_static(payload),
+            NonZeroUsize::new(chunk_size).unwrap(),
+        );
+        assert_eq!(chunks.len(), expected.len());
+
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(payload, last)| Bdat {
+                size: payload.len(),
+                last,
+                payload: Bytes::from_static(payload),
+            })
+            .collect();
+        assert_eq!(chunks.collect::<Vec<_>>(), expected);
+    }
+
+    #[rstest]
+    #[case::single(&[&b"Hi!\r\n"[..]], None, Ok(&b"Hi!"[..]))]
+    #[case::multiple(&[&b"Hello,\r"[..], &b"\nWorld!\r\n"[..]], None, Ok(&b"Hello,\r\nWorld!"[..]))]
+    #[case::no_trailing_crlf(&[&b"Hi!"[..]], None, Ok(&b"Hi!"[..]))]
+    #[case::dot_stuffing(&[&b".\r\n..\r\na.b\r\n"[..]], None, Ok(&b"..\r\n...\r\na.b"[..]))]
+    #[case::eight_bit(&[&b"Hi!\r\n"[..]], Some(Body::EightBitMime), Ok(&b"Hi!"[..]))]
+    #[case::binary(&[&b"Hi!\r\n"[..]], Some(Body::BinaryMime), Err(JoinError::BinaryMime))]
+    #[case::empty(&[], None, Ok(&b""[..]))]
+    #[case::empty_middle(&[&b"Hello,\r\n"[..], &b""[..], &b"World!\r\n"[..]], None, Ok(&b"Hello,\r\nWorld!"[..]))]
+    #[case::empty_last(&[&b"Hi!\r\n"[..], &b""[..]], None, Ok(&b"Hi!"[..]))]
+    fn bdat_join(
+        #[case] chunks: &[&'static [u8]],
+        #[case] body: Option<Body>,
+        #[case] expected: Result<&'static [u8], JoinError>,
+    ) {
+        let chunks = chunks.iter().map(|&payload| Bdat {
+            size: payload.len(),
+            last: false,
+            payload: Bytes::from_static(payload),
+        });
+        assert_eq!(Bdat::join(chunks, body), expected.map(Bytes::from_static));
+    }
+
+    #[test]
+    fn bdat_join_too_long() {
+        let line = Bytes::from(vec![b'a'; max::DATA_LINE]);
+        let chunk = |payload: &Bytes| Bdat {
+            size: payload.len(),
+            last: true,
+            payload: payload.clone(),
+        };
+        assert!(Bdat::join([chunk(&line)], None).is_ok());
+
+        let mut stuffed = BytesMut::from(&b"."[..]);
+        stuffed.extend_from_slice(&line[1..]);
+        assert_eq!(
+            Bdat::join([chunk(&stuffed.freeze())], None),
+            Err(JoinError::TooLong)
+        );
+    }
+
     #[test]
     fn address_parts() {
         let addr = Address(Bytes::from("[test:1234]"));
@@ -588,6 +1441,82 @@ mod tests {
         assert_eq!(input.to_string(), expected);
     }
 
+    #[test]
+    fn from_static() {
+        const DOMAIN: Domain = Domain::from_static("mx.example.com");
+        const EMAIL: Email = Email::from_static("postmaster@example.com");
+        assert_eq!(DOMAIN.bytes(), "mx.example.com");
+        assert_eq!(EMAIL.domain(), "example.com");
+    }
+
+    #[rstest]
+    #[case::simple("example.com", true)]
+    #[case::single("localhost", true)]
+    #[case::hyphen("a-b.example", true)]
+    #[case::empty("", false)]
+    #[case::empty_label("a..b", false)]
+    #[case::trailing_dot("example.com.", false)]
+    #[case::leading_hyphen("-a.example", false)]
+    #[case::trailing_hyphen("a-.example", false)]
+    #[case::underscore("a_b.example", false)]
+    #[case::long_label(concat!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", ".example"), true)]
+    #[case::overlong_label(concat!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", ".example"), false)]
+    fn static_domain(#[case] input: &str, #[case] expected: bool) {
+        let bytes = input.as_bytes();
+        assert_eq!(is_static_domain(bytes, 0, bytes.len()), expected);
+    }
+
+    #[rstest]
+    #[case::simple("bob@example.com", true)]
+    #[case::dots("bob.smith@example.com", true)]
+    #[case::atext("b!#$%&'*+-/=?^_`{|}~b@example.com", true)]
+    #[case::quoted("\"bob smith\"@example.com", true)]
+    #[case::quoted_pair("\"bob\\\"smith\"@example.com", true)]
+    #[case::quoted_at("\"bob@smith\"@example.com", true)]
+    #[case::no_at("example.com", false)]
+    #[case::no_local("@example.com", false)]
+    #[case::no_domain("bob@", false)]
+    #[case::leading_dot(".bob@example.com", false)]
+    #[case::trailing_dot("bob.@example.com", false)]
+    #[case::double_dot("bob..smith@example.com", false)]
+    #[case::space("bob smith@example.com", false)]
+    #[case::unterminated("\"bob@example.com", false)]
+    #[case::bare_quote("\"@example.com", false)]
+    #[case::long_local(concat!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", "@example.com"), false)]
+    fn static_email(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(is_static_email(input.as_bytes()), expected);
+    }
+
+    #[test]
+    #[should_panic = "invalid domain name"]
+    fn from_static_invalid() {
+        let _ = Domain::from_static("not a domain");
+    }
+
+    #[test]
+    fn verb() {
+        assert_eq!(Command::Noop.verb(), "NOOP");
+        assert_eq!(Command::StartTls.verb(), "STARTTLS");
+        assert_eq!(
+            Command::Ehlo(Host::Domain(Domain::from_static("example.com"))).verb(),
+            "EHLO"
+        );
+    }
+
+    #[test]
+    fn host_from_ip() {
+        let v4 = Ipv4Addr::new(192, 0, 2, 1);
+        let v6 = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        assert_eq!(Host::from(v4), Host::Ip(IpAddr::V4(v4)));
+        assert_eq!(Host::from(v6), Host::Ip(IpAddr::V6(v6)));
+        assert_eq!(Host::from(IpAddr::V4(v4)), Host::Ip(IpAddr::V4(v4)));
+        assert_eq!(
+            Host::from_socket_addr(SocketAddr::new(IpAddr::V6(v6), 25)).to_string(),
+            "[IPv6:2001:db8::1]"
+        );
+        assert_eq!(Host::from(v6).to_bytes(), "[IPv6:2001:db8::1]");
+    }
+
     // TODO add Parameter and Parameter
     #[rstest]
     #[case::helo(
@@ -625,4 +1554,13 @@ mod tests {
     fn command_display(#[case] input: Command, #[case] expected: &str) {
         assert_eq!(input.to_string(), expected);
     }
+
+    #[test]
+    fn command_size() {
+        // `Mail` and `Rcpt` are boxed to keep `Command` cheap to move; see `benches/command.rs`
+        assert!(size_of::<Command>() <= 64, "{}", size_of::<Command>());
+        assert!(size_of::<Command>() < size_of::<Mail>());
+        // boxing `Bdat` would not help, as `Host` and `auth::Auth` are nearly as large
+        assert!(size_of::<Command>() <= size_of::<Bdat>() + size_of::<usize>());
+    }
 }
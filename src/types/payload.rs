@@ -0,0 +1,228 @@
+use alloc::collections::VecDeque;
+use alloc::collections::vec_deque;
+use core::hash::{Hash, Hasher};
+
+use super::*;
+
+/// # Segmented Message Payload
+///
+/// An ordered list of `Bytes` segments, so headers can be prepended and chunks concatenated
+/// without copying the rest of the message.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::Payload;
+/// let mut payload = Payload::from(Bytes::from("Subject: Hi!\r\n\r\nHello!\r\n"));
+/// payload.push_front(Bytes::from("Received: from client\r\n"));
+/// assert_eq!(payload.len(), 47);
+/// assert_eq!(payload.segments().len(), 2);
+/// ```
+#[derive(derive_more::Debug, Default, Clone)]
+#[debug("Payload({} bytes)", self.len)]
+pub struct Payload {
+    segments: VecDeque<Bytes>,
+    len: usize,
+}
+
+impl Payload {
+    /// Create an empty `Payload`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            segments: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    /// The total length in bytes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the payload is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Prepend `segment`.
+    pub fn push_front(&mut self, segment: Bytes) {
+        if !segment.is_empty() {
+            self.len += segment.len();
+            self.segments.push_front(segment);
+        }
+    }
+
+    /// Append `segment`.
+    pub fn push_back(&mut self, segment: Bytes) {
+        if !segment.is_empty() {
+            self.len += segment.len();
+            self.segments.push_back(segment);
+        }
+    }
+
+    /// Append the segments of `other`.
+    pub fn append(&mut self, mut other: Self) {
+        self.len += other.len;
+        self.segments.append(&mut other.segments);
+    }
+
+    /// The segments, in order. None are empty.
+    pub fn segments(&self) -> vec_deque::Iter<'_, Bytes> {
+        self.segments.iter()
+    }
+
+    /// Split off and return the first `at` bytes.
+    ///
+    /// Zero-copy if they lie within the first segment, otherwise they are copied.
+    ///
+    /// # Panics
+    ///
+    /// If `at` is greater than [`Payload::len`].
+    pub fn split_to(&mut self, at: usize) -> Bytes {
+        assert!(
+            at <= self.len,
+            "split_to out of bounds: {at} > {}",
+            self.len
+        );
+        self.len -= at;
+
+        let first = self.segments.front().map_or(0, Bytes::len);
+        if at < first {
+            return self.segments[0].split_to(at);
+        }
+        if at == first {
+            return self.segments.pop_front().unwrap_or_default();
+        }
+
+        let mut bytes = BytesMut::with_capacity(at);
+        while bytes.len() < at
+            && let Some(front) = self.segments.front_mut()
+        {
+            let take = front.len().min(at - bytes.len());
+            bytes.extend_from_slice(&front.split_to(take));
+            if front.is_empty() {
+                self.segments.pop_front();
+            }
+        }
+
+        bytes.freeze()
+    }
+
+    /// Join the segments into one `Bytes`, zero-copy if there is at most one segment.
+    #[must_use]
+    pub fn into_bytes(mut self) -> Bytes {
+        let len = self.len;
+        self.split_to(len)
+    }
+}
+
+impl From<Bytes> for Payload {
+    fn from(bytes: Bytes) -> Self {
+        let mut payload = Self::new();
+        payload.push_back(bytes);
+        payload
+    }
+}
+
+impl FromIterator<Bytes> for Payload {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut payload = Self::new();
+        for segment in iter {
+            payload.push_back(segment);
+        }
+        payload
+    }
+}
+
+/// Payloads are equal if their contents are, however they are segmented.
+impl PartialEq for Payload {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .segments()
+                .flat_map(|s| s.iter())
+                .eq(other.segments().flat_map(|s| s.iter()))
+    }
+}
+
+impl Eq for Payload {}
+
+impl Hash for Payload {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // byte by byte, as `Hasher::write` may depend on how the input is split
+        state.write_usize(self.len);
+        for &b in self.segments().flat_map(|s| s.iter()) {
+            state.write_u8(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn segmented(segments: &[&'static [u8]]) -> Payload {
+        segments.iter().copied().map(Bytes::from_static).collect()
+    }
+
+    #[rstest]
+    #[case::empty(&[], 0, b"", &[])]
+    #[case::within(&[&b"abc"[..], b"def"], 2, b"ab", &[&b"c"[..], b"def"])]
+    #[case::segment(&[&b"abc"[..], b"def"], 3, b"abc", &[b"def"])]
+    #[case::across(&[&b"abc"[..], b"def", b"g"], 5, b"abcde", &[&b"f"[..], b"g"])]
+    #[case::all(&[&b"abc"[..], b"def"], 6, b"abcdef", &[])]
+    fn split_to(
+        #[case] segments: &[&'static [u8]],
+        #[case] at: usize,
+        #[case] expected: &[u8],
+        #[case] rest: &[&'static [u8]],
+    ) {
+        let mut payload = segmented(segments);
+        assert_eq!(payload.split_to(at), expected);
+        assert_eq!(payload.len(), rest.iter().map(|s| s.len()).sum::<usize>());
+        assert_eq!(payload.segments().cloned().collect::<Vec<_>>(), rest);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn split_to_out_of_bounds() {
+        segmented(&[b"abc"]).split_to(4);
+    }
+
+    #[test]
+    fn prepend() {
+        let body = Bytes::from_static(b"Hello!\r\n");
+        let mut payload = Payload::from(body.clone());
+        payload.push_front(Bytes::from_static(b"Received: x\r\n"));
+        payload.push_front(Bytes::new());
+
+        assert_eq!(payload.segments().len(), 2);
+        // not copied
+        assert_eq!(payload.segments().nth(1).unwrap().as_ptr(), body.as_ptr());
+        assert_eq!(payload.into_bytes(), "Received: x\r\nHello!\r\n");
+    }
+
+    #[test]
+    fn chunks() {
+        let payload = segmented(&[&b"abcd"[..], b"ef"]);
+        let chunks: Vec<_> = Bdat::chunks(payload.clone(), NonZeroUsize::new(3).unwrap())
+            .map(|bdat| (bdat.payload, bdat.last))
+            .collect();
+        assert_eq!(
+            chunks,
+            [(Bytes::from("abc"), false), (Bytes::from("def"), true)]
+        );
+        assert_eq!(payload.to_bytes(), "abcdef");
+    }
+
+    #[test]
+    fn append() {
+        let mut payload = segmented(&[b"ab"]);
+        payload.append(segmented(&[&b"c"[..], b"d"]));
+        assert_eq!(payload, Payload::from(Bytes::from_static(b"abcd")));
+        assert_ne!(payload, Payload::from(Bytes::from_static(b"abce")));
+    }
+}
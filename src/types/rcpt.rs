@@ -3,22 +3,210 @@ use bitflags::bitflags;
 use super::*;
 
 /// `RCPT` Command Parameters
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+///
+/// As with [`Mail`](crate::mail::Mail), [`Rcpt::raw_parameters`] is ignored when comparing and
+/// hashing.
+#[derive(Debug, Clone)]
 pub struct Rcpt {
-    pub orcpt: Option<Email>,
+    pub orcpt: Option<ORcpt>,
     pub notify: Option<rcpt::Notify>,
+    pub conneg: Option<ConNeg>,
+    /// The parameters exactly as received, as [`Mail::raw_parameters`](crate::mail::Mail::raw_parameters).
+    pub raw_parameters: Option<Bytes>,
     pub to: Email,
 }
 
+impl PartialEq for Rcpt {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            orcpt,
+            notify,
+            conneg,
+            raw_parameters: _,
+            to,
+        } = self;
+        *orcpt == other.orcpt
+            && *notify == other.notify
+            && *conneg == other.conneg
+            && *to == other.to
+    }
+}
+
+impl Eq for Rcpt {}
+
+impl core::hash::Hash for Rcpt {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        let Self {
+            orcpt,
+            notify,
+            conneg,
+            raw_parameters: _,
+            to,
+        } = self;
+        orcpt.hash(state);
+        notify.hash(state);
+        conneg.hash(state);
+        to.hash(state);
+    }
+}
+
+impl Rcpt {
+    /// Create a [`RcptBuilder`] for the given recipient.
+    pub fn builder(to: Email) -> RcptBuilder {
+        RcptBuilder::new(to)
+    }
+}
+
+/// # `RCPT` Command Builder
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::Email;
+/// # use smtpkit::rcpt::{Notify, Rcpt};
+/// let to = unsafe { Email::new_unchecked(Bytes::from("alice@example.com")) };
+/// let rcpt = Rcpt::builder(to.clone())
+///     .on_failure()
+///     .on_delay()
+///     .orcpt(to)
+///     .build();
+/// assert_eq!(rcpt.notify, Some(Notify::FAILURE | Notify::DELAY));
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct RcptBuilder(Rcpt);
+
+impl RcptBuilder {
+    /// Create a `RcptBuilder` for the given recipient, with no parameters.
+    pub fn new(to: Email) -> Self {
+        Self(Rcpt {
+            orcpt: None,
+            notify: None,
+            conneg: None,
+            raw_parameters: None,
+            to,
+        })
+    }
+
+    /// Set the `NOTIFY` flags, replacing any set previously.
+    pub fn notify(mut self, notify: Notify) -> Self {
+        self.0.notify = Some(notify);
+        self
+    }
+
+    /// Request that no DSN be issued (`NOTIFY=NEVER`).
+    pub fn never(self) -> Self {
+        self.notify(Notify::NEVER)
+    }
+
+    /// Request a DSN on successful delivery.
+    pub fn on_success(self) -> Self {
+        self.add_notify(Notify::SUCCESS)
+    }
+
+    /// Request a DSN on delivery failure.
+    pub fn on_failure(self) -> Self {
+        self.add_notify(Notify::FAILURE)
+    }
+
+    /// Request a DSN if delivery is delayed.
+    pub fn on_delay(self) -> Self {
+        self.add_notify(Notify::DELAY)
+    }
+
+    /// Set the original recipient (`ORCPT`).
+    ///
+    /// An [`Email`] is sent as an `rfc822` address type, `XText` encoded as required.
+    pub fn orcpt(mut self, orcpt: impl Into<ORcpt>) -> Self {
+        self.0.orcpt = Some(orcpt.into());
+        self
+    }
+
+    /// Consume the `RcptBuilder`, returning the [`Rcpt`].
+    #[must_use]
+    pub fn build(self) -> Rcpt {
+        self.0
+    }
+
+    fn add_notify(mut self, notify: Notify) -> Self {
+        self.0.notify = Some(self.0.notify.unwrap_or(Notify::NEVER) | notify);
+        self
+    }
+}
+
 /// Parameters for the `RCPT` command.
 #[derive(Debug, Display, PartialEq, Clone, Hash)]
 #[non_exhaustive]
 pub enum Parameter {
-    #[display("ORCPT=<{_0}>")]
-    ORcpt(Email),
+    #[display("ORCPT={_0}")]
+    ORcpt(ORcpt),
     Notify(Notify),
+    #[display("CONNEG={_0}")]
+    ConNeg(ConNeg),
 }
 
+/// # Original Recipient
+///
+/// The address type and the address of the `ORCPT` parameter, e.g. `rfc822;bob+2Blist@example.com`.
+///
+/// The address is kept encoded as received, so address types other than `rfc822`, such as
+/// `utf-8` from [RFC 6533](https://datatracker.ietf.org/doc/html/rfc6533#section-3), are passed
+/// through unchanged.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3461#section-4.2>
+#[derive(derive_more::Debug, Display, PartialEq, Eq, Clone, Hash)]
+#[debug("{}({:?})", self.addr_type.as_bstr(), Redact::bytes(&self.addr))]
+#[display("{};{}", self.addr_type.as_bstr(), self.addr.as_bstr())]
+pub struct ORcpt {
+    /// The address type, e.g. `rfc822`.
+    pub addr_type: Bytes,
+    /// The encoded address, `XText` for `rfc822`.
+    pub addr: Bytes,
+}
+
+impl ORcpt {
+    /// Create an `ORcpt` of the `rfc822` address type.
+    #[must_use]
+    pub fn rfc822(email: &Email) -> Self {
+        Self {
+            addr_type: Bytes::from_static(b"rfc822"),
+            addr: XText::encode(email.bytes()).into_bytes(),
+        }
+    }
+
+    /// Whether the address type is `rfc822`.
+    #[must_use]
+    pub fn is_rfc822(&self) -> bool {
+        self.addr_type.eq_ignore_ascii_case(b"rfc822")
+    }
+
+    /// The decoded address, if the address type is `rfc822` and it is a valid [`Email`].
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn email(&self) -> Option<Email> {
+        if !self.is_rfc822() {
+            return None;
+        }
+
+        let addr = XText::try_from(self.addr.clone()).ok()?;
+        Email::try_from(addr.decode().freeze()).ok()
+    }
+}
+
+impl From<Email> for ORcpt {
+    fn from(email: Email) -> Self {
+        Self::rfc822(&email)
+    }
+}
+
+/// # Content Negotiation
+///
+/// Content negotiation information for the recipient, `XText` encoded.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc4141>
+#[derive(derive_more::Debug, Display, PartialEq, Eq, Clone, Hash)]
+#[debug("{_0:?}")]
+pub struct ConNeg(pub XText);
+
 bitflags! {
     /// Flags for the `NOTIFY` parameter in the `RCPT` command.
     #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -80,6 +268,62 @@ impl fmt::Display for Notify {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn email(s: &'static str) -> Email {
+        unsafe { Email::new_unchecked(Bytes::from(s)) }
+    }
+
+    #[test]
+    fn builder() {
+        let rcpt = Rcpt::builder(email("alice@example.com"))
+            .on_success()
+            .on_failure()
+            .orcpt(email("bob+list@example.com"))
+            .build();
+
+        assert_eq!(
+            rcpt,
+            Rcpt {
+                orcpt: Some(ORcpt::rfc822(&email("bob+list@example.com"))),
+                notify: Some(Notify::SUCCESS | Notify::FAILURE),
+                conneg: None,
+                raw_parameters: None,
+                to: email("alice@example.com"),
+            }
+        );
+        assert_eq!(
+            rcpt.to_bytes().as_ref().as_bstr(),
+            "RCPT TO:<alice@example.com> NOTIFY=FAILURE,SUCCESS ORCPT=rfc822;bob+2Blist@example.com\r\n"
+        );
+    }
+
+    #[test]
+    fn builder_never() {
+        let rcpt = Rcpt::builder(email("alice@example.com"))
+            .on_delay()
+            .never()
+            .build();
+        assert_eq!(rcpt.notify, Some(Notify::NEVER));
+        assert_eq!(rcpt.orcpt, None);
+    }
+
+    #[test]
+    fn orcpt() {
+        let orcpt = ORcpt::rfc822(&email("bob+list@example.com"));
+        assert!(orcpt.is_rfc822());
+        assert_eq!(orcpt.to_string(), "rfc822;bob+2Blist@example.com");
+        #[cfg(feature = "parse")]
+        assert_eq!(orcpt.email(), Some(email("bob+list@example.com")));
+
+        let utf8 = ORcpt {
+            addr_type: Bytes::from_static(b"utf-8"),
+            addr: Bytes::from_static(b"j\\x{E9}r\\x{F4}me@example.com"),
+        };
+        assert!(!utf8.is_rfc822());
+        #[cfg(feature = "parse")]
+        assert_eq!(utf8.email(), None);
+    }
 
     // TODO
     /*
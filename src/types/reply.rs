@@ -0,0 +1,855 @@
+use alloc::vec::Vec;
+
+use derive_more::Display;
+
+use super::*;
+
+/// # [SMTP Reply](https://datatracker.ietf.org/doc/html/rfc5321#section-4.2)
+///
+/// A three-digit reply code and one or more lines of text, the last of which ends the reply.
+///
+/// Serialized with [`ToBytes`], each line but the last is continued with a `-`, and the
+/// [`EnhancedCode`], if any, leads the text of every line.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Reply, ToBytes};
+/// let mut reply = Reply::new(250, Bytes::from("mx.example.com"))?;
+/// reply.push(Bytes::from("PIPELINING"))?;
+/// assert_eq!(reply.code(), 250);
+/// assert_eq!(reply.lines(), ["mx.example.com", "PIPELINING"]);
+/// assert_eq!(reply.to_bytes(), "250-mx.example.com\r\n250 PIPELINING\r\n");
+/// assert!(Reply::new(600, Bytes::new()).is_err());
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reply {
+    code: u16,
+    enhanced: Option<EnhancedCode>,
+    lines: Vec<Bytes>,
+}
+
+impl Reply {
+    /// Create a single-line `Reply`, which may have empty text.
+    ///
+    /// # Errors
+    ///
+    /// If `code` is not a valid reply code, or `text` is not a valid reply line, see
+    /// [`ReplyError`].
+    pub fn new(code: u16, text: Bytes) -> Result<Self, ReplyError> {
+        Self::multiline(code, [text])
+    }
+
+    /// Create a `Reply` with each of `lines`, in order.
+    ///
+    /// # Errors
+    ///
+    /// If `code` is not a valid reply code, `lines` is empty, or any line is not a valid reply
+    /// line, see [`ReplyError`].
+    pub fn multiline(
+        code: u16,
+        lines: impl IntoIterator<Item = Bytes>,
+    ) -> Result<Self, ReplyError> {
+        if !is_valid_code(code) {
+            return Err(ReplyError::InvalidCode);
+        }
+
+        let lines = lines
+            .into_iter()
+            .map(|line| validate_line(&line, 0).map(|()| line))
+            .collect::<Result<Vec<_>, _>>()?;
+        if lines.is_empty() {
+            return Err(ReplyError::Empty);
+        }
+
+        Ok(Self {
+            code,
+            enhanced: None,
+            lines,
+        })
+    }
+
+    /// The reply code.
+    #[must_use]
+    pub const fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// The reply code as a [`ReplyCode`].
+    #[must_use]
+    pub fn reply_code(&self) -> ReplyCode {
+        ReplyCode::from(self.code)
+    }
+
+    /// The [`Severity`], from the first digit of the code.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{Reply, Severity};
+    /// let reply = Reply::new(451, Bytes::from("Try again later"))?;
+    /// assert_eq!(reply.severity(), Severity::TransientNegative);
+    /// assert!(reply.is_transient_failure());
+    /// # Ok::<(), smtpkit::ReplyError>(())
+    /// ```
+    #[must_use]
+    pub const fn severity(&self) -> Severity {
+        match self.code / 100 {
+            2 => Severity::PositiveCompletion,
+            3 => Severity::PositiveIntermediate,
+            4 => Severity::TransientNegative,
+            _ => Severity::PermanentNegative,
+        }
+    }
+
+    /// Whether the command succeeded: a `2yz` code.
+    #[must_use]
+    pub const fn is_positive_completion(&self) -> bool {
+        matches!(self.severity(), Severity::PositiveCompletion)
+    }
+
+    /// Whether the command was accepted, pending more input: a `3yz` code.
+    #[must_use]
+    pub const fn is_positive_intermediate(&self) -> bool {
+        matches!(self.severity(), Severity::PositiveIntermediate)
+    }
+
+    /// Whether the command failed, but may succeed if retried later: a `4yz` code.
+    #[must_use]
+    pub const fn is_transient_failure(&self) -> bool {
+        matches!(self.severity(), Severity::TransientNegative)
+    }
+
+    /// Whether the command failed, and should not be retried as is: a `5yz` code.
+    #[must_use]
+    pub const fn is_permanent_failure(&self) -> bool {
+        matches!(self.severity(), Severity::PermanentNegative)
+    }
+
+    /// Replace the reply code.
+    ///
+    /// # Errors
+    ///
+    /// If `code` is not a valid reply code, or does not match the class of the
+    /// [`EnhancedCode`].
+    pub const fn set_code(&mut self, code: u16) -> Result<(), ReplyError> {
+        if !is_valid_code(code) {
+            return Err(ReplyError::InvalidCode);
+        }
+        if let Some(enhanced) = self.enhanced
+            && !enhanced.matches(code)
+        {
+            return Err(ReplyError::InvalidEnhancedCode);
+        }
+
+        self.code = code;
+        Ok(())
+    }
+
+    /// The enhanced status code.
+    #[must_use]
+    pub const fn enhanced_code(&self) -> Option<EnhancedCode> {
+        self.enhanced
+    }
+
+    /// Set or clear the enhanced status code.
+    ///
+    /// # Errors
+    ///
+    /// If the class of `enhanced` does not match the reply code, or a line would become too long.
+    pub fn set_enhanced_code(&mut self, enhanced: Option<EnhancedCode>) -> Result<(), ReplyError> {
+        if let Some(enhanced) = enhanced {
+            if !enhanced.matches(self.code) {
+                return Err(ReplyError::InvalidEnhancedCode);
+            }
+            for line in &self.lines {
+                validate_line(line, enhanced.len() + 1)?;
+            }
+        }
+
+        self.enhanced = enhanced;
+        Ok(())
+    }
+
+    /// Set the enhanced status code, see [`Reply::set_enhanced_code`].
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{EnhancedCode, Reply, ToBytes};
+    /// let reply = Reply::new(250, Bytes::from("OK"))?.with_enhanced_code(EnhancedCode::OK)?;
+    /// assert_eq!(reply.to_bytes(), "250 2.0.0 OK\r\n");
+    /// # Ok::<(), smtpkit::ReplyError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// As [`Reply::set_enhanced_code`].
+    pub fn with_enhanced_code(mut self, enhanced: EnhancedCode) -> Result<Self, ReplyError> {
+        self.set_enhanced_code(Some(enhanced))?;
+        Ok(self)
+    }
+
+    /// Move the enhanced status code leading the text of the first line to
+    /// [`Reply::enhanced_code`], removing it from every line it leads, as for a reply from a server
+    /// that advertised `ENHANCEDSTATUSCODES`. Returns whether there was one.
+    ///
+    /// Text that only looks like an enhanced status code, because its class does not match the
+    /// reply code, is left alone.
+    ///
+    /// ```rust
+    /// # use bytes::Bytes;
+    /// # use smtpkit::{EnhancedCode, Reply};
+    /// let mut reply = Reply::new(550, Bytes::from("5.1.1 No such user"))?;
+    /// assert!(reply.split_enhanced_code());
+    /// assert_eq!(reply.enhanced_code(), EnhancedCode::new(5, 1, 1));
+    /// assert_eq!(reply.lines(), ["No such user"]);
+    /// # Ok::<(), smtpkit::ReplyError>(())
+    /// ```
+    pub fn split_enhanced_code(&mut self) -> bool {
+        let Some((enhanced, _)) = EnhancedCode::parse_prefix(&self.lines[0]) else {
+            return false;
+        };
+        if self.enhanced.is_some() || !enhanced.matches(self.code) {
+            return false;
+        }
+
+        for line in &mut self.lines {
+            if let Some((code, text)) = EnhancedCode::parse_prefix(line)
+                && code == enhanced
+            {
+                *line = text;
+            }
+        }
+        self.enhanced = Some(enhanced);
+        true
+    }
+
+    /// The lines of text, without the code, separator, or enhanced status code. Never empty.
+    #[must_use]
+    pub fn lines(&self) -> &[Bytes] {
+        &self.lines
+    }
+
+    /// Append a line of text.
+    ///
+    /// # Errors
+    ///
+    /// If `line` is not a valid reply line.
+    pub fn push(&mut self, line: Bytes) -> Result<(), ReplyError> {
+        validate_line(
+            &line,
+            self.enhanced.map_or(0, |enhanced| enhanced.len() + 1),
+        )?;
+        self.lines.push(line);
+        Ok(())
+    }
+
+    /// Whether the reply has more than one line.
+    #[must_use]
+    pub const fn is_multiline(&self) -> bool {
+        self.lines.len() > 1
+    }
+
+    /// Consume the `Reply`, returning the code and lines of text, discarding any enhanced status
+    /// code.
+    #[must_use]
+    pub fn into_parts(self) -> (u16, Vec<Bytes>) {
+        (self.code, self.lines)
+    }
+}
+
+/// # Reply Code
+///
+/// The well-known reply codes of [RFC 5321](https://datatracker.ietf.org/doc/html/rfc5321#section-4.2.3),
+/// [`AUTH`](https://datatracker.ietf.org/doc/html/rfc4954#section-6), and
+/// [null MX](https://datatracker.ietf.org/doc/html/rfc7504), or any other.
+///
+/// ```rust
+/// # use smtpkit::ReplyCode;
+/// assert_eq!(ReplyCode::from(354), ReplyCode::StartMailInput);
+/// assert_eq!(ReplyCode::try_from(&b"599"[..])?, ReplyCode::Other(599));
+/// assert_eq!(ReplyCode::ServiceReady.to_string(), "220");
+/// assert!(ReplyCode::try_from(&b"600"[..]).is_err());
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+#[display("{}", self.code())]
+pub enum ReplyCode {
+    /// `211`: system status, or system help reply.
+    SystemStatus,
+    /// `214`: help message.
+    Help,
+    /// `220`: service ready.
+    ServiceReady,
+    /// `221`: service closing transmission channel.
+    ServiceClosing,
+    /// `235`: authentication succeeded.
+    AuthSuccessful,
+    /// `250`: requested mail action okay, completed.
+    Ok,
+    /// `251`: user not local; will forward.
+    UserNotLocalWillForward,
+    /// `252`: cannot `VRFY` user, but will accept message and attempt delivery.
+    CannotVerify,
+    /// `334`: server challenge, awaiting the next `AUTH` response.
+    AuthContinue,
+    /// `354`: start mail input; end with `<CRLF>.<CRLF>`.
+    StartMailInput,
+    /// `421`: service not available, closing transmission channel.
+    ServiceNotAvailable,
+    /// `432`: a password transition is needed.
+    PasswordTransitionNeeded,
+    /// `450`: mailbox unavailable, e.g. busy or temporarily blocked.
+    MailboxBusy,
+    /// `451`: local error in processing.
+    LocalError,
+    /// `452`: insufficient system storage, or too many recipients.
+    InsufficientStorage,
+    /// `454`: temporary authentication failure, or TLS not available.
+    TemporaryFailure,
+    /// `455`: server unable to accommodate parameters.
+    ParametersNotAccommodated,
+    /// `500`: syntax error, command unrecognized.
+    SyntaxError,
+    /// `501`: syntax error in parameters or arguments.
+    ParameterSyntaxError,
+    /// `502`: command not implemented.
+    CommandNotImplemented,
+    /// `503`: bad sequence of commands.
+    BadSequence,
+    /// `504`: command parameter not implemented.
+    ParameterNotImplemented,
+    /// `521`: host does not accept mail.
+    NoMailAccepted,
+    /// `530`: authentication required.
+    AuthRequired,
+    /// `534`: authentication mechanism is too weak.
+    AuthMechanismTooWeak,
+    /// `535`: authentication credentials invalid.
+    AuthCredentialsInvalid,
+    /// `538`: encryption required for the requested authentication mechanism.
+    EncryptionRequired,
+    /// `550`: mailbox unavailable, e.g. not found, no access, or rejected for policy reasons.
+    MailboxUnavailable,
+    /// `551`: user not local.
+    UserNotLocal,
+    /// `552`: exceeded storage allocation.
+    ExceededStorage,
+    /// `553`: mailbox name not allowed.
+    MailboxNameNotAllowed,
+    /// `554`: transaction failed, or no SMTP service here.
+    TransactionFailed,
+    /// `555`: `MAIL FROM`/`RCPT TO` parameters not recognized or not implemented.
+    ParametersNotRecognized,
+    /// `556`: domain does not accept mail.
+    DomainNoMailAccepted,
+    /// Any other code. Not necessarily valid, see [`ReplyCode::is_valid`].
+    Other(u16),
+}
+
+impl ReplyCode {
+    /// The numeric code.
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::SystemStatus => 211,
+            Self::Help => 214,
+            Self::ServiceReady => 220,
+            Self::ServiceClosing => 221,
+            Self::AuthSuccessful => 235,
+            Self::Ok => 250,
+            Self::UserNotLocalWillForward => 251,
+            Self::CannotVerify => 252,
+            Self::AuthContinue => 334,
+            Self::StartMailInput => 354,
+            Self::ServiceNotAvailable => 421,
+            Self::PasswordTransitionNeeded => 432,
+            Self::MailboxBusy => 450,
+            Self::LocalError => 451,
+            Self::InsufficientStorage => 452,
+            Self::TemporaryFailure => 454,
+            Self::ParametersNotAccommodated => 455,
+            Self::SyntaxError => 500,
+            Self::ParameterSyntaxError => 501,
+            Self::CommandNotImplemented => 502,
+            Self::BadSequence => 503,
+            Self::ParameterNotImplemented => 504,
+            Self::NoMailAccepted => 521,
+            Self::AuthRequired => 530,
+            Self::AuthMechanismTooWeak => 534,
+            Self::AuthCredentialsInvalid => 535,
+            Self::EncryptionRequired => 538,
+            Self::MailboxUnavailable => 550,
+            Self::UserNotLocal => 551,
+            Self::ExceededStorage => 552,
+            Self::MailboxNameNotAllowed => 553,
+            Self::TransactionFailed => 554,
+            Self::ParametersNotRecognized => 555,
+            Self::DomainNoMailAccepted => 556,
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Whether the code is a valid reply code: the first digit `2` to `5`, and the second `0` to
+    /// `5`.
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        is_valid_code(self.code())
+    }
+}
+
+impl From<u16> for ReplyCode {
+    fn from(code: u16) -> Self {
+        match code {
+            211 => Self::SystemStatus,
+            214 => Self::Help,
+            220 => Self::ServiceReady,
+            221 => Self::ServiceClosing,
+            235 => Self::AuthSuccessful,
+            250 => Self::Ok,
+            251 => Self::UserNotLocalWillForward,
+            252 => Self::CannotVerify,
+            334 => Self::AuthContinue,
+            354 => Self::StartMailInput,
+            421 => Self::ServiceNotAvailable,
+            432 => Self::PasswordTransitionNeeded,
+            450 => Self::MailboxBusy,
+            451 => Self::LocalError,
+            452 => Self::InsufficientStorage,
+            454 => Self::TemporaryFailure,
+            455 => Self::ParametersNotAccommodated,
+            500 => Self::SyntaxError,
+            501 => Self::ParameterSyntaxError,
+            502 => Self::CommandNotImplemented,
+            503 => Self::BadSequence,
+            504 => Self::ParameterNotImplemented,
+            521 => Self::NoMailAccepted,
+            530 => Self::AuthRequired,
+            534 => Self::AuthMechanismTooWeak,
+            535 => Self::AuthCredentialsInvalid,
+            538 => Self::EncryptionRequired,
+            550 => Self::MailboxUnavailable,
+            551 => Self::UserNotLocal,
+            552 => Self::ExceededStorage,
+            553 => Self::MailboxNameNotAllowed,
+            554 => Self::TransactionFailed,
+            555 => Self::ParametersNotRecognized,
+            556 => Self::DomainNoMailAccepted,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<ReplyCode> for u16 {
+    fn from(code: ReplyCode) -> Self {
+        code.code()
+    }
+}
+
+impl TryFrom<&[u8]> for ReplyCode {
+    type Error = ReplyError;
+
+    /// Parse three digits as a valid reply code.
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 3 || !value.iter().all(u8::is_ascii_digit) {
+            return Err(ReplyError::InvalidCode);
+        }
+
+        let code = Self::from(
+            value
+                .iter()
+                .fold(0, |code, digit| code * 10 + u16::from(digit - b'0')),
+        );
+        if code.is_valid() {
+            Ok(code)
+        } else {
+            Err(ReplyError::InvalidCode)
+        }
+    }
+}
+
+/// # [Reply Severity](https://datatracker.ietf.org/doc/html/rfc5321#section-4.2.1)
+///
+/// The first digit of a reply code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// `2yz`: the command succeeded.
+    PositiveCompletion,
+    /// `3yz`: the command was accepted, but more input is needed, e.g. `354` for `DATA`.
+    PositiveIntermediate,
+    /// `4yz`: the command failed, but the failure is temporary; retry later.
+    TransientNegative,
+    /// `5yz`: the command failed, and would fail again if retried as is.
+    PermanentNegative,
+}
+
+impl Severity {
+    /// Whether this is a failure: [`Severity::TransientNegative`] or
+    /// [`Severity::PermanentNegative`].
+    #[must_use]
+    pub const fn is_failure(self) -> bool {
+        matches!(self, Self::TransientNegative | Self::PermanentNegative)
+    }
+}
+
+/// # [Enhanced Status Code](https://datatracker.ietf.org/doc/html/rfc3463)
+///
+/// `class.subject.detail`, e.g. `2.1.0`, leading the text of a reply from a server that
+/// advertises `ENHANCEDSTATUSCODES`.
+///
+/// ```rust
+/// # use smtpkit::EnhancedCode;
+/// let code = EnhancedCode::try_from(&b"5.1.1"[..])?;
+/// assert_eq!((code.class(), code.subject(), code.detail()), (5, 1, 1));
+/// assert_eq!(code.to_string(), "5.1.1");
+/// assert!(code.matches(550));
+/// assert!(EnhancedCode::try_from(&b"3.0.0"[..]).is_err());
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display("{class}.{subject}.{detail}")]
+pub struct EnhancedCode {
+    class: u8,
+    subject: u16,
+    detail: u16,
+}
+
+impl EnhancedCode {
+    /// `2.0.0`, success with no further detail.
+    pub const OK: Self = Self {
+        class: 2,
+        subject: 0,
+        detail: 0,
+    };
+
+    /// Create an `EnhancedCode`, or `None` if `class` is not `2`, `4`, or `5`, or `subject` or
+    /// `detail` exceed `999`.
+    #[must_use]
+    pub const fn new(class: u8, subject: u16, detail: u16) -> Option<Self> {
+        if matches!(class, 2 | 4 | 5) && subject <= 999 && detail <= 999 {
+            Some(Self {
+                class,
+                subject,
+                detail,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The class: `2` for success, `4` for a persistent transient failure, or `5` for a permanent
+    /// failure.
+    #[must_use]
+    pub const fn class(self) -> u8 {
+        self.class
+    }
+
+    /// The subject, e.g. `1` for addressing.
+    #[must_use]
+    pub const fn subject(self) -> u16 {
+        self.subject
+    }
+
+    /// The detail within the subject.
+    #[must_use]
+    pub const fn detail(self) -> u16 {
+        self.detail
+    }
+
+    /// Whether the class is the first digit of the reply `code`, as it must be in a reply.
+    #[must_use]
+    pub const fn matches(self, code: u16) -> bool {
+        self.class as u16 == code / 100
+    }
+
+    /// Parse the enhanced status code leading `text`, returning it and the rest of the text after
+    /// the separating space.
+    #[must_use]
+    pub fn parse_prefix(text: &Bytes) -> Option<(Self, Bytes)> {
+        let end = text.find_byte(b' ').unwrap_or(text.len());
+        let code = Self::try_from(&text[..end]).ok()?;
+        Some((code, text.slice((end + 1).min(text.len())..)))
+    }
+
+    /// The length when serialized.
+    const fn len(self) -> usize {
+        const fn digits(n: u16) -> usize {
+            match n {
+                0..=9 => 1,
+                10..=99 => 2,
+                _ => 3,
+            }
+        }
+
+        3 + digits(self.subject) + digits(self.detail)
+    }
+}
+
+impl TryFrom<&[u8]> for EnhancedCode {
+    type Error = ReplyError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut parts = value.splitn(3, |&b| b == b'.').map(|part| {
+            if (1..=3).contains(&part.len()) && part.iter().all(u8::is_ascii_digit) {
+                Some(
+                    part.iter()
+                        .fold(0, |n, digit| n * 10 + u16::from(digit - b'0')),
+                )
+            } else {
+                None
+            }
+        });
+
+        let (Some(Some(class)), Some(Some(subject)), Some(Some(detail))) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ReplyError::InvalidEnhancedCode);
+        };
+        // the class is a single digit
+        if value.get(1) != Some(&b'.') {
+            return Err(ReplyError::InvalidEnhancedCode);
+        }
+        u8::try_from(class)
+            .ok()
+            .and_then(|class| Self::new(class, subject, detail))
+            .ok_or(ReplyError::InvalidEnhancedCode)
+    }
+}
+
+/// # Reply Error
+///
+/// Returned when constructing a [`Reply`] or [`EnhancedCode`].
+#[derive(Debug, Display, derive_more::Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplyError {
+    /// The code is not between `200` and `559`, or its second digit is greater than `5`.
+    #[display("Invalid reply code")]
+    InvalidCode,
+    /// A reply must have at least one line.
+    #[display("Reply has no lines")]
+    Empty,
+    /// A line contains a CR or LF.
+    #[display("Invalid reply text")]
+    InvalidText,
+    /// A line, with the code and separator, exceeds [`max::REPLY_LINE`].
+    #[display("Reply line too long")]
+    TooLong,
+    /// The enhanced status code is malformed, or its class does not match the reply code.
+    #[display("Invalid enhanced status code")]
+    InvalidEnhancedCode,
+}
+
+/// Whether `code` is a valid reply code: the first digit `2` to `5`, and the second `0` to `5`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-4.2.1>
+const fn is_valid_code(code: u16) -> bool {
+    matches!(code, 200..=559) && (code / 10) % 10 <= 5
+}
+
+/// Validate a line of text, to be written after `extra` bytes of enhanced status code.
+fn validate_line(line: &[u8], extra: usize) -> Result<(), ReplyError> {
+    if line.len() + 4 + extra > max::REPLY_LINE {
+        return Err(ReplyError::TooLong);
+    }
+    if line.find_byteset(b"\r\n").is_some() {
+        return Err(ReplyError::InvalidText);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::ok(250, true)]
+    #[case::intermediate(354, true)]
+    #[case::last(559, true)]
+    #[case::low(199, false)]
+    #[case::high(560, false)]
+    #[case::second_digit(260, false)]
+    #[case::zero(0, false)]
+    fn code(#[case] code: u16, #[case] valid: bool) {
+        assert_eq!(Reply::new(code, Bytes::new()).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::empty(b"", Ok(()))]
+    #[case::text(b"2.0.0 OK", Ok(()))]
+    #[case::cr(b"OK\r", Err(ReplyError::InvalidText))]
+    #[case::lf(b"O\nK", Err(ReplyError::InvalidText))]
+    #[case::max(&[b'a'; max::REPLY_LINE - 4], Ok(()))]
+    #[case::too_long(&[b'a'; max::REPLY_LINE - 3], Err(ReplyError::TooLong))]
+    fn line(#[case] line: &'static [u8], #[case] expected: Result<(), ReplyError>) {
+        assert_eq!(
+            Reply::new(250, Bytes::from_static(line)).map(|_| ()),
+            expected
+        );
+    }
+
+    #[test]
+    fn multiline() {
+        assert_eq!(Reply::multiline(250, []).unwrap_err(), ReplyError::Empty);
+
+        let mut reply =
+            Reply::multiline(250, [Bytes::from("mx.example.com"), Bytes::from("SIZE")]).unwrap();
+        assert!(reply.is_multiline());
+        assert_eq!(
+            reply.push(Bytes::from("bad\r\n")),
+            Err(ReplyError::InvalidText)
+        );
+        assert_eq!(reply.lines().len(), 2);
+
+        assert_eq!(reply.set_code(600), Err(ReplyError::InvalidCode));
+        reply.set_code(251).unwrap();
+        assert_eq!(
+            reply.into_parts(),
+            (
+                251,
+                vec![Bytes::from("mx.example.com"), Bytes::from("SIZE")]
+            )
+        );
+    }
+
+    #[rstest]
+    #[case::single(&[&b"2.0.0 OK"[..]], "250 2.0.0 OK\r\n")]
+    #[case::empty(&[b""], "250\r\n")]
+    #[case::multiline(
+        &[&b"mx.example.com"[..], b"PIPELINING", b"SIZE 1000"],
+        "250-mx.example.com\r\n250-PIPELINING\r\n250 SIZE 1000\r\n"
+    )]
+    #[case::empty_lines(&[&b""[..], b""], "250-\r\n250\r\n")]
+    fn to_bytes(#[case] lines: &[&'static [u8]], #[case] expected: &str) {
+        let reply = Reply::multiline(250, lines.iter().copied().map(Bytes::from_static)).unwrap();
+        assert_eq!(reply.to_bytes(), expected);
+    }
+
+    #[rstest]
+    #[case::ok(b"2.0.0", EnhancedCode::new(2, 0, 0))]
+    #[case::long(b"4.999.100", EnhancedCode::new(4, 999, 100))]
+    #[case::class(b"3.0.0", None)]
+    #[case::class_digits(b"02.0.0", None)]
+    #[case::subject_digits(b"5.1000.0", None)]
+    #[case::empty_detail(b"5.1.", None)]
+    #[case::extra(b"5.1.1.1", None)]
+    #[case::short(b"5.1", None)]
+    #[case::letters(b"5.a.1", None)]
+    fn enhanced_code(#[case] input: &[u8], #[case] expected: Option<EnhancedCode>) {
+        let code = EnhancedCode::try_from(input).ok();
+        assert_eq!(code, expected);
+        if let Some(code) = code {
+            assert_eq!(&code.to_bytes()[..], input);
+            assert_eq!(code.len(), input.len());
+        }
+    }
+
+    #[test]
+    fn with_enhanced_code() {
+        let reply = Reply::multiline(250, [Bytes::from("mx.example.com"), Bytes::new()])
+            .unwrap()
+            .with_enhanced_code(EnhancedCode::OK)
+            .unwrap();
+        assert_eq!(
+            reply.to_bytes(),
+            "250-2.0.0 mx.example.com\r\n250 2.0.0\r\n"
+        );
+
+        let mut reply = reply;
+        assert_eq!(reply.set_code(550), Err(ReplyError::InvalidEnhancedCode));
+        assert_eq!(
+            reply.set_enhanced_code(EnhancedCode::new(5, 0, 0)),
+            Err(ReplyError::InvalidEnhancedCode)
+        );
+        reply.set_enhanced_code(None).unwrap();
+        reply.set_code(550).unwrap();
+
+        let long = Bytes::from_static(&[b'a'; max::REPLY_LINE - 4]);
+        assert_eq!(
+            Reply::new(250, long.clone())
+                .unwrap()
+                .with_enhanced_code(EnhancedCode::OK),
+            Err(ReplyError::TooLong)
+        );
+        let mut reply = Reply::new(250, Bytes::from("OK"))
+            .unwrap()
+            .with_enhanced_code(EnhancedCode::OK)
+            .unwrap();
+        assert_eq!(reply.push(long), Err(ReplyError::TooLong));
+    }
+
+    #[rstest]
+    #[case::split(
+        550,
+        b"5.1.1 No such user",
+        EnhancedCode::new(5, 1, 1),
+        b"No such user"
+    )]
+    #[case::bare(250, b"2.0.0", EnhancedCode::new(2, 0, 0), b"")]
+    #[case::mismatched(250, b"5.1.1 No", None, b"5.1.1 No")]
+    #[case::none(250, b"OK", None, b"OK")]
+    fn split_enhanced_code(
+        #[case] code: u16,
+        #[case] text: &'static [u8],
+        #[case] expected: Option<EnhancedCode>,
+        #[case] rest: &[u8],
+    ) {
+        let mut reply = Reply::new(code, Bytes::from_static(text)).unwrap();
+        assert_eq!(reply.split_enhanced_code(), expected.is_some());
+        assert_eq!(reply.enhanced_code(), expected);
+        assert_eq!(reply.lines(), [rest]);
+    }
+
+    #[rstest]
+    #[case::completion(250, Severity::PositiveCompletion)]
+    #[case::intermediate(354, Severity::PositiveIntermediate)]
+    #[case::transient(451, Severity::TransientNegative)]
+    #[case::permanent(550, Severity::PermanentNegative)]
+    fn severity(#[case] code: u16, #[case] expected: Severity) {
+        let reply = Reply::new(code, Bytes::new()).unwrap();
+        assert_eq!(reply.severity(), expected);
+        assert_eq!(
+            [
+                reply.is_positive_completion(),
+                reply.is_positive_intermediate(),
+                reply.is_transient_failure(),
+                reply.is_permanent_failure(),
+            ]
+            .iter()
+            .filter(|&&is| is)
+            .count(),
+            1
+        );
+        assert_eq!(expected.is_failure(), code >= 400);
+    }
+
+    #[test]
+    fn reply_code() {
+        for code in 0..1000 {
+            let reply_code = ReplyCode::from(code);
+            assert_eq!(reply_code.code(), code);
+            assert_eq!(u16::from(reply_code), code);
+            if matches!(reply_code, ReplyCode::Other(_)) {
+                continue;
+            }
+
+            // named codes are valid, and round trip
+            let digits = reply_code.to_string();
+            assert_eq!(ReplyCode::try_from(digits.as_bytes()), Ok(reply_code));
+            assert_eq!(
+                Reply::new(code, Bytes::new()).unwrap().reply_code(),
+                reply_code
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::named(b"250", Ok(ReplyCode::Ok))]
+    #[case::other(b"299", Ok(ReplyCode::Other(299)))]
+    #[case::invalid(b"199", Err(ReplyError::InvalidCode))]
+    #[case::short(b"25", Err(ReplyError::InvalidCode))]
+    #[case::long(b"2500", Err(ReplyError::InvalidCode))]
+    #[case::sign(b"+50", Err(ReplyError::InvalidCode))]
+    fn reply_code_try_from(#[case] input: &[u8], #[case] expected: Result<ReplyCode, ReplyError>) {
+        assert_eq!(ReplyCode::try_from(input), expected);
+    }
+}
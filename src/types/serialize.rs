@@ -41,6 +41,15 @@ impl ToBytes for Bdat {
     }
 }
 
+impl ToBytes for Payload {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        buf.reserve(self.len());
+        for segment in self.segments() {
+            buf.extend_from_slice(segment);
+        }
+    }
+}
+
 impl ToBytes for ReversePath {
     fn to_bytes_into(&self, buf: &mut BytesMut) {
         buf.extend_from_slice(b"<");
@@ -97,6 +106,12 @@ impl ToBytes for Mail {
         buf.extend_from_slice(b"MAIL FROM:");
         self.from.to_bytes_into(buf);
 
+        #[cfg(feature = "parse")]
+        if let Some(raw) = self.replay() {
+            write_raw(raw, buf);
+            return;
+        }
+
         if let Some(size) = self.size {
             buf.extend_from_slice(b" SIZE=");
             let mut f = itoa::Buffer::new();
@@ -123,6 +138,24 @@ impl ToBytes for Mail {
             body.to_bytes_into(buf);
         }
 
+        if let Some(conperm) = &self.conperm {
+            buf.extend_from_slice(b" CONPERM=");
+            conperm.0.to_bytes_into(buf);
+        }
+
+        if let Some(solicit) = &self.solicit {
+            buf.extend_from_slice(b" SOLICIT=");
+            solicit.to_bytes_into(buf);
+        }
+
+        if self.prdr {
+            buf.extend_from_slice(b" PRDR");
+        }
+
+        if self.smtputf8 {
+            buf.extend_from_slice(b" SMTPUTF8");
+        }
+
         buf.extend_from_slice(b"\r\n");
     }
 }
@@ -155,19 +188,61 @@ impl ToBytes for Notify {
 
 impl ToBytes for Rcpt {
     fn to_bytes_into(&self, buf: &mut BytesMut) {
-        buf.extend_from_slice(b"RCPT TO:");
+        buf.extend_from_slice(b"RCPT TO:<");
         self.to.to_bytes_into(buf);
+        buf.extend_from_slice(b">");
+
+        #[cfg(feature = "parse")]
+        if let Some(raw) = self.replay() {
+            write_raw(raw, buf);
+            return;
+        }
+
+        if let Some(notify) = self.notify {
+            buf.extend_from_slice(b" ");
+            notify.to_bytes_into(buf);
+        }
+
+        if let Some(orcpt) = &self.orcpt {
+            buf.extend_from_slice(b" ORCPT=");
+            orcpt.addr_type.to_bytes_into(buf);
+            buf.extend_from_slice(b";");
+            orcpt.addr.to_bytes_into(buf);
+        }
+
+        if let Some(conneg) = &self.conneg {
+            buf.extend_from_slice(b" CONNEG=");
+            conneg.0.to_bytes_into(buf);
+        }
+
         buf.extend_from_slice(b"\r\n");
     }
 }
 
+/// Write the raw parameters of a `MAIL` or `RCPT` command, and the trailing CRLF.
+#[cfg(feature = "parse")]
+fn write_raw(raw: &Bytes, buf: &mut BytesMut) {
+    if !raw.is_empty() {
+        buf.extend_from_slice(b" ");
+        buf.extend_from_slice(raw);
+    }
+
+    buf.extend_from_slice(b"\r\n");
+}
+
 impl ToBytes for Command {
     fn to_bytes_into(&self, buf: &mut BytesMut) {
         match self {
-            Self::Helo(helo) => helo.to_bytes_into(buf),
-            Self::Ehlo(ehlo) => ehlo.to_bytes_into(buf),
-            Self::Mail(mail) => mail.to_bytes_into(buf),
-            Self::Rcpt(rcpt) => rcpt.to_bytes_into(buf),
+            Self::Helo(helo) => {
+                buf.extend_from_slice(b"HELO ");
+                helo.to_bytes_into(buf);
+            }
+            Self::Ehlo(ehlo) => {
+                buf.extend_from_slice(b"EHLO ");
+                ehlo.to_bytes_into(buf);
+            }
+            Self::Mail(mail) => return mail.to_bytes_into(buf),
+            Self::Rcpt(rcpt) => return rcpt.to_bytes_into(buf),
             Self::Data(payload) => {
                 buf.extend_from_slice(b"DATA\r\n");
                 buf.extend_from_slice(payload);
@@ -176,41 +251,80 @@ impl ToBytes for Command {
             Self::Bdat(bdat) => return bdat.to_bytes_into(buf),
             Self::Rset => buf.extend_from_slice(b"RSET"),
             Self::Quit => buf.extend_from_slice(b"QUIT"),
-            Self::Vrfy => todo!(),
-            Self::Expn => todo!(),
-            Self::Help => todo!(),
-            Self::Noop => buf.extend_from_slice(b"NOOP"),
-            Self::StartTls => todo!(),
-            Self::Auth {
-                mechanism,
-                initial_response,
-            } => {
-                mechanism.to_bytes_into(buf);
-                if let Some(ir) = initial_response {
+            Self::Vrfy(user) => {
+                buf.extend_from_slice(b"VRFY ");
+                buf.extend_from_slice(user);
+            }
+            Self::Expn(list) => {
+                buf.extend_from_slice(b"EXPN ");
+                buf.extend_from_slice(list);
+            }
+            Self::Help(topic) => {
+                buf.extend_from_slice(b"HELP");
+                if let Some(topic) = topic {
                     buf.extend_from_slice(b" ");
-                    ir.to_bytes_into(buf);
+                    buf.extend_from_slice(topic);
                 }
             }
+            Self::Noop => buf.extend_from_slice(b"NOOP"),
+            Self::StartTls => buf.extend_from_slice(b"STARTTLS"),
+            Self::Auth(auth) => return auth.to_bytes_into(buf),
         }
         buf.extend_from_slice(b"\r\n");
     }
 }
 
-impl ToBytes for Mechanism {
+#[cfg(feature = "parse")]
+impl ToBytes for Unvalidated {
     fn to_bytes_into(&self, buf: &mut BytesMut) {
         match self {
-            Self::Plain => buf.extend_from_slice(b"PLAIN"),
-            Self::Login => buf.extend_from_slice(b"LOGIN"),
-            Self::CramMd5 => todo!(),
-            Self::Anonymous => todo!(),
-            Self::GssApi => todo!(),
-            Self::Ntlm => todo!(),
-            Self::OAuthBearer => todo!(),
-            Self::DigestMd5 => todo!(),
-            Self::ScramSha1 => todo!(),
-            Self::XOAuth2 => todo!(),
-            Self::ScramSha256 => todo!(),
+            Self::Helo(domain) => {
+                buf.extend_from_slice(b"HELO ");
+                buf.extend_from_slice(domain);
+            }
+            Self::Ehlo(domain) => {
+                buf.extend_from_slice(b"EHLO ");
+                buf.extend_from_slice(domain);
+            }
+            Self::Mail { from, parameters } => {
+                buf.extend_from_slice(b"MAIL FROM:<");
+                if let Some(from) = from {
+                    buf.extend_from_slice(from);
+                }
+                buf.extend_from_slice(b">");
+                return write_raw(parameters, buf);
+            }
+            Self::Rcpt { to, parameters } => {
+                buf.extend_from_slice(b"RCPT TO:<");
+                buf.extend_from_slice(to);
+                buf.extend_from_slice(b">");
+                return write_raw(parameters, buf);
+            }
+            Self::Command(command) => return command.to_bytes_into(buf),
         }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+impl ToBytes for auth::Auth {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(b"AUTH ");
+        self.mechanism.to_bytes_into(buf);
+        match &self.initial_response {
+            Some(ir) if ir.bytes().is_empty() => buf.extend_from_slice(b" ="),
+            Some(ir) => {
+                buf.extend_from_slice(b" ");
+                ir.to_bytes_into(buf);
+            }
+            None => {}
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+impl ToBytes for Mechanism {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(self.name().as_bytes());
     }
 }
 
@@ -218,8 +332,78 @@ impl ToBytes for Host {
     fn to_bytes_into(&self, buf: &mut BytesMut) {
         match self {
             Self::Domain(domain) => domain.to_bytes_into(buf),
-            Self::Ip(ip) => write!(buf, "[{ip}]").unwrap(),
+            Self::Ip(IpAddr::V4(ip)) => write!(buf, "[{ip}]").unwrap(),
+            Self::Ip(IpAddr::V6(ip)) => write!(buf, "[IPv6:{ip}]").unwrap(),
             Self::Address(addr) => addr.to_bytes_into(buf),
         }
     }
 }
+
+impl ToBytes for Reply {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        let mut code = itoa::Buffer::new();
+        let code = code.format(self.code()).as_bytes();
+
+        let (last, rest) = self
+            .lines()
+            .split_last()
+            .expect("a reply has at least one line");
+        // the code, separator, enhanced status code, and CRLF of each line
+        buf.reserve(self.lines().iter().map(|line| line.len() + 16).sum());
+        for line in rest {
+            buf.extend_from_slice(code);
+            buf.extend_from_slice(b"-");
+            write_reply_text(self.enhanced_code(), line, buf);
+            buf.extend_from_slice(b"\r\n");
+        }
+
+        buf.extend_from_slice(code);
+        if self.enhanced_code().is_some() || !last.is_empty() {
+            buf.extend_from_slice(b" ");
+        }
+        write_reply_text(self.enhanced_code(), last, buf);
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Write the enhanced status code, if any, and the text of a reply line.
+fn write_reply_text(enhanced: Option<EnhancedCode>, line: &[u8], buf: &mut BytesMut) {
+    if let Some(enhanced) = enhanced {
+        enhanced.to_bytes_into(buf);
+        if !line.is_empty() {
+            buf.extend_from_slice(b" ");
+        }
+    }
+    buf.extend_from_slice(line);
+}
+
+impl ToBytes for EnhancedCode {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        write!(buf, "{self}").unwrap();
+    }
+}
+
+impl ToBytes for Extension {
+    fn to_bytes_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(self.keyword());
+        match self {
+            Self::Size(0) => {}
+            Self::Size(size) => {
+                let mut size_buf = itoa::Buffer::new();
+                buf.extend_from_slice(b" ");
+                buf.extend_from_slice(size_buf.format(*size).as_bytes());
+            }
+            Self::Auth(mechanisms) => {
+                for mechanism in mechanisms {
+                    buf.extend_from_slice(b" ");
+                    mechanism.to_bytes_into(buf);
+                }
+            }
+            Self::Other(_, params) if !params.is_empty() => {
+                buf.extend_from_slice(b" ");
+                buf.extend_from_slice(params);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+/// # `VRFY`/`EXPN` Mailbox
+///
+/// A mailbox named in a reply to `VRFY` or `EXPN`, e.g. `Fred Smith <Smith@USC-ISIF.ARPA>`.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-3.5>
+#[derive(derive_more::Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mailbox {
+    /// The name before the address, if any.
+    #[debug("{:?}", name.as_ref().map(|name| Redact::bytes(name)))]
+    pub name: Option<Bytes>,
+    /// The address.
+    pub address: Email,
+}
+
+impl Mailbox {
+    /// Parse the text of a reply line: the last address in angle brackets, with any text before
+    /// it as the name, or else the whole text as a bare address. `None` if there is no valid
+    /// address.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn parse(text: &Bytes) -> Option<Self> {
+        let Some(start) = text.rfind_byte(b'<') else {
+            let address = text.trim_ascii();
+            return Email::try_from(text.slice_ref(address))
+                .ok()
+                .map(|address| Self {
+                    name: None,
+                    address,
+                });
+        };
+
+        let end = start + text[start..].find_byte(b'>')?;
+        let address = Email::try_from(text.slice(start + 1..end)).ok()?;
+        let name = text[..start].trim_ascii();
+        Some(Self {
+            name: (!name.is_empty()).then(|| text.slice_ref(name)),
+            address,
+        })
+    }
+}
+
+/// # `VRFY`/`EXPN` Result
+///
+/// The reply to `VRFY` or `EXPN`, interpreted for address verification.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use smtpkit::{Reply, Verification};
+/// let reply = Reply::multiline(
+///     250,
+///     ["Jon Postel <Postel@isi.edu>", "<Quux@foo.example.com>"].map(Bytes::from),
+/// )?;
+/// let Verification::Mailboxes(mailboxes) = Verification::parse(&reply) else {
+///     unreachable!()
+/// };
+/// assert_eq!(mailboxes[0].name.as_deref(), Some(&b"Jon Postel"[..]));
+/// assert_eq!(mailboxes[1].address.as_ref(), b"Quux@foo.example.com");
+/// # Ok::<(), smtpkit::ReplyError>(())
+/// ```
+///
+/// <https://datatracker.ietf.org/doc/html/rfc5321#section-3.5>
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Verification {
+    /// `250`: the mailbox `VRFY` matched, or the members of the list `EXPN` expanded.
+    Mailboxes(Vec<Mailbox>),
+    /// `251`: the user is not local, and mail will be forwarded to the mailbox, if given.
+    Forward(Option<Mailbox>),
+    /// `252`: the server will not verify the user, but will accept mail for it.
+    Unverified,
+    /// `550`: nothing matched.
+    NoMatch,
+    /// `551`: the user is not local, and the client should try the mailbox, if given.
+    NotLocal(Option<Mailbox>),
+    /// `553`: the string is ambiguous, with the candidates the server listed, if any.
+    Ambiguous(Vec<Mailbox>),
+    /// Any other reply, e.g. `502` if the command is not implemented.
+    Other(Reply),
+}
+
+impl Verification {
+    /// Interpret a reply to `VRFY` or `EXPN`. See [`Mailbox::parse`].
+    ///
+    /// An enhanced status code leading a line is skipped, and lines without a valid address are
+    /// ignored.
+    #[cfg(feature = "parse")]
+    #[must_use]
+    pub fn parse(reply: &Reply) -> Self {
+        let mut mailboxes =
+            reply
+                .lines()
+                .iter()
+                .filter_map(|line| match EnhancedCode::parse_prefix(line) {
+                    Some((enhanced, text)) if enhanced.matches(reply.code()) => {
+                        Mailbox::parse(&text)
+                    }
+                    _ => Mailbox::parse(line),
+                });
+
+        match reply.code() {
+            250 => Self::Mailboxes(mailboxes.collect()),
+            251 => Self::Forward(mailboxes.next()),
+            252 => Self::Unverified,
+            550 => Self::NoMatch,
+            551 => Self::NotLocal(mailboxes.next()),
+            553 => Self::Ambiguous(mailboxes.collect()),
+            _ => Self::Other(reply.clone()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "parse"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn mailbox(name: Option<&'static str>, address: &'static str) -> Mailbox {
+        Mailbox {
+            name: name.map(Bytes::from),
+            address: Email::try_from(Bytes::from(address)).unwrap(),
+        }
+    }
+
+    #[rstest]
+    #[case::name(
+        250,
+        &["Fred Smith <Smith@USC-ISIF.ARPA>"],
+        Verification::Mailboxes(vec![mailbox(Some("Fred Smith"), "Smith@USC-ISIF.ARPA")])
+    )]
+    #[case::bare(
+        250,
+        &["Smith@USC-ISIF.ARPA"],
+        Verification::Mailboxes(vec![mailbox(None, "Smith@USC-ISIF.ARPA")])
+    )]
+    #[case::enhanced(
+        250,
+        &["2.1.5 <Smith@USC-ISIF.ARPA>"],
+        Verification::Mailboxes(vec![mailbox(None, "Smith@USC-ISIF.ARPA")])
+    )]
+    #[case::expn(
+        250,
+        &["Jon Postel <Postel@isi.edu>", "Fred Fonebone <Fonebone@physics.foo-u.edu>"],
+        Verification::Mailboxes(vec![
+            mailbox(Some("Jon Postel"), "Postel@isi.edu"),
+            mailbox(Some("Fred Fonebone"), "Fonebone@physics.foo-u.edu"),
+        ])
+    )]
+    #[case::forward(
+        251,
+        &["User not local; will forward to <Postel@USC-ISI.ARPA>"],
+        Verification::Forward(Some(mailbox(
+            Some("User not local; will forward to"),
+            "Postel@USC-ISI.ARPA"
+        )))
+    )]
+    #[case::unverified(252, &["Cannot VRFY user"], Verification::Unverified)]
+    #[case::no_match(550, &["String does not match anything."], Verification::NoMatch)]
+    #[case::not_local(551, &["User not local"], Verification::NotLocal(None))]
+    #[case::ambiguous(
+        553,
+        &[
+            "Ambiguous; Possibilities are",
+            "Joe Smith <jsmith@foo.com>",
+            "Harry Smith <hsmith@foo.com>",
+        ],
+        Verification::Ambiguous(vec![
+            mailbox(Some("Joe Smith"), "jsmith@foo.com"),
+            mailbox(Some("Harry Smith"), "hsmith@foo.com"),
+        ])
+    )]
+    fn parse(#[case] code: u16, #[case] lines: &[&'static str], #[case] expected: Verification) {
+        let reply = Reply::multiline(code, lines.iter().copied().map(Bytes::from)).unwrap();
+        assert_eq!(Verification::parse(&reply), expected);
+    }
+
+    #[test]
+    fn other() {
+        let reply = Reply::new(502, Bytes::from("Command not implemented")).unwrap();
+        assert_eq!(Verification::parse(&reply), Verification::Other(reply));
+    }
+}